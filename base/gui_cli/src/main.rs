@@ -23,14 +23,15 @@ static ALLOC: rt_alloc::Allocator = rt_alloc::Allocator;
 #[cfg(not(test))]
 #[alloc_error_handler]
 fn alloc_error(layout: core::alloc::Layout) -> ! {
-	let _ = rt::io::stderr().map(|o| writeln!(o, "allocation error for {:?}", layout));
+	let _ =
+		rt::io::Stderr::lock_or_bypass().map(|o| writeln!(o, "allocation error for {:?}", layout));
 	rt::exit(129)
 }
 
 #[cfg(not(test))]
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-	let _ = rt::io::stderr().map(|o| writeln!(o, "{}", info));
+	let _ = rt::io::Stderr::lock_or_bypass().map(|o| writeln!(o, "{}", info));
 	rt::exit(128)
 }
 
@@ -177,6 +178,7 @@ fn main(_: isize, _: *const *const u8) -> isize {
 					}
 				}
 				Ok(ipc_wm::Event::Input(_)) => {}
+				Ok(ipc_wm::Event::PointerMove { .. } | ipc_wm::Event::PointerButton { .. }) => {}
 				Ok(ipc_wm::Event::Close) => rt::exit(0),
 				Err(e) => todo!("{:?}", e),
 			}
@@ -69,7 +69,9 @@ fn main() {
 					res = r;
 					break;
 				}
-				ipc_wm::Event::Input(_) => continue,
+				ipc_wm::Event::Input(_)
+				| ipc_wm::Event::PointerMove { .. }
+				| ipc_wm::Event::PointerButton { .. } => continue,
 				ipc_wm::Event::Close => rt::exit(0),
 			}
 		}
@@ -1,10 +1,14 @@
-use std::fs::File;
+use {
+	crate::shortcut::{Action, Chord},
+	std::fs::File,
+};
 
 pub struct Config {
 	pub title_bar: TitleBar,
 	pub cursor: gui3d::Texture,
 	pub font: fontdue::Font,
 	pub margin: u32,
+	pub shortcuts: Vec<(Chord, Action)>,
 }
 
 pub struct TitleBar {
@@ -63,6 +67,14 @@ pub fn load() -> Config {
 
 	let font = load_font("font.tff");
 
+	let shortcuts = [
+		("alt+tab", Action::FocusNext),
+		("alt+f4", Action::CloseWindow),
+	]
+	.into_iter()
+	.map(|(c, a)| (Chord::parse(c).expect("invalid chord"), a))
+	.collect();
+
 	Config {
 		title_bar: TitleBar {
 			height: 16 + 4,
@@ -73,6 +85,7 @@ pub fn load() -> Config {
 		cursor,
 		font,
 		margin: 7,
+		shortcuts,
 	}
 }
 
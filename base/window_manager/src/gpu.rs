@@ -72,6 +72,9 @@ impl Gpu {
 	}
 
 	pub fn sync_rect(&mut self, buffer: Option<u32>, rect: Rect) {
+		if rect.size().area() == 0 {
+			return;
+		}
 		let buffer_id = buffer.unwrap_or(self.shmem_id);
 		self.sync
 			.write(
@@ -79,7 +82,7 @@ impl Gpu {
 					offset: 0,
 					stride: rect.size().x,
 					origin: ipc_gpu::Point { x: rect.low().x, y: rect.low().y },
-					size: ipc_gpu::SizeInclusive { x: rect.size().x as _, y: rect.size().y as _ },
+					size: size_inclusive(rect),
 					buffer_id,
 				}
 				.encode(),
@@ -87,6 +90,27 @@ impl Gpu {
 			.unwrap();
 	}
 
+	/// Sync a region of a buffer that has a different layout than the region itself.
+	///
+	/// `offset` is in bytes, `stride` in pixels.
+	pub fn sync_rect_strided(&mut self, buffer: u32, rect: Rect, offset: u64, stride: u32) {
+		if rect.size().area() == 0 {
+			return;
+		}
+		self.sync
+			.write(
+				&ipc_gpu::Flush {
+					offset,
+					stride,
+					origin: ipc_gpu::Point { x: rect.low().x, y: rect.low().y },
+					size: size_inclusive(rect),
+					buffer_id: buffer,
+				}
+				.encode(),
+			)
+			.unwrap();
+	}
+
 	pub fn copy(&mut self, data: &[u8], to: Rect) {
 		self.shmem[..data.len()].copy_from_slice(data);
 		self.sync_rect(None, to);
@@ -113,3 +137,9 @@ impl Gpu {
 		self.size
 	}
 }
+
+/// `Rect::size` is the amount of pixels while the GPU expects the size minus 1.
+fn size_inclusive(rect: Rect) -> ipc_gpu::SizeInclusive {
+	let s = rect.size();
+	ipc_gpu::SizeInclusive { x: (s.x - 1) as _, y: (s.y - 1) as _ }
+}
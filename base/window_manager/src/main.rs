@@ -10,6 +10,51 @@
 //! ```
 //! <workspace id/name>:<window id>
 //! ```
+//!
+//! ## Workspaces
+//!
+//! Only windows in the current workspace are visible. The current workspace can be switched
+//! by setting the `workspace` property on the root handle to the workspace index in decimal.
+//! A window can be moved to another workspace by setting the `window/workspace` property on
+//! its handle.
+//!
+//! Windows in other workspaces keep their framebuffers but do not receive resize events and
+//! writes to them are not presented. When a workspace is switched to all windows in it receive
+//! a resize event so they can redraw themselves.
+//!
+//! Switching to an empty workspace only shows the background. New windows are created in the
+//! current workspace, unless a saved layout is restored.
+//!
+//! ## Named windows
+//!
+//! A window created with the path `window/<name>` is named. When a named window is closed its
+//! workspace and position are remembered for a minute. A new window with the same name created
+//! in that time is put in the same place. If the rest of the layout changed in the meantime the
+//! window is put as close to its old position as the tree allows.
+//!
+//! At most 32 layouts are remembered, after which the oldest is forgotten. Names are unique:
+//! creating a window with the name of a window that still exists fails with `AlreadyExists`.
+//!
+//! ## Querying windows
+//!
+//! Opening `windows` on the root handle returns a listing of all windows in all workspaces as
+//! they are at the time of the open, with one line per window:
+//!
+//! ```
+//! <window id> <title>\n
+//! ```
+//!
+//! The window id is in decimal. Newlines in titles are replaced with spaces. Each open has its
+//! own read position and reads return an empty buffer at the end of the listing.
+//!
+//! The `focused` property on the root handle returns the id of the focused window in decimal.
+//! If the current workspace is empty it fails with `DoesNotExist`.
+//!
+//! ## Keyboard shortcuts
+//!
+//! Key events written to the `input` handle are checked against the shortcuts in the
+//! configuration before being forwarded to the focused window. By default `alt+tab` focuses the
+//! next window and `alt+f4` asks the focused window to close. See [`shortcut`] for details.
 
 #![feature(core_intrinsics)]
 #![feature(norostb)]
@@ -19,6 +64,7 @@ mod config;
 mod gpu;
 #[macro_use]
 mod manager;
+mod shortcut;
 mod title_bar;
 mod window;
 mod workspace;
@@ -27,13 +73,13 @@ use {
 	core::{cell::RefCell, ptr::NonNull, time::Duration},
 	driver_utils::{
 		os::stream_table::{JobId, Request, Response, StreamTable},
-		task,
+		task, Arena,
 	},
 	gui3d::math::int as math,
 	io_queue_rt::{Pow2Size, Queue},
 	math::{Point2, Rect, Size, Vec2},
 	rt::io::{Error, Handle},
-	std::collections::VecDeque,
+	std::collections::{BTreeMap, VecDeque},
 };
 
 fn main() {
@@ -61,6 +107,11 @@ fn main() {
 	let mut old = None;
 
 	let mut mouse_clicked = false;
+	// The border between two windows that is being dragged, if any.
+	let mut dragging = None;
+	let mut shortcuts = shortcut::Tracker::default();
+	// Open `windows` listings with their read position.
+	let mut listings = Arena::<(Box<[u8]>, usize)>::new();
 
 	loop {
 		queue.poll();
@@ -72,8 +123,14 @@ fn main() {
 		}
 		let mut send_notif = false;
 		let mut draw_focus_borders = None;
+		// Regions of windows to present, in window-local coordinates.
+		// These are merged and synced once after all requests have been processed to reduce
+		// the amount of commands sent to the GPU.
+		let mut dirty = BTreeMap::<Handle, Rect>::new();
 
 		const INPUT: Handle = Handle::MAX - 1;
+		// Handles of `windows` listings count down from here.
+		const WINDOWS: Handle = Handle::MAX - 2;
 
 		let size_x2 = Size::new(
 			(main.size().x - config.margin) * 2,
@@ -95,9 +152,14 @@ fn main() {
 			let r = apply_margin(r);
 			unsize_x2(r)
 		};
+		let to_x2 = |pos: Point2| {
+			Point2::new(
+				(pos.x * 2).saturating_sub(config.margin),
+				(pos.y * 2).saturating_sub(config.margin),
+			)
+		};
 		let window_at = |mgr: &mut manager::Manager, pos: Point2| {
-			let pos = Point2::new(pos.x * 2 - config.margin, pos.y * 2 - config.margin);
-			let (h, r) = mgr.window_at(pos, size_x2).unwrap();
+			let (h, r) = mgr.window_at(to_x2(pos), size_x2)?;
 			if Some(h) != mgr.focused_window() {
 				mgr.set_focused_window(h);
 				let r = apply_margin(r);
@@ -107,44 +169,80 @@ fn main() {
 				None
 			}
 		};
+		// Find the window whose content is under the cursor and translate the position to
+		// window-local coordinates.
+		let content_at = |mgr: &manager::Manager, pos: Point2| {
+			let (h, _) = mgr.window_at(to_x2(pos), size_x2)?;
+			let (_, r) = title_bar::split(&config, window_rect(mgr, h));
+			let (l, s) = (r.low(), r.size());
+			let p = Point2::new(pos.x.checked_sub(l.x)?, pos.y.checked_sub(l.y)?);
+			(p.x < s.x && p.y < s.y).then(|| (h, p))
+		};
 
 		while let Some((handle, job_id, req)) = table.dequeue() {
 			let mut prop_buf = [0; 511];
+			let listing = WINDOWS
+				.checked_sub(handle)
+				.filter(|&l| listings.get(l).is_some());
 			let response = match req {
+				Request::Read { amount } if listing.is_some() => {
+					let (list, offset) = &mut listings[listing.unwrap()];
+					let n = (list.len() - *offset).min(amount.try_into().unwrap_or(usize::MAX));
+					let data = table.alloc(n).expect("out of buffers");
+					data.copy_from(0, &list[*offset..][..n]);
+					*offset += n;
+					Response::Data(data)
+				}
+				Request::Close if listing.is_some() => {
+					listings.remove(listing.unwrap());
+					continue;
+				}
+				_ if listing.is_some() => Response::Error(Error::InvalidOperation),
 				Request::Create { path } => {
-					let mut p = [0; 8];
+					let mut p = [0; 64];
 					let (p, _) = path.copy_into(&mut p);
-					match (handle, &*p) {
-						(Handle::MAX, b"window") => {
-							let h = mgr.new_window(main.size()).unwrap();
-							main.fill(Rect::from_size(Point2::ORIGIN, main.size()), [50; 3]);
-							old = None;
-							for w in mgr!(mgr, current_workspace).windows() {
-								let full_rect = window_rect(&mgr, w);
-								let ww = &mut mgr.windows[w];
-								let (title, rect) = title_bar::split(&config, full_rect);
-								title_bar::render(&mut main, &config, title, mouse_pos, &ww.title);
-								let evt = ipc_wm::Resolution { x: rect.size().x, y: rect.size().y };
-								ww.unread_events.resize = Some(evt);
-								let evt = ipc_wm::Event::Resize(evt).encode();
-								for id in ww.event_listeners.drain(..) {
-									ww.unread_events.resize = None;
-									let data = table.alloc(evt.len()).expect("out of buffers");
-									data.copy_from(0, &evt);
-									table.enqueue(id, Response::Data(data));
-								}
-								if Some(w) == mgr.focused_window() {
-									draw_focus_borders = Some(full_rect);
-								}
-							}
-							Response::Handle(h)
+					let name = match p.strip_prefix(b"window/") {
+						_ if path.len() > p.len() => None,
+						_ if p == b"window" => Some(None),
+						Some(name) if !name.is_empty() => {
+							std::str::from_utf8(name).ok().map(|n| Some(n.into()))
 						}
+						_ => None,
+					};
+					match (handle, name) {
+						(Handle::MAX, Some(name)) => match mgr.new_window(name, main.size()) {
+							Ok(h) => {
+								old = None;
+								let (focus, _) = redraw_workspace(
+									&mut mgr,
+									&mut main,
+									&table,
+									&config,
+									mouse_pos,
+									&window_rect,
+								);
+								draw_focus_borders = focus.or(draw_focus_borders);
+								Response::Handle(h)
+							}
+							Err(manager::NewWindowError::NameInUse) => {
+								Response::Error(Error::AlreadyExists)
+							}
+						},
 						_ => Response::Error(Error::InvalidOperation),
 					}
 				}
 				Request::GetMeta { property } => {
 					let prop = property.get(&mut prop_buf);
 					match (handle, &*prop) {
+						(Handle::MAX, b"focused") => match mgr.focused_window() {
+							Some(h) => {
+								let h = h.to_string();
+								let data = table.alloc(h.len()).expect("out of buffers");
+								data.copy_from(0, h.as_bytes());
+								Response::Data(data)
+							}
+							None => Response::Error(Error::DoesNotExist),
+						},
 						(Handle::MAX, _) => Response::Error(Error::InvalidOperation as _),
 						(h, b"bin/resolution") => {
 							let rect = window_rect(&mgr, h);
@@ -159,8 +257,61 @@ fn main() {
 				}
 				Request::SetMeta { property_value } => {
 					let (prop, val) = property_value.try_get(&mut prop_buf).unwrap();
+					let parse_workspace = |val: &[u8]| {
+						std::str::from_utf8(val)
+							.ok()
+							.and_then(|s| s.parse::<u8>().ok())
+					};
 					match (handle, &*prop) {
+						(Handle::MAX, b"workspace") => {
+							match parse_workspace(val).map(|ws| mgr.switch_workspace(ws)) {
+								Some(Ok(())) => {
+									old = None;
+									let (focus, _) = redraw_workspace(
+										&mut mgr,
+										&mut main,
+										&table,
+										&config,
+										mouse_pos,
+										&window_rect,
+									);
+									draw_focus_borders = focus.or(draw_focus_borders);
+									Response::Amount(0)
+								}
+								Some(Err(manager::InvalidWorkspace)) | None => {
+									Response::Error(Error::InvalidData)
+								}
+							}
+						}
 						(Handle::MAX, _) => Response::Error(Error::InvalidOperation as _),
+						(h, b"window/workspace") => {
+							let was_visible = mgr.is_visible(h);
+							match parse_workspace(val).map(|ws| mgr.move_window(h, ws, main.size()))
+							{
+								Some(Ok(())) => {
+									if was_visible || mgr.is_visible(h) {
+										old = None;
+										let (focus, _) = redraw_workspace(
+											&mut mgr,
+											&mut main,
+											&table,
+											&config,
+											mouse_pos,
+											&window_rect,
+										);
+										draw_focus_borders = focus.or(draw_focus_borders);
+									}
+									Response::Amount(0)
+								}
+								Some(Err(manager::MoveWindowError::InvalidWindow)) => {
+									Response::Error(Error::InvalidOperation)
+								}
+								Some(Err(manager::MoveWindowError::InvalidWorkspace(_))) | None => {
+									Response::Error(Error::InvalidData)
+								}
+							}
+						}
+						(h, b"bin/cmd/fill") if !mgr.is_visible(h) => Response::Amount(0),
 						(h, b"bin/cmd/fill") => {
 							if let &[r, g, b] = &*val {
 								let rect = window_rect(&mgr, h);
@@ -173,9 +324,11 @@ fn main() {
 						}
 						(h, b"title") => {
 							let s = String::from_utf8_lossy(val).into_owned().into_boxed_str();
-							let r = window_rect(&mgr, h);
-							let (r, _) = title_bar::split(&config, r);
-							title_bar::render(&mut main, &config, r, mouse_pos, &s);
+							if mgr.is_visible(h) {
+								let r = window_rect(&mgr, h);
+								let (r, _) = title_bar::split(&config, r);
+								title_bar::render(&mut main, &config, r, mouse_pos, &s);
+							}
 							mgr.window_mut(h).unwrap().title = s;
 							Response::Amount(0)
 						}
@@ -232,18 +385,41 @@ fn main() {
 										(l as u64 * main.size().y as u64 / (1 << 31)) as _;
 									mouse_moved = true;
 								}
-								Type::Button(0) => mouse_clicked = k.is_press(),
+								Type::Button(b) => {
+									if b == 0 {
+										mouse_clicked = k.is_press();
+									}
+									if let Some((w, _)) = content_at(&mgr, mouse_pos) {
+										let evt = ipc_wm::Event::PointerButton {
+											button: b,
+											pressed: k.is_press(),
+										};
+										send_event(&table, mgr.window_mut(w).unwrap(), evt);
+									}
+								}
 								_ => {
-									let Some(w) = mgr.focused_window() else { continue };
-									let u = &mut mgr.window_mut(w).unwrap();
-									if let Some(id) = u.event_listeners.pop_front() {
-										let evt = ipc_wm::Event::Input(k).encode();
-										let d = table.alloc(evt.len()).expect("out of buffers");
-										d.copy_from(0, &evt);
-										table.enqueue(id, Response::Data(d));
-									} else {
-										u.unread_events.inputs.push_back(k);
+									use shortcut::{Action, Outcome};
+									match shortcuts.process(&config.shortcuts, k) {
+										Outcome::Forward => {}
+										Outcome::Consume => continue,
+										Outcome::Trigger(Action::FocusNext) => {
+											if let Some(w) = mgr.focus_next() {
+												draw_focus_borders = Some(window_rect(&mgr, w));
+											}
+											continue;
+										}
+										Outcome::Trigger(Action::CloseWindow) => {
+											if let Some(w) = mgr.focused_window() {
+												request_close(&table, mgr.window_mut(w).unwrap());
+											}
+											continue;
+										}
 									}
+									let Some(w) = mgr.focused_window() else {
+										continue;
+									};
+									let evt = ipc_wm::Event::Input(k);
+									send_event(&table, mgr.window_mut(w).unwrap(), evt);
 								}
 							};
 						}
@@ -251,6 +427,10 @@ fn main() {
 					let edge = !mouse_was_clicked & mouse_clicked;
 					if mouse_moved {
 						main.move_cursor(mouse_pos);
+						if let Some((w, p)) = content_at(&mgr, mouse_pos) {
+							let evt = ipc_wm::Event::PointerMove { x: p.x, y: p.y };
+							send_event(&table, mgr.window_mut(w).unwrap(), evt);
+						}
 					}
 					if mouse_moved | edge {
 						for w in mgr!(mgr, current_workspace).windows() {
@@ -272,14 +452,7 @@ fn main() {
 								mouse_clicked,
 							);
 							if edge & close {
-								if let Some(id) = ww.event_listeners.pop_front() {
-									let evt = ipc_wm::Event::Close.encode();
-									let d = table.alloc(evt.len()).expect("out of buffers");
-									d.copy_from(0, &evt);
-									table.enqueue(id, Response::Data(d));
-								} else {
-									ww.unread_events.close = true;
-								}
+								request_close(&table, ww);
 							}
 						}
 					}
@@ -287,11 +460,36 @@ fn main() {
 						if let Some(r) = window_at(&mut mgr, mouse_pos) {
 							draw_focus_borders = Some(r);
 						}
+						// Grabbing the gap between two windows allows resizing them.
+						dragging = mgr.border_at(to_x2(mouse_pos), size_x2, config.margin);
+					}
+					if !mouse_clicked {
+						dragging = None;
 					}
+					if let Some(border) = dragging.filter(|_| mouse_moved) {
+						// Ensure the title bar of either window remains visible.
+						let min = (u32::from(config.title_bar.height) + 1) * 2 + config.margin * 2;
+						if mgr.move_border(border, to_x2(mouse_pos), size_x2, min) {
+							old = None;
+							let (focus, notif) = redraw_workspace(
+								&mut mgr,
+								&mut main,
+								&table,
+								&config,
+								mouse_pos,
+								&window_rect,
+							);
+							draw_focus_borders = focus.or(draw_focus_borders);
+							send_notif |= notif;
+						}
+					}
+					Response::Amount(data.len() as _)
+				}
+				Request::Write { data } if handle != Handle::MAX && !mgr.is_visible(handle) => {
+					// Not visible, so there is nothing to present.
 					Response::Amount(data.len() as _)
 				}
 				Request::Write { data } if handle != Handle::MAX => {
-					let window = mgr.window(handle).unwrap();
 					let mut header = [0; 12];
 					data.copy_to(0, &mut header);
 					let rect = window_rect(&mgr, handle);
@@ -305,10 +503,13 @@ fn main() {
 					);
 					let draw_orig = draw.origin;
 					let draw_orig = Point2::new(draw_orig.x, draw_orig.y);
-					let draw_rect = rect
-						.calc_global_pos(Rect::from_size(draw_orig, draw_size))
-						.unwrap();
-					main.sync_rect(Some(window.framebuffer), draw_rect);
+					let draw_rect = Rect::from_size(draw_orig, draw_size);
+					// Only check if the rect is in bounds, the actual sync is done later.
+					rect.calc_global_pos(draw_rect).unwrap();
+					dirty
+						.entry(handle)
+						.and_modify(|r| *r = union(*r, draw_rect))
+						.or_insert(draw_rect);
 					Response::Amount(data.len() as _)
 				}
 				Request::Close if handle != INPUT => {
@@ -316,33 +517,35 @@ fn main() {
 					if w.framebuffer != u32::MAX {
 						main.unmap_buffer(w.framebuffer).unwrap();
 					}
-					main.fill(Rect::from_size(Point2::ORIGIN, main.size()), [50, 50, 50]);
 					old = None;
-					for w in mgr!(mgr, current_workspace).windows() {
-						let full_rect = window_rect(&mgr, w);
-						let ww = &mut mgr.windows[w];
-						let (title, rect) = title_bar::split(&config, full_rect);
-						title_bar::render(&mut main, &config, title, mouse_pos, &ww.title);
-						let evt = ipc_wm::Resolution { x: rect.size().x, y: rect.size().y };
-						ww.unread_events.resize = Some(evt);
-						let evt = ipc_wm::Event::Resize(evt).encode();
-						for id in ww.event_listeners.drain(..) {
-							ww.unread_events.resize = None;
-							let data = table.alloc(evt.len()).expect("out of buffers");
-							data.copy_from(0, &evt);
-							table.enqueue(id, Response::Data(data));
-							send_notif = true;
-						}
-						if Some(w) == mgr.focused_window() {
-							draw_focus_borders = Some(full_rect);
-						}
-					}
+					let (focus, notif) = redraw_workspace(
+						&mut mgr,
+						&mut main,
+						&table,
+						&config,
+						mouse_pos,
+						&window_rect,
+					);
+					draw_focus_borders = focus.or(draw_focus_borders);
+					send_notif |= notif;
 					continue;
 				}
 				Request::Close => continue,
 				Request::Open { path } if handle == Handle::MAX => {
 					match &*path.copy_into(&mut [0; 16]).0 {
 						b"input" => Response::Handle(INPUT),
+						b"windows" => {
+							let mut list = Vec::new();
+							for (h, w) in mgr.windows.iter() {
+								list.extend_from_slice(h.to_string().as_bytes());
+								list.push(b' ');
+								list.extend(
+									w.title.bytes().map(|c| if c == b'\n' { b' ' } else { c }),
+								);
+								list.push(b'\n');
+							}
+							Response::Handle(WINDOWS - listings.insert((list.into(), 0)))
+						}
 						_ => Response::Error(Error::DoesNotExist),
 					}
 				}
@@ -360,6 +563,24 @@ fn main() {
 			table.enqueue(job_id, response);
 			send_notif = true;
 		}
+		for (h, draw_rect) in dirty {
+			// The window may have been closed or moved in the meantime.
+			let (Some(window), true) = (mgr.window(h), mgr.is_visible(h)) else {
+				continue;
+			};
+			let rect = window_rect(&mgr, h);
+			let (_, rect) = title_bar::split(&config, rect);
+			let Some(global) = rect.calc_global_pos(draw_rect) else {
+				continue;
+			};
+			// The framebuffer of the window has the same size as the window itself.
+			let stride = rect.size().x;
+			let offset = (u64::from(draw_rect.low().y) * u64::from(stride)
+				+ u64::from(draw_rect.low().x))
+				* 3;
+			main.sync_rect_strided(window.framebuffer, global, offset, stride);
+		}
+
 		send_notif.then(|| table.flush());
 
 		if let Some(new) = draw_focus_borders {
@@ -385,11 +606,81 @@ fn main() {
 	}
 }
 
+/// Calculate the smallest rect that contains both rects.
+fn union(a: Rect, b: Rect) -> Rect {
+	let (al, ah, bl, bh) = (a.low(), a.high(), b.low(), b.high());
+	Rect::from_points(
+		Point2::new(al.x.min(bl.x), al.y.min(bl.y)),
+		Point2::new(ah.x.max(bh.x), ah.y.max(bh.y)),
+	)
+}
+
+/// Clear the screen, redraw the title bars of all windows in the current workspace and notify
+/// each window of its (new) size.
+///
+/// Returns the rect of the focused window, if any, and whether any responses were enqueued.
+fn redraw_workspace(
+	mgr: &mut manager::Manager,
+	main: &mut gpu::Gpu,
+	table: &StreamTable,
+	config: &config::Config,
+	mouse_pos: Point2,
+	window_rect: impl Fn(&manager::Manager, Handle) -> Rect,
+) -> (Option<Rect>, bool) {
+	main.fill(Rect::from_size(Point2::ORIGIN, main.size()), [50; 3]);
+	let (mut focus, mut notified) = (None, false);
+	for w in mgr!(mgr, current_workspace).windows() {
+		let full_rect = window_rect(mgr, w);
+		let ww = &mut mgr.windows[w];
+		let (title, rect) = title_bar::split(config, full_rect);
+		title_bar::render(main, config, title, mouse_pos, &ww.title);
+		let evt = ipc_wm::Resolution { x: rect.size().x, y: rect.size().y };
+		ww.unread_events.resize = Some(evt);
+		let evt = ipc_wm::Event::Resize(evt).encode();
+		for id in ww.event_listeners.drain(..) {
+			ww.unread_events.resize = None;
+			let data = table.alloc(evt.len()).expect("out of buffers");
+			data.copy_from(0, &evt);
+			table.enqueue(id, Response::Data(data));
+			notified = true;
+		}
+		if Some(w) == mgr.focused_window() {
+			focus = Some(full_rect);
+		}
+	}
+	(focus, notified)
+}
+
+/// Send an event to a window or queue it if the window isn't waiting for any.
+fn send_event(table: &StreamTable, window: &mut window::Window, evt: ipc_wm::Event) {
+	if let Some(id) = window.event_listeners.pop_front() {
+		let evt = evt.encode();
+		let d = table.alloc(evt.len()).expect("out of buffers");
+		d.copy_from(0, &evt);
+		table.enqueue(id, Response::Data(d));
+	} else {
+		window.unread_events.push(evt);
+	}
+}
+
+/// Ask a window to close.
+fn request_close(table: &StreamTable, window: &mut window::Window) {
+	if let Some(id) = window.event_listeners.pop_front() {
+		let evt = ipc_wm::Event::Close.encode();
+		let d = table.alloc(evt.len()).expect("out of buffers");
+		d.copy_from(0, &evt);
+		table.enqueue(id, Response::Data(d));
+	} else {
+		window.unread_events.close = true;
+	}
+}
+
 #[derive(Default)]
 pub struct Events {
 	resize: Option<ipc_wm::Resolution>,
 	close: bool,
-	inputs: VecDeque<input::Input>,
+	/// Input and pointer events, in the order they occurred.
+	inputs: VecDeque<ipc_wm::Event>,
 }
 
 impl Events {
@@ -400,6 +691,16 @@ impl Events {
 		self.resize
 			.take()
 			.map(ipc_wm::Event::Resize)
-			.or_else(|| self.inputs.pop_front().map(ipc_wm::Event::Input))
+			.or_else(|| self.inputs.pop_front())
+	}
+
+	fn push(&mut self, evt: ipc_wm::Event) {
+		// Only the latest position is relevant.
+		match (evt, self.inputs.back_mut()) {
+			(ipc_wm::Event::PointerMove { .. }, Some(e @ ipc_wm::Event::PointerMove { .. })) => {
+				*e = evt
+			}
+			_ => self.inputs.push_back(evt),
+		}
 	}
 }
@@ -2,11 +2,12 @@ use {
 	crate::{
 		math::{Point2, Rect, Size, Vec2},
 		window::{PathIter, Window},
-		workspace::{NewWorkspaceError, Workspace},
+		workspace::{Direction, NewWorkspaceError, Path, Ratio, Workspace},
 	},
-	core::cell::Cell,
+	core::{cell::Cell, time::Duration},
 	driver_utils::{Arena, Handle},
-	std::boxed::Box,
+	rt::time::Monotonic,
+	std::{collections::BTreeMap, vec::Vec},
 };
 
 macro_rules! mgr {
@@ -15,11 +16,34 @@ macro_rules! mgr {
 	};
 }
 
+/// The maximum amount of workspaces.
+pub const MAX_WORKSPACES: u8 = 10;
+
+/// How long the layout of a destroyed named window is remembered.
+pub const LAYOUT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// The maximum amount of remembered layouts. If there are more the oldest layout is forgotten.
+pub const MAX_SAVED_LAYOUTS: usize = 32;
+
 pub struct Manager {
 	pub windows: Arena<Window>,
-	pub workspaces: Box<[Workspace]>,
+	/// Workspaces are created lazily, i.e. only once they are switched to or a window is moved
+	/// to them.
+	pub workspaces: Vec<Workspace>,
 	current_workspace: u8,
 	focused_window: Cell<Handle>,
+	/// Layouts of destroyed named windows, which are restored if a window with the same name
+	/// is created.
+	saved_layouts: BTreeMap<Box<str>, SavedLayout>,
+}
+
+/// The position of a destroyed window in its workspace.
+struct SavedLayout {
+	workspace: u8,
+	/// Path to the leaf that took the place of the window.
+	sibling: Path,
+	split: Option<(Direction, Ratio)>,
+	saved_at: Monotonic,
 }
 
 impl Manager {
@@ -30,31 +54,72 @@ impl Manager {
 			workspaces: [ws].into(),
 			current_workspace: 0,
 			focused_window: Handle::MAX.into(),
+			saved_layouts: Default::default(),
 		})
 	}
 
-	pub fn new_window(&mut self, total_size: Size) -> Result<Handle, ()> {
+	/// Create a new window in the current workspace.
+	///
+	/// If a name is given and a window with the same name was destroyed less than
+	/// [`LAYOUT_GRACE_PERIOD`] ago, the new window is put in the same workspace and position
+	/// instead.
+	pub fn new_window(
+		&mut self,
+		name: Option<Box<str>>,
+		total_size: Size,
+	) -> Result<Handle, NewWindowError> {
+		if let Some(name) = &name {
+			if self
+				.windows
+				.iter()
+				.any(|(_, w)| w.name.as_ref() == Some(name))
+			{
+				return Err(NewWindowError::NameInUse);
+			}
+		}
+		self.forget_stale_layouts();
+		let saved = name.as_ref().and_then(|n| self.saved_layouts.remove(n));
+
+		let (ws, path, split) = match saved {
+			Some(l) => {
+				self.create_workspace(l.workspace)
+					.expect("saved layout has invalid workspace");
+				// The tree may have changed in the meantime, so the path may lead to a leaf
+				// earlier than expected. If it is too short, keep going left.
+				let path = PathIter::new(32, l.sibling.directions);
+				(l.workspace, path, l.split)
+			}
+			None => (self.current_workspace, PathIter::right_bottom(), None),
+		};
+
 		let mut update = None;
 		let res = self.windows.insert_with(|handle| {
 			let p;
-			(p, update) = self.workspaces[usize::from(self.current_workspace)]
+			(p, update) = self.workspaces[usize::from(ws)]
 				.split_leaf(
-					PathIter::right_bottom(),
+					path,
 					handle,
-					None,
-					Default::default(),
+					split.map(|(d, _)| d),
+					split.map_or(Default::default(), |(_, r)| r),
 					total_size,
 				)
 				.unwrap_or_else(|e| todo!("{:?}", e));
-			Window::new(self.current_workspace, p)
+			let mut w = Window::new(ws, p);
+			w.name = name;
+			w
 		});
-		update.map(|(handle, path)| self.windows[handle].set_path(self.current_workspace, path));
+		update.map(|(handle, path)| self.windows[handle].set_path(ws, path));
 		Ok(res)
 	}
 
 	pub fn destroy_window(&mut self, handle: Handle) -> Result<Window, ()> {
 		let w = self.windows.remove(handle).ok_or(())?;
 		let (ws, path) = w.path();
+		if let Some(name) = &w.name {
+			let (pos, split) = self.workspaces[usize::from(ws)].leaf_position(path);
+			self.save_layout(name.clone(), ws, pos, split);
+		}
+		let (_, path) = w.path();
 		let path = self.workspaces[usize::from(ws)].remove_leaf(path).unwrap();
 		let len = path.depth.into();
 		self.workspaces[usize::from(ws)].apply_with_prefix(path.into_iter(), |h| {
@@ -63,18 +128,86 @@ impl Manager {
 		Ok(w)
 	}
 
+	/// Move a window to another workspace.
+	///
+	/// The window is added to the bottom right of the target workspace.
+	pub fn move_window(
+		&mut self,
+		handle: Handle,
+		workspace: u8,
+		total_size: Size,
+	) -> Result<(), MoveWindowError> {
+		let (ws, path) = self
+			.windows
+			.get(handle)
+			.ok_or(MoveWindowError::InvalidWindow)?
+			.path();
+		if ws == workspace {
+			return Ok(());
+		}
+		self.create_workspace(workspace)
+			.map_err(MoveWindowError::InvalidWorkspace)?;
+
+		if let Some(path) = self.workspaces[usize::from(ws)].remove_leaf(path) {
+			let len = path.depth.into();
+			self.workspaces[usize::from(ws)].apply_with_prefix(path.into_iter(), |h| {
+				self.windows[h].move_up(len);
+			});
+		}
+
+		let (p, update) = self.workspaces[usize::from(workspace)]
+			.split_leaf(
+				PathIter::right_bottom(),
+				handle,
+				None,
+				Default::default(),
+				total_size,
+			)
+			.unwrap_or_else(|e| todo!("{:?}", e));
+		self.windows[handle].set_path(workspace, p);
+		update.map(|(handle, path)| self.windows[handle].set_path(workspace, path));
+		Ok(())
+	}
+
+	/// Calculate the rect of a window, regardless of whether it is visible.
 	pub fn window_rect(&self, handle: Handle, total_size: Size) -> Option<Rect> {
 		let window = self.windows.get(handle)?;
 		let (ws, path) = window.path();
-		(self.current_workspace == ws)
-			.then(|| self.workspaces[usize::from(ws)].calculate_rect(path, total_size))
-			.flatten()
+		self.workspaces[usize::from(ws)].calculate_rect(path, total_size)
+	}
+
+	/// Whether the window is in the current workspace.
+	pub fn is_visible(&self, handle: Handle) -> bool {
+		self.windows
+			.get(handle)
+			.map_or(false, |w| w.path().0 == self.current_workspace)
 	}
 
 	pub fn window_at(&self, position: Point2, total_size: Size) -> Option<(Handle, Rect)> {
 		self.workspaces[self.current_workspace()].window_at(position, total_size)
 	}
 
+	/// Find the border between two windows in the current workspace near the given position.
+	///
+	/// The returned handle refers to a node in the workspace.
+	pub fn border_at(&self, position: Point2, total_size: Size, distance: u32) -> Option<Handle> {
+		self.workspaces[self.current_workspace()].border_at(position, total_size, distance)
+	}
+
+	/// Move a border in the current workspace to the given position.
+	///
+	/// Neither side of the border will become smaller than `min`.
+	pub fn move_border(
+		&mut self,
+		border: Handle,
+		position: Point2,
+		total_size: Size,
+		min: u32,
+	) -> bool {
+		let ws = self.current_workspace();
+		self.workspaces[ws].move_border(border, position, total_size, min)
+	}
+
 	pub fn window(&self, handle: Handle) -> Option<&Window> {
 		self.windows.get(handle)
 	}
@@ -97,12 +230,102 @@ impl Manager {
 		self.focused_window.set(handle);
 	}
 
+	/// Focus the window after the focused window in the current workspace.
+	///
+	/// Returns the newly focused window, if it changed.
+	pub fn focus_next(&mut self) -> Option<Handle> {
+		let cur = self.focused_window()?;
+		let ws = &self.workspaces[self.current_workspace()];
+		let next = ws
+			.windows()
+			.skip_while(|&h| h != cur)
+			.nth(1)
+			.or_else(|| ws.windows().next())
+			.filter(|&h| h != cur)?;
+		self.focused_window.set(next);
+		Some(next)
+	}
+
 	pub fn current_workspace(&self) -> usize {
 		self.current_workspace.into()
 	}
+
+	/// Switch to another workspace, creating it if it doesn't exist yet.
+	///
+	/// Switching to an empty workspace is valid, in which case there is no focused window.
+	pub fn switch_workspace(&mut self, workspace: u8) -> Result<(), InvalidWorkspace> {
+		self.create_workspace(workspace)?;
+		self.current_workspace = workspace;
+		Ok(())
+	}
+
+	/// Remember the position of a destroyed window.
+	///
+	/// If a layout with the same name already exists it is replaced. If there are too many
+	/// layouts the oldest is forgotten.
+	fn save_layout(
+		&mut self,
+		name: Box<str>,
+		workspace: u8,
+		pos: Path,
+		split: Option<(Direction, Ratio)>,
+	) {
+		self.forget_stale_layouts();
+		if self.saved_layouts.len() >= MAX_SAVED_LAYOUTS && !self.saved_layouts.contains_key(&name)
+		{
+			let oldest = self
+				.saved_layouts
+				.iter()
+				.min_by_key(|(_, l)| l.saved_at)
+				.map(|(k, _)| k.clone());
+			if let Some(k) = oldest {
+				self.saved_layouts.remove(&k);
+			}
+		}
+		// The sibling moves up to the place of the parent.
+		let depth = pos.depth.saturating_sub(1);
+		let sibling = Path { depth, directions: pos.directions & ((1 << depth) - 1) };
+		let saved_at = Monotonic::now();
+		self.saved_layouts
+			.insert(name, SavedLayout { workspace, sibling, split, saved_at });
+	}
+
+	/// Forget layouts that were saved more than [`LAYOUT_GRACE_PERIOD`] ago.
+	fn forget_stale_layouts(&mut self) {
+		let now = Monotonic::now();
+		self.saved_layouts
+			.retain(|_, l| now.saturating_duration_since(l.saved_at) < LAYOUT_GRACE_PERIOD);
+	}
+
+	/// Ensure a workspace and all workspaces before it exist.
+	fn create_workspace(&mut self, workspace: u8) -> Result<(), InvalidWorkspace> {
+		if workspace >= MAX_WORKSPACES {
+			return Err(InvalidWorkspace);
+		}
+		while self.workspaces.len() <= usize::from(workspace) {
+			self.workspaces
+				.push(Workspace::new().unwrap_or_else(|e| match e {}));
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug)]
 pub enum NewManagerError {
 	NewWorkspace(NewWorkspaceError),
 }
+
+#[derive(Debug)]
+pub enum NewWindowError {
+	/// Another window with the same name exists.
+	NameInUse,
+}
+
+#[derive(Debug)]
+pub struct InvalidWorkspace;
+
+#[derive(Debug)]
+pub enum MoveWindowError {
+	InvalidWindow,
+	InvalidWorkspace(InvalidWorkspace),
+}
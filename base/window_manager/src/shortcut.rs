@@ -0,0 +1,198 @@
+//! Keyboard shortcuts.
+//!
+//! A shortcut is a chord of keys bound to an [`Action`]. Chords are written as key names
+//! separated by `+`, e.g. `alt+tab`. Key names are case-insensitive and are either:
+//!
+//! - the name of an [`input::Keyboard`] variant, e.g. `f4` or `capslock`.
+//! - a single character, e.g. `q`. `tab` and `space` are also accepted for their characters.
+//!
+//! The `left` and `right` prefix of modifier keys is ignored, i.e. `shift` matches either shift
+//! key.
+//!
+//! A chord is triggered when its last key is pressed while all other keys are held down. The
+//! press and the corresponding release of that key are consumed. All other key events,
+//! including those of the other keys in the chord, are forwarded to the focused window.
+
+use input::{Input, Type};
+
+/// Modifier keys that may be prefixed with `left` or `right`.
+const MODIFIERS: &[&str] = &["shift", "alt", "control", "gui"];
+
+/// An action that can be bound to a chord.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+	/// Focus the next window in the current workspace.
+	FocusNext,
+	/// Ask the focused window to close.
+	CloseWindow,
+}
+
+/// A combination of keys.
+pub struct Chord {
+	keys: Box<[Box<str>]>,
+}
+
+impl Chord {
+	/// Parse a chord, e.g. `alt+tab`.
+	pub fn parse(s: &str) -> Option<Self> {
+		let keys = s
+			.split('+')
+			.map(|k| k.trim())
+			.map(|k| (!k.is_empty()).then(|| normalize(k)))
+			.collect::<Option<Box<[_]>>>()?;
+		Some(Self { keys })
+	}
+
+	/// Whether pressing the given key triggers this chord.
+	fn is_triggered_by(&self, key: &str, held: &[Box<str>]) -> bool {
+		let (last, rest) = self.keys.split_last().expect("empty chord");
+		&**last == key && rest.iter().all(|k| held.contains(k))
+	}
+}
+
+/// What to do with a key event.
+pub enum Outcome {
+	/// Send the event to the focused window.
+	Forward,
+	/// Drop the event.
+	Consume,
+	/// Drop the event and perform an action.
+	Trigger(Action),
+}
+
+/// Keeps track of held keys to detect chords.
+#[derive(Default)]
+pub struct Tracker {
+	held: Vec<Box<str>>,
+	/// Keys whose press triggered a chord. The release of these keys is consumed too.
+	consumed: Vec<Box<str>>,
+}
+
+impl Tracker {
+	/// Process a key event.
+	pub fn process(&mut self, shortcuts: &[(Chord, Action)], input: Input) -> Outcome {
+		let Some(key) = key_name(input.ty) else {
+			return Outcome::Forward;
+		};
+		if input.is_press() {
+			// Keys that are held down may be repeated.
+			if !self.held.contains(&key) {
+				self.held.push(key.clone());
+			}
+			let action = shortcuts
+				.iter()
+				.find(|(c, _)| c.is_triggered_by(&key, &self.held))
+				.map(|(_, a)| *a);
+			match action {
+				Some(action) => {
+					if !self.consumed.contains(&key) {
+						self.consumed.push(key);
+					}
+					Outcome::Trigger(action)
+				}
+				None => Outcome::Forward,
+			}
+		} else {
+			self.held.retain(|k| k != &key);
+			match self.consumed.iter().position(|k| k == &key) {
+				Some(i) => {
+					self.consumed.swap_remove(i);
+					Outcome::Consume
+				}
+				None => Outcome::Forward,
+			}
+		}
+	}
+}
+
+/// Get the normalized name of a key, if it is a key.
+fn key_name(ty: Type) -> Option<Box<str>> {
+	let name = match ty {
+		Type::Keyboard(k) => format!("{:?}", k),
+		Type::Unicode('\t') => "tab".into(),
+		Type::Unicode(' ') => "space".into(),
+		Type::Unicode(c) if !c.is_control() => c.into(),
+		_ => return None,
+	};
+	Some(normalize(&name))
+}
+
+fn normalize(key: &str) -> Box<str> {
+	let key = key.to_lowercase();
+	let modifier = ["left", "right"]
+		.iter()
+		.find_map(|p| key.strip_prefix(p))
+		.filter(|m| MODIFIERS.contains(m));
+	modifier.map_or(key.as_str(), |m| m).into()
+}
+
+#[cfg(test)]
+mod test {
+	use {
+		super::*,
+		input::Keyboard::{CapsLock, LeftShift, RightShift},
+	};
+
+	fn press(ty: Type) -> Input {
+		Input::new(ty, i32::MAX)
+	}
+
+	fn release(ty: Type) -> Input {
+		Input::new(ty, 0)
+	}
+
+	#[test]
+	fn parse() {
+		let keys = |s: &str| {
+			Chord::parse(s).map(|c| c.keys.iter().map(|k| k.to_string()).collect::<Vec<_>>())
+		};
+		assert_eq!(
+			keys("Alt + Tab"),
+			Some(vec!["alt".to_string(), "tab".to_string()])
+		);
+		assert_eq!(keys("q"), Some(vec!["q".to_string()]));
+		assert!(Chord::parse("").is_none());
+		assert!(Chord::parse("alt+").is_none());
+		assert!(Chord::parse("alt++q").is_none());
+	}
+
+	#[test]
+	fn normalize_modifiers() {
+		assert_eq!(&*normalize("LeftShift"), "shift");
+		assert_eq!(&*normalize("rightalt"), "alt");
+		assert_eq!(&*normalize("leftfoo"), "leftfoo");
+		assert_eq!(
+			key_name(Type::Keyboard(RightShift)).as_deref(),
+			Some("shift")
+		);
+		assert_eq!(
+			key_name(Type::Keyboard(CapsLock)).as_deref(),
+			Some("capslock")
+		);
+		assert_eq!(key_name(Type::Unicode('\t')).as_deref(), Some("tab"));
+		assert_eq!(key_name(Type::Unicode('Q')).as_deref(), Some("q"));
+	}
+
+	#[test]
+	fn consume_on_release() {
+		let shortcuts = [(Chord::parse("shift+q").unwrap(), Action::CloseWindow)];
+		let mut t = Tracker::default();
+		let mut process = |i| t.process(&shortcuts, i);
+		let (shift, q) = (Type::Keyboard(LeftShift), Type::Unicode('q'));
+		assert!(matches!(process(press(shift)), Outcome::Forward));
+		assert!(matches!(
+			process(press(q)),
+			Outcome::Trigger(Action::CloseWindow)
+		));
+		// Repeated presses trigger again.
+		assert!(matches!(
+			process(press(q)),
+			Outcome::Trigger(Action::CloseWindow)
+		));
+		assert!(matches!(process(release(q)), Outcome::Consume));
+		assert!(matches!(process(release(shift)), Outcome::Forward));
+		// Without shift held the key goes to the window.
+		assert!(matches!(process(press(q)), Outcome::Forward));
+		assert!(matches!(process(release(q)), Outcome::Forward));
+	}
+}
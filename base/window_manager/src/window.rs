@@ -13,6 +13,8 @@ pub struct Window {
 	pub unread_events: Events,
 	pub event_listeners: VecDeque<JobId>,
 	pub title: Box<str>,
+	/// Name used to restore the layout of the window if it is recreated.
+	pub name: Option<Box<str>>,
 }
 
 impl Window {
@@ -25,6 +27,7 @@ impl Window {
 			unread_events: Default::default(),
 			event_listeners: Default::default(),
 			title: Default::default(),
+			name: None,
 		}
 	}
 
@@ -1,9 +1,9 @@
 use {
 	crate::{
-		math::{Point2, Ratio, Rect, Size},
+		math::{Point2, Rect, Size},
 		window::PathIter,
 	},
-	core::{fmt, mem},
+	core::{fmt, mem, ops::RangeInclusive},
 	driver_utils::{Arena, Handle},
 };
 
@@ -128,6 +128,38 @@ impl Workspace {
 		}
 	}
 
+	/// Determine the exact path to a leaf and how its parent is split, i.e. the direction and
+	/// ratio to pass to [`Workspace::split_leaf`] to recreate the leaf after it is removed.
+	///
+	/// The split is [`None`] if the leaf is the root.
+	///
+	/// # Panics
+	///
+	/// The path does not lead to a leaf.
+	pub fn leaf_position(&self, mut path: PathIter) -> (Path, Option<(Direction, Ratio)>) {
+		let mut cur = self.root;
+		let mut pos = Path { depth: 0, directions: 0 };
+		let mut split = None;
+		loop {
+			match &self.nodes[cur] {
+				Node::Parent { left, right, vertical, ratio } => {
+					let d = path.next().expect("path does not lead to leaf");
+					let dir = match (vertical, d) {
+						(false, false) => Direction::Left,
+						(false, true) => Direction::Right,
+						(true, false) => Direction::Up,
+						(true, true) => Direction::Down,
+					};
+					split = Some((dir, *ratio));
+					cur = *if d { right } else { left };
+					pos.directions |= u32::from(d) << pos.depth;
+					pos.depth += 1;
+				}
+				Node::Leaf { .. } => return (pos, split),
+			}
+		}
+	}
+
 	/// Call the closure with the handles in all leaves with the given path as prefix.
 	pub fn apply_with_prefix(&self, mut prefix: PathIter, mut cb: impl FnMut(Handle)) {
 		let mut cur = self.root;
@@ -171,6 +203,55 @@ impl Workspace {
 		self.recurse(size, |_, r| r.contains(position))
 	}
 
+	/// Find the parent node whose border between its children is within `distance` of the
+	/// given position.
+	pub fn border_at(&self, position: Point2, size: Size, distance: u32) -> Option<Handle> {
+		let mut cur = self.root;
+		let mut rect = Rect::from_size(Point2::ORIGIN, size);
+		loop {
+			match self.nodes.get(cur)? {
+				Node::Parent { left, right, vertical, ratio } => {
+					let (p, range) = axis(&rect, position, *vertical);
+					let mid = ratio.partition_range(range);
+					if p.abs_diff(mid) <= distance {
+						return Some(cur);
+					}
+					let (rect_l, rect_r) = split_rect(rect, *vertical, *ratio);
+					(cur, rect) = if p > mid {
+						(*right, rect_r)
+					} else {
+						(*left, rect_l)
+					};
+				}
+				Node::Leaf { .. } => return None,
+			}
+		}
+	}
+
+	/// Move the border between the children of a parent node to the given position.
+	///
+	/// The ratio is clamped such that neither child becomes smaller than `min`.
+	///
+	/// Returns `true` if the ratio was changed.
+	pub fn move_border(&mut self, node: Handle, position: Point2, size: Size, min: u32) -> bool {
+		let Some(rect) = self.node_rect(node, size) else {
+			return false;
+		};
+		let Some(Node::Parent { vertical, ratio, .. }) = self.nodes.get_mut(node) else {
+			return false;
+		};
+		let (p, range) = axis(&rect, position, *vertical);
+		let (low, high) = (*range.start(), *range.end());
+		let len = high - low + 1;
+		if len < min * 2 {
+			return false;
+		}
+		let new = Ratio::from_fraction(p.clamp(low + min, high + 1 - min) - low, len);
+		let changed = *ratio != new;
+		*ratio = new;
+		changed
+	}
+
 	/// Return an iterator over all window handles held by this workspace.
 	pub fn windows(&self) -> impl Iterator<Item = Handle> + '_ {
 		self.nodes.iter().flat_map(|(_, n)| match n {
@@ -184,6 +265,24 @@ impl Workspace {
 		self.nodes.is_empty()
 	}
 
+	/// Calculate the [`Rect`] of any node.
+	fn node_rect(&self, node: Handle, size: Size) -> Option<Rect> {
+		fn f(slf: &Workspace, cur: Handle, rect: Rect, node: Handle) -> Option<Rect> {
+			if cur == node {
+				return Some(rect);
+			}
+			match &slf.nodes[cur] {
+				Node::Parent { left, right, vertical, ratio } => {
+					let (rect_l, rect_r) = split_rect(rect, *vertical, *ratio);
+					f(slf, *left, rect_l, node).or_else(|| f(slf, *right, rect_r, node))
+				}
+				Node::Leaf { .. } => None,
+			}
+		}
+		self.nodes.get(self.root)?;
+		f(self, self.root, Rect::from_size(Point2::ORIGIN, size), node)
+	}
+
 	/// Recurse in the tree, going left (`false`) or right (`true`) based on the given predicate.
 	///
 	/// Returns the handle of the window if any were found as well as the calculated rect.
@@ -196,21 +295,7 @@ impl Workspace {
 		loop {
 			match cur {
 				Node::Parent { left, right, ratio, vertical } => {
-					let (rect_l, rect_r) = if *vertical {
-						let mid = ratio.partition_range(rect.y());
-						let (y_l, y_r) = (rect.low().y..=mid, mid + 1..=rect.high().y);
-						(
-							Rect::from_ranges(rect.x(), y_l),
-							Rect::from_ranges(rect.x(), y_r),
-						)
-					} else {
-						let mid = ratio.partition_range(rect.x());
-						let (x_l, x_r) = (rect.low().x..=mid, mid + 1..=rect.high().x);
-						(
-							Rect::from_ranges(x_l, rect.y()),
-							Rect::from_ranges(x_r, rect.y()),
-						)
-					};
+					let (rect_l, rect_r) = split_rect(rect, *vertical, *ratio);
 					cur = &self.nodes[*if pred(&rect_l, &rect_r) {
 						rect = rect_r;
 						right
@@ -225,6 +310,69 @@ impl Workspace {
 	}
 }
 
+/// Split a rect in two along the border determined by the ratio.
+fn split_rect(rect: Rect, vertical: bool, ratio: Ratio) -> (Rect, Rect) {
+	if vertical {
+		let mid = ratio.partition_range(rect.y());
+		let (y_l, y_r) = (rect.low().y..=mid, mid + 1..=rect.high().y);
+		(
+			Rect::from_ranges(rect.x(), y_l),
+			Rect::from_ranges(rect.x(), y_r),
+		)
+	} else {
+		let mid = ratio.partition_range(rect.x());
+		let (x_l, x_r) = (rect.low().x..=mid, mid + 1..=rect.high().x);
+		(
+			Rect::from_ranges(x_l, rect.y()),
+			Rect::from_ranges(x_r, rect.y()),
+		)
+	}
+}
+
+/// Get the coordinate of the position and the range of the rect along the split axis.
+fn axis(rect: &Rect, position: Point2, vertical: bool) -> (u32, RangeInclusive<u32>) {
+	if vertical {
+		(position.y, rect.y())
+	} else {
+		(position.x, rect.x())
+	}
+}
+
+/// The fraction of a node assigned to the left or top child, in units of `1 / 65536`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ratio(u16);
+
+impl Ratio {
+	pub const HALF: Self = Self(1 << 15);
+
+	/// Create a ratio such that the left child of a node of size `len` gets `part` units.
+	pub fn from_fraction(part: u32, len: u32) -> Self {
+		assert!(part <= len, "part is larger than whole");
+		// Round up so partition returns the same value.
+		let r = ((u64::from(part) << 16) + u64::from(len) - 1) / u64::from(len);
+		Self(r.min(u16::MAX.into()) as u16)
+	}
+
+	/// Split a length in two.
+	pub fn partition(self, len: u32) -> (u32, u32) {
+		let l = ((u64::from(len) * u64::from(self.0)) >> 16) as u32;
+		(l, len - l)
+	}
+
+	/// Get the last coordinate of the left part of the range.
+	pub fn partition_range(self, range: RangeInclusive<u32>) -> u32 {
+		let (low, high) = (*range.start(), *range.end());
+		let (l, _) = self.partition(high - low + 1);
+		low + l.max(1) - 1
+	}
+}
+
+impl Default for Ratio {
+	fn default() -> Self {
+		Self::HALF
+	}
+}
+
 #[derive(Clone, Copy)]
 pub struct Path {
 	pub depth: u8,
@@ -254,9 +402,7 @@ impl fmt::Debug for Path {
 
 #[derive(Clone, Copy)]
 pub enum Direction {
-	#[allow(dead_code)]
 	Left,
-	#[allow(dead_code)]
 	Up,
 	Right,
 	Down,
@@ -340,6 +486,64 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn ratio_from_fraction_roundtrip() {
+		for len in [1, 7, 100, 1920] {
+			for part in 0..len {
+				assert_eq!(Ratio::from_fraction(part, len).partition(len).0, part);
+			}
+		}
+	}
+
+	#[test]
+	fn border_at_half() {
+		let mut ws = ws();
+		split_leaf_dir(&mut ws, Direction::Up);
+		split_leaf_dir(&mut ws, Direction::Right);
+		let size = Size::new(100, 100);
+		assert_eq!(ws.border_at(Point2::new(51, 10), size, 2), Some(ws.root));
+		assert_eq!(ws.border_at(Point2::new(20, 10), size, 2), None);
+	}
+
+	#[test]
+	fn move_border_clamp() {
+		let mut ws = ws();
+		split_leaf_dir(&mut ws, Direction::Up);
+		let path = split_leaf_dir(&mut ws, Direction::Left);
+		let size = Size::new(100, 100);
+		assert!(ws.move_border(ws.root, Point2::new(25, 10), size, 10));
+		assert_eq!(
+			ws.calculate_rect(path.into_iter(), size),
+			Some(Rect::from_size(Point2::ORIGIN, Size::new(25, 100))),
+		);
+		assert!(ws.move_border(ws.root, Point2::new(0, 10), size, 10));
+		assert_eq!(
+			ws.calculate_rect(path.into_iter(), size),
+			Some(Rect::from_size(Point2::ORIGIN, Size::new(10, 100))),
+		);
+	}
+
+	#[test]
+	fn leaf_position_restore() {
+		let mut ws = ws();
+		split_leaf_dir(&mut ws, Direction::Up);
+		let path = split_leaf_dir(&mut ws, Direction::Up);
+		let size = Size::new(100, 100);
+		assert!(ws.move_border(ws.root, Point2::new(10, 30), size, 10));
+		let rect = ws.calculate_rect(path.into_iter(), size);
+
+		let (pos, split) = ws.leaf_position(path.into_iter());
+		assert_eq!((pos.depth, pos.directions), (path.depth, path.directions));
+		let (dir, ratio) = split.unwrap();
+		assert!(matches!(dir, Direction::Up));
+
+		ws.remove_leaf(path.into_iter());
+		let (path, _) = ws
+			.split_leaf(PathIter::default(), 1, Some(dir), ratio, size)
+			.unwrap();
+		assert_eq!(ws.calculate_rect(path.into_iter(), size), rect);
+	}
+
 	#[test]
 	fn split_leaf_path_root_only() {
 		let mut ws = ws();
@@ -6,15 +6,21 @@ extern crate alloc;
 use alloc::boxed::Box;
 
 use {
-	alloc::string::ToString,
 	core::{
+		mem,
 		ptr::NonNull,
 		sync::atomic::{AtomicU32, Ordering},
 		time::Duration,
 	},
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	driver_utils::{
+		os::{
+			display::{Buffer, Display, DisplayDevice},
+			stream_table::StreamTable,
+		},
+		Handle,
+	},
 	framebuffer::{Bgrx8888, FrameBuffer, Rgbx8888},
-	rt::{sync::Mutex, Error},
+	rt::sync::Mutex,
 	rt_default as _,
 };
 
@@ -37,79 +43,53 @@ fn main() -> ! {
 	let [bpp, r_pos, r_mask, g_pos, g_mask, b_pos, b_mask]: [u8; 7] =
 		fb_info[8..].try_into().unwrap();
 
-	assert_eq!((bpp, r_mask, g_mask, b_mask), (32, 8, 8, 8));
+	let packed = match bpp {
+		32 => {
+			assert_eq!((r_mask, g_mask, b_mask), (8, 8, 8));
+			None
+		}
+		16 | 24 => Some(
+			PackedFormat::new(bpp, (r_pos, r_mask), (g_pos, g_mask), (b_pos, b_mask))
+				.expect("unsupported pixel format"),
+		),
+		_ => panic!("unsupported bits per pixel: {}", bpp),
+	};
 
 	let map_len = stride as usize * (height as usize + 1);
 	let (base, len) = fb.map_object(None, rt::RWX::RW, 0, map_len).unwrap();
 	assert!(len >= map_len);
 
-	// Encoding doesn't matter, really
-	let mut back_fb = unsafe { FrameBuffer::<Rgbx8888>::new(base.cast(), width, height, stride) };
+	let mut back_fb = match packed {
+		// Encoding doesn't matter, really
+		None => BackFb::Direct(unsafe {
+			FrameBuffer::<Rgbx8888>::new(base.cast(), width, height, stride)
+		}),
+		Some(format) => BackFb::Packed { base, stride, format },
+	};
 
 	let fb_stride = (u32::from(width) + 1) * 4;
 	let fb_len = fb_stride as usize * (usize::from(height) + 1);
 	let (fb_ptr, _) = rt::mem::alloc(None, fb_len, rt::RWX::RW).unwrap();
-	enum Fb {
-		Rgbx8888(FrameBuffer<Rgbx8888>),
-		Bgrx8888(FrameBuffer<Bgrx8888>),
-	}
-	let mut fb = unsafe {
+	let fb = unsafe {
 		match (r_pos, g_pos, b_pos) {
-			(0, 8, 16) => Fb::Rgbx8888(FrameBuffer::new(fb_ptr.cast(), width, height, fb_stride)),
-			(16, 8, 0) => Fb::Bgrx8888(FrameBuffer::new(fb_ptr.cast(), width, height, fb_stride)),
+			// Pixels are converted when flushing to the real framebuffer.
+			_ if packed.is_some() => Fb::new(false, fb_ptr.cast(), width, height, fb_stride),
+			(0, 8, 16) => Fb::new(false, fb_ptr.cast(), width, height, fb_stride),
+			(16, 8, 0) => Fb::new(true, fb_ptr.cast(), width, height, fb_stride),
 			_ => panic!("unsupported pixel format"),
 		}
 	};
 
 	let (tbl, _) = rt::Object::new(rt::NewObject::SharedMemory { size: 1 << 8 }).unwrap();
 	let tbl = StreamTable::new(&tbl, 64.try_into().unwrap(), 64.try_into().unwrap());
-	share.create(b"gpu").unwrap().share(tbl.public()).unwrap();
-
-	let mut buffers = driver_utils::Arena::new();
-
-	// AtomicU32 is more efficient than AtomicBool on some architectures (e.g. RISC-V).
-	static CHANGES: AtomicU32 = AtomicU32::new(0);
-	static CURSOR: Mutex<Cursor> = Mutex::new({
-		let cur @ prev = CursorRect { x: 0, y: 0, w: 0, h: 0 };
-		Cursor { cur, prev, img: [0; 64 * 64] }
-	});
-
-	let mut cursor_img = [0i32; 64 * 64];
-	let project_cursor = |fb: &Fb, cursor: &[i32; 64 * 64], cc: &mut Cursor| {
-		let c = &cc.cur;
-		let w = u16::from(c.w).min(width - c.x);
-		let h = u16::from(c.h).min(height - c.y);
-		let region = match fb {
-			Fb::Rgbx8888(fb) => fb.iter_region(c.x, c.y, w, h),
-			Fb::Bgrx8888(fb) => fb.iter_region(c.x, c.y, w, h),
-		};
-		for (y, r) in region.enumerate() {
-			for (x, e) in r.enumerate() {
-				let i = y * (usize::from(c.w) + 1) + x;
-				let [p @ .., a] = cursor[i].to_le_bytes();
-				let [q @ .., _] = e.to_le_bytes();
-				let mut w = [0; 4];
-				for ((w, p), q) in w.iter_mut().zip(p).zip(q) {
-					*w = ((p as u32 * a as u32 + q as u32 * (255 - a as u32)) / 255) as u8;
-				}
-				cc.img[i] = i32::from_le_bytes(w);
-			}
-		}
-	};
-
-	#[derive(Clone, Copy)]
-	struct CursorRect {
-		x: u16,
-		y: u16,
-		w: u8,
-		h: u8,
-	}
+	share
+		.create_with_type(b"gpu", b"gpu")
+		.unwrap()
+		.share(tbl.public())
+		.unwrap();
 
-	struct Cursor {
-		cur: CursorRect,
-		prev: CursorRect,
-		img: [i32; 64 * 64],
-	}
+	let mut display = Display::new();
+	let mut dev = Device { fb, cursor_img: [0; 64 * 64], width, height };
 
 	rt::thread::Thread::new(
 		1 << 10,
@@ -121,28 +101,32 @@ fn main() -> ! {
 			// Right now this thread wakes 60 times per second, which isn't very efficient.
 			let changes = CHANGES.fetch_and(0, Ordering::Acquire);
 			if changes & 1 != 0 {
-				// Flush the entire screen
-				//
-				// TODO investigate methods to reduce the amount of data copied without adding
-				// excessive overhead.
-				// It is not high priority as it is still plenty fast (can flush 1080p in ~3ms!)
-				// but it would be nice to save some energy.
-				unsafe {
-					back_fb.copy_from_raw_untrusted_32(
-						fb_ptr.cast().as_ptr(),
-						fb_stride,
-						0,
-						0,
-						width,
-						height,
-					)
+				// Only flush the area that has actually been written to.
+				if let Some(d) = DIRTY.lock().take() {
+					unsafe {
+						let ptr = fb_ptr
+							.as_ptr()
+							.add(fb_stride as usize * usize::from(d.y0))
+							.cast::<i32>()
+							.add(usize::from(d.x0));
+						back_fb.copy_from_raw_untrusted_32(
+							ptr,
+							fb_stride,
+							d.x0,
+							d.y0,
+							d.x1 - d.x0,
+							d.y1 - d.y0,
+						)
+					}
 				}
 			}
 			if changes & 3 != 0 {
 				let mut cc = CURSOR.lock();
 				// Clear the previous cursor
+				//
+				// This is necessary even if a flush happened as it may not cover the cursor.
 				let c = &cc.prev;
-				if changes & 1 == 0 && c.x <= width && c.y <= height {
+				if c.x <= width && c.y <= height {
 					unsafe {
 						let ptr = fb_ptr
 							.as_ptr()
@@ -185,136 +169,378 @@ fn main() -> ! {
 	loop {
 		tbl.wait();
 		let mut flush = false;
-		while let Some((_, job_id, req)) = tbl.dequeue() {
-			let resp = match req {
-				Request::GetMeta { property } => match &*property.get(&mut [0; 64]) {
-					b"resolution" => {
-						let (w, h) = (width.to_string(), height.to_string());
-						let data = tbl.alloc(w.len() + 1 + h.len()).expect("out of buffers");
-						data.copy_from(0, w.as_bytes());
-						data.copy_from(w.len(), &[b'x']);
-						data.copy_from(w.len() + 1, h.as_bytes());
-						Response::Data(data)
-					}
-					b"bin/resolution" => {
-						let r = ipc_gpu::Resolution { x: width as _, y: height as _ }.encode();
-						let data = tbl.alloc(r.len()).unwrap();
-						data.copy_from(0, &r);
-						Response::Data(data)
-					}
-					_ => Response::Error(Error::DoesNotExist),
-				},
-				Request::SetMeta { property_value } => match property_value.try_get(&mut [0; 64]) {
-					Ok((b"bin/cursor/pos", &mut [a, b, c, d])) => {
-						let x = u16::from_le_bytes([a, b]);
-						let y = u16::from_le_bytes([c, d]);
-						let mut c = CURSOR.lock();
-						(c.cur.x, c.cur.y) = (x, y);
-						project_cursor(&fb, &cursor_img, &mut c);
-						drop(c);
-						CHANGES.fetch_or(2, Ordering::Release);
-						Response::Amount(0)
-					}
-					Ok((b"bin/cursor/pos", _)) => Response::Error(Error::InvalidData),
-					Ok((b"bin/buffer/unmap", &mut [a, b, c, d])) => {
-						let buffer_id = u32::from_le_bytes([a, b, c, d]);
-						if buffers.remove(buffer_id).is_some() {
-							rt::dbg!();
-							Response::Amount(0)
-						} else {
-							Response::Error(Error::InvalidData)
-						}
-					}
-					Ok(_) => Response::Error(Error::DoesNotExist),
-					Err(_) => Response::Error(Error::InvalidData),
-				},
-				Request::Write { data } => {
-					let mut buf = [0; 64];
-					let (d, _) = data.copy_into(&mut buf);
-					// Blit a specific area
-					if let Ok(d) = d.try_into() {
-						let cmd = ipc_gpu::Flush::decode(d);
-						let buf: &Buffer = buffers.get(cmd.buffer_id).unwrap(); // FIXME don't panic
-						assert!(cmd.stride != 0 && cmd.size.x != 0 && cmd.size.y != 0);
-						unsafe {
-							match &mut fb {
-								Fb::Rgbx8888(fb) => fb.copy_from_raw_untrusted_rgb24_to_rgbx32(
-									buf.ptr.as_ptr().add(cmd.offset as _).cast(),
-									cmd.stride * 3,
-									cmd.origin.x as _,
-									cmd.origin.y as _,
-									(cmd.size.x - 1) as _,
-									(cmd.size.y - 1) as _,
-								),
-								Fb::Bgrx8888(fb) => fb.copy_from_raw_untrusted_rgb24_to_bgrx32(
-									buf.ptr.as_ptr().add(cmd.offset as _).cast(),
-									cmd.stride * 3,
-									cmd.origin.x as _,
-									cmd.origin.y as _,
-									(cmd.size.x - 1) as _,
-									(cmd.size.y - 1) as _,
-								),
-							}
+		while let Some((handle, job_id, req)) = tbl.dequeue() {
+			if let Some(resp) = display.handle(&mut dev, &tbl, handle, req) {
+				tbl.enqueue(job_id, resp);
+				flush = true;
+			}
+		}
+		flush.then(|| tbl.flush());
+	}
+}
+
+// AtomicU32 is more efficient than AtomicBool on some architectures (e.g. RISC-V).
+static CHANGES: AtomicU32 = AtomicU32::new(0);
+static CURSOR: Mutex<Cursor> = Mutex::new({
+	let cur @ prev = CursorRect { x: 0, y: 0, w: 0, h: 0 };
+	Cursor { cur, prev, img: [0; 64 * 64] }
+});
+static DIRTY: Mutex<Option<DirtyRect>> = Mutex::new(None);
+
+#[derive(Clone, Copy)]
+struct CursorRect {
+	x: u16,
+	y: u16,
+	w: u8,
+	h: u8,
+}
+
+struct Cursor {
+	cur: CursorRect,
+	prev: CursorRect,
+	img: [i32; 64 * 64],
+}
+
+/// Inclusive bounds of the area that needs to be flushed.
+#[derive(Clone, Copy)]
+struct DirtyRect {
+	x0: u16,
+	y0: u16,
+	x1: u16,
+	y1: u16,
+}
+
+impl DirtyRect {
+	fn union(self, rhs: Self) -> Self {
+		Self {
+			x0: self.x0.min(rhs.x0),
+			y0: self.y0.min(rhs.y0),
+			x1: self.x1.max(rhs.x1),
+			y1: self.y1.max(rhs.y1),
+		}
+	}
+}
+
+/// Draws to the framebuffer in memory. The flush thread copies changes to the real framebuffer.
+struct Device {
+	fb: Fb,
+	cursor_img: [i32; 64 * 64],
+	width: u16,
+	height: u16,
+}
+
+impl Device {
+	/// Copy the area under the cursor and draw the cursor on top of it.
+	fn project_cursor(&self, cc: &mut Cursor) {
+		let c = cc.cur;
+		if c.x > self.width || c.y > self.height {
+			return;
+		}
+		let w = u16::from(c.w).min(self.width - c.x);
+		let h = u16::from(c.h).min(self.height - c.y);
+		let stride = usize::from(c.w) + 1;
+		// Copy the area under the cursor first, then draw the cursor on top of it.
+		for (y, r) in self.fb.iter_region(c.x, c.y, w, h).enumerate() {
+			for (x, e) in r.enumerate() {
+				cc.img[y * stride + x] = e;
+			}
+		}
+		let mut img = unsafe {
+			Fb::new(
+				self.fb.is_bgrx(),
+				NonNull::from(&mut cc.img).cast(),
+				c.w.into(),
+				c.h.into(),
+				stride as u32 * 4,
+			)
+		};
+		img.blend_from_rgba32(&self.cursor_img, stride, 0, 0, w, h);
+	}
+}
+
+impl DisplayDevice for Device {
+	fn resolution(&self) -> ipc_gpu::Resolution {
+		ipc_gpu::Resolution { x: self.width.into(), y: self.height.into() }
+	}
+
+	fn flush(&mut self, _: Handle, buf: &Buffer, cmd: ipc_gpu::Flush) {
+		unsafe {
+			let src = buf.as_ptr().as_ptr().add(cmd.offset as _).cast();
+			let stride = cmd.stride * 3;
+			let (x, y) = (cmd.origin.x as _, cmd.origin.y as _);
+			let (w, h) = (cmd.size.x as _, cmd.size.y as _);
+			match &mut self.fb.format {
+				FbFormat::Rgbx8888(fb) => {
+					fb.copy_from_raw_untrusted_rgb24_to_rgbx32(src, stride, x, y, w, h)
+				}
+				FbFormat::Bgrx8888(fb) => {
+					fb.copy_from_raw_untrusted_rgb24_to_bgrx32(src, stride, x, y, w, h)
+				}
+			}
+		}
+		self.project_cursor(&mut CURSOR.lock());
+		let (width, height) = (u32::from(self.width), u32::from(self.height));
+		let (x, y) = (cmd.origin.x, cmd.origin.y);
+		let (w, h) = (u32::from(cmd.size.x), u32::from(cmd.size.y));
+		let rect = DirtyRect {
+			x0: x.min(width) as u16,
+			y0: y.min(height) as u16,
+			x1: x.saturating_add(w).min(width) as u16,
+			y1: y.saturating_add(h).min(height) as u16,
+		};
+		let mut dirty = DIRTY.lock();
+		*dirty = Some(dirty.map_or(rect, |r| r.union(rect)));
+		drop(dirty);
+		CHANGES.fetch_or(1, Ordering::Release);
+	}
+
+	fn set_cursor_image(&mut self, buf: &Buffer, w: u8, h: u8) {
+		let l = (usize::from(w) + 1) * (usize::from(h) + 1);
+		// FIXME untrusted
+		unsafe {
+			buf.as_ptr()
+				.as_ptr()
+				.cast::<i32>()
+				.copy_to_nonoverlapping(self.cursor_img.as_mut_ptr(), l);
+		}
+		let mut c = CURSOR.lock();
+		(c.cur.w, c.cur.h) = (w, h);
+		self.project_cursor(&mut c);
+		drop(c);
+		CHANGES.fetch_or(2, Ordering::Release);
+	}
+
+	fn set_cursor_position(&mut self, x: u16, y: u16) {
+		let mut c = CURSOR.lock();
+		(c.cur.x, c.cur.y) = (x, y);
+		self.project_cursor(&mut c);
+		drop(c);
+		CHANGES.fetch_or(2, Ordering::Release);
+	}
+}
+
+/// A framebuffer in memory that clients draw to.
+struct Fb {
+	width: u16,
+	height: u16,
+	format: FbFormat,
+}
+
+enum FbFormat {
+	Rgbx8888(FrameBuffer<Rgbx8888>),
+	Bgrx8888(FrameBuffer<Bgrx8888>),
+}
+
+impl Fb {
+	/// # Safety
+	///
+	/// See [`FrameBuffer::new`].
+	unsafe fn new(bgrx: bool, base: NonNull<i32>, width: u16, height: u16, stride: u32) -> Self {
+		let format = if bgrx {
+			FbFormat::Bgrx8888(FrameBuffer::new(base, width, height, stride))
+		} else {
+			FbFormat::Rgbx8888(FrameBuffer::new(base, width, height, stride))
+		};
+		Self { width, height, format }
+	}
+
+	fn is_bgrx(&self) -> bool {
+		matches!(self.format, FbFormat::Bgrx8888(_))
+	}
+
+	fn iter_region(
+		&self,
+		x: u16,
+		y: u16,
+		w: u16,
+		h: u16,
+	) -> impl Iterator<Item = impl Iterator<Item = i32> + '_> + '_ {
+		let region = match &self.format {
+			FbFormat::Rgbx8888(fb) => fb.iter_region(x, y, w, h),
+			FbFormat::Bgrx8888(fb) => fb.iter_region(x, y, w, h),
+		};
+		region.map(|r| r.map(|e| i32::from_le_bytes(e.to_le_bytes())))
+	}
+
+	/// Composite a 32-bit RGBA image over the given area.
+	///
+	/// `src_stride` is in pixels. `w` and `h` are inclusive, like the rest of the `FrameBuffer`
+	/// API. The area is clipped to the edges of the framebuffer.
+	///
+	/// This is considerably slower than the `copy_from_raw_*` methods, so only use it for images
+	/// that are actually translucent.
+	fn blend_from_rgba32(
+		&mut self,
+		src: &[i32],
+		src_stride: usize,
+		x: u16,
+		y: u16,
+		w: u16,
+		h: u16,
+	) {
+		if x > self.width || y > self.height {
+			return;
+		}
+		let w = w.min(self.width - x);
+		let h = h.min(self.height - y);
+		let bgrx = self.is_bgrx();
+		// Blend in chunks so we don't need to allocate a buffer for an entire row.
+		let mut row = [0i32; 64];
+		for dy in 0..=h {
+			let src = &src[usize::from(dy) * src_stride..];
+			for dx in (0..=w).step_by(row.len()) {
+				let cw = (w - dx).min(row.len() as u16 - 1);
+				let (cx, cy) = (x + dx, y + dy);
+				let row = &mut row[..=usize::from(cw)];
+				for (r, e) in row
+					.iter_mut()
+					.zip(self.iter_region(cx, cy, cw, 0).flatten())
+				{
+					*r = e;
+				}
+				for (r, s) in row.iter_mut().zip(&src[usize::from(dx)..]) {
+					*r = blend_rgba32(*s, *r, bgrx);
+				}
+				// SAFETY: row is valid for the region being copied.
+				let stride = row.len() as u32 * 4;
+				unsafe {
+					match &mut self.format {
+						FbFormat::Rgbx8888(fb) => {
+							fb.copy_from_raw_32(row.as_ptr(), stride, cx, cy, cw, 0)
 						}
-						project_cursor(&fb, &cursor_img, &mut CURSOR.lock());
-						CHANGES.store(1, Ordering::Release);
-						Response::Amount(d.len().try_into().unwrap())
-					} else if let Ok([0xc5, a, b, c, d, w, h]) = <[u8; 7]>::try_from(&*d) {
-						let buffer_id = u32::from_le_bytes([a, b, c, d]);
-						let buf: &Buffer = buffers.get(buffer_id).unwrap(); // FIXME don't panic
-						let l = (usize::from(w) + 1) * (usize::from(h) + 1);
-						if l * 4 <= buf.len {
-							// FIXME untrusted
-							unsafe {
-								buf.ptr
-									.as_ptr()
-									.cast::<i32>()
-									.copy_to_nonoverlapping(cursor_img.as_mut_ptr(), l);
-							}
-							let mut c = CURSOR.lock();
-							(c.cur.w, c.cur.h) = (w, h);
-							project_cursor(&fb, &cursor_img, &mut c);
-							drop(c);
-							CHANGES.fetch_or(2, Ordering::Release);
-							Response::Amount(l as _)
-						} else {
-							Response::Error(Error::InvalidData)
+						FbFormat::Bgrx8888(fb) => {
+							fb.copy_from_raw_32(row.as_ptr(), stride, cx, cy, cw, 0)
 						}
-					} else {
-						Response::Error(Error::InvalidData)
 					}
 				}
-				Request::Share { share } => {
-					Buffer::new(share).map_or_else(Response::Error, |buf| {
-						let h = buffers.insert(buf);
-						Response::Amount(h.into())
-					})
-				}
-				Request::Close => continue,
-				_ => Response::Error(Error::InvalidOperation),
-			};
-			tbl.enqueue(job_id, resp);
-			flush = true;
+			}
 		}
-		flush.then(|| tbl.flush());
 	}
 }
 
-pub struct Buffer {
-	ptr: NonNull<u8>,
-	len: usize,
+/// Composite a RGBA pixel over an opaque RGBX or BGRX pixel.
+fn blend_rgba32(src: i32, dst: i32, bgrx: bool) -> i32 {
+	let [mut r, g, mut b, a] = src.to_le_bytes();
+	if bgrx {
+		mem::swap(&mut r, &mut b);
+	}
+	let a = u32::from(a);
+	let [q @ .., _] = dst.to_le_bytes();
+	let mut w = [0; 4];
+	for ((w, p), q) in w.iter_mut().zip([r, g, b]).zip(q) {
+		// Premultiply the source and add whatever is left of the destination.
+		*w = ((u32::from(p) * a + u32::from(q) * (255 - a) + 127) / 255) as u8;
+	}
+	i32::from_le_bytes(w)
+}
+
+/// The hardware framebuffer.
+enum BackFb {
+	Direct(FrameBuffer<Rgbx8888>),
+	Packed { base: NonNull<u8>, stride: u32, format: PackedFormat },
 }
 
-impl Buffer {
-	pub fn new(obj: rt::Object) -> rt::io::Result<Self> {
-		obj.map_object(None, rt::io::RWX::R, 0, 1 << 30)
-			.map(|(ptr, len)| Self { ptr, len })
+impl BackFb {
+	/// # Safety
+	///
+	/// See [`FrameBuffer::copy_from_raw_untrusted_32`].
+	unsafe fn copy_from_raw_untrusted_32(
+		&mut self,
+		src: *const i32,
+		src_stride: u32,
+		x: u16,
+		y: u16,
+		w: u16,
+		h: u16,
+	) {
+		match self {
+			Self::Direct(fb) => fb.copy_from_raw_untrusted_32(src, src_stride, x, y, w, h),
+			Self::Packed { base, stride, format } => {
+				format.copy_from_raw_32(*base, *stride, src, src_stride, x, y, w, h)
+			}
+		}
+	}
+
+	/// # Safety
+	///
+	/// See [`FrameBuffer::copy_from_raw_32`].
+	unsafe fn copy_from_raw_32(
+		&mut self,
+		src: *const i32,
+		src_stride: u32,
+		x: u16,
+		y: u16,
+		w: u16,
+		h: u16,
+	) {
+		match self {
+			Self::Direct(fb) => fb.copy_from_raw_32(src, src_stride, x, y, w, h),
+			Self::Packed { base, stride, format } => {
+				format.copy_from_raw_32(*base, *stride, src, src_stride, x, y, w, h)
+			}
+		}
 	}
 }
 
-impl Drop for Buffer {
-	fn drop(&mut self) {
-		// SAFETY; we have exclusive access to the buffer.
-		let _ = unsafe { rt::mem::dealloc(self.ptr, self.len) };
+/// A 16 or 24 bits per pixel format, e.g. RGB565 or packed RGB888.
+///
+/// These aren't supported by the `framebuffer` crate, so pixels are converted from RGBX8888 one
+/// at a time. This is slow but only needs to be done when flushing.
+#[derive(Clone, Copy)]
+struct PackedFormat {
+	bytes: u8,
+	/// Position and size in bits of each component.
+	r: (u8, u8),
+	g: (u8, u8),
+	b: (u8, u8),
+}
+
+impl PackedFormat {
+	fn new(bpp: u8, r: (u8, u8), g: (u8, u8), b: (u8, u8)) -> Option<Self> {
+		let fits = |(pos, mask): (u8, u8)| (1..=8).contains(&mask) && pos + mask <= bpp;
+		(matches!(bpp, 16 | 24) && fits(r) && fits(g) && fits(b)).then(|| Self {
+			bytes: bpp / 8,
+			r,
+			g,
+			b,
+		})
+	}
+
+	fn pack(&self, rgbx: u32) -> u32 {
+		let [r, g, b, _] = rgbx.to_le_bytes();
+		let f = |c: u8, (pos, mask): (u8, u8)| u32::from(c >> (8 - mask)) << pos;
+		f(r, self.r) | f(g, self.g) | f(b, self.b)
+	}
+
+	/// # Safety
+	///
+	/// `src` must be valid for the given region. `dst` must point to a framebuffer large enough
+	/// to contain the region.
+	#[allow(clippy::too_many_arguments)]
+	unsafe fn copy_from_raw_32(
+		&self,
+		dst: NonNull<u8>,
+		dst_stride: u32,
+		src: *const i32,
+		src_stride: u32,
+		x: u16,
+		y: u16,
+		w: u16,
+		h: u16,
+	) {
+		let bytes = usize::from(self.bytes);
+		for iy in 0..=usize::from(h) {
+			let s = src.cast::<u8>().add(iy * src_stride as usize).cast::<u32>();
+			let d = dst
+				.as_ptr()
+				.add((usize::from(y) + iy) * dst_stride as usize)
+				.add(usize::from(x) * bytes);
+			for ix in 0..=usize::from(w) {
+				let p = self.pack(s.add(ix).read_volatile()).to_le_bytes();
+				for (k, b) in p[..bytes].iter().enumerate() {
+					d.add(ix * bytes + k).write_volatile(*b);
+				}
+			}
+		}
 	}
 }
@@ -11,6 +11,9 @@ use {
 	},
 };
 
+/// The maximum amount of data to read from a file at once.
+const MAX_READ: u32 = 1 << 15;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 	let mut args = std::env::args().skip(1);
 	let table_name = args.next().ok_or("expected table name")?;
@@ -38,7 +41,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 	let mut objects = driver_utils::Arena::new();
 	enum Object {
-		File(String, u64),
+		/// The path, the current offset and data that was read ahead of the offset.
+		File(String, u64, Vec<u8>),
 		#[allow(dead_code)]
 		Dir(String, u64),
 		Query(Vec<String>, usize),
@@ -66,9 +70,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 								Response::Handle(objects.insert(Object::Query(entries, 0)))
 							}
 							Ok(path) => match fs.root_dir().open_file(path) {
-								Ok(_) => Response::Handle(
-									objects.insert(Object::File(path.to_string(), 0u64)),
-								),
+								Ok(_) => Response::Handle(objects.insert(Object::File(
+									path.to_string(),
+									0u64,
+									Vec::new(),
+								))),
 								Err(e) => Response::Error(match e {
 									fatfs::Error::NotFound => rt::Error::DoesNotExist,
 									fatfs::Error::AlreadyExists => rt::Error::AlreadyExists,
@@ -89,16 +95,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 							Ok("") | Ok("/") => Response::Error(rt::Error::AlreadyExists),
 							Ok(path) if path.bytes().last() == Some(b'/') => {
 								match fs.root_dir().create_dir(&path[..path.len() - 1]) {
-									Ok(_) => Response::Handle(
-										objects.insert(Object::File(path.to_string(), 0u64)),
-									),
+									Ok(_) => Response::Handle(objects.insert(Object::File(
+										path.to_string(),
+										0u64,
+										Vec::new(),
+									))),
 									Err(e) => todo!("{:?}", e),
 								}
 							}
 							Ok(path) => match fs.root_dir().create_file(path) {
 								Ok(mut f) => {
 									f.truncate().unwrap();
-									let h = objects.insert(Object::File(path.to_string(), 0u64));
+									let h = objects.insert(Object::File(
+										path.to_string(),
+										0u64,
+										Vec::new(),
+									));
 									Response::Handle(h)
 								}
 								Err(e) => todo!("{:?}", e),
@@ -108,15 +120,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 					}
 				}
 				Request::Read { amount } => match &mut objects[handle] {
-					Object::File(path, offset) => {
-						let mut file = fs.root_dir().open_file(path).unwrap();
-						file.seek(std::io::SeekFrom::Start(*offset)).unwrap();
-						let len = amount.min(4096) as usize;
-						let len = file.read(&mut buf[..len]).unwrap();
-						let data = tbl.alloc(len).expect("out of buffers");
-						data.copy_from(0, &buf[..len]);
+					Object::File(path, offset, ahead) => {
+						// Large reads are sent in chunks. Whatever isn't sent yet is kept and
+						// used for the next read.
+						if ahead.is_empty() {
+							let mut file = fs.root_dir().open_file(path).unwrap();
+							file.seek(std::io::SeekFrom::Start(*offset)).unwrap();
+							ahead.resize(amount.min(MAX_READ) as usize, 0);
+							let len = file.read(ahead).unwrap();
+							ahead.truncate(len);
+						}
+						let data = &ahead[..ahead.len().min(amount as usize)];
+						let len = tbl.enqueue_chunked(job_id, data, buf.len());
+						ahead.drain(..len);
 						*offset += u64::try_from(len).unwrap();
-						Response::Data(data)
+						flush = true;
+						continue;
 					}
 					Object::Dir(..) => todo!(),
 					Object::Query(list, index) => {
@@ -133,7 +152,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 					}
 				},
 				Request::Write { data } => match &mut objects[handle] {
-					Object::File(path, offset) => {
+					Object::File(path, offset, ahead) => {
+						ahead.clear();
 						let l = data.len();
 						data.copy_to(0, &mut buf[..l]);
 						let mut file = fs.root_dir().open_file(path).unwrap();
@@ -149,7 +169,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 				Request::Seek { from } => {
 					use rt::io::SeekFrom;
 					match &mut objects[handle] {
-						Object::File(path, offset) => {
+						Object::File(path, offset, ahead) => {
+							ahead.clear();
 							match from {
 								SeekFrom::Start(n) => *offset = n,
 								SeekFrom::Current(n) => *offset = offset.wrapping_add(n as u64),
@@ -202,6 +223,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 					_ => Response::Error(rt::Error::DoesNotExist),
 				},
 				Request::SetMeta { .. } => todo!(),
+				Request::Readv { .. }
+				| Request::Writev { .. }
+				| Request::ReadAt { .. }
+				| Request::WriteAt { .. } => Response::Error(rt::Error::InvalidOperation),
 			};
 			tbl.enqueue(job_id, resp);
 			flush = true;
@@ -1,24 +1,42 @@
+//! ## Modifying the table
+//!
+//! Partitions can be added by creating an object on the root. The path has the form
+//! `<type-guid> <guid> <start-lba> <end-lba> [name]`, where the LBA range is inclusive. The new
+//! entry is put in the first free slot and a handle to the partition is returned.
+//!
+//! Partitions can be removed by destroying them on the root. The path is the index of the
+//! partition in decimal, e.g. `2`, as used to open it. Partitions that are still open can't be
+//! removed.
+//!
+//! Both the primary and backup copy of the table are updated.
+//!
+//! ## Positional access
+//!
+//! Partitions support `ReadAt` and `WriteAt` with the same restrictions as reads and writes:
+//! the offset must be a multiple of 512 and a single sector is transferred at a time. The
+//! position of the handle is not affected.
+//!
 //! ## References
 //!
 //! * https://wiki.osdev.org/GPT
 
 #![no_std]
 #![feature(start)]
-#![feature(str_internals)]
 
 extern crate alloc;
 
 use {
 	alloc::{
+		format,
 		string::{String, ToString},
 		vec::Vec,
 	},
 	core::str,
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	driver_utils::os::stream_table::{positional, Request, Response, StreamTable},
 	rt_default as _,
 };
 
-use core::{fmt, str::lossy::Utf8Lossy};
+use core::fmt;
 
 #[start]
 fn start(_: isize, _: *const *const u8) -> isize {
@@ -32,30 +50,40 @@ fn main() {
 
 	let mut disk = Controller::new(disk);
 
-	let buf = disk.read(1);
-
-	let header = PartitionTableHeader::try_from(&buf[..]).unwrap();
-	assert!(
-		header.partition_entry_count < 1 << 20,
-		"todo: deal with huge partition count efficiently"
-	);
-
-	let mut partitions = Vec::new();
-
-	for i in 0..header.partition_entry_count {
-		let offt = u64::from(header.partition_entry_size) * u64::from(i);
-		let lba = header.partition_entry_array_lba + offt / 512;
-		let buf = disk.read(lba);
-		let e = PartitionEntry::try_from(&buf[offt as usize % 512..]).unwrap();
-		if e.is_used() {
-			let i = i as usize;
-			partitions.resize(i + 1, None);
-			partitions[i] = Some((e.start_lba, e.end_lba));
+	let (mut table, mut partitions) = match load_table(&mut disk, 1) {
+		Ok(p) => p,
+		Err((alt, e)) => {
+			rt::eprintln!("[GPT] primary table is invalid: {:?}", e);
+			let alt = match alt {
+				Some(alt) => alt,
+				None => match disk.dev.seek(rt::io::SeekFrom::End(0)) {
+					Ok(len) => len / 512 - 1,
+					Err(e) => {
+						rt::eprintln!("[GPT] failed to get disk size: {:?}", e);
+						return;
+					}
+				},
+			};
+			match load_table(&mut disk, alt) {
+				Ok(p) => {
+					rt::eprintln!("[GPT] using backup table at LBA {}", alt);
+					p
+				}
+				Err((_, e)) => {
+					rt::eprintln!("[GPT] backup table is invalid: {:?}", e);
+					return;
+				}
+			}
 		}
-	}
+	};
 
 	let (buf, _) = rt::Object::new(rt::NewObject::SharedMemory { size: 1 << 12 }).unwrap();
-	let tbl = StreamTable::new(&buf, 512.try_into().unwrap(), 512 - 1);
+	// Leave room for the offset of WriteAt requests.
+	let tbl = StreamTable::new(
+		&buf,
+		512.try_into().unwrap(),
+		(512 + positional::OFFSET_SIZE - 1).try_into().unwrap(),
+	);
 	share.create(b"gpt").unwrap().share(tbl.public()).unwrap();
 
 	let mut obj = driver_utils::Arena::new();
@@ -84,6 +112,36 @@ fn main() {
 						Response::Error(rt::Error::InvalidData)
 					}
 				}
+				Request::Create { path } if handle == rt::Handle::MAX => {
+					let mut buf = [0; 256];
+					match path.copy_into(&mut buf) {
+						(p, l) if p.len() == l => {
+							match create_partition(&mut table, &mut partitions, disk, p) {
+								Ok(i) => Response::Handle(obj.insert((i, 0))),
+								Err(e) => Response::Error(e),
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
+				Request::Destroy { path } if handle == rt::Handle::MAX => {
+					let mut buf = [0; 32];
+					let (s, _) = path.copy_into(&mut buf);
+					match str::from_utf8(s).ok().and_then(|s| s.parse::<usize>().ok()) {
+						Some(i) if partitions.get(i).map_or(false, |e| e.is_some()) => {
+							if obj.iter().any(|(_, &(k, _))| k == i) {
+								Response::Error(rt::Error::InvalidOperation)
+							} else {
+								match destroy_partition(&mut table, &mut partitions, disk, i) {
+									Ok(()) => Response::Amount(0),
+									Err(e) => Response::Error(e),
+								}
+							}
+						}
+						Some(_) => Response::Error(rt::Error::DoesNotExist),
+						None => Response::Error(rt::Error::InvalidData),
+					}
+				}
 				Request::Read { amount } if handle != rt::Handle::MAX => {
 					let amount = amount.min(512);
 					if handle & 1 << 31 != 0 {
@@ -104,15 +162,17 @@ fn main() {
 						Response::Error(rt::Error::InvalidData)
 					} else {
 						let (i, pos) = &mut obj[handle];
-						let (start, end) = partitions[*i].unwrap();
+						let Partition { start, end, .. } = *partitions[*i].as_ref().unwrap();
 						if *pos <= end - start {
 							let buf = tbl.alloc(512).unwrap();
-							disk.seek(rt::io::SeekFrom::Start((start + *pos) * 512))
-								.unwrap();
-							disk.read(unsafe { buf.blocks().next().unwrap().1.as_mut() })
-								.unwrap();
-							*pos += 1;
-							Response::Data(buf)
+							let b = unsafe { buf.blocks().next().unwrap().1.as_mut() };
+							match read_block(disk, start + *pos, b) {
+								Ok(()) => {
+									*pos += 1;
+									Response::Data(buf)
+								}
+								Err(e) => Response::Error(e),
+							}
 						} else {
 							Response::Error(rt::Error::InvalidData)
 						}
@@ -123,24 +183,83 @@ fn main() {
 						Response::Error(rt::Error::InvalidData)
 					} else {
 						let (i, pos) = &mut obj[handle];
-						let (start, end) = partitions[*i].unwrap();
+						let Partition { start, end, .. } = *partitions[*i].as_ref().unwrap();
 						if *pos <= end - start {
 							let (_, b) = data.blocks().next().unwrap();
-							disk.seek(rt::io::SeekFrom::Start((start + *pos) * 512))
-								.unwrap();
-							disk.write(unsafe { b.as_ref() }.try_into().unwrap())
-								.unwrap();
-							*pos += 1;
-							Response::Amount(512)
+							// The disk may be read-only, in which case the error is passed on.
+							match write_block(disk, start + *pos, unsafe { b.as_ref() }) {
+								Ok(_) => {
+									*pos += 1;
+									Response::Amount(512)
+								}
+								Err(e) => Response::Error(e),
+							}
 						} else {
 							Response::Error(rt::Error::InvalidData)
 						}
 					}
 				}
+				Request::ReadAt { args } if handle & 1 << 31 == 0 => {
+					match positional::decode_read_at(
+						args.copy_into(&mut [0; positional::READ_AT_SIZE]).0,
+					) {
+						Ok((offset, amount)) if offset % 512 == 0 && amount >= 512 => {
+							let (i, _) = obj[handle];
+							let Partition { start, end, .. } = *partitions[i].as_ref().unwrap();
+							if offset / 512 <= end - start {
+								let buf = tbl.alloc(512).unwrap();
+								let b = unsafe { buf.blocks().next().unwrap().1.as_mut() };
+								match read_block(disk, start + offset / 512, b) {
+									Ok(()) => Response::Data(buf),
+									Err(e) => Response::Error(e),
+								}
+							} else {
+								Response::Error(rt::Error::InvalidData)
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
+				Request::WriteAt { data } if handle & 1 << 31 == 0 => {
+					let mut buf = [0; positional::OFFSET_SIZE + 512];
+					match data.copy_into(&mut buf) {
+						(b, l) if l == positional::OFFSET_SIZE + 512 => {
+							let (offset, d) = positional::decode_write_at(b).unwrap();
+							let (i, _) = obj[handle];
+							let Partition { start, end, .. } = *partitions[i].as_ref().unwrap();
+							if offset % 512 == 0 && offset / 512 <= end - start {
+								match write_block(disk, start + offset / 512, d) {
+									Ok(_) => Response::Amount(512),
+									Err(e) => Response::Error(e),
+								}
+							} else {
+								Response::Error(rt::Error::InvalidData)
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
+				Request::GetMeta { property }
+					if handle & 1 << 31 == 0 && handle != rt::Handle::MAX =>
+				{
+					let (i, _) = obj[handle];
+					let p = partitions[i].as_ref().unwrap();
+					let v = match &*property.get(&mut [0; 16]) {
+						b"type-guid" => Some(fmt_guid(p.type_guid)),
+						b"guid" => Some(fmt_guid(p.guid)),
+						b"name" => Some(p.name.clone()),
+						_ => None,
+					};
+					v.map_or(Response::Error(rt::Error::DoesNotExist), |v| {
+						let buf = tbl.alloc(v.len()).unwrap();
+						buf.copy_from(0, v.as_bytes());
+						Response::Data(buf)
+					})
+				}
 				Request::Seek { from } if handle & 1 << 31 == 0 => match from {
 					rt::io::SeekFrom::Start(n) if n % 512 == 0 => {
 						let (i, pos) = &mut obj[handle];
-						let (start, end) = partitions[*i].unwrap();
+						let Partition { start, end, .. } = *partitions[*i].as_ref().unwrap();
 						*pos = (n / 512).min(end - start);
 						Response::Position(n)
 					}
@@ -165,33 +284,332 @@ fn main() {
 	}
 }
 
+/// Parse a new partition entry and write it to the first free slot.
+///
+/// Returns the index of the new partition.
+fn create_partition(
+	table: &mut Table,
+	partitions: &mut Vec<Option<Partition>>,
+	disk: rt::RefObject<'_>,
+	path: &[u8],
+) -> Result<usize, rt::Error> {
+	let mut args = str::from_utf8(path)
+		.map_err(|_| rt::Error::InvalidData)?
+		.splitn(5, ' ');
+	let mut next = || args.next().ok_or(rt::Error::InvalidData);
+	let type_guid = parse_guid(next()?).ok_or(rt::Error::InvalidData)?;
+	let guid = parse_guid(next()?).ok_or(rt::Error::InvalidData)?;
+	let start = next()?.parse::<u64>().map_err(|_| rt::Error::InvalidData)?;
+	let end = next()?.parse::<u64>().map_err(|_| rt::Error::InvalidData)?;
+	let name = next().unwrap_or("");
+
+	// A zero type GUID marks an entry as unused.
+	if type_guid == 0 || start > end || start < table.first_usable || end > table.last_usable {
+		return Err(rt::Error::InvalidData);
+	}
+	if partitions
+		.iter()
+		.flatten()
+		.any(|p| start <= p.end && p.start <= end)
+	{
+		return Err(rt::Error::AlreadyExists);
+	}
+	let mut raw_name = [0; 72];
+	let mut units = name.encode_utf16();
+	for (c, u) in raw_name.chunks_exact_mut(2).zip(&mut units) {
+		c.copy_from_slice(&u.to_le_bytes());
+	}
+	if units.next().is_some() {
+		return Err(rt::Error::InvalidData);
+	}
+
+	let i = (0..table.entry_count)
+		.find(|&i| partitions.get(i).map_or(true, |e| e.is_none()))
+		.ok_or(rt::Error::CantCreateObject)?;
+	let e = PartitionEntry {
+		type_guid,
+		partition_guid: guid,
+		start_lba: start,
+		end_lba: end,
+		attributes: 0,
+		partition_name: raw_name,
+	};
+	table.set_entry(disk, i, |b| e.write(b))?;
+
+	if partitions.len() <= i {
+		partitions.resize(i + 1, None);
+	}
+	partitions[i] = Some(Partition { start, end, type_guid, guid, name: e.name() });
+	Ok(i)
+}
+
+/// Clear the entry of a partition.
+fn destroy_partition(
+	table: &mut Table,
+	partitions: &mut [Option<Partition>],
+	disk: rt::RefObject<'_>,
+	index: usize,
+) -> Result<(), rt::Error> {
+	table.set_entry(disk, index, |b| b.fill(0))?;
+	partitions[index] = None;
+	Ok(())
+}
+
+/// Load and verify the partition table with the header at the given LBA.
+///
+/// On error, the LBA of the alternate header is returned if the header itself could be parsed.
+fn load_table(
+	disk: &mut Controller,
+	lba: u64,
+) -> Result<(Table, Vec<Option<Partition>>), (Option<u64>, InvalidPartitionTable)> {
+	let raw_header = *disk
+		.read(lba)
+		.map_err(|e| (None, InvalidPartitionTable::Io(e)))?;
+	let header = PartitionTableHeader::try_from(&raw_header[..])
+		.map_err(|e| (None, InvalidPartitionTable::Header(e)))?;
+	let err = |e| (Some(header.alt_header_lba), e);
+	if header.header_lba != lba {
+		return Err(err(InvalidPartitionTable::WrongHeaderLba));
+	}
+	if !header.verify(&raw_header) {
+		return Err(err(InvalidPartitionTable::HeaderCrc));
+	}
+	let size = header.partition_entry_size;
+	if size < 0x80 || !size.is_power_of_two() || size > 512 {
+		return Err(err(InvalidPartitionTable::UnsupportedEntrySize));
+	}
+	assert!(
+		header.partition_entry_count < 1 << 20,
+		"todo: deal with huge partition count efficiently"
+	);
+
+	let mut partitions = Vec::new();
+	let mut entries = Vec::new();
+	let mut crc = !0;
+
+	// Parse all entries in a sector before moving on to the next so each sector is read once.
+	let count = header.partition_entry_count as usize;
+	let per_sector = 512 / size as usize;
+	for (s, first) in (0..count).step_by(per_sector).enumerate() {
+		let buf = disk
+			.read(header.partition_entry_array_lba + s as u64)
+			.map_err(|e| err(InvalidPartitionTable::Io(e)))?;
+		entries.extend_from_slice(buf);
+		let n = per_sector.min(count - first);
+		for (i, buf) in (first..).zip(buf.chunks_exact(size as usize).take(n)) {
+			crc = crc32_update(crc, buf);
+			let e = PartitionEntry::try_from(buf).unwrap();
+			if e.is_used() {
+				partitions.resize(i + 1, None);
+				partitions[i] = Some(Partition {
+					start: e.start_lba,
+					end: e.end_lba,
+					type_guid: e.type_guid,
+					guid: e.partition_guid,
+					name: e.name(),
+				});
+			}
+		}
+	}
+
+	if !crc != header.partition_entry_array_crc32 {
+		return Err(err(InvalidPartitionTable::EntryArrayCrc));
+	}
+
+	let alt = (header.alt_header_lba, alt_entry_array_lba(disk, &header));
+	let this = (lba, header.partition_entry_array_lba);
+	let table = Table {
+		header: raw_header,
+		header_size: header.header_size as usize,
+		copies: if lba < alt.0 {
+			[this, alt]
+		} else {
+			[alt, this]
+		},
+		first_usable: header.first_usable_block,
+		last_usable: header.last_usable_block,
+		entry_count: count,
+		entry_size: size as usize,
+		entries,
+	};
+	Ok((table, partitions))
+}
+
+/// Find the entry array of the other copy of the table.
+///
+/// If the other header is invalid or can't be read the array is assumed to be right after the
+/// primary header or right before the backup header.
+fn alt_entry_array_lba(disk: &mut Controller, header: &PartitionTableHeader) -> u64 {
+	let alt = header.alt_header_lba;
+	let h = disk.read(alt).ok().and_then(|buf| {
+		PartitionTableHeader::try_from(&buf[..])
+			.ok()
+			.filter(|h| h.header_lba == alt && h.verify(buf))
+	});
+	match h {
+		Some(h) => h.partition_entry_array_lba,
+		None if alt < header.header_lba => alt + 1,
+		None => {
+			let size =
+				u64::from(header.partition_entry_count) * u64::from(header.partition_entry_size);
+			alt - (size + 511) / 512
+		}
+	}
+}
+
+/// The state needed to modify the partition table.
+struct Table {
+	/// The header the table was loaded from, used as a template when writing headers.
+	header: [u8; 512],
+	header_size: usize,
+	/// The LBA of the header and the entry array of the primary and backup copy, in that order.
+	copies: [(u64, u64); 2],
+	first_usable: u64,
+	last_usable: u64,
+	entry_count: usize,
+	entry_size: usize,
+	/// The raw entry array, including any padding up to the end of the last sector.
+	entries: Vec<u8>,
+}
+
+impl Table {
+	/// Modify an entry and write it to both copies of the table.
+	///
+	/// If writing fails the entry is restored, though the copies on disk may be left in an
+	/// inconsistent state.
+	fn set_entry(
+		&mut self,
+		disk: rt::RefObject<'_>,
+		index: usize,
+		f: impl FnOnce(&mut [u8]),
+	) -> Result<(), rt::Error> {
+		let entry = index * self.entry_size..(index + 1) * self.entry_size;
+		let old = self.entries[entry.clone()].to_vec();
+		f(&mut self.entries[entry.clone()]);
+		self.commit(disk, index).map_err(|e| {
+			self.entries[entry].copy_from_slice(&old);
+			e
+		})
+	}
+
+	/// Write the sector holding the given entry and the header to both copies of the table.
+	///
+	/// The backup copy is written first, so if writing fails halfway the primary copy is still
+	/// intact.
+	fn commit(&self, disk: rt::RefObject<'_>, index: usize) -> Result<(), rt::Error> {
+		let crc = !crc32_update(!0, &self.entries[..self.entry_count * self.entry_size]);
+		let sector = index * self.entry_size / 512;
+		let data = &self.entries[sector * 512..][..512];
+		for (i, &(header_lba, array_lba)) in self.copies.iter().enumerate().rev() {
+			let (alt_lba, _) = self.copies[1 - i];
+			write_block(disk, array_lba + sector as u64, data)?;
+			let mut h = self.header;
+			h[0x10..0x14].fill(0);
+			h[0x18..0x20].copy_from_slice(&header_lba.to_le_bytes());
+			h[0x20..0x28].copy_from_slice(&alt_lba.to_le_bytes());
+			h[0x48..0x50].copy_from_slice(&array_lba.to_le_bytes());
+			h[0x58..0x5c].copy_from_slice(&crc.to_le_bytes());
+			let header_crc = !crc32_update(!0, &h[..self.header_size]);
+			h[0x10..0x14].copy_from_slice(&header_crc.to_le_bytes());
+			write_block(disk, header_lba, &h)?;
+		}
+		Ok(())
+	}
+}
+
+/// Read a block, falling back to seek + read if the disk doesn't support positional reads.
+fn read_block(disk: rt::RefObject<'_>, lba: u64, buf: &mut [u8]) -> Result<(), rt::Error> {
+	match disk.read_at(lba * 512, buf) {
+		Err(rt::Error::InvalidOperation) => {
+			disk.seek(rt::io::SeekFrom::Start(lba * 512))?;
+			disk.read(buf)
+		}
+		r => r,
+	}
+	.map(|_| ())
+}
+
+/// Write a block, falling back to seek + write if the disk doesn't support positional writes.
+///
+/// Fails if not all of `data` was written, as a partially written table is corrupt.
+fn write_block(disk: rt::RefObject<'_>, lba: u64, data: &[u8]) -> Result<(), rt::Error> {
+	match disk.write_at(lba * 512, data) {
+		Err(rt::Error::InvalidOperation) => {
+			disk.seek(rt::io::SeekFrom::Start(lba * 512))?;
+			disk.write(data)
+		}
+		r => r,
+	}
+	.and_then(|n| {
+		if n == data.len() {
+			Ok(())
+		} else {
+			Err(rt::Error::Unknown)
+		}
+	})
+}
+
+#[derive(Clone)]
+struct Partition {
+	start: u64,
+	end: u64,
+	type_guid: u128,
+	guid: u128,
+	name: String,
+}
+
+#[derive(Debug)]
+enum InvalidPartitionTable {
+	Io(rt::Error),
+	Header(InvalidPartitionTableHeader),
+	WrongHeaderLba,
+	HeaderCrc,
+	EntryArrayCrc,
+	UnsupportedEntrySize,
+}
+
+/// Update a CRC32 (IEEE 802.3, reflected) with the given data.
+///
+/// The CRC must be initialized with `!0` and inverted when done.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+	for &b in data {
+		crc ^= u32::from(b);
+		for _ in 0..8 {
+			crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+		}
+	}
+	crc
+}
+
 #[derive(Debug)]
 struct PartitionTableHeader {
 	#[allow(dead_code)]
 	gpt_revision: u32,
-	#[allow(dead_code)]
 	header_size: u32,
-	#[allow(dead_code)]
 	crc32: u32,
-	#[allow(dead_code)]
 	header_lba: u64,
-	#[allow(dead_code)]
 	alt_header_lba: u64,
-	#[allow(dead_code)]
 	first_usable_block: u64,
-	#[allow(dead_code)]
 	last_usable_block: u64,
 	#[allow(dead_code)]
 	guid: u128,
 	partition_entry_array_lba: u64,
 	partition_entry_count: u32,
 	partition_entry_size: u32,
-	#[allow(dead_code)]
 	partition_entry_array_crc32: u32,
 }
 
 impl PartitionTableHeader {
 	const SIGNATURE: [u8; 8] = *b"EFI PART";
+
+	/// Check if the CRC32 of the header matches.
+	///
+	/// `raw` must be the block the header was parsed from.
+	fn verify(&self, raw: &[u8; 512]) -> bool {
+		let mut raw = *raw;
+		raw[0x10..0x14].fill(0);
+		!crc32_update(!0, &raw[..self.header_size as usize]) == self.crc32
+	}
 }
 
 impl TryFrom<&[u8]> for PartitionTableHeader {
@@ -207,6 +625,9 @@ impl TryFrom<&[u8]> for PartitionTableHeader {
 		let f4 = |i| u32::from_le_bytes(a[i..][..4].try_into().unwrap());
 		let f8 = |i| u64::from_le_bytes(a[i..][..8].try_into().unwrap());
 		let f16 = |i| u128::from_le_bytes(a[i..][..16].try_into().unwrap());
+		if !(0x5c..=a.len().min(512)).contains(&(f4(0xc) as usize)) {
+			return Err(InvalidPartitionTableHeader::InvalidHeaderSize);
+		}
 		Ok(Self {
 			gpt_revision: f4(0x8),
 			header_size: f4(0xc),
@@ -227,6 +648,7 @@ impl TryFrom<&[u8]> for PartitionTableHeader {
 #[derive(Debug)]
 enum InvalidPartitionTableHeader {
 	InvalidSignature,
+	InvalidHeaderSize,
 	TooShort,
 }
 
@@ -243,6 +665,72 @@ impl PartitionEntry {
 	fn is_used(&self) -> bool {
 		self.type_guid != 0
 	}
+
+	/// Decode the UTF-16LE name, stopping at the first NUL.
+	///
+	/// Invalid code units are replaced with U+FFFD.
+	fn name(&self) -> String {
+		let units = self
+			.partition_name
+			.chunks_exact(2)
+			.map(|c| u16::from_le_bytes([c[0], c[1]]))
+			.take_while(|&c| c != 0);
+		char::decode_utf16(units)
+			.map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+			.collect()
+	}
+
+	/// Encode the entry. Any bytes past the standard fields are cleared.
+	fn write(&self, a: &mut [u8]) {
+		a[0x0..0x10].copy_from_slice(&self.type_guid.to_le_bytes());
+		a[0x10..0x20].copy_from_slice(&self.partition_guid.to_le_bytes());
+		a[0x20..0x28].copy_from_slice(&self.start_lba.to_le_bytes());
+		a[0x28..0x30].copy_from_slice(&self.end_lba.to_le_bytes());
+		a[0x30..0x38].copy_from_slice(&self.attributes.to_le_bytes());
+		a[0x38..0x80].copy_from_slice(&self.partition_name);
+		a[0x80..].fill(0);
+	}
+}
+
+/// Format a GUID in the usual mixed-endian textual form,
+/// e.g. `c12a7328-f81f-11d2-ba4b-00a0c93ec93b`.
+fn fmt_guid(guid: u128) -> String {
+	let b = guid.to_le_bytes();
+	format!(
+		"{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+		u32::from_le_bytes(b[..4].try_into().unwrap()),
+		u16::from_le_bytes(b[4..6].try_into().unwrap()),
+		u16::from_le_bytes(b[6..8].try_into().unwrap()),
+		b[8],
+		b[9],
+		b[10],
+		b[11],
+		b[12],
+		b[13],
+		b[14],
+		b[15],
+	)
+}
+
+/// Parse a GUID in the form produced by [`fmt_guid`].
+fn parse_guid(s: &str) -> Option<u128> {
+	let mut f = s.split('-');
+	let mut next = |len| {
+		f.next()
+			.filter(|s| s.len() == len)
+			.and_then(|s| u64::from_str_radix(s, 16).ok())
+	};
+	let (a, b, c, d, e) = (next(8)?, next(4)?, next(4)?, next(4)?, next(12)?);
+	if f.next().is_some() {
+		return None;
+	}
+	let mut g = [0; 16];
+	g[..4].copy_from_slice(&(a as u32).to_le_bytes());
+	g[4..6].copy_from_slice(&(b as u16).to_le_bytes());
+	g[6..8].copy_from_slice(&(c as u16).to_le_bytes());
+	g[8..10].copy_from_slice(&(d as u16).to_be_bytes());
+	g[10..].copy_from_slice(&e.to_be_bytes()[2..]);
+	Some(u128::from_le_bytes(g))
 }
 
 impl TryFrom<&[u8]> for PartitionEntry {
@@ -260,7 +748,6 @@ impl TryFrom<&[u8]> for PartitionEntry {
 			start_lba: f8(0x20),
 			end_lba: f8(0x28),
 			attributes: f8(0x30),
-			// FIXME Actually UTF-16
 			partition_name: a[0x38..][..72].try_into().unwrap(),
 		})
 	}
@@ -277,10 +764,7 @@ impl fmt::Debug for PartitionEntry {
 			.field("start_lba", &self.start_lba)
 			.field("end_lba", &self.end_lba)
 			.field("attributes", &self.attributes)
-			.field(
-				"partition_name",
-				&Utf8Lossy::from_bytes(&self.partition_name),
-			)
+			.field("partition_name", &self.name())
 			.finish()
 	}
 }
@@ -301,12 +785,13 @@ impl Controller {
 		Self { dev, cache: [0; 512], cache_pos: u64::MAX }
 	}
 
-	fn read(&mut self, pos: u64) -> &[u8; 512] {
+	fn read(&mut self, pos: u64) -> Result<&[u8; 512], rt::Error> {
 		if self.cache_pos != pos {
-			self.dev.seek(rt::io::SeekFrom::Start(pos * 512)).unwrap();
-			self.dev.read(&mut self.cache).unwrap();
+			// Don't keep a partially read block around.
+			self.cache_pos = u64::MAX;
+			read_block(self.dev, pos, &mut self.cache)?;
 			self.cache_pos = pos;
 		}
-		&self.cache
+		Ok(&self.cache)
 	}
 }
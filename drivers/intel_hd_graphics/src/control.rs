@@ -1,23 +1,27 @@
-use core::ptr::{self, NonNull};
+use {core::ptr::NonNull, volatile::VolatilePtr};
 
 pub struct Control {
-	base: NonNull<u8>,
+	base: VolatilePtr<'static, u8>,
 }
 
 impl Control {
-	pub fn new(base: NonNull<u8>) -> Self {
+	/// # Safety
+	///
+	/// `base` must point to the mapped control registers.
+	pub unsafe fn new(base: NonNull<u8>) -> Self {
 		assert_eq!(base.as_ptr() as usize & 3, 0, "bad alignment");
-		Self { base }
+		Self { base: VolatilePtr::new(base) }
 	}
 
 	pub unsafe fn load(&mut self, offset: u32) -> u32 {
-		ptr::read_volatile(self.base.as_ptr().add(offset.try_into().unwrap()).cast())
+		self.base
+			.byte_offset::<u32>(offset.try_into().unwrap())
+			.read()
 	}
 
 	pub unsafe fn store(&mut self, offset: u32, value: u32) {
-		ptr::write_volatile(
-			self.base.as_ptr().add(offset.try_into().unwrap()).cast(),
-			value,
-		)
+		self.base
+			.byte_offset::<u32>(offset.try_into().unwrap())
+			.write(value)
 	}
 }
@@ -1,4 +1,7 @@
-use crate::control::Control;
+use crate::{
+	control::Control,
+	transcoder::{Ddi, Transcoder},
+};
 
 reg! {
 	DpAuxCtl
@@ -133,6 +136,19 @@ bit2enum! {
 	None      0b111
 }
 
+impl PortClock {
+	/// The value to write to the `LINK_BW_SET` DPCD register for the link rate this clock
+	/// produces.
+	fn link_bw(&self) -> Option<u8> {
+		match self {
+			Self::LcPll810 => Some(0x06),
+			Self::LcPll1350 => Some(0x0a),
+			Self::LcPll2700 => Some(0x14),
+			Self::SPll | Self::WrPll1 | Self::WrPll2 | Self::None => None,
+		}
+	}
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Port {
 	/// Known as DDI_AUX_{CTL,DATA} in vol2a and located at an entirely different address from the
@@ -181,18 +197,53 @@ impl Port {
 		control.store(self.data_port(instance), data.0)
 	}
 
+	fn index(&self) -> u32 {
+		match self {
+			Self::A => 0,
+			Self::B => 1,
+			Self::C => 2,
+			Self::D => 3,
+			Self::E => 4,
+		}
+	}
+
 	unsafe fn offset(&self) -> u32 {
-		0x100
-			* match self {
-				Self::A => 0,
-				Self::B => 1,
-				Self::C => 2,
-				Self::D => 3,
-				Self::E => 4,
-			}
+		0x100 * self.index()
+	}
+
+	/// The transcoder that drives this port.
+	///
+	/// DDI A is hardwired to the eDP transcoder. All other ports are driven by transcoder A,
+	/// which is attached to pipe A.
+	pub fn transcoder(&self) -> Transcoder {
+		match self {
+			Self::A => Transcoder::EDP,
+			Self::B | Self::C | Self::D | Self::E => Transcoder::A,
+		}
+	}
+
+	/// The DDI to select in the transcoder's clock & function registers.
+	///
+	/// This is `None` for DDI A as the eDP transcoder can only drive DDI A.
+	pub fn ddi(&self) -> Option<Ddi> {
+		match self {
+			Self::A => None,
+			Self::B => Some(Ddi::B),
+			Self::C => Some(Ddi::C),
+			Self::D => Some(Ddi::D),
+			Self::E => Some(Ddi::E),
+		}
+	}
+
+	// PORT_CLK_SEL registers are 4 bytes apart, unlike the other DDI registers.
+	unsafe fn load_port_clk_sel(&self, control: &mut Control) -> PortClockSelect {
+		PortClockSelect(control.load(0x46100 + self.index() * 4))
+	}
+
+	unsafe fn store_port_clk_sel(&self, control: &mut Control, value: PortClockSelect) {
+		control.store(0x46100 + self.index() * 4, value.0)
 	}
 
-	impl_reg!(0x46100 PortClockSelect load_port_clk_sel store_port_clk_sel);
 	impl_reg!(0x64040 TransportControl load_dp_tp_ctl store_dp_tp_ctl);
 	impl_reg!(0x64000 DdiBufferControl load_ddi_buf_ctl store_ddi_buf_ctl);
 }
@@ -238,6 +289,112 @@ unsafe fn aux_ch_read_reply<'a>(
 	Ok((ctl, buf))
 }
 
+/// Read bytes from the DPCD of the receiver.
+unsafe fn aux_native_read(
+	control: &mut Control,
+	port: Port,
+	address: u32,
+	buf: &mut [u8],
+) -> Result<(), AuxError> {
+	assert!(address < 1 << 20, "invalid DPCD address");
+	assert!(
+		!buf.is_empty() && buf.len() <= 16,
+		"can only read 1 to 16 bytes"
+	);
+
+	let mut reply_buf = [0; 20];
+	let mut i = 3u8;
+	loop {
+		let mut ctl = port.load_ctl(control);
+		let mut data = DpAuxData(0);
+		data.set_byte_0(aux_native_format_request(true, address));
+		data.set_byte_1((address >> 8) as u8);
+		data.set_byte_2(address as u8);
+		data.set_byte_3((buf.len() - 1) as u8);
+		ctl.set_message_size(MessageSize::N4);
+		ctl.set_receive_error(true); // Clear error
+		ctl.set_time_out_error(true); // Clear timeout
+		ctl.set_send_busy(true);
+		port.store_data(control, 0, data);
+		port.store_ctl(control, ctl.clone());
+
+		match aux_ch_read_reply(control, port, &mut reply_buf).map_err(AuxError::ReplyError) {
+			Ok((_, reply)) => match reply.split_first() {
+				Some((&0, data)) if data.len() == buf.len() => {
+					buf.copy_from_slice(data);
+					return Ok(());
+				}
+				Some((&r, _)) if r >> 4 == 0b10 => rt::thread::yield_now(), // AUX_DEFER
+				Some((&r, _)) if r >> 4 == 0b01 => return Err(AuxError::NotAcknowledged),
+				_ => return Err(AuxError::InvalidReply),
+			},
+			Err(e) => {
+				if let Some(ni) = i.checked_sub(1) {
+					i = ni;
+				} else {
+					return Err(e);
+				}
+			}
+		}
+	}
+}
+
+/// Write bytes to the DPCD of the receiver.
+unsafe fn aux_native_write(
+	control: &mut Control,
+	port: Port,
+	address: u32,
+	data: &[u8],
+) -> Result<(), AuxError> {
+	assert!(address < 1 << 20, "invalid DPCD address");
+	assert!(
+		!data.is_empty() && data.len() <= 16,
+		"can only write 1 to 16 bytes"
+	);
+
+	let mut reply_buf = [0; 20];
+	let mut i = 3u8;
+	loop {
+		let mut ctl = port.load_ctl(control);
+		let mut msg = [0; 20];
+		msg[0] = aux_native_format_request(false, address);
+		msg[1] = (address >> 8) as u8;
+		msg[2] = address as u8;
+		msg[3] = (data.len() - 1) as u8;
+		msg[4..4 + data.len()].copy_from_slice(data);
+		let len = 4 + data.len();
+		for (k, c) in msg[..(len + 3) & !3].chunks_exact(4).enumerate() {
+			let mut d = DpAuxData(0);
+			d.set_byte_0(c[0]);
+			d.set_byte_1(c[1]);
+			d.set_byte_2(c[2]);
+			d.set_byte_3(c[3]);
+			port.store_data(control, k as u8, d);
+		}
+		ctl.set_message_size(MessageSize::try_from(len as u32).unwrap());
+		ctl.set_receive_error(true); // Clear error
+		ctl.set_time_out_error(true); // Clear timeout
+		ctl.set_send_busy(true);
+		port.store_ctl(control, ctl.clone());
+
+		match aux_ch_read_reply(control, port, &mut reply_buf).map_err(AuxError::ReplyError) {
+			Ok((_, reply)) => match reply.first().map(|r| r >> 4) {
+				Some(0b00) => return Ok(()),
+				Some(0b10) => rt::thread::yield_now(), // AUX_DEFER
+				Some(0b01) => return Err(AuxError::NotAcknowledged),
+				_ => return Err(AuxError::InvalidReply),
+			},
+			Err(e) => {
+				if let Some(ni) = i.checked_sub(1) {
+					i = ni;
+				} else {
+					return Err(e);
+				}
+			}
+		}
+	}
+}
+
 /// Send initial I2C packet.
 unsafe fn i2c_init(
 	control: &mut Control,
@@ -474,6 +631,20 @@ pub enum I2CError {
 	I2CReplyError(I2CReplyError),
 }
 
+#[derive(Debug)]
+pub enum AuxError {
+	ReplyError(ReplyError),
+	NotAcknowledged,
+	InvalidReply,
+}
+
+fn aux_native_format_request(read: bool, address: u32) -> u8 {
+	// bit 3   -> 1 for native AUX
+	// bit 2:0 -> 000 = write, 001 = read
+	// Lower nibble holds bits 19:16 of the address
+	(0b1000 | u8::from(read)) << 4 | (address >> 16) as u8 & 0xf
+}
+
 fn i2c_format_request(read: bool, middle_of_transaction: bool) -> u8 {
 	// bit 1:0 -> 00 = write, 01 = read, 10 = write status_request, 11 = reserved
 	// bit 2   -> middle-of-transaction (MOT)
@@ -550,7 +721,9 @@ pub unsafe fn configure(control: &mut Control, port: Port, clock: PortClock) {
 	//
 	// "For a closed, embedded connection, the DisplayPort transmitter and receiver may be set to pre-calibrated parameters without going through the full link training sequence. In this mode, the DisplayPort Source Device may start a normal operation without the AUX CH handshake for link training, as described in Section 2.5.3.3."
 	if port != Port::A {
-		todo!()
+		if let Err(e) = train_link(control, port, clock) {
+			log!("link training on port {:?} failed: {:?}", port, e);
+		}
 	}
 
 	// g. If DisplayPort multi-stream - Set DP_TP_CTL link training to Idle Pattern, wait
@@ -559,10 +732,81 @@ pub unsafe fn configure(control: &mut Control, port: Port, clock: PortClock) {
 
 	// h. Set DP_TP_CTL link training to Normal, skip if eDP (DDI A)
 	if port != Port::A {
-		todo!()
+		set_training_pattern(control, port, LinkTraining::Normal);
 	}
 }
 
+/// Perform link training with the receiver as described in the DisplayPort specification.
+///
+/// The transmitter must already be sending training pattern 1.
+unsafe fn train_link(control: &mut Control, port: Port, clock: PortClock) -> Result<(), AuxError> {
+	// DPCD registers
+	const LINK_BW_SET: u32 = 0x100;
+	const TRAINING_PATTERN_SET: u32 = 0x102;
+	const LANE0_1_STATUS: u32 = 0x202;
+	const LANE_ALIGN_STATUS_UPDATED: u32 = 0x204;
+	// TRAINING_PATTERN_SET bits
+	const SCRAMBLING_DISABLE: u8 = 1 << 5;
+	// LANEx_y_STATUS bits (per lane nibble)
+	const CR_DONE: u8 = 1 << 0;
+	const CHANNEL_EQ_DONE: u8 = 1 << 1;
+	const SYMBOL_LOCKED: u8 = 1 << 2;
+	// LANE_ALIGN_STATUS_UPDATED bits
+	const INTERLANE_ALIGN_DONE: u8 = 1 << 0;
+
+	let lanes: u8 = match port.load_ddi_buf_ctl(control).port_width() {
+		Some(PortWidth::X1) => 1,
+		Some(PortWidth::X2) | None => 2,
+		Some(PortWidth::X4) => 4,
+	};
+	let lanes_ok = |status: &[u8; 2], mask: u8| {
+		(0..lanes).all(|l| (status[usize::from(l / 2)] >> (l % 2 * 4)) & mask == mask)
+	};
+	let read_status = |control: &mut Control| -> Result<[u8; 2], AuxError> {
+		// Reads both LANE0_1_STATUS and LANE2_3_STATUS
+		let mut s = [0; 2];
+		aux_native_read(control, port, LANE0_1_STATUS, &mut s)?;
+		Ok(s)
+	};
+
+	// Configure link rate, lane count & enhanced framing
+	let bw = clock
+		.link_bw()
+		.expect("port clock is not a DisplayPort link rate");
+	aux_native_write(control, port, LINK_BW_SET, &[bw, lanes | 1 << 7])?;
+
+	// Clock recovery
+	aux_native_write(
+		control,
+		port,
+		TRAINING_PATTERN_SET,
+		&[1 | SCRAMBLING_DISABLE],
+	)?;
+	rt::thread::sleep(core::time::Duration::from_micros(100));
+	if !lanes_ok(&read_status(control)?, CR_DONE) {
+		log!("clock recovery did not finish on all lanes");
+	}
+
+	// Channel equalization
+	set_training_pattern(control, port, LinkTraining::Pattern2);
+	aux_native_write(
+		control,
+		port,
+		TRAINING_PATTERN_SET,
+		&[2 | SCRAMBLING_DISABLE],
+	)?;
+	rt::thread::sleep(core::time::Duration::from_micros(400));
+	let status = read_status(control)?;
+	let mut align = [0];
+	aux_native_read(control, port, LANE_ALIGN_STATUS_UPDATED, &mut align)?;
+	if !lanes_ok(&status, CHANNEL_EQ_DONE | SYMBOL_LOCKED) || align[0] & INTERLANE_ALIGN_DONE == 0 {
+		log!("channel equalization did not finish on all lanes");
+	}
+
+	// Disable training on the receiver side
+	aux_native_write(control, port, TRAINING_PATTERN_SET, &[0])
+}
+
 pub unsafe fn disable(control: &mut Control, port: Port) {
 	// a. Disable DDI_BUF_CTL
 	let mut bufctl = port.load_ddi_buf_ctl(control);
@@ -204,7 +204,7 @@ macro_rules! impl_reg {
 
 macro_rules! log {
 	($($arg:tt)*) => {{
-		let _ = rt::io::stderr().map(|o| writeln!(o, $($arg)*));
+		let _ = rt::io::Stderr::lock().map(|o| writeln!(o, $($arg)*));
 	}};
 }
 
@@ -244,7 +244,10 @@ mod watermark;
 use core::arch::x86_64;
 use {
 	core::ptr::NonNull,
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	driver_utils::os::{
+		portio::PortIo,
+		stream_table::{Request, Response, StreamTable},
+	},
 	rt::{Error, Handle},
 };
 
@@ -261,7 +264,7 @@ fn alloc_error(_: core::alloc::Layout) -> ! {
 
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-	let _ = rt::io::stderr().map(|o| writeln!(o, "{}", info));
+	let _ = rt::io::Stderr::lock_or_bypass().map(|o| writeln!(o, "{}", info));
 	rt::exit(128)
 }
 
@@ -283,12 +286,34 @@ impl Model {
 fn main(_: isize, _: *const *const u8) -> isize {
 	let root = rt::io::file_root().unwrap();
 
+	let mut preferred_port = None;
+	let mut args = rt::args::args().skip(1);
+	while let Some(a) = args.next() {
+		match a {
+			b"--port" => {
+				let p = match args.next().expect("expected port") {
+					b"a" | b"A" => displayport::Port::A,
+					b"b" | b"B" => displayport::Port::B,
+					b"c" | b"C" => displayport::Port::C,
+					b"d" | b"D" => displayport::Port::D,
+					b"e" | b"E" => displayport::Port::E,
+					p => panic!("invalid port {:?}", core::str::from_utf8(p)),
+				};
+				preferred_port = Some(p);
+			}
+			_ => panic!("invalid argument {:?}", core::str::from_utf8(a)),
+		}
+	}
+
 	// Open & configure
 	let dev = rt::args::handles()
 		.find(|(name, _)| name == b"pci")
 		.expect("no 'pci' object")
 		.1;
-	let ioport = root.open(b"portio/map").expect("can't access I/O ports");
+	let ioport = root
+		.open(b"portio/map")
+		.map(PortIo::from)
+		.expect("can't access I/O ports");
 
 	let (pci_config, _) = dev.map_object(None, rt::RWX::R, 0, usize::MAX).unwrap();
 
@@ -315,7 +340,7 @@ fn main(_: isize, _: *const *const u8) -> isize {
 				let control = map_bar(0);
 				let memory = map_bar(2);
 
-				let mut control = control::Control::new(control.cast());
+				let mut control = unsafe { control::Control::new(control.cast()) };
 
 				// This is the only errata I found wrt. GMBUS. (see vol15) and DP AUX
 				// It doesn't seem to do anything though.
@@ -339,12 +364,14 @@ fn main(_: isize, _: *const *const u8) -> isize {
 				let mut edid = [0; 128];
 				let mut port = None;
 				let port = unsafe {
-					for p in [
+					let ports = [
 						displayport::Port::A,
 						displayport::Port::B,
 						displayport::Port::C,
 						displayport::Port::D,
-					] {
+					];
+					// Try the preferred port first, then fall back to whichever port responds.
+					for p in preferred_port.into_iter().chain(ports) {
 						match displayport::i2c_write_read(&mut control, p, 0x50, &[0], &mut edid) {
 							Ok(()) => {
 								for c in edid.as_chunks::<16>().0 {
@@ -363,7 +390,7 @@ fn main(_: isize, _: *const *const u8) -> isize {
 						return 1;
 					}
 				};
-				assert_eq!(port, displayport::Port::A, "TODO support multiple ports");
+				log!("using port {:?}", port);
 				let edid = edid::Edid::new(edid).unwrap();
 				let mode = mode::Mode::from_edid(&edid).unwrap();
 
@@ -397,57 +424,55 @@ fn main(_: isize, _: *const *const u8) -> isize {
 
 				// See vol11 p. 112 "Sequences for DisplayPort"
 				// FIXME configure PLL ourselves instead of relying on preset value.
-				use transcoder::Transcoder;
+				let (trans, ddi) = (port.transcoder(), port.ddi());
 				unsafe {
 					// Disable sequence
 					// b. Disable planes (VGA or hires)
-					vga::disable_vga(&mut control, (&ioport).into());
+					vga::disable_vga(&mut control, &ioport);
 					plane::disable(&mut control, plane::Plane::A);
 					// c. Disable TRANS_CONF
-					transcoder::disable(&mut control, Transcoder::EDP);
+					transcoder::disable(&mut control, trans);
 					// h. Disable panel fitter
 					panel::disable_fitter(&mut control, panel::Pipe::A);
 					// i. Configure Transcoder Clock Select to direct no clock to the transcoder
-					transcoder::disable_clock(&mut control, Transcoder::EDP);
-					displayport::disable(&mut control, displayport::Port::A);
-					backlight::disable(&mut control);
-					displayport::set_port_clock(
-						&mut control,
-						displayport::Port::A,
-						displayport::PortClock::None,
-					);
+					transcoder::disable_clock(&mut control, trans);
+					displayport::disable(&mut control, port);
+					if port == displayport::Port::A {
+						backlight::disable(&mut control);
+					}
+					displayport::set_port_clock(&mut control, port, displayport::PortClock::None);
 
 					//pipe::configure(&mut control, pipe::Pipe::A, &mode);
 
 					// FIXME don't hardcode port clock, configure it properly instead
-					backlight::enable_panel(&mut control);
-					displayport::configure(
-						&mut control,
-						displayport::Port::A,
-						displayport::PortClock::LcPll1350,
-					);
+					if port == displayport::Port::A {
+						backlight::enable_panel(&mut control);
+					}
+					displayport::configure(&mut control, port, displayport::PortClock::LcPll1350);
 					// a. If DisplayPort multi-stream - use AUX to program receiver VC Payload ID
 					// table to add stream
 
 					// b. Configure Transcoder Clock Select to direct the Port clock to the
 					// Transcoder
-					transcoder::configure_clock(&mut control, Transcoder::EDP, None);
+					transcoder::configure_clock(&mut control, trans, ddi);
 					// c. Configure and enable planes (VGA or hires). This can be done later if
 					// desired.
 					pipe::configure(&mut control, pipe::Pipe::A, &mode);
 					plane::enable(&mut control, plane::Plane::A, config);
-					transcoder::configure_rest(&mut control, Transcoder::EDP, None, mode);
-					//transcoder::enable_only(&mut control, Transcoder::EDP);
+					transcoder::configure_rest(&mut control, trans, ddi, mode);
+					//transcoder::enable_only(&mut control, trans);
 					// k. If eDP (DDI A), set DP_TP_CTL link training to Normal
-					displayport::set_training_pattern(
-						&mut control,
-						displayport::Port::A,
-						displayport::LinkTraining::Normal,
-					);
-					backlight::enable_backlight(&mut control);
-
-					let v = control.load(SRD_CTL_EDP);
-					control.store(SRD_CTL_EDP, v | (1 << 31));
+					if port == displayport::Port::A {
+						displayport::set_training_pattern(
+							&mut control,
+							port,
+							displayport::LinkTraining::Normal,
+						);
+						backlight::enable_backlight(&mut control);
+
+						let v = control.load(SRD_CTL_EDP);
+						control.store(SRD_CTL_EDP, v | (1 << 31));
+					}
 				}
 
 				let plane_buf = memory.cast::<[u8; 4]>();
@@ -507,7 +532,10 @@ fn main(_: isize, _: *const *const u8) -> isize {
 	let table = {
 		let (buf, _) = rt::Object::new(rt::NewObject::SharedMemory { size: 1 << 12 }).unwrap();
 		let tbl = StreamTable::new(&buf, rt::io::Pow2Size(5), (1 << 8) - 1);
-		root.create(b"gpu").unwrap().share(tbl.public()).unwrap();
+		root.create_with_type(b"gpu", b"gpu")
+			.unwrap()
+			.share(tbl.public())
+			.unwrap();
 		tbl
 	};
 
@@ -552,8 +580,10 @@ fn main(_: isize, _: *const *const u8) -> isize {
 					match handle {
 						// Blit a specific area
 						SYNC_HANDLE => {
-							if let Ok(d) = d.try_into() {
-								let cmd = ipc_gpu::Flush::decode(d);
+							let cmd = d.try_into().ok().and_then(|d| {
+								ipc_gpu::Flush::decode_checked(d, command_buf.1).ok()
+							});
+							if let Some(cmd) = cmd {
 								unsafe {
 									display_fb.copy_from_raw_untrusted_rgb24_to_bgrx32(
 										command_buf.0.as_ptr().add(cmd.offset as _).cast(),
@@ -587,9 +617,13 @@ fn main(_: isize, _: *const *const u8) -> isize {
 					_ => unreachable!(),
 				},
 				Request::Create { path } => (Response::Error(Error::InvalidOperation as _)),
-				Request::Read { .. } | Request::Destroy { .. } | Request::Seek { .. } => {
-					Response::Error(Error::InvalidOperation as _)
-				}
+				Request::Read { .. }
+				| Request::Destroy { .. }
+				| Request::Seek { .. }
+				| Request::Readv { .. }
+				| Request::Writev { .. }
+				| Request::ReadAt { .. }
+				| Request::WriteAt { .. } => Response::Error(Error::InvalidOperation as _),
 			};
 			flush = true;
 			table.enqueue(job_id, resp);
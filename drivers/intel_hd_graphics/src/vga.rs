@@ -1,4 +1,4 @@
-use crate::control::Control;
+use {crate::control::Control, driver_utils::os::portio::PortIo};
 
 reg! {
 	/// # Note
@@ -11,7 +11,6 @@ reg! {
 }
 
 const SR_INDEX: u16 = 0x3c4;
-#[allow(dead_code)]
 const SR_DATA: u16 = 0x3c5;
 
 struct Sr01(u8);
@@ -25,14 +24,13 @@ impl Sr01 {
 	}
 }
 
-pub unsafe fn disable_vga(control: &mut Control, io: rt::RefObject<'_>) {
+pub unsafe fn disable_vga(control: &mut Control, io: &PortIo) {
 	// Disable VGA screen
-	io.seek(rt::io::SeekFrom::Start(SR_INDEX.into())).unwrap();
-	io.write(&[Sr01::INDEX]).unwrap();
-	let mut sr01 = Sr01(0);
-	io.read(core::slice::from_mut(&mut sr01.0)).unwrap();
+	let (index, data) = (io.port::<u8>(SR_INDEX), io.port::<u8>(SR_DATA));
+	index.write(Sr01::INDEX);
+	let mut sr01 = Sr01(data.read());
 	sr01.set_disabled(true);
-	io.write(core::slice::from_ref(&sr01.0)).unwrap();
+	data.write(sr01.0);
 	rt::thread::sleep(core::time::Duration::from_micros(100));
 
 	// Disable VGA plane
@@ -6,7 +6,6 @@ mod scanset2;
 
 use {
 	super::*,
-	alloc::collections::VecDeque,
 	core::cell::{Cell, RefCell},
 	driver_utils::os::stream_table::JobId,
 	input::{
@@ -20,9 +19,8 @@ pub mod cmd {
 }
 
 pub struct Keyboard {
-	readers: RefCell<VecDeque<JobId>>,
-	events: RefCell<LossyRingBuffer<Input>>,
-	config: Config,
+	events: RefCell<EventRing<Input>>,
+	config: RefCell<Config>,
 	translator: RefCell<scanset2::Translator>,
 	modifiers: Cell<u8>,
 }
@@ -35,39 +33,40 @@ const APPLY_CAPS: u8 = MOD_LSHIFT | MOD_RSHIFT | MOD_CAPS;
 
 impl Keyboard {
 	pub fn new() -> Self {
-		let config = {
-			let f = rt::io::file_root()
-				.unwrap()
-				.open(b"drivers/keyboard.scf")
-				.unwrap();
-			let len = f
-				.seek(rt::io::SeekFrom::End(0))
-				.unwrap()
-				.try_into()
-				.unwrap();
-			f.seek(rt::io::SeekFrom::Start(0)).unwrap();
-			let mut buf = alloc::vec::Vec::with_capacity(len);
-			let mut offt = 0;
-			while offt < len {
-				offt += f
-					.read_uninit(&mut buf.spare_capacity_mut()[offt..])
-					.unwrap()
-					.0
-					.len();
-			}
-			unsafe { buf.set_len(len) };
-			input::config::parse(&buf).expect("failed to parse config")
-		};
+		let config = Self::load_config(b"drivers/keyboard.scf").expect("failed to load config");
 
 		Self {
-			events: Default::default(),
-			readers: Default::default(),
-			config,
+			events: EventRing::new(EVENT_BUFFER_SIZE, Overflow::DropNewest).into(),
+			config: config.into(),
 			translator: Default::default(),
 			modifiers: 0.into(),
 		}
 	}
 
+	/// Read and parse a keymap.
+	fn load_config(path: &[u8]) -> Result<Config, Error> {
+		let f = rt::io::file_root().unwrap().open(path)?;
+		let len = f
+			.seek(rt::io::SeekFrom::End(0))?
+			.try_into()
+			.map_err(|_| Error::InvalidData)?;
+		f.seek(rt::io::SeekFrom::Start(0))?;
+		let mut buf = alloc::vec::Vec::with_capacity(len);
+		let mut offt = 0;
+		while offt < len {
+			let l = f
+				.read_uninit(&mut buf.spare_capacity_mut()[offt..])?
+				.0
+				.len();
+			if l == 0 {
+				return Err(Error::InvalidData);
+			}
+			offt += l;
+		}
+		unsafe { buf.set_len(len) };
+		input::config::parse(&buf).map_err(|_| Error::InvalidData)
+	}
+
 	fn toggle_modifier(&self, input: Input) {
 		use {Kbd::*, Type::Keyboard as K};
 		let mut m = self.modifiers.get();
@@ -87,13 +86,9 @@ impl Keyboard {
 
 impl Device for Keyboard {
 	fn add_reader<'a>(&self, reader: JobId, buf: &'a mut [u8; 8]) -> Option<JobId> {
-		if let Some(e) = self.events.borrow_mut().pop() {
-			buf.copy_from_slice(&u64::from(e).to_le_bytes());
-			Some(reader)
-		} else {
-			self.readers.borrow_mut().push_back(reader);
-			None
-		}
+		let e = self.events.borrow_mut().read(reader)?;
+		buf.copy_from_slice(&u64::from(e).to_le_bytes());
+		Some(reader)
 	}
 
 	fn handle_interrupt<'a>(&self, ps2: &mut Ps2, buf: &'a mut [u8; 8]) -> Option<JobId> {
@@ -101,12 +96,13 @@ impl Device for Keyboard {
 		let mut tr = self.translator.borrow_mut();
 		let (release, seq) = tr.push(b, buf)?;
 
-		let Some(code) = self.config.raw(seq) else {
+		let config = self.config.borrow();
+		let Some(code) = config.raw(seq) else {
 			log!("unknown HID sequence {:02x?}", seq);
 			return None;
 		};
 		let m = self.modifiers.get();
-		let code = self.config.modified(
+		let code = config.modified(
 			code,
 			Modifiers {
 				altgr: m & MOD_ALTGR != 0,
@@ -116,12 +112,19 @@ impl Device for Keyboard {
 		);
 		let code = Input::new(code, i32::from(!release) * i32::MAX);
 		self.toggle_modifier(code);
-		if let Some(id) = self.readers.borrow_mut().pop_front() {
-			buf.copy_from_slice(&u64::from(code).to_le_bytes());
-			Some(id)
-		} else {
-			self.events.borrow_mut().push(code);
-			None
+		let (id, code) = self.events.borrow_mut().push(code).ok()??;
+		buf.copy_from_slice(&u64::from(code).to_le_bytes());
+		Some(id)
+	}
+
+	fn set_meta(&self, property: &[u8], value: &[u8]) -> Result<(), Error> {
+		match property {
+			// Replace the keymap with the one at the given path.
+			b"keymap" => {
+				*self.config.borrow_mut() = Self::load_config(value)?;
+				Ok(())
+			}
+			_ => Err(Error::DoesNotExist),
 		}
 	}
 }
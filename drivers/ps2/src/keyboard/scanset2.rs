@@ -1,9 +1,10 @@
 /// ## References
 ///
 /// (USB HID to PS/2)[https://web.archive.org/web/20030701121507/http://microsoft.com/hwdev/download/tech/input/translate.pdf]
-use core::mem;
-
 /// PS/2 Scanset2 to USB HID translator
+///
+/// Bytes are fed one at a time as they arrive, so sequences may be split across any number of
+/// interrupts.
 #[derive(Default)]
 pub struct Translator {
 	state: State,
@@ -15,17 +16,20 @@ enum State {
 	#[default]
 	None,
 	Escape,
-	LongEscape(PauseState),
+	/// Index of the next expected byte in [`PAUSE`].
+	Pause(u8),
 }
 
-#[derive(Clone, Copy)]
-enum PauseState {
-	Wait14,
-	Wait77,
-}
+/// Pause has no break code. Instead, the make sequence includes what looks like a break sequence.
+const PAUSE: [u8; 8] = [0xe1, 0x14, 0x77, 0xe1, 0xf0, 0x14, 0xf0, 0x77];
+/// USB HID usage of Pause.
+const USB_PAUSE: u8 = 0x48;
 
 impl Translator {
 	pub fn push<'a>(&mut self, byte: u8, buf: &'a mut [u8; 8]) -> Option<(bool, &'a [u8])> {
+		if let State::Pause(i) = self.state {
+			return self.push_pause(i, byte, buf);
+		}
 		match byte {
 			0xe0 => {
 				if !matches!(self.state, State::None) {
@@ -35,14 +39,15 @@ impl Translator {
 				None
 			}
 			0xe1 => {
-				if !matches!(self.state, State::None) {
+				if !matches!(self.state, State::None) || self.release {
 					log!("long escape sequence sent when state is not none");
 				}
-				self.state = State::LongEscape(PauseState::Wait14);
+				self.state = State::Pause(1);
+				self.release = false;
 				None
 			}
 			0xf0 => {
-				if self.release && !matches!(self.state, State::LongEscape(_)) {
+				if self.release {
 					log!("release already set");
 				}
 				self.release = true;
@@ -51,33 +56,60 @@ impl Translator {
 			_ => {
 				let b = match self.state {
 					State::None => translate_single(byte),
-					State::Escape if byte == 0x12 => {
-						// Just ignore, I'm done with this multi-scancode bullshit.
-						// (This is prepended to print screen, which is E0 7C)
-						self.state = State::None;
+					// Fake shifts are sent around some keys (e.g. print screen, which is E0 7C)
+					// to undo the effect of any held shift key. Ignore them.
+					State::Escape if byte == 0x12 || byte == 0x59 => {
+						self.reset();
 						return None;
 					}
 					State::Escape => translate_escaped(byte),
-					State::LongEscape(PauseState::Wait14) if byte == 0x14 => {
-						self.state = State::LongEscape(PauseState::Wait77);
-						return None;
-					}
-					State::LongEscape(PauseState::Wait77) if byte == 0x77 => Some(0x48),
-					State::LongEscape(_) => None,
+					State::Pause(_) => unreachable!(),
 				};
 				if let Some(b) = b {
-					buf[0] = b;
-					self.state = State::None;
-					Some((mem::take(&mut self.release), &buf[..1]))
+					let release = self.release;
+					self.reset();
+					Some(emit(release, b, buf))
 				} else {
 					log!("unknown sequence");
-					self.state = State::None;
-					self.release = false;
+					self.reset();
 					None
 				}
 			}
 		}
 	}
+
+	fn push_pause<'a>(
+		&mut self,
+		i: u8,
+		byte: u8,
+		buf: &'a mut [u8; 8],
+	) -> Option<(bool, &'a [u8])> {
+		if PAUSE[usize::from(i)] != byte {
+			log!("unexpected byte {:02x} in pause sequence", byte);
+			self.reset();
+			return None;
+		}
+		let i = i + 1;
+		self.state = State::Pause(i);
+		match usize::from(i) {
+			3 => Some(emit(false, USB_PAUSE, buf)),
+			n if n == PAUSE.len() => {
+				self.reset();
+				Some(emit(true, USB_PAUSE, buf))
+			}
+			_ => None,
+		}
+	}
+
+	fn reset(&mut self) {
+		self.state = State::None;
+		self.release = false;
+	}
+}
+
+fn emit(release: bool, code: u8, buf: &mut [u8; 8]) -> (bool, &[u8]) {
+	buf[0] = code;
+	(release, &buf[..1])
 }
 
 macro_rules! map {
@@ -241,3 +273,72 @@ fn translate_escaped(byte: u8) -> Option<u8> {
 		// TODO bunch of USB HID page 0x0c stuff
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn feed(tr: &mut Translator, bytes: &[u8]) -> alloc::vec::Vec<(bool, u8)> {
+		let mut buf = [0; 8];
+		bytes
+			.iter()
+			.filter_map(|&b| tr.push(b, &mut buf).map(|(r, s)| (r, s[0])))
+			.collect()
+	}
+
+	#[test]
+	fn single() {
+		let mut tr = Translator::default();
+		// A
+		assert_eq!(
+			feed(&mut tr, &[0x1c, 0xf0, 0x1c]),
+			[(false, 0x04), (true, 0x04)]
+		);
+	}
+
+	#[test]
+	fn escaped() {
+		let mut tr = Translator::default();
+		// Right control
+		assert_eq!(
+			feed(&mut tr, &[0xe0, 0x14, 0xe0, 0xf0, 0x14]),
+			[(false, 0xe4), (true, 0xe4)]
+		);
+	}
+
+	#[test]
+	fn print_screen() {
+		let mut tr = Translator::default();
+		let seq = [0xe0, 0x12, 0xe0, 0x7c, 0xe0, 0xf0, 0x7c, 0xe0, 0xf0, 0x12];
+		assert_eq!(feed(&mut tr, &seq), [(false, 0x46), (true, 0x46)]);
+		// The release flag of the fake shift must not leak into the next key.
+		assert_eq!(feed(&mut tr, &[0x1c]), [(false, 0x04)]);
+	}
+
+	#[test]
+	fn pause() {
+		let mut tr = Translator::default();
+		assert_eq!(
+			feed(&mut tr, &PAUSE),
+			[(false, USB_PAUSE), (true, USB_PAUSE)]
+		);
+		assert_eq!(feed(&mut tr, &[0x1c]), [(false, 0x04)]);
+	}
+
+	#[test]
+	fn split() {
+		let mut tr = Translator::default();
+		assert_eq!(feed(&mut tr, &[0xe0]), []);
+		assert_eq!(feed(&mut tr, &[0xf0]), []);
+		assert_eq!(feed(&mut tr, &[0x75]), [(true, 0x52)]);
+		assert_eq!(feed(&mut tr, &PAUSE[..2]), []);
+		assert_eq!(feed(&mut tr, &PAUSE[2..5]), [(false, USB_PAUSE)]);
+		assert_eq!(feed(&mut tr, &PAUSE[5..]), [(true, USB_PAUSE)]);
+	}
+
+	#[test]
+	fn bad_pause() {
+		let mut tr = Translator::default();
+		assert_eq!(feed(&mut tr, &[0xe1, 0x14, 0x1c, 0x1c]), [(false, 0x04)]);
+	}
+}
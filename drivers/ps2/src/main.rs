@@ -2,6 +2,7 @@
 #![feature(start)]
 #![feature(const_trait_impl, inline_const)]
 #![feature(let_else)]
+#![feature(never_type)]
 #![deny(unused_must_use)]
 
 extern crate alloc;
@@ -17,23 +18,24 @@ macro_rules! log {
 }
 
 mod keyboard;
-mod lossy_ring_buffer;
 mod mouse;
 
 //use acpi::{fadt::Fadt, sdt::Signature, AcpiHandler, AcpiTables};
 use {
+	alloc::boxed::Box,
 	async_std::{
 		io::{Read, Write},
 		object::{AsyncObject, RefAsyncObject},
 		task,
 	},
-	core::{cell::RefCell, time::Duration},
-	driver_utils::os::{
-		portio::PortIo,
-		stream_table::{JobId, Request, Response, StreamTable},
+	core::{cell::RefCell, future::Future, pin::Pin, time::Duration},
+	driver_utils::{
+		io::{EventRing, Overflow},
+		os::{
+			portio::PortIo,
+			stream_table::{JobId, Request, Response, StreamTable},
+		},
 	},
-	futures_util::future,
-	lossy_ring_buffer::LossyRingBuffer,
 	rt::{self as _, Error, Handle, NewObject, Object},
 	rt_default as _,
 };
@@ -104,6 +106,14 @@ async fn main() -> ! {
 							continue;
 						}
 					}
+					Request::SetMeta { property_value } if handle == KEYBOARD_HANDLE => {
+						match property_value.try_get(&mut [0; 256 + 256]) {
+							Ok((p, v)) => dev1
+								.set_meta(p, v)
+								.map_or_else(Response::Error, |()| Response::Amount(0)),
+							Err(_) => Response::Error(Error::InvalidData),
+						}
+					}
 					Request::Close => continue,
 					_ => Response::Error(rt::Error::InvalidOperation),
 				};
@@ -134,16 +144,9 @@ async fn main() -> ! {
 	}
 	let dev1_loop = f_loop(&tbl, &ps2, &dev1, dev1_intr);
 	let dev2_loop = f_loop(&tbl, &ps2, &dev2, dev2_intr);
-	futures_util::pin_mut!(tbl_loop);
-	futures_util::pin_mut!(dev1_loop);
-	futures_util::pin_mut!(dev2_loop);
-	match future::select(tbl_loop, future::select(dev1_loop, dev2_loop)).await {
-		future::Either::Left(v) => v.0,
-		future::Either::Right(v) => match v.0 {
-			future::Either::Left(v) => v.0,
-			future::Either::Right(v) => v.0,
-		},
-	}
+	let loops: [Pin<Box<dyn Future<Output = !>>>; 3] =
+		[Box::pin(tbl_loop), Box::pin(dev1_loop), Box::pin(dev2_loop)];
+	task::select(loops).await.1
 }
 
 const DATA: u16 = 0x60;
@@ -160,6 +163,9 @@ const CTRL_CFG_PORT_1_TRANSLATION: u8 = 1 << 6;
 const PORT_ACKNOWLEDGE: u8 = 0xfa;
 const PORT_RESEND: u8 = 0xfe;
 
+/// The maximum amount of input events buffered per device.
+const EVENT_BUFFER_SIZE: usize = 128;
+
 // TODO determine what a reasonable timeout is.
 const TIMEOUT_MS: u32 = 100;
 
@@ -189,6 +195,11 @@ trait Device {
 
 	#[must_use]
 	fn handle_interrupt<'a>(&self, ps2: &mut Ps2, buf: &'a mut [u8; 8]) -> Option<JobId>;
+
+	fn set_meta(&self, property: &[u8], value: &[u8]) -> Result<(), Error> {
+		let _ = (property, value);
+		Err(Error::InvalidOperation)
+	}
 }
 
 pub struct Ps2 {
@@ -282,6 +293,18 @@ impl Ps2 {
 		let _ = self.read_port_acknowledge();
 	}
 
+	/// Send a magic sample rate sequence and return the resulting device ID.
+	///
+	/// Devices that don't support the extension simply keep their current ID.
+	fn enable_mouse_extension(&mut self, rates: &[u8]) -> Option<u8> {
+		for &r in rates {
+			self.write_mouse(mouse::cmd::SET_SAMPLE_RATE);
+			self.write_mouse(r);
+		}
+		self.write_mouse(mouse::cmd::GET_ID);
+		self.read_port_data().ok()
+	}
+
 	fn init() -> (Self, keyboard::Keyboard, mouse::Mouse) {
 		// https://wiki.osdev.org/%228042%22_PS/2_Controller#Initialising_the_PS.2F2_Controller
 		let mut slf = Self { io: PortIo::new().unwrap() };
@@ -311,14 +334,24 @@ impl Ps2 {
 		slf.write_keyboard(keyboard::cmd::GET_SET_SCANCODE_SET);
 		slf.write_keyboard(2);
 
-		log!("set mouse defaults & enable");
+		log!("set mouse defaults");
 		slf.write_mouse(mouse::cmd::SET_DEFAULTS);
+
+		log!("enable mouse extensions");
+		let mut mouse_id = slf.enable_mouse_extension(&mouse::ENABLE_WHEEL);
+		if mouse_id == Some(mouse::device::WHEEL) {
+			mouse_id = slf.enable_mouse_extension(&mouse::ENABLE_5_BUTTONS);
+		}
+		let mouse_id = mouse_id.unwrap_or(mouse::device::STANDARD);
+		log!("mouse ID: {}", mouse_id);
+
+		log!("enable mouse");
 		slf.write_mouse(mouse::cmd::DATA_ON);
 
 		log!("load keyboard driver");
 		let keyboard = keyboard::Keyboard::new();
 		log!("load mouse driver");
-		let mouse = mouse::Mouse::default();
+		let mouse = mouse::Mouse::new(mouse_id);
 
 		(slf, keyboard, mouse)
 	}
@@ -1,22 +1,33 @@
 use {
 	super::*,
-	alloc::collections::VecDeque,
 	core::cell::{Cell, RefCell},
 	driver_utils::os::stream_table::JobId,
 	input::{Input, Movement, Type},
 };
 
 pub mod cmd {
+	pub const GET_ID: u8 = 0xf2;
+	pub const SET_SAMPLE_RATE: u8 = 0xf3;
 	pub const SET_DEFAULTS: u8 = 0xf8;
 	pub const DATA_ON: u8 = 0xf4;
 }
 
-#[derive(Default)]
+/// Device IDs returned by [`cmd::GET_ID`].
+pub mod device {
+	pub const STANDARD: u8 = 0;
+	pub const WHEEL: u8 = 3;
+	pub const WHEEL_5_BUTTONS: u8 = 4;
+}
+
+/// Sample rate sequences that enable the IntelliMouse extensions.
+pub const ENABLE_WHEEL: [u8; 3] = [200, 100, 80];
+pub const ENABLE_5_BUTTONS: [u8; 3] = [200, 200, 80];
+
 pub struct Mouse {
-	readers: RefCell<VecDeque<JobId>>,
-	events: RefCell<LossyRingBuffer<Input>>,
+	events: RefCell<EventRing<Input>>,
 	buf: Cell<Buf>,
 	buttons_pressed: Cell<u8>,
+	id: u8,
 }
 
 #[derive(Default)]
@@ -25,14 +36,41 @@ enum Buf {
 	N0,
 	N1,
 	N2,
+	N3,
 }
 
 impl Mouse {
+	pub fn new(id: u8) -> Self {
+		Self {
+			events: EventRing::new(EVENT_BUFFER_SIZE, Overflow::DropNewest).into(),
+			buf: Default::default(),
+			buttons_pressed: Default::default(),
+			id,
+		}
+	}
+
+	/// Emit events for any buttons in `mask` whose state differs from `pressed`.
+	fn update_buttons(&self, pressed: u8, mask: u8, buf: &mut [u8; 8], id: &mut Option<JobId>) {
+		let old = self.buttons_pressed.get();
+		let d = (pressed ^ old) & mask;
+		for i in 0..5 {
+			let m = 1 << i;
+			if d & m != 0 {
+				let inp = Input::new(Type::Button(i), i32::from(pressed & m != 0) * i32::MAX);
+				let r = self.add_input(inp, buf, id.is_none());
+				*id = id.or(r);
+			}
+		}
+		self.buttons_pressed.set(old & !mask | pressed & mask);
+	}
+
 	fn add_input(&self, inp: Input, buf: &mut [u8; 8], pop: bool) -> Option<JobId> {
-		if let Some(id) = pop.then(|| self.readers.borrow_mut().pop_front()).flatten() {
+		let mut events = self.events.borrow_mut();
+		if pop {
+			let (id, inp) = events.push(inp).ok()??;
 			Some(finish_job(id, buf, inp))
 		} else {
-			self.events.borrow_mut().push(inp);
+			let _ = events.enqueue(inp);
 			None
 		}
 	}
@@ -40,12 +78,8 @@ impl Mouse {
 
 impl Device for Mouse {
 	fn add_reader<'a>(&self, id: JobId, buf: &'a mut [u8; 8]) -> Option<JobId> {
-		if let Some(inp) = self.events.borrow_mut().pop() {
-			Some(finish_job(id, buf, inp))
-		} else {
-			self.readers.borrow_mut().push_back(id);
-			None
-		}
+		let inp = self.events.borrow_mut().read(id)?;
+		Some(finish_job(id, buf, inp))
 	}
 
 	fn handle_interrupt<'a>(&self, ps2: &mut Ps2, buf: &'a mut [u8; 8]) -> Option<JobId> {
@@ -54,19 +88,8 @@ impl Device for Mouse {
 		let mut id = None;
 		self.buf.set(match self.buf.take() {
 			Buf::N0 => {
-				// Button presses.
-				const BL: u8 = 1 << 0;
-				const BR: u8 = 1 << 1;
-				const BM: u8 = 1 << 2;
-				let d = x ^ self.buttons_pressed.get();
-				for i in 0..2 {
-					let m = 1 << i;
-					if d & m != 0 {
-						let inp = Input::new(Type::Button(i), i32::from(x & m != 0) * i32::MAX);
-						id = self.add_input(inp, buf, id.is_none())
-					}
-				}
-				self.buttons_pressed.set(x);
+				// Left, right & middle button.
+				self.update_buttons(x & 0b111, 0b111, buf, &mut id);
 				Buf::N1
 			}
 			Buf::N1 => {
@@ -79,6 +102,25 @@ impl Device for Mouse {
 				// Y movement
 				let inp = Input::new(Type::Relative(0, Movement::TranslationY), x as i8 as i32);
 				id = self.add_input(inp, buf, true);
+				match self.id {
+					device::WHEEL | device::WHEEL_5_BUTTONS => Buf::N3,
+					_ => Buf::N0,
+				}
+			}
+			Buf::N3 => {
+				// Z movement & button 4 and 5
+				let z = if self.id == device::WHEEL_5_BUTTONS {
+					self.update_buttons((x >> 1) & 0b11000, 0b11000, buf, &mut id);
+					// Sign-extend 4-bit value
+					(x << 4) as i8 >> 4
+				} else {
+					x as i8
+				};
+				if z != 0 {
+					let inp = Input::new(Type::Relative(0, Movement::TranslationZ), z.into());
+					let r = self.add_input(inp, buf, id.is_none());
+					id = id.or(r);
+				}
 				Buf::N0
 			}
 		});
@@ -20,9 +20,10 @@ use {
 
 #[start]
 fn main(_: isize, _: *const *const u8) -> isize {
-	let mut args = rt::args::args().skip(1);
-	let table_name = args.next().expect("expected table path");
-	let input = args.next().expect("expected input object path");
+	let [_, table_name, input] = match rt::args::args_required() {
+		Ok(a) => a,
+		Err(e) => panic!("{} (expected table path and input object path)", e),
+	};
 
 	let root = rt::io::file_root().unwrap();
 
@@ -81,6 +81,21 @@ impl<'a> Drivers<'a> {
 						};
 						Some(Event::GetDescriptor { recipient, ty, index, len })
 					}
+					ipc_usb::SEND_TY_SET_REPORT => {
+						let [_, ty, id]: [u8; 3] = (&*buf).try_into().expect("invalid msg");
+						let ty = ipc_usb::ReportType::try_from(ty).expect("invalid report type");
+						let mut buf = [0; 1024 + 32];
+						let l = driver.stdout.read(&mut buf).unwrap();
+						let mut data = crate::dma::Dma::new_slice(l).unwrap();
+						unsafe { data.as_mut().copy_from_slice(&buf[..l]) }
+						Some(Event::SetReport { interface: driver.interface, ty, id, data })
+					}
+					ipc_usb::SEND_TY_GET_REPORT => {
+						let [_, ty, id, a, b]: [u8; 5] = (&*buf).try_into().expect("invalid msg");
+						let ty = ipc_usb::ReportType::try_from(ty).expect("invalid report type");
+						let len = u16::from_le_bytes([a, b]);
+						Some(Event::GetReport { interface: driver.interface, ty, id, len })
+					}
 					_ => todo!(
 						"invalid msg: {:?}",
 						alloc::string::String::from_utf8_lossy(&buf)
@@ -103,6 +118,7 @@ impl<'a> Drivers<'a> {
 		driver: &crate::config::Driver,
 		base: (u8, u8, u8),
 		interface: (u8, u8, u8),
+		interface_number: u8,
 		endpoints: &[Endpoint],
 	) -> rt::io::Result<()> {
 		trace!("load driver for {}", slot);
@@ -110,7 +126,12 @@ impl<'a> Drivers<'a> {
 			.entry(slot)
 			.and_modify(|_| panic!("driver already present in slot {}", slot))
 			.or_insert(DeviceDriver::new(
-				driver, self.queue, base, interface, endpoints,
+				driver,
+				self.queue,
+				base,
+				interface,
+				interface_number,
+				endpoints,
 			)?);
 		Ok(())
 	}
@@ -172,6 +193,7 @@ struct DeviceDriver<'a> {
 	write_tasks: Vec<Write<'a, Vec<u8>>>,
 	share_task: Option<Open<'a, ()>>,
 	name: Box<str>,
+	interface: u8,
 	msg_in_counter: Wrapping<u32>,
 	msg_out_counter: Wrapping<u32>,
 }
@@ -182,6 +204,7 @@ impl<'a> DeviceDriver<'a> {
 		queue: &'a Queue,
 		base: (u8, u8, u8),
 		interface: (u8, u8, u8),
+		interface_number: u8,
 		endpoints: &[Endpoint],
 	) -> rt::io::Result<Self> {
 		let (stdin, proc_stdin) = rt::Object::new(rt::NewObject::MessagePipe)?;
@@ -256,6 +279,7 @@ impl<'a> DeviceDriver<'a> {
 			write_tasks: Default::default(),
 			share_task: Default::default(),
 			name: driver.name.as_deref().unwrap_or("unnamed{n}").into(),
+			interface: interface_number,
 			msg_in_counter: Wrapping(0),
 			msg_out_counter: Wrapping(0),
 		})
@@ -266,6 +290,8 @@ pub enum Event {
 	DataIn { endpoint: u8, size: u32 },
 	DataOut { endpoint: u8, data: crate::dma::Dma<[u8]> },
 	GetDescriptor { recipient: Recipient, ty: u8, index: u8, len: u16 },
+	SetReport { interface: u8, ty: ipc_usb::ReportType, id: u8, data: crate::dma::Dma<[u8]> },
+	GetReport { interface: u8, ty: ipc_usb::ReportType, id: u8, len: u16 },
 }
 
 pub enum Recipient {
@@ -0,0 +1,279 @@
+//! # USB hub support
+//!
+//! Devices attached to a hub are detected through the hub's status change endpoint. Each port
+//! that reports a change is queried, after which connected devices are reset and handed to the
+//! controller as new devices.
+//!
+//! Only USB 2.0 hubs are supported. Multi-TT hubs are used in single-TT mode. Super-speed hubs
+//! use a different descriptor and need their depth to be set, neither of which is implemented.
+//!
+//! See chapter 11 of the USB 2.0 specification for details.
+
+use {
+	crate::{
+		dma::Dma,
+		request::{DIR_OUT, RECIPIENT_OTHER, TYPE_CLASS},
+		xhci,
+	},
+	alloc::{collections::VecDeque, vec::Vec},
+	core::{num::NonZeroU8, time::Duration},
+	usb_request::RawRequest as R,
+};
+
+/// Device class of hubs.
+pub const CLASS: u8 = 0x09;
+
+/// Device protocol of super-speed hubs.
+pub const PROTOCOL_SUPER_SPEED: u8 = 0x03;
+
+const DESCRIPTOR_TYPE: u8 = 0x29;
+
+const GET_STATUS: u8 = 0x00;
+const CLEAR_FEATURE: u8 = 0x01;
+const SET_FEATURE: u8 = 0x03;
+
+const PORT_RESET: u16 = 4;
+const PORT_POWER: u16 = 8;
+const C_PORT_CONNECTION: u16 = 16;
+const C_PORT_ENABLE: u16 = 17;
+const C_PORT_SUSPEND: u16 = 18;
+const C_PORT_OVER_CURRENT: u16 = 19;
+const C_PORT_RESET: u16 = 20;
+
+const STATUS_CONNECTION: u16 = 1 << 0;
+const STATUS_ENABLE: u16 = 1 << 1;
+const STATUS_LOW_SPEED: u16 = 1 << 9;
+const STATUS_HIGH_SPEED: u16 = 1 << 10;
+
+/// Relevant fields of a hub descriptor.
+pub struct Descriptor {
+	ports: u8,
+	characteristics: u16,
+	/// Time in units of 2ms until power is good on a port.
+	power_on_time: u8,
+}
+
+impl Descriptor {
+	pub fn decode(buf: &[u8]) -> Option<Self> {
+		match buf {
+			&[len, DESCRIPTOR_TYPE, ports, c0, c1, power_on_time, ..] if len >= 7 => {
+				Some(Self { ports, characteristics: u16::from_le_bytes([c0, c1]), power_on_time })
+			}
+			_ => None,
+		}
+	}
+
+	pub fn ports(&self) -> u8 {
+		self.ports
+	}
+
+	pub fn config(&self) -> xhci::HubConfig {
+		xhci::HubConfig {
+			ports: self.ports,
+			tt_think_time: ((self.characteristics >> 5) & 0b11) as u8,
+		}
+	}
+}
+
+/// A transfer of a hub that is waited on.
+pub enum Transfer {
+	/// An intermediate request, there is nothing to do when it finishes.
+	Out,
+	/// A port has been powered on.
+	PowerOn { port: u8 },
+	/// The status change endpoint reported changes.
+	StatusChange,
+	/// The status of a port is available.
+	PortStatus { port: u8 },
+	/// All requests for a port have finished.
+	PortDone,
+}
+
+pub struct Hub {
+	slot: NonZeroU8,
+	/// The status change endpoint.
+	endpoint: u8,
+	ports: u8,
+	power_on_delay: Duration,
+	/// Ports with changes that haven't been handled yet.
+	pending: VecDeque<u8>,
+}
+
+impl Hub {
+	pub fn new(slot: NonZeroU8, endpoint: u8, descriptor: &Descriptor) -> Self {
+		Self {
+			slot,
+			endpoint,
+			ports: descriptor.ports,
+			power_on_delay: Duration::from_millis(u64::from(descriptor.power_on_time) * 2),
+			pending: Default::default(),
+		}
+	}
+
+	/// Request the hub descriptor.
+	pub fn get_descriptor(ctrl: &mut xhci::Xhci, slot: NonZeroU8) -> u64 {
+		let req = R {
+			request_type: R::DIR_IN | TYPE_CLASS | R::RECIPIENT_DEVICE,
+			request: R::GET_DESCRIPTOR,
+			value: u16::from(DESCRIPTOR_TYPE) << 8,
+			index: 0,
+		};
+		ctrl.send_request(slot, req, Dma::new_slice(64).unwrap())
+			.unwrap_or_else(|_| todo!())
+	}
+
+	/// Power on the first port.
+	///
+	/// Ports are powered on one by one to avoid filling the transfer ring.
+	pub fn power_on(&self, ctrl: &mut xhci::Xhci, track: &mut dyn FnMut(u64, Transfer)) {
+		self.powered_on(ctrl, 0, track)
+	}
+
+	/// Power on the next port.
+	///
+	/// Once all ports are powered on, wait until power is good and begin listening for changes.
+	///
+	/// # Note
+	///
+	/// This blocks for the power on time reported by the hub, which is at most 510ms.
+	pub fn powered_on(
+		&self,
+		ctrl: &mut xhci::Xhci,
+		port: u8,
+		track: &mut dyn FnMut(u64, Transfer),
+	) {
+		if port < self.ports {
+			let id = self.port_request(ctrl, SET_FEATURE, PORT_POWER, port + 1);
+			track(id, Transfer::PowerOn { port: port + 1 });
+		} else {
+			rt::thread::sleep(self.power_on_delay);
+			self.poll_status(ctrl, track);
+		}
+	}
+
+	/// Handle a report from the status change endpoint.
+	pub fn status_changed(
+		&mut self,
+		ctrl: &mut xhci::Xhci,
+		data: &[u8],
+		track: &mut dyn FnMut(u64, Transfer),
+	) {
+		// Bit 0 refers to the hub itself, which we don't care about.
+		self.pending.extend((1..=self.ports).filter(|&p| {
+			data.get(usize::from(p / 8))
+				.map_or(false, |b| b & (1 << (p % 8)) != 0)
+		}));
+		self.next_port(ctrl, track);
+	}
+
+	/// Handle the status of a port.
+	pub fn port_status(
+		&mut self,
+		ctrl: &mut xhci::Xhci,
+		port: u8,
+		data: &[u8],
+		track: &mut dyn FnMut(u64, Transfer),
+	) {
+		let &[s0, s1, c0, c1, ..] = data else {
+			warn!("hub {}: short port status", self.slot);
+			return self.next_port(ctrl, track);
+		};
+		let status = u16::from_le_bytes([s0, s1]);
+		let change = u16::from_le_bytes([c0, c1]);
+		trace!(
+			"hub {} port {}: status {:#06x} change {:#06x}",
+			self.slot,
+			port,
+			status,
+			change
+		);
+
+		// Acknowledge all changes first.
+		let mut ids = Vec::new();
+		for (bit, feature) in [
+			(0, C_PORT_CONNECTION),
+			(1, C_PORT_ENABLE),
+			(2, C_PORT_SUSPEND),
+			(3, C_PORT_OVER_CURRENT),
+			(4, C_PORT_RESET),
+		] {
+			if change & (1 << bit) != 0 {
+				ids.push(self.port_request(ctrl, CLEAR_FEATURE, feature, port));
+			}
+		}
+
+		let connected = status & STATUS_CONNECTION != 0;
+		if change & (1 << 0) != 0 {
+			if connected {
+				// The device can only be addressed after a reset.
+				trace!("hub {} port {}: reset", self.slot, port);
+				ids.push(self.port_request(ctrl, SET_FEATURE, PORT_RESET, port));
+			} else {
+				info!("hub {} port {}: disconnected", self.slot, port);
+				ctrl.detach_device(self.slot, port);
+			}
+		} else if change & (1 << 4) != 0 && connected && status & STATUS_ENABLE != 0 {
+			let speed = if status & STATUS_LOW_SPEED != 0 {
+				xhci::HubPortSpeed::Low
+			} else if status & STATUS_HIGH_SPEED != 0 {
+				xhci::HubPortSpeed::High
+			} else {
+				xhci::HubPortSpeed::Full
+			};
+			info!("hub {} port {}: {:?} speed device", self.slot, port, speed);
+			ctrl.attach_device(self.slot, port, speed);
+		}
+
+		// Only the last request needs to be waited on as requests are processed in order.
+		match ids.pop() {
+			Some(last) => {
+				ids.into_iter().for_each(|id| track(id, Transfer::Out));
+				track(last, Transfer::PortDone);
+			}
+			None => self.next_port(ctrl, track),
+		}
+	}
+
+	/// Query the status of the next port with changes.
+	///
+	/// If there are none left, wait for new changes instead.
+	pub fn next_port(&mut self, ctrl: &mut xhci::Xhci, track: &mut dyn FnMut(u64, Transfer)) {
+		match self.pending.pop_front() {
+			Some(port) => {
+				let req = R {
+					request_type: R::DIR_IN | TYPE_CLASS | RECIPIENT_OTHER,
+					request: GET_STATUS,
+					value: 0,
+					index: port.into(),
+				};
+				let id = ctrl
+					.send_request(self.slot, req, Dma::new_slice(4).unwrap())
+					.unwrap_or_else(|_| todo!());
+				track(id, Transfer::PortStatus { port });
+			}
+			// Changes are only reported again after they are acknowledged, so only wait
+			// once all ports have been handled.
+			None => self.poll_status(ctrl, track),
+		}
+	}
+
+	fn poll_status(&self, ctrl: &mut xhci::Xhci, track: &mut dyn FnMut(u64, Transfer)) {
+		// One bit for the hub and one for each port.
+		let len = usize::from(self.ports) / 8 + 1;
+		let id = ctrl
+			.transfer(self.slot, self.endpoint, Dma::new_slice(len).unwrap(), true)
+			.unwrap_or_else(|_| todo!());
+		track(id, Transfer::StatusChange);
+	}
+
+	fn port_request(&self, ctrl: &mut xhci::Xhci, request: u8, feature: u16, port: u8) -> u64 {
+		let req = R {
+			request_type: DIR_OUT | TYPE_CLASS | RECIPIENT_OTHER,
+			request,
+			value: feature,
+			index: port.into(),
+		};
+		ctrl.send_request(self.slot, req, Dma::new_slice(0).unwrap())
+			.unwrap_or_else(|_| todo!())
+	}
+}
@@ -1,4 +1,33 @@
 //! # USB driver
+//!
+//! ## Objects
+//!
+//! - `devices`: lists the product name of each device, in slot order.
+//! - `devices/<slot>/descriptors`: lists the descriptors of the device in the given slot. The
+//!   slot of a device is printed when it is attached.
+//! - `handlers`: lists the registered handlers, which can be opened with `handlers/<name>`.
+//!
+//! ## Descriptors
+//!
+//! Each read from a `descriptors` object returns one descriptor, in the order the device
+//! reported them. The device descriptor comes first, followed by the first configuration and
+//! all its interface and endpoint descriptors. An empty read indicates the end of the list.
+//!
+//! The descriptors are captured when the device is attached, so reading them does not issue any
+//! requests to the device. Class, subclass and protocol codes are formatted as two hexadecimal
+//! digits, other numbers are in decimal:
+//!
+//! ```text
+//! device <class>/<subclass>/<protocol>
+//! configuration <value> interfaces <count>
+//! interface <number>.<alternate setting> <class>/<subclass>/<protocol> endpoints <count>
+//! endpoint <number> <in|out> <transfer type> max-packet-size <size> interval <interval>
+//! hid
+//! string
+//! unknown <type>
+//! ```
+//!
+//! The transfer type is one of `Control`, `Isochronous`, `Bulk` or `Interrupt`.
 
 #![no_std]
 #![feature(start)]
@@ -50,21 +79,25 @@ macro_rules! warn {
 mod config;
 mod dma;
 mod driver;
+mod hub;
 mod loader;
+mod request;
 mod xhci;
 
 use {
-	alloc::{boxed::Box, collections::BTreeMap, vec::Vec},
-	core::{future::Future, num::NonZeroU8, pin::Pin, str, task::Context, time::Duration},
+	alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec::Vec},
+	core::{num::NonZeroU8, str, time::Duration},
 	dma::Dma,
 	driver_utils::{
 		os::stream_table::{JobId, Request, Response, StreamTable},
-		task::waker,
+		task::Reactor,
 	},
 	io_queue_rt::{Pow2Size, Queue},
 	rt::{Error, Handle},
 	rt_default as _,
-	usb_request::descriptor::{Configuration, Descriptor, Device, Endpoint, Interface},
+	usb_request::descriptor::{
+		Configuration, Descriptor, Device, Direction, Endpoint, EndpointTransfer, Interface,
+	},
 };
 
 #[start]
@@ -94,17 +127,26 @@ fn main() -> ! {
 		.unwrap();
 	let mut objects = driver_utils::Arena::new();
 
+	let reactor = Reactor::new(&queue);
 	let mut poll_ctrl = queue.submit_read(ctrl.notifier().as_raw(), ()).unwrap();
 	let mut poll_tbl = queue.submit_read(tbl.notifier().as_raw(), ()).unwrap();
 
 	let mut transfers = BTreeMap::default();
 	let mut wait_finish_config = BTreeMap::default();
+	let mut hubs = BTreeMap::<_, hub::Hub>::default();
+	// Raw descriptors captured during enumeration, see the module documentation.
+	let mut descriptors = BTreeMap::<NonZeroU8, Vec<u8>>::default();
 
 	enum Transfer<'a> {
 		Job(Job),
 		GetDevice,
 		GetConfiguration(GetConfiguration),
 		SetConfiguration(Box<SetConfiguration<'a>>),
+		/// A control request from a driver that doesn't return any data.
+		DriverOut,
+		GetHubDescriptor(Box<HubSetup>),
+		SetHubConfiguration(Box<HubSetup>, hub::Descriptor),
+		Hub(hub::Transfer),
 	}
 	struct GetConfiguration {
 		device: Device,
@@ -123,11 +165,19 @@ fn main() -> ! {
 		device: Device,
 		config: Configuration,
 	}
+	struct HubSetup {
+		interface: Interface,
+		/// The status change endpoint.
+		endpoint: Endpoint,
+		config: Configuration,
+	}
+	enum WaitConfig<'a> {
+		Driver(EvaluateContext<'a>),
+		Hub(Box<HubSetup>, hub::Descriptor),
+	}
 
 	loop {
-		let w = waker::dummy();
-		let mut cx = Context::from_waker(&w);
-		if Pin::new(&mut poll_ctrl).poll(&mut cx).is_ready() {
+		if reactor.poll(&mut poll_ctrl).is_ready() {
 			trace!("controller events");
 			poll_ctrl = queue.submit_read(ctrl.notifier().as_raw(), ()).unwrap();
 			while let Some(e) = ctrl.poll() {
@@ -183,6 +233,8 @@ fn main() -> ! {
 									let mut it =
 										usb_request::descriptor::decode(unsafe { buffer.as_ref() });
 									let device = it.next().unwrap().unwrap().into_device().unwrap();
+									let raw = unsafe { buffer.as_ref() };
+									descriptors.insert(slot, raw[..descriptor_len(raw)].to_vec());
 									let base = (device.class, device.subclass, device.protocol);
 									info!(
 										"slot {}: device {:02x}/{:02x}/{:02x}",
@@ -204,6 +256,7 @@ fn main() -> ! {
 										Transfer::GetConfiguration(GetConfiguration { device }),
 									);
 								}
+								Transfer::DriverOut => trace!("DriverOut"),
 								Transfer::GetConfiguration(j) => {
 									trace!("GetConfiguration");
 									let buffer = buffer.unwrap();
@@ -211,6 +264,59 @@ fn main() -> ! {
 										usb_request::descriptor::decode(unsafe { buffer.as_ref() });
 									let config =
 										it.next().unwrap().unwrap().into_configuration().unwrap();
+									let raw = unsafe { buffer.as_ref() };
+									let total = raw
+										.get(2..4)
+										.map_or(0, |l| u16::from_le_bytes([l[0], l[1]]).into());
+									descriptors
+										.entry(slot)
+										.or_default()
+										.extend_from_slice(&raw[..usize::min(total, raw.len())]);
+									if j.device.class == hub::CLASS {
+										if j.device.protocol == hub::PROTOCOL_SUPER_SPEED {
+											warn!(
+												"slot {}: super-speed hubs are not supported",
+												slot
+											);
+											continue;
+										}
+										let mut interface = None;
+										let mut endpoint = None;
+										for d in it {
+											match d {
+												Ok(Descriptor::Interface(i))
+													if interface.is_none() =>
+												{
+													interface = Some(i)
+												}
+												Ok(Descriptor::Endpoint(e))
+													if interface.is_some() =>
+												{
+													endpoint = Some(e);
+													break;
+												}
+												_ => {}
+											}
+										}
+										let (Some(interface), Some(endpoint)) =
+											(interface, endpoint)
+										else {
+											warn!(
+												"slot {}: hub has no status change endpoint",
+												slot
+											);
+											continue;
+										};
+										let id = hub::Hub::get_descriptor(&mut ctrl, slot);
+										transfers.insert(
+											id,
+											Transfer::GetHubDescriptor(
+												HubSetup { interface, endpoint, config }.into(),
+											),
+										);
+										continue;
+									}
+
 									let mut n = usize::from(config.num_interfaces);
 									let mut driver = None;
 									let mut endpoints = Vec::new();
@@ -293,19 +399,85 @@ fn main() -> ! {
 											config: &c.config,
 											interface: &c.interface,
 											endpoints: &c.endpoints,
+											hub: None,
 										},
 									);
 									wait_finish_config.insert(
 										id,
-										EvaluateContext {
+										WaitConfig::Driver(EvaluateContext {
 											driver: c.driver,
 											endpoints: c.endpoints,
 											interface: c.interface,
 											device: c.device,
 											config: c.config,
-										}
-										.into(),
+										}),
+									);
+								}
+								Transfer::GetHubDescriptor(h) => {
+									trace!("GetHubDescriptor");
+									let buffer = buffer.unwrap();
+									let Some(d) =
+										hub::Descriptor::decode(unsafe { buffer.as_ref() })
+									else {
+										warn!("slot {}: invalid hub descriptor", slot);
+										continue;
+									};
+									let id = ctrl
+										.send_request(
+											slot,
+											usb_request::Request::SetConfiguration {
+												value: h.config.index_configuration,
+											},
+											Dma::new_slice(0).unwrap(),
+										)
+										.unwrap_or_else(|_| todo!());
+									transfers.insert(id, Transfer::SetHubConfiguration(h, d));
+								}
+								Transfer::SetHubConfiguration(h, d) => {
+									trace!("SetHubConfiguration");
+									let id = ctrl.configure_device(
+										slot,
+										xhci::DeviceConfig {
+											config: &h.config,
+											interface: &h.interface,
+											endpoints: core::slice::from_ref(&h.endpoint),
+											hub: Some(d.config()),
+										},
 									);
+									wait_finish_config.insert(id, WaitConfig::Hub(h, d));
+								}
+								Transfer::Hub(t) => {
+									let Some(hub) = hubs.get_mut(&slot) else {
+										trace!("transfer for detached hub {}", slot);
+										continue;
+									};
+									let track = &mut |id, t| {
+										transfers.insert(id, Transfer::Hub(t));
+									};
+									match t {
+										hub::Transfer::Out => {}
+										hub::Transfer::PowerOn { port } => {
+											hub.powered_on(&mut ctrl, port, track)
+										}
+										hub::Transfer::StatusChange => {
+											let buffer = buffer.unwrap();
+											hub.status_changed(
+												&mut ctrl,
+												unsafe { buffer.as_ref() },
+												track,
+											)
+										}
+										hub::Transfer::PortStatus { port } => {
+											let buffer = buffer.unwrap();
+											hub.port_status(
+												&mut ctrl,
+												port,
+												unsafe { buffer.as_ref() },
+												track,
+											)
+										}
+										hub::Transfer::PortDone => hub.next_port(&mut ctrl, track),
+									}
 								}
 							}
 						} else {
@@ -326,7 +498,19 @@ fn main() -> ! {
 					Event::DeviceConfigured { slot, id, code } => {
 						assert_eq!(code, Ok(::xhci::ring::trb::event::CompletionCode::Success));
 						trace!("configured device slot {}, {:?}", slot, code);
-						let c: EvaluateContext = wait_finish_config.remove(&id).unwrap();
+						let c = match wait_finish_config.remove(&id).unwrap() {
+							WaitConfig::Driver(c) => c,
+							WaitConfig::Hub(h, d) => {
+								info!("slot {}: hub with {} ports", slot, d.ports());
+								let ep = h.endpoint.address.number() << 1 | 1;
+								let hub = hub::Hub::new(slot, ep, &d);
+								hub.power_on(&mut ctrl, &mut |id, t| {
+									transfers.insert(id, Transfer::Hub(t));
+								});
+								hubs.insert(slot, hub);
+								continue;
+							}
+						};
 						let base = (c.device.class, c.device.subclass, c.device.protocol);
 						let intf = (
 							c.interface.class,
@@ -334,10 +518,22 @@ fn main() -> ! {
 							c.interface.protocol,
 						);
 						drivers
-							.load_driver(slot, c.driver, base, intf, &c.endpoints)
+							.load_driver(
+								slot,
+								c.driver,
+								base,
+								intf,
+								c.interface.number,
+								&c.endpoints,
+							)
 							.unwrap();
 						code.unwrap();
 					}
+					Event::DeviceDetached { slot } => {
+						trace!("detached device, slot {}", slot);
+						hubs.remove(&slot);
+						descriptors.remove(&slot);
+					}
 				}
 			}
 		}
@@ -350,6 +546,11 @@ fn main() -> ! {
 					let ep = endpoint << 1 | 1;
 					assert!(ep < 32);
 					let mut buf = Dma::new_slice(size.try_into().unwrap()).unwrap();
+					// The controller schedules periodic endpoints on its own, so a transfer
+					// only completes once the next service interval has passed.
+					if let Some(interval) = ctrl.interval(slot, ep) {
+						trace!("periodic IN, slot {} ep {} every {:?}", slot, ep, interval);
+					}
 					ctrl.transfer(slot, ep.try_into().unwrap(), buf, true)
 				}
 				Event::DataOut { endpoint, data } => {
@@ -373,6 +574,35 @@ fn main() -> ! {
 					};
 					ctrl.send_request(slot, req, buf).map_err(|_| todo!())
 				}
+				Event::SetReport { interface, ty, id, data } => {
+					use {
+						request::{DIR_OUT, TYPE_CLASS},
+						usb_request::RawRequest as R,
+					};
+					let req = R {
+						request_type: DIR_OUT | TYPE_CLASS | R::RECIPIENT_INTERFACE,
+						request: HID_SET_REPORT,
+						value: (ty as u16) << 8 | u16::from(id),
+						index: interface.into(),
+					};
+					ctrl.send_request(slot, req, data)
+						.map(|id| {
+							transfers.insert(id, Transfer::DriverOut);
+							id
+						})
+						.map_err(|_| todo!())
+				}
+				Event::GetReport { interface, ty, id, len } => {
+					use {request::TYPE_CLASS, usb_request::RawRequest as R};
+					let buf = Dma::new_slice(len.into()).unwrap();
+					let req = R {
+						request_type: R::DIR_IN | TYPE_CLASS | R::RECIPIENT_INTERFACE,
+						request: HID_GET_REPORT,
+						value: (ty as u16) << 8 | u16::from(id),
+						index: interface.into(),
+					};
+					ctrl.send_request(slot, req, buf).map_err(|_| todo!())
+				}
 			};
 			match res {
 				Ok(_id) => {}
@@ -402,9 +632,10 @@ fn main() -> ! {
 			Root { i: u8 },
 			ListDevices { slot: u8 },
 			ListHandlers { index: usize },
+			ListDescriptors { slot: NonZeroU8, offset: usize },
 		}
 
-		if Pin::new(&mut poll_tbl).poll(&mut cx).is_ready() {
+		if reactor.poll(&mut poll_tbl).is_ready() {
 			poll_tbl = queue.submit_read(tbl.notifier().as_raw(), ()).unwrap();
 			'req: while let Some((handle, job_id, req)) = tbl.dequeue() {
 				let mut buf = [0; 64];
@@ -419,6 +650,20 @@ fn main() -> ! {
 						(Handle::MAX, b"handlers") | (Handle::MAX, b"handlers/") => {
 							Response::Handle(objects.insert(Object::ListHandlers { index: 0 }))
 						}
+						(Handle::MAX, p) if p.starts_with(b"devices/") => {
+							let p = &p["devices/".len()..];
+							let slot = p
+								.strip_suffix(b"/descriptors")
+								.and_then(|s| str::from_utf8(s).ok())
+								.and_then(|s| s.parse::<NonZeroU8>().ok())
+								.filter(|s| descriptors.contains_key(s));
+							match slot {
+								Some(slot) => Response::Handle(
+									objects.insert(Object::ListDescriptors { slot, offset: 0 }),
+								),
+								None => Response::Error(Error::DoesNotExist),
+							}
+						}
 						(Handle::MAX, p) if p.starts_with(b"handlers/") => {
 							let p = &p["handlers/".len()..];
 							if let Ok(Some(h)) = str::from_utf8(p).map(|p| drivers.handler(p)) {
@@ -466,6 +711,20 @@ fn main() -> ! {
 								Response::Data(tbl.alloc(0).unwrap())
 							}
 						}
+						Object::ListDescriptors { slot, offset } => {
+							// The device may have been detached and its slot reused.
+							let raw = descriptors.get(&*slot).map_or(&[][..], |d| &d[..]);
+							let raw = raw.get(*offset..).unwrap_or(&[]);
+							let len = descriptor_len(raw);
+							*offset += len.max(1);
+							let s = usb_request::descriptor::decode(&raw[..len])
+								.next()
+								.map(|d| d.map_or_else(|_| "invalid".into(), format_descriptor))
+								.unwrap_or_default();
+							let b = tbl.alloc(s.len().min(amount as _)).expect("out of buffers");
+							b.copy_from(0, &s.as_bytes()[..b.len()]);
+							Response::Data(b)
+						}
 					},
 					Request::Close => {
 						objects.remove(handle);
@@ -478,12 +737,14 @@ fn main() -> ! {
 			}
 		}
 
-		queue.poll();
-		queue.wait(Duration::MAX);
-		queue.process();
+		reactor.wait(Duration::MAX);
 	}
 }
 
+// HID class-specific requests (see HID 1.11, section 7.2)
+const HID_GET_REPORT: u8 = 0x01;
+const HID_SET_REPORT: u8 = 0x09;
+
 struct Job {
 	state: JobState,
 	job_id: JobId,
@@ -491,9 +752,13 @@ struct Job {
 
 enum JobState {
 	WaitDeviceInfo,
+	WaitLanguages { index: u8 },
 	WaitDeviceName,
 }
 
+/// Language used if a device doesn't report any supported languages (English - United States).
+const DEFAULT_LANGID: u16 = 0x0409;
+
 impl Job {
 	fn get_info(ctrl: &mut xhci::Xhci, slot: NonZeroU8, job_id: JobId) -> (u64, Self) {
 		let buffer = Dma::new_slice(64).unwrap();
@@ -509,6 +774,19 @@ impl Job {
 		(id, Self { state: JobState::WaitDeviceInfo, job_id })
 	}
 
+	/// Request a string descriptor in the given language.
+	fn get_string(ctrl: &mut xhci::Xhci, slot: NonZeroU8, index: u8, langid: u16) -> u64 {
+		use usb_request::RawRequest as R;
+		let req = R {
+			request_type: R::DIR_IN | R::TYPE_STANDARD | R::RECIPIENT_DEVICE,
+			request: R::GET_DESCRIPTOR,
+			value: u16::from(STRING_DESCRIPTOR) << 8 | u16::from(index),
+			index: langid,
+		};
+		ctrl.send_request(slot, req, Dma::new_slice(255).unwrap())
+			.unwrap_or_else(|_| todo!())
+	}
+
 	fn progress<'a>(
 		mut self,
 		ctrl: &mut xhci::Xhci,
@@ -516,26 +794,17 @@ impl Job {
 		buf: Dma<[u8]>,
 		tbl: &'a StreamTable,
 	) -> JobResult<'a> {
-		let res = usb_request::descriptor::decode(unsafe { buf.as_ref() })
-			.next()
-			.unwrap()
-			.unwrap();
 		match &self.state {
 			JobState::WaitDeviceInfo => {
+				let res = usb_request::descriptor::decode(unsafe { buf.as_ref() })
+					.next()
+					.unwrap()
+					.unwrap();
 				let info = res.into_device().unwrap();
 				if info.index_product != 0 {
-					let id = ctrl
-						.send_request(
-							slot,
-							usb_request::Request::GetDescriptor {
-								ty: usb_request::descriptor::GetDescriptor::String {
-									index: info.index_product,
-								},
-							},
-							Dma::new_slice(0).unwrap(),
-						)
-						.unwrap_or_else(|_| todo!());
-					self.state = JobState::WaitDeviceName;
+					// String descriptor 0 lists the supported languages.
+					let id = Self::get_string(ctrl, slot, 0, 0);
+					self.state = JobState::WaitLanguages { index: info.index_product };
 					JobResult::Next { id, job: self }
 				} else {
 					let name = tbl.alloc(3).expect("out of buffers");
@@ -543,14 +812,25 @@ impl Job {
 					JobResult::Done { job_id: self.job_id, response: Response::Data(name) }
 				}
 			}
+			&JobState::WaitLanguages { index } => {
+				let langid = string_descriptor_units(unsafe { buf.as_ref() })
+					.next()
+					.unwrap_or(DEFAULT_LANGID);
+				let id = Self::get_string(ctrl, slot, index, langid);
+				self.state = JobState::WaitDeviceName;
+				JobResult::Next { id, job: self }
+			}
 			JobState::WaitDeviceName => {
-				let s = res.into_string().unwrap();
-				let name = tbl.alloc(s.len()).expect("out of buffers");
-				for (i, mut c) in s.enumerate() {
-					if c > 127 {
-						c = b'?' as _
-					}
-					name.copy_from(i, &[c as _]);
+				let s = string_descriptor_units(unsafe { buf.as_ref() });
+				let s = char::decode_utf16(s).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER));
+				let len = s.clone().map(char::len_utf8).sum();
+				let name = tbl.alloc(len).expect("out of buffers");
+				let mut i = 0;
+				for c in s {
+					let mut b = [0; 4];
+					let b = c.encode_utf8(&mut b);
+					name.copy_from(i, b.as_bytes());
+					i += b.len();
 				}
 				JobResult::Done { job_id: self.job_id, response: Response::Data(name) }
 			}
@@ -558,6 +838,61 @@ impl Job {
 	}
 }
 
+const STRING_DESCRIPTOR: u8 = 3;
+
+/// Iterate over the UTF-16LE code units of a string descriptor.
+///
+/// Any data beyond the length indicated by the descriptor is ignored.
+fn string_descriptor_units(buf: &[u8]) -> impl Iterator<Item = u16> + Clone + '_ {
+	let data = match buf {
+		[len, STRING_DESCRIPTOR, ..] => &buf[2..usize::from(*len).clamp(2, buf.len())],
+		_ => &[][..],
+	};
+	data.array_chunks::<2>().map(|&c| u16::from_le_bytes(c))
+}
+
+/// The length of the descriptor at the start of the buffer, clamped to the size of the buffer.
+fn descriptor_len(buf: &[u8]) -> usize {
+	buf.first().map_or(0, |&l| usize::from(l).min(buf.len()))
+}
+
+/// Format a descriptor as described in the module documentation.
+fn format_descriptor(d: Descriptor) -> String {
+	match d {
+		Descriptor::Device(d) => format!(
+			"device {:02x}/{:02x}/{:02x}",
+			d.class, d.subclass, d.protocol
+		),
+		Descriptor::Configuration(c) => format!(
+			"configuration {} interfaces {}",
+			c.configuration_value, c.num_interfaces
+		),
+		Descriptor::Interface(i) => format!(
+			"interface {}.{} {:02x}/{:02x}/{:02x} endpoints {}",
+			i.number, i.alternate_setting, i.class, i.subclass, i.protocol, i.num_endpoints
+		),
+		Descriptor::Endpoint(e) => format!(
+			"endpoint {} {} {} max-packet-size {} interval {}",
+			e.address.number(),
+			match e.address.direction() {
+				Direction::In => "in",
+				Direction::Out => "out",
+			},
+			match e.attributes.transfer() {
+				EndpointTransfer::Control => "Control",
+				EndpointTransfer::Isoch => "Isochronous",
+				EndpointTransfer::Bulk => "Bulk",
+				EndpointTransfer::Interrupt => "Interrupt",
+			},
+			e.max_packet_size,
+			e.interval
+		),
+		Descriptor::Hid(_) => "hid".into(),
+		Descriptor::String(_) => "string".into(),
+		Descriptor::Unknown { ty, .. } => format!("unknown {}", ty),
+	}
+}
+
 enum JobResult<'a> {
 	Next { id: u64, job: Job },
 	Done { job_id: JobId, response: Response<'a, 'static> },
@@ -0,0 +1,8 @@
+//! Request type bits that are missing from [`usb_request::RawRequest`].
+
+/// The request transfers data from the host to the device.
+pub const DIR_OUT: u8 = 0 << 7;
+/// The request is defined by the class of the device or interface.
+pub const TYPE_CLASS: u8 = 1 << 5;
+/// The request is directed at something other than a device, interface or endpoint, e.g. a port.
+pub const RECIPIENT_OTHER: u8 = 3;
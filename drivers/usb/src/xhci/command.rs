@@ -9,7 +9,7 @@ use {
 };
 
 pub(super) enum Pending {
-	AllocSlot { port: NonZeroU8, port_speed: u8 },
+	AllocSlot { port: NonZeroU8, port_speed: u8, parent: Option<device::Parent> },
 	DeallocSlot { slot: NonZeroU8 },
 	SetAddress(device::SetAddress),
 	ConfigureDev,
@@ -38,11 +38,16 @@ impl Xhci {
 			.remove(&id)
 			.expect("no pending command with id")
 		{
-			Pending::AllocSlot { port, port_speed } => {
+			Pending::AllocSlot { port, port_speed, parent } => {
 				assert_eq!(code, Ok(CompletionCode::Success));
 				let slot = NonZeroU8::new(slot).expect("AllocSlot for slot 0");
-				self.port_slot_map[usize::from(port.get() - 1)] = Some(slot);
-				self.set_address(port, slot, port_speed);
+				match parent {
+					None => self.port_slot_map[usize::from(port.get() - 1)] = Some(slot),
+					Some(p) => {
+						self.hub_slot_map.insert((p.hub, p.port), slot);
+					}
+				}
+				self.set_address(port, slot, port_speed, parent);
 				None
 			}
 			Pending::SetAddress(mut e) => {
@@ -78,7 +83,7 @@ impl Xhci {
 			Pending::DeallocSlot { slot } => {
 				// SAFETY: the controller has just deallocated the slot.
 				unsafe { self.dealloc_slot(slot) }
-				None
+				Some(Event::DeviceDetached { slot })
 			}
 		}
 	}
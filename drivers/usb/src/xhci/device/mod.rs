@@ -4,20 +4,22 @@ use {
 	super::{ring, DeviceConfig, Pending, Xhci},
 	crate::dma::Dma,
 	alloc::{boxed::Box, vec::Vec},
-	core::num::NonZeroU8,
+	core::{num::NonZeroU8, time::Duration},
 	usb_request::{
 		descriptor::{Direction, EndpointTransfer},
 		RawRequest,
 	},
 	xhci::{
-		context::{Device32Byte, EndpointState, EndpointType, Input32Byte, InputHandler},
+		context::{
+			Device32Byte, EndpointState, EndpointType, Input32Byte, InputHandler, SlotHandler,
+		},
 		ring::trb::{command, transfer},
 	},
 };
 
-const FULL_SPEED: u8 = 1;
-const LOW_SPEED: u8 = 2;
-const HIGH_SPEED: u8 = 3;
+pub(super) const FULL_SPEED: u8 = 1;
+pub(super) const LOW_SPEED: u8 = 2;
+pub(super) const HIGH_SPEED: u8 = 3;
 const SUPERSPEED_GEN1_X1: u8 = 4;
 const SUPERSPEED_GEN2_X1: u8 = 5;
 const SUPERSPEED_GEN1_X2: u8 = 6;
@@ -25,11 +27,28 @@ const SUPERSPEED_GEN2_X2: u8 = 7;
 
 pub(super) struct Device {
 	slot: NonZeroU8,
+	/// The root hub port the device is attached to, possibly through other hubs.
 	port: NonZeroU8,
 	port_speed: u8,
-	_output_dev_context: Dma<Device32Byte>,
+	/// Location of the device in the hub topology, see 8.9 of the USB 3.2 specification.
+	route: u32,
+	/// Amount of hubs between the device and the root hub.
+	depth: u8,
+	/// The slot and port of the high-speed hub whose transaction translator is used by a low-
+	/// or full-speed device.
+	tt: Option<(NonZeroU8, u8)>,
+	output_dev_context: Dma<Device32Byte>,
 	transfer_ring: ring::Ring<transfer::Allowed>,
-	endpoints: Box<[Option<ring::Ring<transfer::Normal>>]>,
+	endpoints: Box<[Option<Endpoint>]>,
+}
+
+struct Endpoint {
+	ring: ring::Ring<transfer::Normal>,
+	/// The service interval as programmed in the endpoint context, i.e. the exponent of
+	/// `2^interval * 125us`.
+	///
+	/// This is `None` for non-periodic endpoints.
+	interval: Option<u8>,
 }
 
 impl Device {
@@ -112,10 +131,22 @@ impl Device {
 			.get_mut(usize::from(endpoint).wrapping_sub(2))
 			.and_then(|o| o.as_mut())
 			.ok_or(TransferError::InvalidEndpoint { endpoint })?
+			.ring
 			.enqueue(xfer);
 		Ok(id)
 	}
 
+	/// The period at which the host controller services the given endpoint.
+	///
+	/// Returns `None` if the endpoint doesn't exist or isn't periodic.
+	pub fn interval(&self, endpoint: u8) -> Option<Duration> {
+		self.endpoints
+			.get(usize::from(endpoint).wrapping_sub(2))
+			.and_then(|o| o.as_ref())
+			.and_then(|e| e.interval)
+			.map(|i| Duration::from_micros(125 << i))
+	}
+
 	pub fn configure(&mut self, config: DeviceConfig<'_>) -> (command::Allowed, Dma<Input32Byte>) {
 		trace!("configure device slot {}", self.slot);
 		let mut input_context = Dma::<Input32Byte>::new().unwrap_or_else(|_| todo!());
@@ -146,6 +177,11 @@ impl Device {
 			);
 
 			let ring = ring::Ring::new().unwrap_or_else(|_| todo!());
+			let interval = calc_interval(
+				self.port_speed,
+				ep_descr.attributes.transfer(),
+				ep_descr.interval,
+			);
 
 			// 4.8.2.4
 			let ep = inp.device_mut().endpoint_mut(index);
@@ -158,21 +194,27 @@ impl Device {
 			ep.set_max_burst_size(0);
 			ep.set_tr_dequeue_pointer(ring.as_phys());
 			ep.set_dequeue_cycle_state();
-			ep.set_interval(ep_descr.interval);
+			ep.set_interval(interval.unwrap_or(0));
 			ep.set_max_primary_streams(0);
 			ep.set_mult(0);
 			ep.set_error_count(3);
 
-			endpoints[index - 2] = Some(ring);
+			endpoints[index - 2] = Some(Endpoint { ring, interval });
 
 			inp.control_mut().set_add_context_flag(index);
 			max_dci = max_dci.max(index as _);
 		}
 
 		let sl = inp.device_mut().slot_mut();
-		sl.set_root_hub_port_number(self.port.get());
-		sl.set_speed(self.port_speed);
+		self.init_slot_context(sl);
 		sl.set_context_entries(max_dci);
+		if let Some(hub) = config.hub {
+			sl.set_hub();
+			sl.set_number_of_ports(hub.ports);
+			if self.port_speed == HIGH_SPEED {
+				sl.set_tt_think_time(hub.tt_think_time);
+			}
+		}
 		inp.control_mut().set_add_context_flag(0);
 
 		self.endpoints = endpoints.into();
@@ -185,6 +227,46 @@ impl Device {
 	pub fn slot(&self) -> NonZeroU8 {
 		self.slot
 	}
+
+	pub fn port(&self) -> NonZeroU8 {
+		self.port
+	}
+
+	/// Set the fields of the slot context that describe the location of the device.
+	fn init_slot_context(&self, sl: &mut dyn SlotHandler) {
+		sl.set_route_string(self.route);
+		sl.set_root_hub_port_number(self.port.get());
+		sl.set_speed(self.port_speed);
+		if let Some((hub, port)) = self.tt {
+			sl.set_parent_hub_slot_id(hub.get());
+			sl.set_parent_port_number(port);
+		}
+	}
+}
+
+/// Describes a hub, which must be set in the slot context before any devices behind it can be
+/// addressed.
+#[derive(Clone, Copy)]
+pub struct HubConfig {
+	pub ports: u8,
+	/// The time the transaction translator needs between transactions, as encoded in the hub
+	/// descriptor.
+	pub tt_think_time: u8,
+}
+
+/// The speed of a device attached to a hub, as reported by the hub's port status.
+#[derive(Clone, Copy, Debug)]
+pub enum HubPortSpeed {
+	Low,
+	Full,
+	High,
+}
+
+/// The hub port a device is attached to.
+#[derive(Clone, Copy)]
+pub(super) struct Parent {
+	pub hub: NonZeroU8,
+	pub port: u8,
 }
 
 pub enum TransferError {
@@ -192,7 +274,13 @@ pub enum TransferError {
 }
 
 impl Xhci {
-	pub(super) fn set_address(&mut self, port: NonZeroU8, slot: NonZeroU8, port_speed: u8) {
+	pub(super) fn set_address(
+		&mut self,
+		port: NonZeroU8,
+		slot: NonZeroU8,
+		port_speed: u8,
+		parent: Option<Parent>,
+	) {
 		trace!(
 			"set address port {} slot {} speed {}",
 			port,
@@ -207,15 +295,42 @@ impl Xhci {
 		input.control_mut().set_add_context_flag(0);
 		input.control_mut().set_add_context_flag(1);
 
-		// Initialize the Input Slot Context
-		// FIXME how? what's the topology?
-		let sl = input.device_mut().slot_mut();
-		sl.set_root_hub_port_number(port.get());
-		sl.set_context_entries(1);
-		sl.set_speed(port_speed);
+		// Determine the location in the topology.
+		let (route, depth, tt) = match parent {
+			None => (0, 0, None),
+			Some(Parent { hub, port }) => {
+				let hub_dev = self.devices.get(&hub).expect("no hub at slot");
+				// Each tier is 4 bits wide, port numbers above 15 are clamped.
+				let route = hub_dev.route | u32::from(port.min(15)) << (hub_dev.depth * 4);
+				// Low- and full-speed devices behind a high-speed hub use its translator.
+				let tt = match (hub_dev.port_speed, port_speed) {
+					(HIGH_SPEED, LOW_SPEED | FULL_SPEED) => Some((hub, port)),
+					_ => hub_dev.tt,
+				};
+				(route, hub_dev.depth + 1, tt)
+			}
+		};
 
 		// Allocate and initialize the Transfer Ring for the Default Control Endpoint
 		let transfer_ring = ring::Ring::new().unwrap_or_else(|_| todo!());
+		// Allocate the Output Device Context data structure and set to '0'
+		let output_dev_context = Dma::<Device32Byte>::new().unwrap_or_else(|_| todo!());
+		let dev = Device {
+			slot,
+			port,
+			port_speed,
+			route,
+			depth,
+			tt,
+			output_dev_context,
+			transfer_ring,
+			endpoints: Default::default(),
+		};
+
+		// Initialize the Input Slot Context
+		let sl = input.device_mut().slot_mut();
+		dev.init_slot_context(sl);
+		sl.set_context_entries(1);
 
 		let (pkt_size, adjust_packet_size) = calc_packet_size(port_speed);
 		trace!(
@@ -228,15 +343,12 @@ impl Xhci {
 		let ep = input.device_mut().endpoint_mut(1);
 		ep.set_endpoint_type(EndpointType::Control);
 		ep.set_max_packet_size(pkt_size);
-		ep.set_tr_dequeue_pointer(transfer_ring.as_phys());
+		ep.set_tr_dequeue_pointer(dev.transfer_ring.as_phys());
 		ep.set_dequeue_cycle_state();
 		ep.set_error_count(3);
 
-		// Allocate the Output Device Context data structure and set to '0'
-		let _output_dev_context = Dma::<Device32Byte>::new().unwrap_or_else(|_| todo!());
-
 		// Load the appropriate (Device Slot ID) entry in the Device Context Base Address Array
-		self.dcbaa.set(slot, _output_dev_context.as_phys());
+		self.dcbaa.set(slot, dev.output_dev_context.as_phys());
 
 		// Issue an Address Device Command for the Device Slot
 		self.enqueue_command(
@@ -245,18 +357,7 @@ impl Xhci {
 					.set_slot_id(slot.get())
 					.set_input_context_pointer(input_context.as_phys()),
 			),
-			Pending::SetAddress(SetAddress {
-				dev: Device {
-					slot,
-					port,
-					port_speed,
-					_output_dev_context,
-					transfer_ring,
-					endpoints: Default::default(),
-				},
-				input_context,
-				adjust_packet_size,
-			}),
+			Pending::SetAddress(SetAddress { dev, input_context, adjust_packet_size }),
 		);
 	}
 }
@@ -322,6 +423,32 @@ fn calc_packet_size(speed: u8) -> (u16, bool) {
 	}
 }
 
+/// Convert the `bInterval` field of an endpoint descriptor to the exponent used by the
+/// endpoint context.
+///
+/// Returns `None` for non-periodic endpoints.
+///
+/// # Note
+///
+/// See table 6-12 of the xHCI specification.
+fn calc_interval(speed: u8, transfer: EndpointTransfer, interval: u8) -> Option<u8> {
+	match (transfer, speed) {
+		(EndpointTransfer::Control | EndpointTransfer::Bulk, _) => None,
+		// bInterval is expressed in frames (1ms) and may range from 1 to 255.
+		// Round down to the nearest power of 2 of 125us microframes.
+		(EndpointTransfer::Interrupt, FULL_SPEED | LOW_SPEED) => {
+			let frames = u16::from(interval.max(1)) * 8;
+			Some((15 - frames.leading_zeros() as u8).clamp(3, 10))
+		}
+		// bInterval is an exponent of 2^(bInterval - 1) frames.
+		(EndpointTransfer::Isoch, FULL_SPEED) => Some(interval.clamp(1, 16) - 1 + 3),
+		// bInterval is an exponent of 2^(bInterval - 1) microframes.
+		(EndpointTransfer::Interrupt | EndpointTransfer::Isoch, _) => {
+			Some(interval.clamp(1, 16) - 1)
+		}
+	}
+}
+
 fn map_endpoint_type(transfer: EndpointTransfer, dir: Direction) -> EndpointType {
 	match (transfer, dir) {
 		(EndpointTransfer::Interrupt, Direction::In) => EndpointType::InterruptIn,
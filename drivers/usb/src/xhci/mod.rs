@@ -27,7 +27,7 @@ use {
 	},
 };
 
-pub use device::TransferError;
+pub use device::{HubConfig, HubPortSpeed, TransferError};
 
 type Registers = xhci::Registers<driver_utils::accessor::Identity>;
 
@@ -42,6 +42,8 @@ pub struct Xhci {
 	poll: rt::Object,
 	transfers_config_packet_size: BTreeMap<ring::EntryId, (device::SetAddress, Dma<[u8]>)>,
 	port_slot_map: [Option<NonZeroU8>; 255],
+	/// Slots of devices attached to hubs, keyed by the slot and port of the hub.
+	hub_slot_map: BTreeMap<(NonZeroU8, u8), NonZeroU8>,
 }
 
 impl Xhci {
@@ -232,6 +234,7 @@ impl Xhci {
 			poll,
 			transfers_config_packet_size: Default::default(),
 			port_slot_map: [None; 255],
+			hub_slot_map: Default::default(),
 		})
 	}
 
@@ -286,6 +289,13 @@ impl Xhci {
 		Ok(id)
 	}
 
+	/// The period at which an endpoint of a device is serviced.
+	///
+	/// Returns `None` if the endpoint doesn't exist or isn't periodic.
+	pub fn interval(&self, slot: NonZeroU8, endpoint: u8) -> Option<Duration> {
+		self.devices.get(&slot)?.interval(endpoint)
+	}
+
 	pub fn configure_device(&mut self, slot: NonZeroU8, config: DeviceConfig<'_>) -> ring::EntryId {
 		trace!("configure device, slot {}", slot);
 		let (cmd, buf) = self
@@ -439,10 +449,16 @@ pub enum Event {
 		id: ring::EntryId,
 		code: Result<xhci::ring::trb::event::CompletionCode, u8>,
 	},
+	/// The device was detached and its slot has been freed.
+	DeviceDetached {
+		slot: NonZeroU8,
+	},
 }
 
 pub struct DeviceConfig<'a> {
 	pub config: &'a Configuration,
 	pub interface: &'a Interface,
 	pub endpoints: &'a [Endpoint],
+	/// Set if the device is a hub.
+	pub hub: Option<HubConfig>,
 }
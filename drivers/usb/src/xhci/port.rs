@@ -1,5 +1,9 @@
 use {
-	super::{Pending, Xhci},
+	super::{
+		device::{self, HubPortSpeed, Parent},
+		Pending, Xhci,
+	},
+	alloc::vec::Vec,
 	core::num::NonZeroU8,
 	xhci::ring::trb::{
 		command::{Allowed, DisableSlot, EnableSlot},
@@ -47,11 +51,45 @@ impl Xhci {
 		info!("enable slot for port {}", port);
 		self.enqueue_command(
 			Allowed::EnableSlot(*EnableSlot::new().set_slot_type(0)),
-			Pending::AllocSlot { port, port_speed },
+			Pending::AllocSlot { port, port_speed, parent: None },
 		);
 	}
 
+	/// Allocate a slot for a device attached to a port of a hub.
+	///
+	/// The port must have been reset and enabled. A [`super::Event::NewDevice`] is returned
+	/// when the device is ready.
+	pub fn attach_device(&mut self, hub: NonZeroU8, port: u8, speed: HubPortSpeed) {
+		let root_port = self.devices.get(&hub).expect("no hub at slot").port();
+		let port_speed = match speed {
+			HubPortSpeed::Low => device::LOW_SPEED,
+			HubPortSpeed::Full => device::FULL_SPEED,
+			HubPortSpeed::High => device::HIGH_SPEED,
+		};
+		info!("enable slot for hub {} port {}", hub, port);
+		self.enqueue_command(
+			Allowed::EnableSlot(*EnableSlot::new().set_slot_type(0)),
+			Pending::AllocSlot { port: root_port, port_speed, parent: Some(Parent { hub, port }) },
+		);
+	}
+
+	/// Disable the slot of a device that was detached from a port of a hub.
+	pub fn detach_device(&mut self, hub: NonZeroU8, port: u8) {
+		if let Some(slot) = self.hub_slot_map.remove(&(hub, port)) {
+			self.disable_slot(slot);
+		}
+	}
+
 	fn disable_slot(&mut self, slot: NonZeroU8) {
+		// Devices behind a hub disappear with it.
+		let children = self
+			.hub_slot_map
+			.range((slot, 0)..=(slot, u8::MAX))
+			.map(|(&k, _)| k)
+			.collect::<Vec<_>>();
+		for (hub, port) in children {
+			self.detach_device(hub, port);
+		}
 		info!("disable slot {}", slot);
 		self.enqueue_command(
 			Allowed::DisableSlot(*DisableSlot::new().set_slot_id(slot.get())),
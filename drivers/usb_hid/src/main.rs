@@ -14,6 +14,7 @@ fn main(_: isize, _: *const *const u8) -> isize {
 	let stdin = rt::io::stdin().unwrap();
 
 	let mut intr_in = None;
+	let mut keyboard = false;
 
 	let mut args = rt::args::args().skip(1);
 	while let Some(a) = args.next() {
@@ -26,8 +27,10 @@ fn main(_: isize, _: *const *const u8) -> isize {
 		};
 		match a {
 			"--class" => {
-				// Just ignore for now.
-				args.next().unwrap();
+				// Format is "bc,bs,bp,ic,is,ip". We're only interested in the interface protocol.
+				let c = args.next().expect("expected class");
+				// Boot interface subclass 1, protocol 1 is a keyboard.
+				keyboard = c.ends_with(b"01,01");
 			}
 			"--intr-in" => decode_ep(&mut intr_in, &mut args),
 			"--bulk-out" | "--bulk-in" | "--intr-out" | "--isoch-out" | "--isoch-in" => {
@@ -68,6 +71,13 @@ fn main(_: isize, _: *const *const u8) -> isize {
 		}
 	};
 
+	// Turn on Num Lock so the numpad behaves as expected.
+	if keyboard {
+		const NUM_LOCK: u8 = 1 << 0;
+		ipc_usb::send_set_report(ipc_usb::ReportType::Output, 0, |d| stdout.write(d)).unwrap();
+		stdout.write(&[NUM_LOCK]).unwrap();
+	}
+
 	let (public_in, public_out) = rt::Object::new(rt::NewObject::MessagePipe).unwrap();
 
 	let intr_in = intr_in.unwrap();
@@ -6,7 +6,7 @@
 extern crate alloc;
 
 use {
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	driver_utils::os::stream_table::{positional, Request, Response, StreamTable},
 	rt_default as _,
 };
 
@@ -67,9 +67,20 @@ fn main() {
 	assert!(attr.block_length.is_power_of_two());
 	let block_length_p2 = attr.block_length.trailing_zeros();
 	let block_length_mask = attr.block_length - 1;
+	// Convert a byte offset to a block address.
+	let to_address = |n: u64| {
+		(n & u64::from(block_length_mask) == 0
+			&& n >> block_length_p2 <= u64::from(attr.returned_logical_block_address))
+		.then(|| (n >> block_length_p2) as u32)
+	};
 
 	let (buf, _) = rt::Object::new(rt::NewObject::SharedMemory { size: 1 << 16 }).unwrap();
-	let tbl = StreamTable::new(&buf, 512.try_into().unwrap(), 512.try_into().unwrap());
+	// Leave room for the offset of WriteAt requests.
+	let tbl = StreamTable::new(
+		&buf,
+		512.try_into().unwrap(),
+		(512 + positional::OFFSET_SIZE).try_into().unwrap(),
+	);
 
 	ipc_usb::send_public_object(|d| stdout.write(d)).unwrap();
 	stdout.share(tbl.public()).unwrap();
@@ -134,6 +145,60 @@ fn main() {
 						Response::Amount(data.len() as _)
 					}
 				}
+				Request::ReadAt { args } if handle != rt::Handle::MAX => {
+					match positional::decode_read_at(
+						args.copy_into(&mut [0; positional::READ_AT_SIZE]).0,
+					) {
+						Ok((offset, amount)) if amount >= attr.block_length => {
+							match to_address(offset) {
+								Some(address) => {
+									let cmd = scsi::Read10 {
+										flags: 0,
+										address,
+										length: 1,
+										group_number: 0,
+										control: 0,
+									};
+									match dev.transfer_in(cmd, attr.block_length) {
+										Ok(data) => {
+											let b = tbl.alloc(data.len()).expect("out of buffers");
+											b.copy_from(0, &data);
+											Response::Data(b)
+										}
+										Err(e) => Response::Error(e),
+									}
+								}
+								None => Response::Error(rt::Error::InvalidData),
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
+				Request::WriteAt { data } if handle != rt::Handle::MAX => {
+					let mut b = alloc::vec![0; data.len()];
+					data.copy_to(0, &mut b);
+					match positional::decode_write_at(&b) {
+						Ok((offset, d)) if d.len() == attr.block_length as usize => {
+							match to_address(offset) {
+								Some(address) => {
+									let cmd = scsi::Write10 {
+										flags: 0,
+										address,
+										length: 1,
+										group_number: 0,
+										control: 0,
+									};
+									match dev.transfer_out(cmd, d) {
+										Ok(_) => Response::Amount(d.len() as _),
+										Err(e) => Response::Error(e),
+									}
+								}
+								None => Response::Error(rt::Error::InvalidData),
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
 				Request::Seek { from } => match from {
 					rt::io::SeekFrom::Start(n)
 						if n & u64::from(block_length_mask) == 0
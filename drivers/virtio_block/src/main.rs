@@ -4,7 +4,7 @@
 extern crate alloc;
 
 use {
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	driver_utils::os::stream_table::{positional, Data, Request, Response, StreamTable},
 	rt::{io::Pow2Size, Handle},
 	rt_default as _,
 	virtio_block::Sector,
@@ -19,15 +19,9 @@ fn start(_: isize, _: *const *const u8) -> isize {
 
 fn main() -> ! {
 	let file_root = rt::io::file_root().expect("no file root");
-	let table_name = rt::args::args()
-		.skip(1)
-		.next()
-		.expect("expected table name");
-
-	let dev = rt::args::handles()
-		.find(|(name, _)| name == b"pci")
-		.expect("no 'pci' object")
-		.1;
+	let table_name = rt::args::arg(1).expect("expected table name");
+
+	let dev = rt::args::handle(b"pci").expect("no 'pci' object");
 	let poll = dev.open(b"poll").unwrap();
 	let pci_config = dev.map_object(None, rt::RWX::R, 0, usize::MAX).unwrap().0;
 
@@ -65,9 +59,11 @@ fn main() -> ! {
 	let (tbl, dma_phys) = {
 		let (dma, dma_phys) =
 			driver_utils::dma::alloc_dma_object((1 << 16).try_into().unwrap()).unwrap();
-		let tbl = StreamTable::new(&dma, Pow2Size(9), (1 << 12) - 1);
+		// Leave room for the offset of WriteAt requests.
+		let max_request_mem = (1 << 12) + positional::OFFSET_SIZE - 1;
+		let tbl = StreamTable::new(&dma, Pow2Size(9), max_request_mem.try_into().unwrap());
 		file_root
-			.create(table_name)
+			.create_with_type(table_name, b"block")
 			.unwrap()
 			.share(tbl.public())
 			.unwrap();
@@ -75,9 +71,13 @@ fn main() -> ! {
 	};
 
 	let mut data_handles = driver_utils::Arena::new();
+	// Each buffer block is a separate region, so don't read more than fits in the queue.
+	let max_read = (1 << 13).min(u32::try_from(dev.max_regions()).unwrap() * SECTOR_SIZE);
 
 	loop {
-		let wait = || poll.read(&mut []).unwrap();
+		let wait = || {
+			poll.read(&mut []).unwrap();
+		};
 
 		let mut flush = false;
 		while let Some((handle, job_id, req)) = tbl.dequeue() {
@@ -103,48 +103,87 @@ fn main() -> ! {
 					} else {
 						// TODO how do we with unaligned reads/writes?
 						assert!(amount % SECTOR_SIZE == 0);
-						let amount = amount.min(1 << 13);
+						let amount = amount.min(max_read);
 						let offset = data_handles[handle];
 
 						let data = tbl
 							.alloc(amount.try_into().unwrap())
 							.expect("out of buffers");
-						let sectors = data.blocks().map(|b| virtio::PhysRegion {
-							base: virtio::PhysAddr::new(dma_phys + u64::from(b.0) * 512),
-							size: 512,
-						});
-
-						let tk = unsafe { dev.read(sectors, offset).unwrap() };
-						// TODO proper async
-						while dev.poll_finished(|t| assert_eq!(t, tk)) != 1 {
-							wait();
+						match transfer(&mut dev, &data, dma_phys, offset, false, wait) {
+							Ok(()) => {
+								data_handles[handle] += u64::from(amount / SECTOR_SIZE);
+								Response::Data(data)
+							}
+							Err(e) => Response::Error(e),
 						}
-
-						data_handles[handle] += u64::from(amount / SECTOR_SIZE);
-
-						Response::Data(data)
 					}
 				}
+				Request::ReadAt { args } => {
+					match positional::decode_read_at(
+						args.copy_into(&mut [0; positional::READ_AT_SIZE]).0,
+					) {
+						_ if handle == Handle::MAX => Response::Error(rt::Error::InvalidOperation),
+						Ok((offset, amount))
+							if offset % u64::from(SECTOR_SIZE) == 0
+								&& amount % SECTOR_SIZE == 0 =>
+						{
+							let amount = amount.min(max_read);
+							let data = tbl
+								.alloc(amount.try_into().unwrap())
+								.expect("out of buffers");
+							let sector = offset / u64::from(SECTOR_SIZE);
+							match transfer(&mut dev, &data, dma_phys, sector, false, wait) {
+								Ok(()) => Response::Data(data),
+								Err(e) => Response::Error(e),
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
+					}
+				}
+				Request::Write { .. } | Request::WriteAt { .. } if dev.is_read_only() => {
+					Response::Error(rt::Error::InvalidOperation)
+				}
 				Request::Write { data } => {
 					// TODO ditto
 					assert!(data.len() % Sector::SIZE == 0);
 					let offset = data_handles[handle];
 
-					let sectors = data.blocks().map(|b| virtio::PhysRegion {
-						base: virtio::PhysAddr::new(dma_phys + u64::from(b.0) * 512),
-						size: 512,
-					});
-
-					let tk = unsafe { dev.write(sectors, offset).unwrap() };
-					// TODO proper async
-					while dev.poll_finished(|t| assert_eq!(t, tk)) != 1 {
-						wait();
+					match transfer(&mut dev, &data, dma_phys, offset, true, wait) {
+						Ok(()) => {
+							let len = data.len();
+							data_handles[handle] += u64::try_from(len / Sector::SIZE).unwrap();
+							Response::Amount(len.try_into().unwrap())
+						}
+						Err(e) => Response::Error(e),
+					}
+				}
+				Request::WriteAt { data } => {
+					let mut head = [0; positional::OFFSET_SIZE];
+					let head = &mut head[..data.len().min(positional::OFFSET_SIZE)];
+					data.copy_to(0, head);
+					let len = data.len().saturating_sub(positional::OFFSET_SIZE);
+					match positional::decode_offset(head) {
+						_ if handle == Handle::MAX => Response::Error(rt::Error::InvalidOperation),
+						Ok(offset)
+							if offset % u64::from(SECTOR_SIZE) == 0 && len % Sector::SIZE == 0 =>
+						{
+							// The offset precedes the data, so the sectors aren't aligned to
+							// the buffer blocks. Copy them to a separate buffer for DMA.
+							let buf = tbl.alloc(len).expect("out of buffers");
+							let mut s = [0; Sector::SIZE];
+							for i in (0..len).step_by(Sector::SIZE) {
+								data.copy_to(positional::OFFSET_SIZE + i, &mut s);
+								buf.copy_from(i, &s);
+							}
+							drop(data);
+							let sector = offset / u64::from(SECTOR_SIZE);
+							match transfer(&mut dev, &buf, dma_phys, sector, true, wait) {
+								Ok(()) => Response::Amount(len.try_into().unwrap()),
+								Err(e) => Response::Error(e),
+							}
+						}
+						_ => Response::Error(rt::Error::InvalidData),
 					}
-					let len = data.len();
-
-					data_handles[handle] += u64::try_from(len / Sector::SIZE).unwrap();
-
-					Response::Amount(len.try_into().unwrap())
 				}
 				Request::Seek { from } => {
 					let offset = match from {
@@ -170,3 +209,66 @@ fn main() -> ! {
 		tbl.wait();
 	}
 }
+
+/// Read or write sectors from or to the blocks of a buffer.
+fn transfer(
+	dev: &mut virtio_block::BlockDevice<'_>,
+	data: &Data<'_>,
+	dma_phys: u64,
+	sector: u64,
+	write: bool,
+	wait: impl Fn(),
+) -> Result<(), rt::Error> {
+	let sectors = || {
+		data.blocks().map(|b| virtio::PhysRegion {
+			base: virtio::PhysAddr::new(dma_phys + u64::from(b.0) * 512),
+			size: 512,
+		})
+	};
+
+	if sectors().len() > dev.max_regions() {
+		// The request can never fit in the queue.
+		return Err(rt::Error::InvalidData);
+	}
+
+	let tk = loop {
+		// SAFETY: the buffer remains valid until the operation has finished.
+		let r = unsafe {
+			if write {
+				dev.write(sectors(), sector).map_err(|e| match e {
+					virtio_block::WriteError::QueueFull => None,
+					virtio_block::WriteError::ReadOnly => Some(rt::Error::InvalidOperation),
+				})
+			} else {
+				dev.read(sectors(), sector).map_err(|e| match e {
+					virtio_block::ReadError::QueueFull => None,
+				})
+			}
+		};
+		match r {
+			Ok(tk) => break tk,
+			// Wait for other operations to finish and free up descriptors. Each transfer waits
+			// for its own operation to finish, so nobody is interested in their status.
+			Err(None) => {
+				wait();
+				dev.poll_finished(|_, _| {});
+			}
+			Err(Some(e)) => return Err(e),
+		}
+	};
+
+	// TODO proper async
+	let mut status = Ok(());
+	while dev.poll_finished(|t, s| {
+		assert_eq!(t, tk);
+		status = s;
+	}) != 1
+	{
+		wait();
+	}
+	status.map_err(|e| {
+		let op = if write { "write" } else { "read" };
+		rt::eprintln!("virtio_block: {} failed: {:?}", op, e);
+		rt::Error::Unknown
+	})
+}
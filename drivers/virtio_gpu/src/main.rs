@@ -5,9 +5,15 @@
 extern crate alloc;
 
 use {
-	alloc::string::ToString,
-	core::ptr::NonNull,
-	driver_utils::os::stream_table::{Request, Response, StreamTable},
+	alloc::vec::Vec,
+	core::num::NonZeroU32,
+	driver_utils::{
+		dma::{DmaBuf, DmaPool},
+		os::{
+			display::{Buffer, Display, DisplayDevice},
+			stream_table::{Request, Response, StreamTable},
+		},
+	},
 	rt::io::{Error, Handle},
 	virtio_gpu::Rect,
 };
@@ -25,16 +31,13 @@ fn alloc_error(_: core::alloc::Layout) -> ! {
 
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-	let _ = rt::io::stderr().map(|o| writeln!(o, "{}", info));
+	let _ = rt::io::Stderr::lock_or_bypass().map(|o| writeln!(o, "{}", info));
 	rt::exit(128)
 }
 
 #[start]
 fn main(_: isize, _: *const *const u8) -> isize {
-	let table_name = rt::args::args()
-		.skip(1)
-		.next()
-		.expect("expected table path");
+	let table_name = rt::args::arg(1).expect("expected table path");
 
 	let dev = rt::args::handle(b"pci").expect("pci undefined");
 	let poll = dev.open(b"poll").unwrap();
@@ -70,93 +73,58 @@ fn main(_: isize, _: *const *const u8) -> isize {
 			_ => unreachable!(),
 		}
 	};
-	let wait = || poll.read(&mut []).unwrap();
-	let wait_tk = |dev: &mut virtio_gpu::Device, tk| {
-		while dev.poll_control_queue(|t| assert_eq!(tk, t)) == 0 {
-			wait();
-		}
-	};
-	let wait_tk2 = |dev: &mut virtio_gpu::Device, tk| {
-		while dev.poll_cursor_queue(|t| assert_eq!(tk, t)) == 0 {
-			wait();
-		}
-	};
+	let wait_tk = |dev: &mut virtio_gpu::Device, tk| wait_control(&poll, dev, tk);
 
-	// Allocate buffers for virtio queue requests
-	let (buf, buf_phys, buf_size) = driver_utils::dma::alloc_dma(256.try_into().unwrap()).unwrap();
-	let mut buf = unsafe {
-		virtio::PhysMap::new(buf.cast(), virtio::PhysAddr::new(buf_phys), buf_size.get())
-	};
-	let (buf2, buf2_phys, buf2_size) =
-		driver_utils::dma::alloc_dma(256.try_into().unwrap()).unwrap();
-	let buf2 = unsafe {
-		virtio::PhysMap::new(
-			buf2.cast(),
-			virtio::PhysAddr::new(buf2_phys),
-			buf2_size.get(),
-		)
-	};
-	let (buf3, buf3_phys, buf3_size) =
-		driver_utils::dma::alloc_dma(256.try_into().unwrap()).unwrap();
-	let buf3 = unsafe {
-		virtio::PhysMap::new(
-			buf3.cast(),
-			virtio::PhysAddr::new(buf3_phys),
-			buf3_size.get(),
-		)
+	// Small buffers for virtio queue requests and backing storages are taken from a pool,
+	// which avoids using up a whole page for each of them.
+	let pool = DmaPool::new(
+		256.try_into().unwrap(),
+		(2 + dev.num_scanouts() as usize).try_into().unwrap(),
+	)
+	.unwrap();
+	let alloc_phys = |size: usize| {
+		let (virt, phys, size) = driver_utils::dma::alloc_dma(size.try_into().unwrap()).unwrap();
+		unsafe {
+			virtio::PhysMap::new(
+				virt.cast(),
+				virtio::PhysAddr::new(phys.try_into().unwrap()),
+				size.get(),
+			)
+		}
 	};
+	let cmd_buf = pool.alloc().unwrap();
+	let mut buf = phys_map(&cmd_buf);
 
-	// Allocate draw buffer
 	let (width, height) = (1920, 1080);
-	let (fb, fb_phys, fb_size) =
-		driver_utils::dma::alloc_dma((width * height * 4).try_into().unwrap()).unwrap();
-	let fb = unsafe {
-		virtio::PhysMap::new(
-			fb.cast(),
-			virtio::PhysAddr::new(fb_phys.try_into().unwrap()),
-			fb_size.get(),
-		)
-	};
-
-	let (cursor, cursor_phys, cursor_size) =
-		driver_utils::dma::alloc_dma((64 * 64 * 4).try_into().unwrap()).unwrap();
-	let cursor = unsafe {
-		virtio::PhysMap::new(
-			cursor.cast(),
-			virtio::PhysAddr::new(cursor_phys.try_into().unwrap()),
-			cursor_size.get(),
-		)
-	};
-
-	// Set up scanout
-	let mut backing = virtio_gpu::BackingStorage::new(buf2);
-	let mut cursor_backing = virtio_gpu::BackingStorage::new(buf3);
-	backing.push(&fb);
-	cursor_backing.push(&cursor);
-	let scanout_id = 0;
-	let scanout_resource_id = 1.try_into().unwrap();
-	let cursor_resource_id = 2.try_into().unwrap();
+	let cursor = alloc_phys(64 * 64 * 4);
+	let cursor_backing = backing_for(&pool, &cursor, alloc_phys);
+	let cursor_resource_id = 1.try_into().unwrap();
 
+	// Set up a framebuffer for each scanout
+	//
+	// The backing storage only holds a strip of rows, which are transferred one strip at a time.
 	let rect = Rect::new(0, 0, width.try_into().unwrap(), height.try_into().unwrap());
-	unsafe {
-		let tk = dev
-			.create_resource_2d(
-				scanout_resource_id,
-				rect,
-				virtio_gpu::Format::Rgbx8Unorm,
-				&mut buf,
-			)
-			.unwrap();
-		wait_tk(&mut dev, tk);
-		let tk = dev
-			.attach_resource_2d(scanout_resource_id, backing, &mut buf)
-			.unwrap();
-		wait_tk(&mut dev, tk);
-		let tk = dev
-			.init_scanout(scanout_id, scanout_resource_id, rect, &mut buf)
-			.unwrap();
-		wait_tk(&mut dev, tk);
-	}
+	let scanouts = (0..dev.num_scanouts())
+		.map(|id| {
+			let fb = alloc_phys(STRIP_SIZE);
+			let backing = backing_for(&pool, &fb, alloc_phys);
+			let resource_id = NonZeroU32::new(id + 2).unwrap();
+			unsafe {
+				let tk = dev
+					.create_resource_2d(resource_id, rect, virtio_gpu::Format::Rgbx8Unorm, &mut buf)
+					.unwrap();
+				wait_tk(&mut dev, tk);
+				let tk = dev
+					.attach_resource_2d(resource_id, backing, &mut buf)
+					.unwrap();
+				wait_tk(&mut dev, tk);
+				let tk = dev.init_scanout(id, resource_id, rect, &mut buf).unwrap();
+				wait_tk(&mut dev, tk);
+			}
+			Scanout { resource_id, fb }
+		})
+		.collect::<Vec<_>>();
+	assert!(!scanouts.is_empty(), "no scanouts");
 
 	unsafe {
 		let rect = Rect::new(0, 0, 64, 64);
@@ -176,30 +144,14 @@ fn main(_: isize, _: *const *const u8) -> isize {
 	}
 
 	// Draw colors
-	for y in 0..height {
-		for x in 0..width {
+	for s in scanouts.iter() {
+		blit(&mut dev, s, width, rect, &mut buf, wait_tk, |x, y| {
+			let (x, y) = (x as usize, y as usize);
 			let r = x * 255 / width;
 			let g = y * 255 / height;
 			let b = 255 - (r + g) / 2;
-			unsafe {
-				fb.virt()
-					.cast::<[u8; 4]>()
-					.as_ptr()
-					.add(y * width + x)
-					.write([r as u8, g as u8, b as u8, 255]);
-			}
-		}
-	}
-
-	unsafe {
-		let tk = dev
-			.transfer(scanout_resource_id, rect, &mut buf)
-			.expect("failed to draw");
-		wait_tk(&mut dev, tk);
-		let tk = dev
-			.flush(scanout_resource_id, rect, &mut buf)
-			.expect("failed to draw");
-		wait_tk(&mut dev, tk);
+			[r as u8, g as u8, b as u8, 255]
+		});
 	}
 
 	// Create table
@@ -208,12 +160,23 @@ fn main(_: isize, _: *const *const u8) -> isize {
 
 	rt::io::file_root()
 		.unwrap()
-		.create(table_name)
+		.create_with_type(table_name, b"gpu")
 		.unwrap()
 		.share(tbl.public())
 		.unwrap();
 
-	let mut command_buf = (NonNull::new(kernel::Page::SIZE as *mut u8).unwrap(), 0);
+	let mut gpu = Gpu {
+		dev,
+		buf,
+		poll: &poll,
+		scanouts,
+		cursor,
+		cursor_resource_id,
+		cursor_pos: (0, 0),
+		width,
+		height,
+	};
+	let mut display = Display::new();
 
 	// Begin event loop
 	let mut tiny_buf = [0; 32];
@@ -221,148 +184,19 @@ fn main(_: isize, _: *const *const u8) -> isize {
 		let mut send_notif = false;
 		while let Some((handle, job_id, req)) = tbl.dequeue() {
 			let response = match req {
-				Request::GetMeta { property } => {
-					let prop = property.get(&mut tiny_buf);
-					match &*prop {
-						b"resolution" => {
-							let (w, h) = (width.to_string(), height.to_string());
-							let data = tbl.alloc(w.len() + 1 + h.len()).expect("out of buffers");
-							data.copy_from(0, w.as_bytes());
-							data.copy_from(w.len(), &[b'x']);
-							data.copy_from(w.len() + 1, h.as_bytes());
-							Response::Data(data)
-						}
-						b"bin/resolution" => {
-							let r = ipc_gpu::Resolution { x: width as _, y: height as _ }.encode();
-							let data = tbl.alloc(r.len()).unwrap();
-							data.copy_from(0, &r);
-							Response::Data(data)
-						}
+				// Each scanout beyond the first has its own handle.
+				Request::Open { path } if handle == Handle::MAX => {
+					let path = path.copy_into(&mut tiny_buf).0;
+					match path.strip_prefix(b"scanout/").and_then(parse_index) {
+						Some(0) => Response::Handle(Handle::MAX),
+						Some(i) if i < gpu.scanouts.len() => Response::Handle(i as _),
 						_ => Response::Error(Error::DoesNotExist),
 					}
 				}
-				Request::SetMeta { property_value } => match property_value.try_get(&mut [0; 32]) {
-					Ok((b"bin/cursor/pos", &mut [a, b, c, d])) => {
-						let x = u16::from_le_bytes([a, b]);
-						let y = u16::from_le_bytes([c, d]);
-						unsafe {
-							// In QEMU 7.0, if a Data IN is sent to the USB tablet *after*
-							// the cursor texture is set it will disappear.
-							// The easiest workaround is to always use update_cursor.
-							// If this proves to be too inefficient we can refresh it, say, every second.
-							let tk = dev
-								.update_cursor(
-									0,
-									cursor_resource_id,
-									x.into(),
-									y.into(),
-									0,
-									0,
-									&mut buf,
-								)
-								//.move_cursor(0, cursor_resource_id, x.into(), y.into(), &mut buf)
-								.unwrap();
-							wait_tk2(&mut dev, tk);
-						}
-						Response::Amount(0)
-					}
-					Ok((b"bin/cursor/pos", _)) => Response::Error(Error::InvalidData),
-					Ok(_) => Response::Error(Error::DoesNotExist),
-					Err(_) => Response::Error(Error::InvalidData),
+				req => match display.handle(&mut gpu, &tbl, handle, req) {
+					Some(r) => r,
+					None => continue,
 				},
-				Request::Write { data } => {
-					let mut d = [0; 64];
-					let d = &mut d[..data.len()];
-					data.copy_to(0, d);
-					// Blit a specific area
-					if let Ok(d) = d.try_into() {
-						let cmd = ipc_gpu::Flush::decode(d);
-						assert_eq!(cmd.offset, 0, "todo: offset");
-						assert_eq!(cmd.stride, u32::from(cmd.size.x), "todo: stride");
-						let r = Rect::new(
-							cmd.origin.x,
-							cmd.origin.y,
-							cmd.size.x.into(),
-							cmd.size.y.into(),
-						);
-						let area = r.height() as usize * r.width() as usize;
-						assert!(area * 4 <= fb.size());
-						assert!(area * 3 <= command_buf.1);
-						unsafe {
-							fb.virt().as_ptr().write_bytes(200, fb.size());
-							for (fy, ty) in (0..r.height()).map(|h| (h, h)) {
-								for (fx, tx) in (0..r.width()).map(|w| (w, w)) {
-									let fi = fy as usize * r.width() as usize + fx as usize;
-									// QEMU uses the stride of the *host* for the *guest*
-									// memory too. Don't ask me why, this is documented literally
-									// nowhere.
-									// This, by the way, is the *only* reason we're forced to
-									// allocate a framebuffer matching the host size.
-									let ti = ty as usize * width as usize + tx as usize;
-									let [r, g, b] =
-										*command_buf.0.as_ptr().cast::<[u8; 3]>().add(fi);
-									fb.virt()
-										.as_ptr()
-										.cast::<[u8; 4]>()
-										.add(ti)
-										.write([r, g, b, 0]);
-								}
-							}
-						}
-						unsafe {
-							let tk = dev
-								.transfer(scanout_resource_id, r, &mut buf)
-								.expect("failed to draw");
-							wait_tk(&mut dev, tk);
-							let tk = dev
-								.flush(scanout_resource_id, r, &mut buf)
-								.expect("failed to draw");
-							wait_tk(&mut dev, tk);
-						}
-						Response::Amount(d.len().try_into().unwrap())
-					} else if let Ok([0xc5, w, h]) = <[u8; 3]>::try_from(&*d) {
-						rt::dbg!();
-						let l = (usize::from(w) + 1) * (usize::from(h) + 1);
-						if l * 4 <= command_buf.1 {
-							unsafe {
-								let r = Rect::new(0, 0, 64, 64);
-
-								cursor.virt().as_ptr().write_bytes(0, 64 * 64 * 4);
-								for y in 0..usize::from(h) + 1 {
-									let t = cursor.virt().as_ptr().add(64 * 4 * y);
-									let f =
-										command_buf.0.as_ptr().add((usize::from(w) + 1) * 4 * y);
-									t.copy_from_nonoverlapping(f, (usize::from(w) + 1) * 4);
-								}
-								let tk = dev.transfer(cursor_resource_id, r, &mut buf).unwrap();
-								wait_tk(&mut dev, tk);
-								let tk = dev.flush(cursor_resource_id, r, &mut buf).unwrap();
-								wait_tk(&mut dev, tk);
-
-								let tk = dev
-									.update_cursor(0, cursor_resource_id, 0, 0, 0, 0, &mut buf)
-									.unwrap();
-								wait_tk2(&mut dev, tk);
-							}
-							Response::Amount(l as _)
-						} else {
-							Response::Error(Error::InvalidData)
-						}
-					} else {
-						Response::Error(Error::InvalidData as _)
-					}
-				}
-				Request::Share { share } => {
-					match share.map_object(None, rt::io::RWX::R, 0, 1 << 30) {
-						Err(e) => Response::Error(e as _),
-						Ok((buf, size)) => {
-							command_buf = (buf.cast(), size);
-							Response::Amount(0)
-						}
-					}
-				}
-				Request::Close => continue,
-				_ => Response::Error(Error::InvalidOperation as _),
 			};
 			tbl.enqueue(job_id, response);
 			send_notif = true;
@@ -371,3 +205,221 @@ fn main(_: isize, _: *const *const u8) -> isize {
 		tbl.wait();
 	}
 }
+
+/// The virtio GPU with its scanouts and cursor.
+struct Gpu<'a> {
+	dev: virtio_gpu::Device<'a>,
+	/// Buffer for virtio queue requests.
+	buf: virtio::PhysMap<'a>,
+	poll: &'a rt::Object,
+	scanouts: Vec<Scanout<'a>>,
+	/// 64x64 RGBA cursor image.
+	cursor: virtio::PhysMap<'a>,
+	cursor_resource_id: NonZeroU32,
+	cursor_pos: (u32, u32),
+	width: usize,
+	height: usize,
+}
+
+impl DisplayDevice for Gpu<'_> {
+	fn resolution(&self) -> ipc_gpu::Resolution {
+		ipc_gpu::Resolution { x: self.width as _, y: self.height as _ }
+	}
+
+	fn flush(&mut self, handle: Handle, buffer: &Buffer, cmd: ipc_gpu::Flush) {
+		let s = match handle {
+			Handle::MAX => &self.scanouts[0],
+			h => &self.scanouts[h as usize],
+		};
+		// Clip the area to the scanout.
+		let (o, w, h) = match cmd.clip(self.width as _, self.height as _) {
+			Some(c) => c,
+			None => return,
+		};
+		let r = Rect::new(o.x, o.y, w, h);
+		let src = buffer.as_ptr().as_ptr().wrapping_add(cmd.offset as _);
+		let stride = cmd.stride as usize;
+		let poll = self.poll;
+		let wait_tk = |dev: &mut virtio_gpu::Device, tk| wait_control(poll, dev, tk);
+		blit(
+			&mut self.dev,
+			s,
+			self.width,
+			r,
+			&mut self.buf,
+			wait_tk,
+			|x, y| {
+				let i = (y - r.y()) as usize * stride + (x - r.x()) as usize;
+				let [r, g, b] = unsafe { *src.cast::<[u8; 3]>().add(i) };
+				[r, g, b, 0]
+			},
+		);
+	}
+
+	fn set_cursor_image(&mut self, buffer: &Buffer, w: u8, h: u8) {
+		// The cursor resource is only 64x64 pixels large.
+		let (src_stride, w, h) = (
+			(usize::from(w) + 1) * 4,
+			usize::from(w.min(63)),
+			usize::from(h.min(63)),
+		);
+		let r = Rect::new(0, 0, 64, 64);
+		unsafe {
+			self.cursor.virt().as_ptr().write_bytes(0, 64 * 64 * 4);
+			for y in 0..=h {
+				let t = self.cursor.virt().as_ptr().add(64 * 4 * y);
+				let f = buffer.as_ptr().as_ptr().add(src_stride * y);
+				t.copy_from_nonoverlapping(f, (w + 1) * 4);
+			}
+			let tk = self
+				.dev
+				.transfer(self.cursor_resource_id, r, &mut self.buf)
+				.unwrap();
+			wait_control(self.poll, &mut self.dev, tk);
+			let tk = self
+				.dev
+				.flush(self.cursor_resource_id, r, &mut self.buf)
+				.unwrap();
+			wait_control(self.poll, &mut self.dev, tk);
+			let (x, y) = self.cursor_pos;
+			let tk = self
+				.dev
+				.update_cursor(0, self.cursor_resource_id, x, y, 0, 0, &mut self.buf)
+				.unwrap();
+			wait_cursor(self.poll, &mut self.dev, tk);
+		}
+	}
+
+	fn set_cursor_position(&mut self, x: u16, y: u16) {
+		self.cursor_pos = (x.into(), y.into());
+		unsafe {
+			// In QEMU 7.0, if a Data IN is sent to the USB tablet *after*
+			// the cursor texture is set it will disappear.
+			// The easiest workaround is to always use update_cursor.
+			// If this proves to be too inefficient we can refresh it, say, every second.
+			let tk = self
+				.dev
+				.update_cursor(
+					0,
+					self.cursor_resource_id,
+					x.into(),
+					y.into(),
+					0,
+					0,
+					&mut self.buf,
+				)
+				//.move_cursor(0, self.cursor_resource_id, x.into(), y.into(), &mut self.buf)
+				.unwrap();
+			wait_cursor(self.poll, &mut self.dev, tk);
+		}
+	}
+}
+
+/// Wait for a control queue operation to finish.
+fn wait_control(poll: &rt::Object, dev: &mut virtio_gpu::Device, tk: virtio_gpu::ControlOpToken) {
+	while dev.control_queue_pending() == 0 {
+		poll.read(&mut []).unwrap();
+	}
+	dev.poll_control_queue(|t| assert_eq!(tk, t));
+}
+
+/// Wait for a cursor queue operation to finish.
+fn wait_cursor(poll: &rt::Object, dev: &mut virtio_gpu::Device, tk: virtio_gpu::CursorOpToken) {
+	while dev.cursor_queue_pending() == 0 {
+		poll.read(&mut []).unwrap();
+	}
+	dev.poll_cursor_queue(|t| assert_eq!(tk, t));
+}
+
+/// Create a backing storage with the regions of a map.
+///
+/// The storage is taken from the pool unless the map has too many regions, in which case a
+/// larger storage is allocated with `alloc_phys`.
+fn backing_for<'a>(
+	pool: &'a DmaPool,
+	map: &virtio::PhysMap<'a>,
+	alloc_phys: impl FnOnce(usize) -> virtio::PhysMap<'a>,
+) -> virtio_gpu::BackingStorage<'a> {
+	let buf = pool.alloc().expect("out of DMA buffers");
+	let mut backing = virtio_gpu::BackingStorage::new(phys_map(&buf));
+	if let Err(e) = backing.try_push(map) {
+		let storage = alloc_phys(virtio_gpu::BackingStorage::size_for(e.required));
+		backing
+			.grow(storage)
+			.unwrap_or_else(|_| unreachable!("new storage is too small"));
+		// The old storage is returned to the pool when buf is dropped.
+		backing.push(map);
+	} else {
+		// The storage is used by the device for as long as the driver runs.
+		core::mem::forget(buf);
+	}
+	backing
+}
+
+fn phys_map<'a>(buf: &DmaBuf<'a>) -> virtio::PhysMap<'a> {
+	// SAFETY: the buffer is valid for as long as the pool is.
+	unsafe {
+		virtio::PhysMap::new(
+			buf.as_ptr().cast(),
+			virtio::PhysAddr::new(buf.phys()),
+			buf.size().get(),
+		)
+	}
+}
+
+/// The size of the backing storage of each scanout.
+const STRIP_SIZE: usize = 1 << 19;
+
+/// A display with its own framebuffer.
+struct Scanout<'a> {
+	resource_id: NonZeroU32,
+	/// A strip of rows of the framebuffer.
+	fb: virtio::PhysMap<'a>,
+}
+
+/// Draw a rect of a scanout and present it.
+///
+/// The host reads each row of the rect at the stride of the resource, which is the width of the
+/// scanout. The rect is split in strips of as many rows as fit in the backing storage, each of
+/// which is transferred separately. `pixel` is called with the global coordinates of each pixel.
+fn blit(
+	dev: &mut virtio_gpu::Device,
+	scanout: &Scanout,
+	stride: usize,
+	rect: Rect,
+	buf: &mut virtio::PhysMap,
+	wait_tk: impl Fn(&mut virtio_gpu::Device, virtio_gpu::ControlOpToken),
+	mut pixel: impl FnMut(u32, u32) -> [u8; 4],
+) {
+	let rows = u32::try_from(scanout.fb.size() / (stride * 4)).unwrap_or(u32::MAX);
+	assert!(rows > 0, "strip is too small for a single row");
+	let fb = scanout.fb.virt().as_ptr().cast::<[u8; 4]>();
+	let mut y = rect.y();
+	while y < rect.y() + rect.height() {
+		let h = rows.min(rect.y() + rect.height() - y);
+		for sy in 0..h {
+			for sx in 0..rect.width() {
+				let p = pixel(rect.x() + sx, y + sy);
+				unsafe { fb.add(sy as usize * stride + sx as usize).write(p) };
+			}
+		}
+		let strip = Rect::new(rect.x(), y, rect.width(), h);
+		unsafe {
+			let tk = dev
+				.transfer(scanout.resource_id, strip, buf)
+				.expect("failed to draw");
+			wait_tk(dev, tk);
+		}
+		y += h;
+	}
+	unsafe {
+		let tk = dev
+			.flush(scanout.resource_id, rect, buf)
+			.expect("failed to draw");
+		wait_tk(dev, tk);
+	}
+}
+
+fn parse_index(s: &[u8]) -> Option<usize> {
+	core::str::from_utf8(s).ok()?.parse().ok()
+}
@@ -106,6 +106,11 @@ impl<'d> Dev<'d> {
 			map != 0
 		}
 	}
+
+	/// Whether the link is up.
+	pub fn link_up(&self) -> bool {
+		self.0.borrow().virtio.link_up()
+	}
 }
 
 fn pop_bit(m: &mut u64) -> Option<usize> {
@@ -190,16 +195,21 @@ impl<'a, 'd: 'a> TxToken for DevTxToken<'a, 'd> {
 		unsafe {
 			let (mut virt, phys) = self.dev.borrow_mut().get(self.index);
 			let r = f(&mut virt.as_mut().data[..len]);
-			ManuallyDrop::new(self)
+			let size = Packet::size_with_data(len);
+			let res = self
 				.dev
 				.borrow_mut()
 				.virtio
-				.send(
-					virt,
-					PhysRegion { base: phys, size: Packet::size_with_data(len) },
-				)
-				.unwrap();
-			r
+				.send(virt, PhysRegion { base: phys, size });
+			match res {
+				Ok(_) => {
+					// The packet is returned to us in process()
+					let _ = ManuallyDrop::new(self);
+					r
+				}
+				// Drop the packet and let smoltcp try again later.
+				Err(virtio_net::SendError::QueueFull) => Err(smoltcp::Error::Exhausted),
+			}
 		}
 	}
 }
@@ -25,7 +25,10 @@ use {
 		str::{self, FromStr},
 		time::Duration,
 	},
-	driver_utils::os::stream_table::{JobId, Request, Response, StreamTable},
+	driver_utils::{
+		io::{EventRing, Overflow},
+		os::stream_table::{JobId, Request, Response, StreamTable},
+	},
 	rt::Error,
 	rt_default as _,
 	smoltcp::wire,
@@ -33,6 +36,9 @@ use {
 	udp::UdpSocket,
 };
 
+/// The maximum amount of reads or writes that can wait for a socket at once.
+const MAX_PENDING: usize = 256;
+
 enum Socket {
 	TcpListener(TcpListener<5>),
 	TcpConnection(TcpConnection),
@@ -46,15 +52,9 @@ fn start(_: isize, _: *const *const u8) -> isize {
 }
 
 fn main() {
-	let table_name = rt::args::args()
-		.skip(1)
-		.next()
-		.expect("expected table name");
-
-	let dev = rt::args::handles()
-		.find(|(name, _)| name == b"pci")
-		.expect("no 'pci' object")
-		.1;
+	let table_name = rt::args::arg(1).expect("expected table name");
+
+	let dev = rt::args::handle(b"pci").expect("no 'pci' object");
 	let poll = AsyncObject::from_raw(dev.open(b"poll").unwrap().into_raw());
 
 	let pci = dev.map_object(None, rt::RWX::R, 0, usize::MAX).unwrap();
@@ -83,7 +83,11 @@ fn main() {
 					Ok((d.cast(), virtio::PhysAddr::new(a.try_into().unwrap())))
 				};
 
-				let msix = virtio_net::Msix { receive_queue: Some(0), transmit_queue: Some(1) };
+				let msix = virtio_net::Msix {
+					receive_queue: Some(0),
+					transmit_queue: Some(1),
+					control_queue: None,
+				};
 
 				unsafe { virtio_net::Device::new(h, map_bar, dma_alloc, msix).unwrap() }
 			}
@@ -136,49 +140,65 @@ fn main() {
 		len: u32,
 	}
 	// FIXME avoid closing before finishing.
-	let mut pending_writes = Vec::<PendingWrite>::new();
-	let mut pending_reads = Vec::<PendingRead>::new();
+	let mut pending_writes = EventRing::<PendingWrite>::new(MAX_PENDING, Overflow::Reject);
+	let mut pending_reads = EventRing::<PendingRead>::new(MAX_PENDING, Overflow::Reject);
 
 	let mut t;
 	let mut buf = [0; 2048];
 	loop {
-		// Finish pending writes
-		for i in (0..pending_writes.len()).rev() {
-			let p = &mut pending_writes[i];
+		// Finish pending writes. Unfinished writes are put back at the end.
+		for _ in 0..pending_writes.len() {
+			let p = pending_writes.pop().unwrap();
 			match &mut table.objects[p.handle] {
 				Object::Socket(Socket::TcpConnection(sock)) => {
 					if let Some(r) = sock.write_all(&p.data, &mut iface) {
 						r.unwrap();
 						table.amount(p.job_id, p.data.len() as _);
-						pending_writes.swap_remove(i);
+						continue;
 					}
 				}
-				Object::Socket(Socket::Udp(_)) => todo!(),
+				Object::Socket(Socket::Udp(sock)) => match sock.write(&p.data, &mut iface) {
+					Err(smoltcp::Error::Exhausted) => {}
+					r => {
+						table.amount(p.job_id, r.unwrap());
+						continue;
+					}
+				},
 				_ => unreachable!(),
 			}
+			let _ = pending_writes.enqueue(p);
 		}
 
-		// Finish pending reads
-		for i in (0..pending_reads.len()).rev() {
-			let p = &mut pending_reads[i];
+		// Finish pending reads. Unfinished reads are put back at the end.
+		for _ in 0..pending_reads.len() {
+			let p = pending_reads.pop().unwrap();
 			match &mut table.objects[p.handle] {
 				Object::Socket(Socket::TcpConnection(sock)) => {
 					match sock.read(&mut buf[..p.len as _], &mut iface) {
 						Ok(0) => {}
 						Ok(l) => {
 							table.data(p.job_id, &buf[..l]);
-							pending_reads.swap_remove(i);
+							continue;
 						}
 						Err(smoltcp::Error::Illegal) | Err(smoltcp::Error::Finished) => {
 							table.error(p.job_id, Error::Unknown);
-							pending_reads.swap_remove(i);
+							continue;
 						}
 						Err(e) => todo!("{:?}", e),
 					}
 				}
-				Object::Socket(Socket::Udp(_)) => todo!(),
+				Object::Socket(Socket::Udp(sock)) => {
+					match sock.read(&mut buf[..p.len as _], &mut iface) {
+						Err(smoltcp::Error::Exhausted) => {}
+						r => {
+							table.data(p.job_id, &buf[..r.unwrap()]);
+							continue;
+						}
+					}
+				}
 				_ => unreachable!(),
 			}
+			let _ = pending_reads.enqueue(p);
 		}
 
 		// Advance TCP connection state.
@@ -228,7 +248,9 @@ fn main() {
 
 		// Handle incoming requests
 		loop {
-			let Some((handle, job_id, req)) = table.table.dequeue() else { break };
+			let Some((handle, job_id, req)) = table.table.dequeue() else {
+				break;
+			};
 			match req {
 				v @ Request::Open { .. } => {
 					let (path, _) = v.into_data().copy_into(&mut buf);
@@ -242,8 +264,12 @@ fn main() {
 							("default", None, _) | ("default", Some(""), None) => {
 								let addr = into_ip6(iface.ip_addrs()[0].address());
 								Query::SourceAddr(addr, Protocol::Tcp)
-							},
-							(addr, None, _) | (addr, Some(""), None) if let Ok(addr) = wire::IpAddress::from_str(addr) => todo!(),
+							}
+							(addr, None, _) | (addr, Some(""), None)
+								if let Ok(addr) = wire::IpAddress::from_str(addr) =>
+							{
+								todo!()
+							}
 							path => todo!("{:?}", path),
 						};
 						table.insert(job_id, Object::Query(Some(query)));
@@ -324,7 +350,15 @@ fn main() {
 										_ => todo!(),
 									}
 								}
-								"udp" => Socket::Udp(UdpSocket::new(&mut iface)),
+								"udp" => {
+									let port = match parts.next().map(|p| p.parse()) {
+										None | Some(Ok(0)) => alloc_port(),
+										Some(Ok(p)) => p,
+										Some(Err(_)) => todo!(),
+									};
+									let source = wire::IpEndpoint { addr: source, port };
+									Socket::Udp(UdpSocket::new(&mut iface, source))
+								}
 								_ => todo!(),
 							}),
 						);
@@ -344,11 +378,13 @@ fn main() {
 						Object::Socket(Socket::TcpConnection(sock)) => {
 							let r = sock.read(&mut buf[..len], &mut iface);
 							match r {
-								Ok(0) => pending_reads.push(PendingRead {
-									handle,
-									job_id,
-									len: len.try_into().unwrap(),
-								}),
+								Ok(0) => pending_reads
+									.enqueue(PendingRead {
+										handle,
+										job_id,
+										len: len.try_into().unwrap(),
+									})
+									.unwrap_or_else(|p| table.error(p.job_id, Error::WouldBlock)),
 								Ok(len) => table.data(job_id, &buf[..len]),
 								Err(smoltcp::Error::Illegal) | Err(smoltcp::Error::Finished) => {
 									table.error(job_id, Error::Unknown)
@@ -356,8 +392,21 @@ fn main() {
 								Err(e) => todo!("handle {:?}", e),
 							}
 						}
-						Object::Socket(Socket::Udp(_sock)) => {
-							todo!("udp remote address")
+						Object::Socket(Socket::Udp(_)) if len < udp::HEADER_SIZE => {
+							table.error(job_id, Error::InvalidData)
+						}
+						Object::Socket(Socket::Udp(sock)) => {
+							match sock.read(&mut buf[..len], &mut iface) {
+								Ok(len) => table.data(job_id, &buf[..len]),
+								Err(smoltcp::Error::Exhausted) => pending_reads
+									.enqueue(PendingRead {
+										handle,
+										job_id,
+										len: len.try_into().unwrap(),
+									})
+									.unwrap_or_else(|p| table.error(p.job_id, Error::WouldBlock)),
+								Err(e) => todo!("handle {:?}", e),
+							}
 						}
 						Object::Query(q) => match q {
 							Some(Query::Root(q @ QueryRoot::Default)) => {
@@ -396,19 +445,29 @@ fn main() {
 						let (data, _) = v.into_data().copy_into(&mut buf);
 						match sock.write(data, &mut iface) {
 							Ok(l) if l == 0 => {
-								pending_writes.push(PendingWrite {
-									handle,
-									job_id,
-									data: (&*data).into(),
-								});
+								pending_writes
+									.enqueue(PendingWrite { handle, job_id, data: (&*data).into() })
+									.unwrap_or_else(|p| table.error(p.job_id, Error::WouldBlock));
 							}
 							Ok(l) => table.amount(job_id, l),
 							Err(smoltcp::Error::Illegal) => table.error(job_id, Error::Unknown),
 							Err(e) => todo!("handle {:?}", e),
 						}
 					}
-					Object::Socket(Socket::Udp(_sock)) => {
-						todo!("udp remote address")
+					Object::Socket(Socket::Udp(sock)) => {
+						let (data, _) = v.into_data().copy_into(&mut buf);
+						match sock.write(data, &mut iface) {
+							Ok(l) => table.amount(job_id, l),
+							Err(smoltcp::Error::Exhausted) => {
+								pending_writes
+									.enqueue(PendingWrite { handle, job_id, data: (&*data).into() })
+									.unwrap_or_else(|p| table.error(p.job_id, Error::WouldBlock));
+							}
+							Err(smoltcp::Error::Illegal) | Err(smoltcp::Error::Truncated) => {
+								table.error(job_id, Error::InvalidData)
+							}
+							Err(e) => todo!("handle {:?}", e),
+						}
 					}
 					Object::Query(_) => todo!(),
 				},
@@ -424,11 +483,30 @@ fn main() {
 					}
 					continue;
 				}
-				v @ Request::Seek { .. } => {
+				v @ Request::Seek { .. }
+				| v @ Request::Readv { .. }
+				| v @ Request::Writev { .. }
+				| v @ Request::ReadAt { .. }
+				| v @ Request::WriteAt { .. } => {
 					drop(v);
 					table.error(job_id, Error::InvalidOperation);
 				}
-				Request::GetMeta { .. } => todo!(),
+				v @ Request::GetMeta { .. } => {
+					let (property, _) = v.into_data().copy_into(&mut buf);
+					if handle != rt::Handle::MAX {
+						table.error(job_id, Error::InvalidOperation);
+						continue;
+					}
+					match property {
+						b"mac" => {
+							let mac: &[u8; 6] = addr.as_ref();
+							table.data(job_id, mac)
+						}
+						b"link" if iface.device().link_up() => table.data(job_id, b"up"),
+						b"link" => table.data(job_id, b"down"),
+						_ => table.error(job_id, Error::DoesNotExist),
+					}
+				}
 				Request::SetMeta { .. } => todo!(),
 				Request::Destroy { .. } => todo!(),
 				Request::Share { .. } => todo!(),
@@ -510,7 +588,7 @@ impl Table {
 		let table = StreamTable::new(&buf, rt::io::Pow2Size(9), (1 << 12) - 1);
 		rt::io::file_root()
 			.unwrap()
-			.create(table_name)
+			.create_with_type(table_name, b"net")
 			.unwrap()
 			.share(&table.public())
 			.unwrap();
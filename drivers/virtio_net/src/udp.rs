@@ -1,18 +1,38 @@
+//! ## Framing
+//!
+//! Each read or write on a UDP socket handles exactly one datagram. The datagram is prefixed
+//! with a header containing the address of the peer:
+//!
+//! | Offset | Size | Field                                       |
+//! |--------|------|---------------------------------------------|
+//! | 0      | 16   | IPv6 address, IPv4 addresses are mapped     |
+//! | 16     | 2    | Port, little-endian                         |
+//! | 18     | *    | Payload                                     |
+//!
+//! On read the header holds the source address, on write the destination address.
+
 use {
 	alloc::vec::Vec,
 	smoltcp::{
 		iface::{Interface, SocketHandle},
 		phy::Device,
 		socket::{self, UdpPacketMetadata, UdpSocketBuffer},
+		wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
 	},
 };
 
+/// The size of the address header that precedes each datagram.
+pub const HEADER_SIZE: usize = 18;
+
 pub struct UdpSocket {
 	handle: SocketHandle,
 }
 
 impl UdpSocket {
-	pub fn new(iface: &mut Interface<impl for<'d> Device<'d>>) -> Self {
+	pub fn new(
+		iface: &mut Interface<impl for<'d> Device<'d>>,
+		source: impl Into<IpEndpoint>,
+	) -> Self {
 		let rx = UdpSocketBuffer::new(
 			Vec::from([UdpPacketMetadata::EMPTY; 5]),
 			Vec::from([0; 1024]),
@@ -21,12 +41,59 @@ impl UdpSocket {
 			Vec::from([UdpPacketMetadata::EMPTY; 5]),
 			Vec::from([0; 1024]),
 		);
-		let sock = socket::UdpSocket::new(rx, tx);
+		let mut sock = socket::UdpSocket::new(rx, tx);
+		sock.bind(source).unwrap();
 		let handle = iface.add_socket(sock);
 		Self { handle }
 	}
 
+	/// Receive a single datagram, including the header.
+	pub fn read(
+		&mut self,
+		data: &mut [u8],
+		iface: &mut Interface<impl for<'d> Device<'d>>,
+	) -> smoltcp::Result<usize> {
+		let (hdr, data) = data.split_at_mut(HEADER_SIZE);
+		let sock = iface.get_socket::<socket::UdpSocket>(self.handle);
+		let (len, source) = sock.recv_slice(data)?;
+		hdr.copy_from_slice(&encode_header(source));
+		Ok(HEADER_SIZE + len)
+	}
+
+	/// Send a single datagram, including the header.
+	pub fn write(
+		&mut self,
+		data: &[u8],
+		iface: &mut Interface<impl for<'d> Device<'d>>,
+	) -> smoltcp::Result<usize> {
+		let (dest, payload) = decode_header(data).ok_or(smoltcp::Error::Illegal)?;
+		let sock = iface.get_socket::<socket::UdpSocket>(self.handle);
+		sock.send_slice(payload, dest)?;
+		Ok(data.len())
+	}
+
 	pub fn close(self, iface: &mut Interface<impl for<'d> Device<'d>>) {
 		iface.remove_socket(self.handle);
 	}
 }
+
+fn encode_header(endpoint: IpEndpoint) -> [u8; HEADER_SIZE] {
+	let mut h = [0; HEADER_SIZE];
+	h[..16].copy_from_slice(&super::into_ip6(endpoint.addr).0);
+	h[16..].copy_from_slice(&endpoint.port.to_le_bytes());
+	h
+}
+
+fn decode_header(data: &[u8]) -> Option<(IpEndpoint, &[u8])> {
+	(data.len() >= HEADER_SIZE).then(|| {
+		let (h, payload) = data.split_at(HEADER_SIZE);
+		let addr = match <[u8; 16]>::try_from(&h[..16]).unwrap() {
+			[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+				IpAddress::Ipv4(Ipv4Address([a, b, c, d]))
+			}
+			a => IpAddress::Ipv6(Ipv6Address(a)),
+		};
+		let port = u16::from_le_bytes([h[16], h[17]]);
+		(IpEndpoint { addr, port }, payload)
+	})
+}
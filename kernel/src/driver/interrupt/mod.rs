@@ -7,7 +7,7 @@ use {
 	},
 	crate::{
 		arch,
-		object_table::{Error, Object, Root, Ticket, TicketWaker},
+		object_table::{Error, Object, Root, Ticket, TicketWaker, TinySlice},
 		sync::SpinLock,
 	},
 	alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec},
@@ -25,6 +25,22 @@ struct InterruptTable;
 
 impl Object for InterruptTable {
 	fn create(self: Arc<Self>, path: &[u8]) -> Ticket<Arc<dyn Object>> {
+		if path == b"msi" {
+			let Ok(vector) = arch::allocate_irq() else { return Error::CantCreateObject.into() };
+			unsafe {
+				arch::amd64::set_interrupt_handler(vector.into(), handle_irq);
+			}
+			LISTENERS.lock().insert(
+				vector,
+				Entry {
+					mode: TriggerMode::Edge,
+					irq: None,
+					triggered: false,
+					wake: Default::default(),
+				},
+			);
+			return Ticket::new_complete(Ok(Arc::new(Interrupt(vector))));
+		}
 		let (mode, irq) = match path {
 			p if p.starts_with(b"edge/") => (TriggerMode::Edge, &p[5..]),
 			p if p.starts_with(b"level/") => (TriggerMode::Level, &p[6..]),
@@ -42,7 +58,7 @@ impl Object for InterruptTable {
 		}
 		LISTENERS.lock().insert(
 			vector,
-			Entry { mode, irq, triggered: false, wake: Default::default() },
+			Entry { mode, irq: Some(irq), triggered: false, wake: Default::default() },
 		);
 		unsafe {
 			io_apic::set_irq(irq, 0, vector, mode, false);
@@ -53,7 +69,8 @@ impl Object for InterruptTable {
 
 struct Entry {
 	mode: TriggerMode,
-	irq: InterruptIrq,
+	/// The I/O APIC IRQ line, if any. MSI(-X) interrupts don't go through the I/O APIC.
+	irq: Option<InterruptIrq>,
 	triggered: bool,
 	wake: Vec<TicketWaker<Box<[u8]>>>,
 }
@@ -62,6 +79,8 @@ struct Interrupt(InterruptVector);
 
 impl Drop for Interrupt {
 	fn drop(&mut self) {
+		// Any interrupts arriving after this point are ignored by handle_irq, which effectively
+		// masks the vector.
 		LISTENERS.auto_lock().remove(&self.0).unwrap();
 		unsafe { arch::deallocate_irq(self.0) }
 	}
@@ -84,12 +103,25 @@ impl Object for Interrupt {
 		let mut l = LISTENERS.auto_lock();
 		let e = l.get_mut(&self.0).unwrap();
 		unsafe {
-			if e.mode == TriggerMode::Level {
-				io_apic::mask_irq(e.irq, false);
+			if let (TriggerMode::Level, Some(irq)) = (e.mode, e.irq) {
+				io_apic::mask_irq(irq, false);
 			}
 		}
 		0.into()
 	}
+
+	fn get_meta(self: Arc<Self>, property: &TinySlice<u8>) -> Ticket<Box<[u8]>> {
+		Ticket::new_complete(match &**property {
+			// The message address and data to program into a MSI(-X) capability, in that order.
+			b"bin/msi" => {
+				let mut b = [0; 12];
+				b[..8].copy_from_slice(&local_apic::get_phys().to_le_bytes());
+				b[8..].copy_from_slice(&u32::from(self.0).to_le_bytes());
+				Ok(b[..].into())
+			}
+			_ => Err(Error::DoesNotExist),
+		})
+	}
 }
 
 pub fn post_init(root: &Root) {
@@ -100,14 +132,15 @@ pub fn post_init(root: &Root) {
 
 extern "C" fn handle_irq(vector: u32) {
 	let mut l = LISTENERS.isr_lock();
-	let e = l.get_mut(&(vector as _)).unwrap();
-	if let Some(w) = e.wake.pop() {
-		w.isr_complete(Ok([].into()));
-	} else {
-		e.triggered = true;
-	}
-	if e.mode == TriggerMode::Level {
-		unsafe { io_apic::mask_irq(e.irq, true) };
+	if let Some(e) = l.get_mut(&(vector as _)) {
+		if let Some(w) = e.wake.pop() {
+			w.isr_complete(Ok([].into()));
+		} else {
+			e.triggered = true;
+		}
+		if let (TriggerMode::Level, Some(irq)) = (e.mode, e.irq) {
+			unsafe { io_apic::mask_irq(irq, true) };
+		}
 	}
 	local_apic::get().eoi.set(0);
 }
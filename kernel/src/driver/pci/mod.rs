@@ -16,7 +16,7 @@ use {
 		sync::SpinLock,
 	},
 	acpi::{sdt::Signature, AcpiHandler, AcpiTables},
-	alloc::sync::Arc,
+	alloc::{sync::Arc, vec::Vec},
 	core::ptr::NonNull,
 	pci::{
 		capability::{Capability, Msi, MsiX},
@@ -79,13 +79,9 @@ pub(super) fn post_init(root: &Root) {
 /// Allocate IRQs for all PCI devices that need it.
 /// While there are "only" ~220 available IRQs, this should be enough for the foreseeable future.
 unsafe fn allocate_irqs(pci: &mut Pci) {
-	for dev in pci.iter().flat_map(|b| b.iter()) {
+	for dev in pci.iter().flat_map(|b| b.iter()).flat_map(|d| d.iter()) {
 		let h = dev.header();
-		let mut cmd = h.common().command();
-		cmd &= !pci::HeaderCommon::COMMAND_INTERRUPT_DISABLE;
-		cmd |= pci::HeaderCommon::COMMAND_MMIO_MASK;
-		cmd |= pci::HeaderCommon::COMMAND_BUS_MASTER_MASK;
-		h.set_command(cmd);
+		h.enable_bus_master_mmio();
 		enum Int<'a> {
 			None,
 			Msi(&'a Msi),
@@ -114,7 +110,7 @@ unsafe fn allocate_irqs(pci: &mut Pci) {
 			irq
 		};
 
-		let (bus, dev, func) = (dev.bus(), dev.device(), 0);
+		let (bus, dev, func) = (dev.bus(), dev.device(), dev.function());
 		match int {
 			Int::None => info!("No MSI or MSI-X for {:02x}:{:02x}.{:x}", bus, dev, func),
 			Int::Msi(msi) => {
@@ -139,34 +135,22 @@ unsafe fn allocate_irqs(pci: &mut Pci) {
 				msi.set_message_control(ctrl);
 			}
 			Int::MsiX(msix) => {
-				let mut ctrl = msix.message_control();
+				let ctrl = msix.message_control();
 				let table_size = usize::from(ctrl.table_size()) + 1;
 				info!(
 					"{} MSI-X vectors for {:02x}:{:02x}.{:x}",
 					table_size, bus, dev, func
 				);
 
-				let (table_offset, table) = msix.table();
-				let table = h.full_base_address(table.into()).expect("bar");
-				let table = table.try_as_mmio().expect("mmio bar") + u64::from(table_offset);
-
-				let table = unsafe { phys_to_virt(table) };
-				let table = unsafe {
-					core::slice::from_raw_parts_mut(
-						table.cast::<pci::msix::TableEntry>(),
-						table_size,
-					)
+				let entries = (0..table_size)
+					.map(|_| (local_apic::get_phys(), alloc_irq().into()))
+					.collect::<Vec<(u64, u32)>>();
+				let map = |bar: u8| {
+					let bar = h.full_base_address(bar.into()).expect("bar");
+					let bar = bar.try_as_mmio().expect("mmio bar");
+					NonNull::new(unsafe { phys_to_virt(bar) }).unwrap().cast()
 				};
-
-				for e in table.iter_mut() {
-					let irq = alloc_irq();
-					e.set_message_data(irq.into());
-					e.set_message_address(local_apic::get_phys());
-					e.set_vector_control_mask(false);
-				}
-
-				ctrl.set_enable(true);
-				msix.set_message_control(ctrl);
+				unsafe { msix.configure(&entries, map).unwrap() };
 			}
 		}
 	}
@@ -50,29 +50,53 @@ impl Object for IoMap {
 	}
 
 	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
-		let p = self.pos.load(Ordering::Relaxed);
-		// SAFETY: nada *shrugs*
-		unsafe {
-			Ticket::new_complete(match length {
-				1 => Ok(io::in8(p).to_le_bytes().into()),
-				2 => Ok(io::in16(p).to_le_bytes().into()),
-				4 => Ok(io::in32(p).to_le_bytes().into()),
-				_ => Err(Error::InvalidData),
-			})
-		}
+		Ticket::new_complete(port_in(self.pos.load(Ordering::Relaxed), length))
 	}
 
 	fn write(self: Arc<Self>, data: &[u8]) -> Ticket<u64> {
-		let p = self.pos.load(Ordering::Relaxed);
-		// SAFETY: *shrugs again*
-		unsafe {
-			match data {
-				&[a] => io::out8(p, a),
-				&[a, b] => io::out16(p, u16::from_le_bytes([a, b])),
-				&[a, b, c, d] => io::out32(p, u32::from_le_bytes([a, b, c, d])),
-				_ => return Ticket::new_complete(Err(Error::InvalidData)),
-			}
+		Ticket::new_complete(port_out(self.pos.load(Ordering::Relaxed), data))
+	}
+
+	fn read_at(self: Arc<Self>, offset: u64, length: usize) -> Ticket<Box<[u8]>> {
+		Ticket::new_complete(
+			offset
+				.try_into()
+				.map_err(|_| Error::InvalidData)
+				.and_then(|p| port_in(p, length)),
+		)
+	}
+
+	fn write_at(self: Arc<Self>, offset: u64, data: &[u8]) -> Ticket<u64> {
+		Ticket::new_complete(
+			offset
+				.try_into()
+				.map_err(|_| Error::InvalidData)
+				.and_then(|p| port_out(p, data)),
+		)
+	}
+}
+
+fn port_in(port: u16, length: usize) -> Result<Box<[u8]>, Error> {
+	// SAFETY: nada *shrugs*
+	unsafe {
+		match length {
+			1 => Ok(io::in8(port).to_le_bytes().into()),
+			2 => Ok(io::in16(port).to_le_bytes().into()),
+			4 => Ok(io::in32(port).to_le_bytes().into()),
+			_ => Err(Error::InvalidData),
+		}
+	}
+}
+
+fn port_out(port: u16, data: &[u8]) -> Result<u64, Error> {
+	// SAFETY: *shrugs again*
+	unsafe {
+		match data {
+			&[a] => io::out8(port, a),
+			&[a, b] => io::out16(port, u16::from_le_bytes([a, b])),
+			&[a, b, c, d] => io::out32(port, u32::from_le_bytes([a, b, c, d])),
+			_ => return Err(Error::InvalidData),
 		}
-		Ticket::new_complete(Ok(data.len().try_into().unwrap()))
 	}
+	Ok(data.len().try_into().unwrap())
 }
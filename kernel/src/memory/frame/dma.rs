@@ -20,10 +20,23 @@ struct DmaFrame {
 }
 
 impl DmaFrame {
-	fn new(count: NonZeroUsize) -> Result<Self, Error> {
+	/// Allocate a range of pages.
+	///
+	/// The range does not cross a multiple of `boundary`, if any, and ends at or below
+	/// `max_address`.
+	fn new(
+		count: NonZeroUsize,
+		boundary: Option<NonZeroUsize>,
+		max_address: usize,
+	) -> Result<Self, Error> {
+		let size = count.get() * Page::SIZE;
 		let base = DMA
 			.auto_lock()
-			.pop_range(count)
+			.pop_range(count, |start| {
+				let end = start + size;
+				end <= max_address
+					&& boundary.map_or(true, |b| start / b.get() == (end - 1) / b.get())
+			})
 			.ok_or(Error::CantCreateObject)?;
 		unsafe {
 			base.as_ptr().write_bytes(0, count.get());
@@ -102,17 +115,37 @@ impl Object for Phys {
 	}
 }
 
+/// Allocator for physically contiguous memory.
+///
+/// The path is either `<size>` or `<size>,<boundary>,<max address>`. If a boundary is given
+/// the range will not cross any multiple of it. The boundary must be a power of two or `0` for
+/// none. The range will end at or below the max address.
 struct Dma;
 
 impl Object for Dma {
 	fn create(self: Arc<Self>, path: &[u8]) -> Ticket<Arc<dyn Object>> {
 		Ticket::new_complete((|| {
 			let path = str::from_utf8(path).map_err(|_| Error::InvalidData)?;
-			let n = path
-				.parse::<NonZeroUsize>()
-				.map_err(|_| Error::InvalidData)?;
+			let mut args = path.split(',');
+			let n = args
+				.next()
+				.and_then(|s| s.parse::<NonZeroUsize>().ok())
+				.ok_or(Error::InvalidData)?;
+			let (boundary, max_address) = match (args.next(), args.next(), args.next()) {
+				(None, None, None) => (None, usize::MAX),
+				(Some(b), Some(m), None) => (
+					NonZeroUsize::new(b.parse().map_err(|_| Error::InvalidData)?),
+					m.parse().map_err(|_| Error::InvalidData)?,
+				),
+				_ => return Err(Error::InvalidData),
+			};
 			let n = NonZeroUsize::new(Page::min_pages_for_bytes(n.get())).unwrap();
-			DmaFrame::new(n).map(|o| Arc::new(o) as Arc<dyn Object>)
+			if boundary.map_or(false, |b| {
+				!b.is_power_of_two() || b.get() < n.get() * Page::SIZE
+			}) {
+				return Err(Error::InvalidData);
+			}
+			DmaFrame::new(n, boundary, max_address).map(|o| Arc::new(o) as Arc<dyn Object>)
 		})())
 	}
 }
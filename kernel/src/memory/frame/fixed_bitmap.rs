@@ -27,13 +27,20 @@ impl FixedBitmap {
 		}
 	}
 
-	pub fn pop_range(&mut self, n: NonZeroUsize) -> Option<PPN> {
+	/// Allocate a range of pages whose physical address range satisfies the given predicate.
+	///
+	/// The predicate receives the physical address of the start of the range.
+	pub fn pop_range(
+		&mut self,
+		n: NonZeroUsize,
+		mut accept: impl FnMut(usize) -> bool,
+	) -> Option<PPN> {
 		if n.get() > 128 * self.bitmap.0.len() {
 			return None;
 		}
 		let mut mask = Bitmap::ones(n.get());
 		let mut shift = 0usize;
-		while self.bitmap & mask != mask {
+		while self.bitmap & mask != mask || !accept(self.base.skip(shift as _).as_phys()) {
 			shift += 1;
 			if shift == 128 * self.bitmap.0.len() {
 				return None;
@@ -16,6 +16,8 @@ pub enum MapError {
 	UnalignedOffset,
 	Permission,
 	Reserved,
+	/// The range overlaps with an existing mapping.
+	Overlap,
 	Arch(crate::arch::r#virtual::MapError),
 }
 
@@ -62,6 +64,9 @@ impl AddressSpace {
 		if !rwx.is_subset_of(perms) {
 			return Err(MapError::Permission);
 		}
+		// Don't reserve address space for pages before the offset.
+		let len = object.physical_pages_len() * Page::SIZE;
+		max_length = max_length.min(len.checked_sub(offset).ok_or(MapError::Overflow)?);
 
 		let (range, index) = Self::map_object_common(
 			&self.objects,
@@ -162,7 +167,6 @@ impl AddressSpace {
 			return Err(MapError::Reserved);
 		}
 
-		// FIXME we need to ensure the range doesn't overlap with any other range.
 		let end = base
 			.as_ptr()
 			.wrapping_add(count.get().min(max_length))
@@ -173,6 +177,14 @@ impl AddressSpace {
 			return Err(MapError::Overflow);
 		}
 		let end = NonNull::new(end).unwrap();
+		// Ranges are sorted, so only the neighbours need to be checked.
+		let overlaps_low = index
+			.checked_sub(1)
+			.map_or(false, |i| objects[i].0.end() >= &base);
+		let overlaps_high = objects.get(index).map_or(false, |e| e.0.start() <= &end);
+		if overlaps_low || overlaps_high {
+			return Err(MapError::Overlap);
+		}
 		Ok((base..=end, index))
 	}
 
@@ -181,8 +193,25 @@ impl AddressSpace {
 		base: NonNull<Page>,
 		count: NonZeroUsize,
 	) -> Result<(), UnmapError> {
+		// The range may span multiple objects, e.g. if a mapping has been grown in place.
+		let end = base.as_ptr().wrapping_add(count.get());
+		let mut b = base;
+		while b.as_ptr() < end {
+			let i = self.objects.iter().position(|e| e.0.contains(&b)).unwrap();
+			let obj_end = self.objects[i]
+				.0
+				.end()
+				.as_ptr()
+				.cast::<u8>()
+				.wrapping_add(1)
+				.cast();
+			let n = unsafe { obj_end.min(end).offset_from(b.as_ptr()) } as usize;
+			unsafe {
+				Self::unmap_object_common(&mut self.objects, b, NonZeroUsize::new(n).unwrap())?;
+			}
+			b = NonNull::new(b.as_ptr().wrapping_add(n)).unwrap();
+		}
 		unsafe {
-			Self::unmap_object_common(&mut self.objects, base, count)?;
 			self.mmu_address_space.unmap(base, count).unwrap();
 		}
 		Ok(())
@@ -224,12 +253,24 @@ impl AddressSpace {
 		// It may also make sense to special-case regular memory.
 		if &unmap_range == range {
 			Ok(Some(objects.remove(i).1))
+		} else if unmap_range.end() > range.end() {
+			todo!("unmap across objects {:?} != {:?}", unmap_range, range);
 		} else if unmap_range.end() == range.end() {
 			let end = unsafe { NonNull::new_unchecked(unmap_range.start().as_ptr().byte_sub(1)) };
 			objects[i].0 = *range.start()..=end;
 			Ok(None)
+		} else if unmap_range.start() == range.start() {
+			let start = unsafe { NonNull::new_unchecked(unmap_range.end().as_ptr().byte_add(1)) };
+			objects[i].0 = start..=*range.end();
+			Ok(None)
 		} else {
-			todo!("partial unmap {:?} != {:?}", unmap_range, range);
+			// Split the range in two, with both halves referring to the same object.
+			let (range, obj) = objects[i].clone();
+			let end = unsafe { NonNull::new_unchecked(unmap_range.start().as_ptr().byte_sub(1)) };
+			let start = unsafe { NonNull::new_unchecked(unmap_range.end().as_ptr().byte_add(1)) };
+			objects[i].0 = *range.start()..=end;
+			objects.insert(i + 1, (start..=*range.end(), obj));
+			Ok(None)
 		}
 	}
 
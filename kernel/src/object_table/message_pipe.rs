@@ -43,6 +43,47 @@ struct PipeIn(Arc<Mutex<PipeInner>>);
 
 struct PipeOut(Arc<Mutex<PipeInner>>);
 
+impl PipeInner {
+	/// Pass a message to a waiting reader or enqueue it.
+	///
+	/// Returns `true` if a reader took the message directly.
+	fn send(&mut self, data: &[u8]) -> bool {
+		while let Some((max_len, w)) = self.wake_read.pop_front() {
+			if max_len >= data.len() {
+				w.complete(Ok(data.into()));
+				return true;
+			} else {
+				w.complete(Err(Error::InvalidData)); // TODO better error codes
+			}
+		}
+		self.queue.push_back(data.into());
+		false
+	}
+
+	/// Take the next message without blocking.
+	///
+	/// Returns `None` if there are no messages.
+	fn try_recv(&mut self, length: usize) -> Option<Result<Box<[u8]>, Error>> {
+		if let Some(msg) = self.queue.pop_front() {
+			Some(if length < msg.len() {
+				self.queue.push_front(msg);
+				// TODO we need more / better error codes
+				Err(Error::InvalidData)
+			} else {
+				self.wake_write
+					.pop_front()
+					.map(|w| w.complete(Ok(msg.len() as _)));
+				Ok(msg)
+			})
+		} else if self.flags & WRITE_CLOSED != 0 {
+			// FIXME an error like Error::Closed (or similar) would be better.
+			Some(Ok([].into()))
+		} else {
+			None
+		}
+	}
+}
+
 impl Object for PipeIn {
 	fn write(self: Arc<Self>, data: &[u8]) -> Ticket<u64> {
 		if data.len() >= PACKET_MAX_SIZE {
@@ -52,16 +93,7 @@ impl Object for PipeIn {
 		if pipe.flags & READ_CLOSED != 0 {
 			return 0.into();
 		}
-		while let Some((max_len, w)) = pipe.wake_read.pop_front() {
-			if max_len >= data.len() {
-				w.complete(Ok(data.into()));
-				return (data.len() as u64).into();
-			} else {
-				w.complete(Err(Error::InvalidData)); // TODO better error codes
-			}
-		}
-		pipe.queue.push_back(data.into());
-		if pipe.total_bytes < SOFT_TOTAL_BYTE_LIMIT {
+		if pipe.send(data) || pipe.total_bytes < SOFT_TOTAL_BYTE_LIMIT {
 			(data.len() as u64).into()
 		} else {
 			// Block the caller so it doesn't send a massive amount of data.
@@ -71,6 +103,21 @@ impl Object for PipeIn {
 		}
 	}
 
+	fn write_nonblocking(self: Arc<Self>, data: &[u8]) -> Result<u64, Error> {
+		if data.len() >= PACKET_MAX_SIZE {
+			return Err(Error::InvalidData);
+		}
+		let mut pipe = self.0.lock();
+		if pipe.flags & READ_CLOSED != 0 {
+			return Ok(0);
+		}
+		if pipe.wake_read.is_empty() && pipe.total_bytes >= SOFT_TOTAL_BYTE_LIMIT {
+			return Err(Error::WouldBlock);
+		}
+		pipe.send(data);
+		Ok(data.len() as u64)
+	}
+
 	fn share(&self, object: &Arc<dyn Object>) -> Ticket<u64> {
 		let mut pipe = self.0.lock();
 		if let Some(w) = pipe.wake_open.pop_front() {
@@ -90,25 +137,19 @@ impl Object for PipeIn {
 impl Object for PipeOut {
 	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
 		let mut pipe = self.0.lock();
-		Ticket::new_complete(if let Some(msg) = pipe.queue.pop_front() {
-			if length < msg.len() {
-				pipe.queue.push_front(msg);
-				// TODO we need more / better error codes
-				Err(Error::InvalidData)
-			} else {
-				pipe.wake_write
-					.pop_front()
-					.map(|w| w.complete(Ok(msg.len() as _)));
-				Ok(msg)
-			}
-		} else if pipe.flags & WRITE_CLOSED != 0 {
-			// FIXME an error like Error::Closed (or similar) would be better.
-			Ok([].into())
-		} else {
-			let (t, w) = Ticket::new();
-			pipe.wake_read.push_back((length, w));
-			return t;
-		})
+		if let Some(r) = pipe.try_recv(length) {
+			return Ticket::new_complete(r);
+		}
+		let (t, w) = Ticket::new();
+		pipe.wake_read.push_back((length, w));
+		t
+	}
+
+	fn read_nonblocking(self: Arc<Self>, length: usize) -> Result<Box<[u8]>, Error> {
+		self.0
+			.lock()
+			.try_recv(length)
+			.unwrap_or(Err(Error::WouldBlock))
 	}
 
 	fn open(self: Arc<Self>, path: &[u8]) -> Ticket<Arc<dyn Object>> {
@@ -1,6 +1,7 @@
 use {
 	super::{Error, MemoryObject, Ticket, TinySlice},
 	alloc::{boxed::Box, sync::Arc},
+	norostb_kernel::io::CreateFlags,
 };
 
 /// A single object.
@@ -22,6 +23,22 @@ pub trait Object {
 		not_implemented()
 	}
 
+	/// Create a new object with the given path and flags.
+	///
+	/// The default implementation forwards to [`Self::create`] if no flags are set and fails
+	/// with [`Error::InvalidOperation`] otherwise.
+	fn create_with_flags(
+		self: Arc<Self>,
+		path: &[u8],
+		flags: CreateFlags,
+	) -> Ticket<Arc<dyn Object>> {
+		if flags.is_empty() {
+			self.create(path)
+		} else {
+			Ticket::new_complete(Err(Error::InvalidOperation))
+		}
+	}
+
 	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
 		let _ = length;
 		not_implemented()
@@ -31,6 +48,62 @@ pub trait Object {
 		not_implemented()
 	}
 
+	/// Perform multiple reads in order.
+	///
+	/// The response is encoded like that of a `Readv` stream table request, see
+	/// [`nora_stream_table::vectored`]. Objects that don't support vectored reads fail with
+	/// [`Error::InvalidOperation`].
+	fn read_vectored(self: Arc<Self>, amounts: &[u32]) -> Ticket<Box<[u8]>> {
+		let _ = amounts;
+		not_implemented()
+	}
+
+	/// Perform multiple writes in order.
+	///
+	/// The response is encoded like that of a `Writev` stream table request, see
+	/// [`nora_stream_table::vectored`]. Objects that don't support vectored writes fail with
+	/// [`Error::InvalidOperation`].
+	fn write_vectored(self: Arc<Self>, data: &[&[u8]]) -> Ticket<Box<[u8]>> {
+		let _ = data;
+		not_implemented()
+	}
+
+	/// Read data only if it is available immediately.
+	///
+	/// Unlike [`Self::read`] no operation is left pending if no data is ready, in which case
+	/// this fails with [`Error::WouldBlock`]. Objects that can't tell whether a read would block
+	/// fail with [`Error::InvalidOperation`].
+	fn read_nonblocking(self: Arc<Self>, length: usize) -> Result<Box<[u8]>, Error> {
+		let _ = length;
+		Err(Error::InvalidOperation)
+	}
+
+	/// Write data only if it can be accepted immediately.
+	///
+	/// Unlike [`Self::write`] the data is not written if this fails with [`Error::WouldBlock`].
+	/// Objects that can't tell whether a write would block fail with
+	/// [`Error::InvalidOperation`].
+	fn write_nonblocking(self: Arc<Self>, data: &[u8]) -> Result<u64, Error> {
+		let _ = data;
+		Err(Error::InvalidOperation)
+	}
+
+	/// Read data at an offset without using or modifying the seek head.
+	///
+	/// Objects that don't support positioning fail with [`Error::InvalidOperation`].
+	fn read_at(self: Arc<Self>, offset: u64, length: usize) -> Ticket<Box<[u8]>> {
+		let _ = (offset, length);
+		not_implemented()
+	}
+
+	/// Write data at an offset without using or modifying the seek head.
+	///
+	/// Objects that don't support positioning fail with [`Error::InvalidOperation`].
+	fn write_at(self: Arc<Self>, offset: u64, data: &[u8]) -> Ticket<u64> {
+		let _ = (offset, data);
+		not_implemented()
+	}
+
 	fn seek(&self, _from: norostb_kernel::io::SeekFrom) -> Ticket<u64> {
 		not_implemented()
 	}
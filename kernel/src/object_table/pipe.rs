@@ -1,5 +1,5 @@
 use {
-	super::{Object, Ticket, TicketWaker},
+	super::{Error, Object, Ticket, TicketWaker},
 	crate::sync::Mutex,
 	alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec},
 };
@@ -31,51 +31,41 @@ struct PipeIn(Arc<Mutex<PipeInner>>);
 
 struct PipeOut(Arc<Mutex<PipeInner>>);
 
-impl Object for PipeIn {
-	fn write(self: Arc<Self>, data: &[u8]) -> Ticket<u64> {
-		if data.is_empty() {
-			return 0.into();
-		}
-
-		let mut pipe = self.0.lock();
-		if pipe.flags & READ_CLOSED != 0 {
-			return 0.into();
+impl PipeInner {
+	/// Write as much data as possible without blocking.
+	///
+	/// Returns `None` if no data could be written.
+	fn try_write(&mut self, data: &[u8]) -> Option<u64> {
+		if data.is_empty() || self.flags & READ_CLOSED != 0 {
+			return Some(0);
 		}
 
 		let mut offt = 0;
 
-		while let Some((max_len, w)) = pipe.wake_read.pop_front() {
-			let max_len = max_len.min(data.len());
+		while let Some((max_len, w)) = self.wake_read.pop_front() {
+			let max_len = max_len.min(data.len() - offt);
 			w.complete(Ok(data[offt..][..max_len].into()));
 			offt += max_len;
 			if offt == data.len() {
-				return (offt as u64).into();
+				return Some(offt as u64);
 			}
 		}
 		let data = &data[offt..];
 
-		let max_enqueue = data.len().min(MAX_SIZE - pipe.buf.len());
-		pipe.buf.extend(&data[..max_enqueue]);
-		let data = &data[max_enqueue..];
+		let max_enqueue = data.len().min(MAX_SIZE - self.buf.len());
+		self.buf.extend(&data[..max_enqueue]);
 		offt += max_enqueue;
 
-		if offt > 0 {
-			(offt as u64).into()
-		} else {
-			let max_len = data.len().min(MAX_SIZE - pipe.buf.len());
-			let (t, w) = Ticket::new();
-			pipe.wake_write.push_back((data[..max_len].into(), w));
-			t
-		}
+		(offt > 0).then(|| offt as u64)
 	}
-}
 
-impl Object for PipeOut {
-	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
-		let mut pipe = self.0.lock();
-		Ticket::new_complete(if !pipe.buf.is_empty() {
-			let len = length.min(pipe.buf.len());
-			let (a, b) = pipe.buf.as_slices();
+	/// Read data without blocking.
+	///
+	/// Returns `None` if no data is available.
+	fn try_read(&mut self, length: usize) -> Option<Box<[u8]>> {
+		if !self.buf.is_empty() {
+			let len = length.min(self.buf.len());
+			let (a, b) = self.buf.as_slices();
 			let mut ret = Vec::with_capacity(len);
 			if len > a.len() {
 				ret.extend_from_slice(a);
@@ -84,21 +74,52 @@ impl Object for PipeOut {
 				ret.extend_from_slice(&a[..len]);
 			}
 			(0..len).for_each(|_| {
-				pipe.buf.pop_front();
+				self.buf.pop_front();
 			});
-			Ok(ret.into())
-		} else if let Some((b, w)) = pipe.wake_write.pop_front() {
+			Some(ret.into())
+		} else if let Some((b, w)) = self.wake_write.pop_front() {
 			let mut b = b.to_vec();
 			b.resize(length.min(b.len()), 0xfa); // 0xfa so bugs are obvious
 			w.complete(Ok(b.len() as _));
-			Ok(b.into())
-		} else if pipe.flags & WRITE_CLOSED != 0 {
-			Ok([].into())
+			Some(b.into())
+		} else if self.flags & WRITE_CLOSED != 0 {
+			Some([].into())
 		} else {
-			let (t, w) = Ticket::new();
-			pipe.wake_read.push_back((length.min(MAX_SIZE), w));
-			return t;
-		})
+			None
+		}
+	}
+}
+
+impl Object for PipeIn {
+	fn write(self: Arc<Self>, data: &[u8]) -> Ticket<u64> {
+		let mut pipe = self.0.lock();
+		if let Some(n) = pipe.try_write(data) {
+			return n.into();
+		}
+		let max_len = data.len().min(MAX_SIZE - pipe.buf.len());
+		let (t, w) = Ticket::new();
+		pipe.wake_write.push_back((data[..max_len].into(), w));
+		t
+	}
+
+	fn write_nonblocking(self: Arc<Self>, data: &[u8]) -> Result<u64, Error> {
+		self.0.lock().try_write(data).ok_or(Error::WouldBlock)
+	}
+}
+
+impl Object for PipeOut {
+	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
+		let mut pipe = self.0.lock();
+		if let Some(b) = pipe.try_read(length) {
+			return Ticket::new_complete(Ok(b));
+		}
+		let (t, w) = Ticket::new();
+		pipe.wake_read.push_back((length.min(MAX_SIZE), w));
+		t
+	}
+
+	fn read_nonblocking(self: Arc<Self>, length: usize) -> Result<Box<[u8]>, Error> {
+		self.0.lock().try_read(length).ok_or(Error::WouldBlock)
 	}
 }
 
@@ -1,6 +1,9 @@
 use {
 	super::{Error, Object, Ticket},
-	crate::{object_table::QueryIter, sync::Mutex},
+	crate::{
+		object_table::{QueryIter, TinySlice},
+		sync::Mutex,
+	},
 	alloc::{
 		boxed::Box,
 		collections::BTreeMap,
@@ -8,6 +11,7 @@ use {
 		vec::Vec,
 	},
 	core::mem,
+	norostb_kernel::io::CreateFlags,
 };
 
 /// A root object. This object has multiple child objects which can be accessed by a name, e.g.
@@ -24,8 +28,26 @@ use {
 /// 	...
 /// process/
 /// ```
+///
+/// ## Types
+///
+/// Each object can have a type tag, which is set with the `type` property on the handle
+/// returned by `create` before sharing the object. The tag of an object can be retrieved
+/// without opening it by getting the `type/<name>` property of the root.
+///
+/// A tag is 1 to [`TYPE_MAX_LEN`] bytes long and consists of lowercase ASCII letters, digits,
+/// `-` and `_`, e.g. `gpu` or `net`. Untyped objects have an empty tag.
 pub struct Root {
-	objects: Mutex<BTreeMap<Box<[u8]>, Weak<dyn Object>>>,
+	objects: Mutex<BTreeMap<Box<[u8]>, Entry>>,
+}
+
+/// The maximum length of a type tag.
+pub const TYPE_MAX_LEN: usize = 16;
+
+struct Entry {
+	object: Weak<dyn Object>,
+	/// The type tag of the object. Empty if untyped.
+	typ: Box<[u8]>,
 }
 
 impl Root {
@@ -36,7 +58,14 @@ impl Root {
 
 	/// Add a new object to the root.
 	pub fn add(&self, name: impl Into<Box<[u8]>>, object: Weak<dyn Object>) {
-		self.objects.lock().insert(name.into(), object);
+		self.add_typed(name, [].into(), object)
+	}
+
+	/// Add a new object with a type tag to the root.
+	fn add_typed(&self, name: impl Into<Box<[u8]>>, typ: Box<[u8]>, object: Weak<dyn Object>) {
+		self.objects
+			.lock()
+			.insert(name.into(), Entry { object, typ });
 	}
 
 	fn apply<'a, R, F>(&self, path: &'a [u8], f: F) -> Option<R>
@@ -48,8 +77,8 @@ impl Root {
 			.position(|c| *c == b'/')
 			.map_or((path, None), |i| (&path[..i], Some(&path[i + 1..])));
 		let mut objects = self.objects.lock();
-		if let Some(obj) = objects.get(object) {
-			if let Some(obj) = Weak::upgrade(&obj) {
+		if let Some(entry) = objects.get(object) {
+			if let Some(obj) = Weak::upgrade(&entry.object) {
 				let (remove, ret) = f(obj, object, rest);
 				if remove {
 					objects.remove(object).unwrap();
@@ -90,19 +119,32 @@ impl Object for Root {
 	}
 
 	fn create(self: Arc<Self>, path: &[u8]) -> Ticket<Arc<dyn Object>> {
+		self.create_with_flags(path, CreateFlags::empty())
+	}
+
+	/// Entries of the root itself are always created exclusively. Directories are not
+	/// supported.
+	fn create_with_flags(
+		self: Arc<Self>,
+		path: &[u8],
+		flags: CreateFlags,
+	) -> Ticket<Arc<dyn Object>> {
 		Ticket::new_complete(if path.is_empty() {
 			Err(Error::InvalidData)
 		} else if let Some((obj, _, path)) = self.find(path) {
 			match path {
 				None => Err(Error::AlreadyExists),
-				Some(path) => return obj.create(path),
+				Some(path) => return obj.create_with_flags(path, flags),
 			}
 		} else if path.contains(&b'/') {
 			Err(Error::DoesNotExist)
+		} else if flags.contains(CreateFlags::DIRECTORY) {
+			Err(Error::InvalidOperation)
 		} else {
 			Ok(Arc::new(CreateRootEntry {
 				root: self,
 				name: Mutex::new(path.into()),
+				typ: Default::default(),
 			}))
 		})
 	}
@@ -122,11 +164,29 @@ impl Object for Root {
 			}
 		})
 	}
+
+	fn get_meta(self: Arc<Self>, property: &TinySlice<u8>) -> Ticket<Box<[u8]>> {
+		Ticket::new_complete(match property.strip_prefix(b"type/") {
+			Some(name) => {
+				let mut objects = self.objects.lock();
+				match objects.get(name) {
+					Some(e) if e.object.strong_count() > 0 => Ok(e.typ.clone()),
+					Some(_) => {
+						objects.remove(name);
+						Err(Error::DoesNotExist)
+					}
+					None => Err(Error::DoesNotExist),
+				}
+			}
+			None => Err(Error::DoesNotExist),
+		})
+	}
 }
 
 struct CreateRootEntry {
 	root: Arc<Root>,
 	name: Mutex<Box<[u8]>>,
+	typ: Mutex<Box<[u8]>>,
 }
 
 impl Object for CreateRootEntry {
@@ -135,10 +195,37 @@ impl Object for CreateRootEntry {
 		Ticket::new_complete(if name.is_empty() {
 			Err(Error::InvalidData)
 		} else {
-			self.root.add(mem::take(&mut *name), Arc::downgrade(share));
+			let typ = mem::take(&mut *self.typ.lock());
+			self.root
+				.add_typed(mem::take(&mut *name), typ, Arc::downgrade(share));
 			Ok(0)
 		})
 	}
+
+	fn get_meta(self: Arc<Self>, property: &TinySlice<u8>) -> Ticket<Box<[u8]>> {
+		Ticket::new_complete(match &**property {
+			b"type" => Ok(self.typ.lock().clone()),
+			_ => Err(Error::DoesNotExist),
+		})
+	}
+
+	fn set_meta(self: Arc<Self>, property: &TinySlice<u8>, value: &TinySlice<u8>) -> Ticket<u64> {
+		Ticket::new_complete(match &**property {
+			b"type" if is_valid_type(value) => {
+				*self.typ.lock() = (&**value).into();
+				Ok(0)
+			}
+			b"type" => Err(Error::InvalidData),
+			_ => Err(Error::DoesNotExist),
+		})
+	}
+}
+
+fn is_valid_type(typ: &[u8]) -> bool {
+	(1..=TYPE_MAX_LEN).contains(&typ.len())
+		&& typ
+			.iter()
+			.all(|c| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
 }
 
 fn not_found<T>() -> Ticket<T> {
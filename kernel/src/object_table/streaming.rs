@@ -16,12 +16,12 @@ use {
 	},
 	arena::Arena,
 	core::sync::atomic::Ordering,
-	nora_stream_table::{Buffers, ClientQueue, JobId, Request, Slice},
+	nora_stream_table::{positional, vectored, Buffers, ClientQueue, JobId, Request, Slice},
 	norostb_kernel::{io::SeekFrom, object::Pow2Size, syscall::Handle},
 };
 
 pub struct StreamingTable {
-	jobs: Mutex<Arena<AnyTicketWaker, ()>>,
+	jobs: Mutex<Arena<Job, ()>>,
 	/// Objects that are being shared. They can be taken with an `open` operation
 	///
 	/// Is `None` if sharing objects is not supported by the server.
@@ -41,6 +41,22 @@ pub struct StreamingTable {
 	share_out: Mutex<Arena<Arc<dyn Object>, ()>>,
 }
 
+/// A request that is waiting for a response.
+struct Job {
+	waker: AnyTicketWaker,
+	/// Set for `Read` requests, which may be answered in multiple chunks.
+	read: Option<ChunkedRead>,
+}
+
+#[derive(Default)]
+struct ChunkedRead {
+	handle: Handle,
+	/// The amount of bytes that has not been received yet.
+	remaining: u32,
+	/// The chunks received so far.
+	data: Vec<u8>,
+}
+
 pub enum NewStreamingTableError {
 	Alloc(AllocateError),
 	Map(MapError),
@@ -85,6 +101,20 @@ impl StreamingTable {
 	}
 
 	fn submit_job<T, F>(&self, handle: Handle, f: F) -> Ticket<T>
+	where
+		F: FnOnce(&mut ClientQueue) -> Request,
+		AnyTicketWaker: From<TicketWaker<T>>,
+	{
+		self.submit_job_with(handle, None, f)
+	}
+
+	/// Submit a `Read` request whose response may be split into multiple chunks.
+	fn submit_read(&self, handle: Handle, amount: u32) -> Ticket<Box<[u8]>> {
+		let read = ChunkedRead { handle, remaining: amount, data: Vec::new() };
+		self.submit_job_with(handle, Some(read), |_| Request::Read { amount })
+	}
+
+	fn submit_job_with<T, F>(&self, handle: Handle, read: Option<ChunkedRead>, f: F) -> Ticket<T>
 	where
 		F: FnOnce(&mut ClientQueue) -> Request,
 		AnyTicketWaker: From<TicketWaker<T>>,
@@ -96,7 +126,7 @@ impl StreamingTable {
 			let job_id = JobId::new(h.into_raw().0.try_into().unwrap());
 			q.try_enqueue(handle, job_id, r)
 				.unwrap_or_else(|e| todo!("{:?}", e));
-			ticket_waker.into()
+			Job { waker: ticket_waker.into(), read }
 		});
 		self.notify_singleton.wake_readers();
 		ticket.into()
@@ -135,11 +165,12 @@ impl StreamingTable {
 	fn process_responses(self: &Arc<Self>) {
 		let mut q = self.queue.lock();
 		let mut j = self.jobs.lock();
+		let mut resubmitted = false;
 		while let Some((job_id, resp)) = q.dequeue() {
 			let job_id = arena::Handle::from_raw(job_id.get() as _, ());
-			let job = j.remove(job_id).unwrap_or_else(|| todo!("invalid job id"));
+			let Job { waker, read } = j.remove(job_id).unwrap_or_else(|| todo!("invalid job id"));
 			match resp.get() {
-				Ok(v) => match job {
+				Ok(v) => match waker {
 					AnyTicketWaker::Object(w) => w.complete(Ok(if v & 1 << 32 != 0 {
 						self.share_out
 							.lock()
@@ -149,18 +180,45 @@ impl StreamingTable {
 						Arc::new(StreamObject { table: Arc::downgrade(self), handle: v as _ })
 					})),
 					AnyTicketWaker::Data(w) => {
-						let s = resp.as_slice().unwrap();
-						let buf = self.buffer_mem.get(s);
-						let mut b = Box::new_uninit_slice(buf.len());
-						buf.copy_to_uninit(0, &mut b);
+						let Some((buf, more)) = resp
+							.as_chunk()
+							.ok()
+							.and_then(|(s, more)| Some((self.buffer_mem.try_get(s)?, more)))
+						else {
+							w.complete(Err(Error::InvalidData));
+							continue;
+						};
+						let mut read = read.unwrap_or_default();
+						let start = read.data.len();
+						read.data.reserve(buf.len());
+						buf.copy_to_uninit(0, &mut read.data.spare_capacity_mut()[..buf.len()]);
+						unsafe { read.data.set_len(start + buf.len()) };
+						let received = buf.len() as u32;
 						buf.manual_drop(q.buffer_head_ref());
-						w.complete(Ok(unsafe { Box::<[_]>::assume_init(b) }))
+						if more && received < read.remaining {
+							// Ask for the next chunk and only complete once all have arrived.
+							read.remaining -= received;
+							let (handle, amount) = (read.handle, read.remaining);
+							j.insert_with(|h| {
+								let job_id = JobId::new(h.into_raw().0.try_into().unwrap());
+								q.try_enqueue(handle, job_id, Request::Read { amount })
+									.unwrap_or_else(|e| todo!("{:?}", e));
+								Job { waker: w.into(), read: Some(read) }
+							});
+							resubmitted = true;
+						} else {
+							w.complete(Ok(read.data.into()))
+						}
 					}
 					AnyTicketWaker::U64(w) => w.complete(Ok(v)),
 				},
-				Err(e) => job.complete_err(Error::from(e)),
+				Err(e) => waker.complete_err(Error::from(e)),
 			}
 		}
+		drop((q, j));
+		if resubmitted {
+			self.notify_singleton.wake_readers();
+		}
 	}
 
 	fn requests_enqueued(&self) -> u32 {
@@ -215,11 +273,11 @@ impl Drop for StreamingTable {
 	fn drop(&mut self) {
 		// Wake any waiting tasks so they don't get stuck endlessly.
 		let intr = crate::arch::interrupts_enabled();
-		for (_, task) in self.jobs.get_mut().drain() {
+		for (_, Job { waker, .. }) in self.jobs.get_mut().drain() {
 			if intr {
-				task.complete_err(Error::Cancelled)
+				waker.complete_err(Error::Cancelled)
 			} else {
-				task.isr_complete_err(Error::Cancelled)
+				waker.isr_complete_err(Error::Cancelled)
 			}
 		}
 	}
@@ -250,6 +308,8 @@ impl Object for StreamObject {
 		})
 	}
 
+	// The stream table protocol can't pass create flags, so requests with flags are rejected
+	// by the default create_with_flags.
 	fn create(self: Arc<Self>, path: &[u8]) -> Ticket<Arc<dyn Object>> {
 		self.with_table(|tbl| {
 			tbl.submit_job(self.handle, |q| Request::Create {
@@ -268,7 +328,7 @@ impl Object for StreamObject {
 
 	fn read(self: Arc<Self>, length: usize) -> Ticket<Box<[u8]>> {
 		let amount = length.try_into().unwrap_or(u32::MAX);
-		self.with_table(|tbl| tbl.submit_job(self.handle, |_| Request::Read { amount }))
+		self.with_table(|tbl| tbl.submit_read(self.handle, amount))
 	}
 
 	fn write(self: Arc<Self>, data: &[u8]) -> Ticket<u64> {
@@ -279,6 +339,63 @@ impl Object for StreamObject {
 		})
 	}
 
+	fn read_at(self: Arc<Self>, offset: u64, length: usize) -> Ticket<Box<[u8]>> {
+		let amount = length.try_into().unwrap_or(u32::MAX);
+		self.with_table(|tbl| {
+			tbl.submit_job(self.handle, |q| Request::ReadAt {
+				args: tbl.copy_data_from(q, &positional::encode_read_at(offset, amount)),
+			})
+		})
+	}
+
+	fn write_at(self: Arc<Self>, offset: u64, data: &[u8]) -> Ticket<u64> {
+		self.with_table(|tbl| {
+			tbl.submit_job(self.handle, |q| Request::WriteAt {
+				data: tbl.copy_data_from_scatter(q, &[&offset.to_le_bytes(), data]),
+			})
+		})
+	}
+
+	fn read_vectored(self: Arc<Self>, amounts: &[u32]) -> Ticket<Box<[u8]>> {
+		if amounts.len() > vectored::MAX_OPS {
+			return Error::InvalidData.into();
+		}
+		let mut ops = [0; vectored::MAX_OPS * 4];
+		for (o, a) in ops.chunks_exact_mut(4).zip(amounts) {
+			o.copy_from_slice(&a.to_le_bytes());
+		}
+		self.with_table(|tbl| {
+			tbl.submit_job(self.handle, |q| Request::Readv {
+				ops: tbl.copy_data_from(q, &ops[..amounts.len() * 4]),
+			})
+		})
+	}
+
+	fn write_vectored(self: Arc<Self>, data: &[&[u8]]) -> Ticket<Box<[u8]>> {
+		if data.len() > vectored::MAX_OPS {
+			return Error::InvalidData.into();
+		}
+		self.with_table(|tbl| {
+			// Only send as much as fits in a single request. Operations that are cut off
+			// are reported as short writes.
+			let mut max = tbl.max_request_mem as usize + 1;
+			let mut lens = [[0; 4]; vectored::MAX_OPS];
+			let mut ops = Vec::with_capacity(data.len() * 2);
+			for (d, l) in data.iter().zip(&mut lens) {
+				if max < 4 {
+					break;
+				}
+				let d = &d[..d.len().min(max - 4)];
+				*l = (d.len() as u32).to_le_bytes();
+				ops.extend([&l[..], d]);
+				max -= 4 + d.len();
+			}
+			tbl.submit_job(self.handle, |q| Request::Writev {
+				ops: tbl.copy_data_from_scatter(q, &ops),
+			})
+		})
+	}
+
 	fn get_meta(self: Arc<Self>, property: &TinySlice<u8>) -> Ticket<Box<[u8]>> {
 		self.with_table(|tbl| {
 			tbl.submit_job(self.handle, |q| Request::GetMeta {
@@ -16,7 +16,7 @@ use {
 		ptr::{self, NonNull},
 		task::Poll,
 	},
-	norostb_kernel::io::{self as k_io, Request, Response, SeekFrom},
+	norostb_kernel::io::{self as k_io, CreateFlags, Request, Response, SeekFrom},
 };
 
 pub enum CreateQueueError {
@@ -138,6 +138,22 @@ impl super::Process {
 		let mut queue = k_io_queue;
 
 		while let Ok(e) = unsafe { queue.dequeue_request() } {
+			if e.ty == Request::CANCEL {
+				// We are not supposed to return a response for the cancel request itself.
+				let all = e.arguments_8[0] != 0;
+				for i in (0..tickets.len()).rev() {
+					if all || tickets[i].user_data == e.arguments_64[0] {
+						let tk = tickets.swap_remove(i);
+						let _ = unsafe {
+							queue.enqueue_response(Response {
+								user_data: tk.user_data,
+								value: Error::Cancelled as i64,
+							})
+						};
+					}
+				}
+				continue;
+			}
 			let mut push_resp = |value| {
 				// It is the responsibility of the user process to ensure no more requests are in
 				// flight than there is space for responses.
@@ -197,7 +213,11 @@ impl super::Process {
 					let path_ptr = e.arguments_64[0] as *const u8;
 					let path_len = e.arguments_64[1] as usize;
 					let path = unsafe { core::slice::from_raw_parts(path_ptr, path_len) };
-					let mut ticket = object.clone().create(path);
+					let Some(flags) = CreateFlags::from_bits(e.arguments_8[0]) else {
+						push_resp(Error::InvalidData as i64);
+						continue;
+					};
+					let mut ticket = object.clone().create_with_flags(path, flags);
 					match poll(&mut ticket) {
 						Poll::Pending => push_pending(ptr::null_mut(), 0, ticket.into()),
 						Poll::Ready(Ok(o)) => {
@@ -252,8 +272,30 @@ impl super::Process {
 						Poll::Ready(Err(e)) => push_resp(e as i64),
 					}
 				}
-				Request::SET_META => todo!(),
-				Request::DESTROY => todo!(),
+				Request::SET_META => {
+					let [prop_len, val_len, _] = e.arguments_8;
+					let [prop_ptr, val_ptr] = e.arguments_64;
+					let prop =
+						unsafe { TinySlice::from_raw_parts(prop_ptr as *const u8, prop_len) };
+					let val = unsafe { TinySlice::from_raw_parts(val_ptr as *const u8, val_len) };
+					let mut ticket = object.clone().set_meta(prop, val);
+					match poll(&mut ticket) {
+						Poll::Pending => push_pending(ptr::null_mut(), 0, ticket.into()),
+						Poll::Ready(Ok(n)) => push_resp(n as i64),
+						Poll::Ready(Err(e)) => push_resp(e as i64),
+					}
+				}
+				Request::DESTROY => {
+					let path_ptr = e.arguments_64[0] as *const u8;
+					let path_len = e.arguments_64[1] as usize;
+					let path = unsafe { core::slice::from_raw_parts(path_ptr, path_len) };
+					let mut ticket = object.destroy(path);
+					match poll(&mut ticket) {
+						Poll::Pending => push_pending(ptr::null_mut(), 0, ticket.into()),
+						Poll::Ready(Ok(n)) => push_resp(n as i64),
+						Poll::Ready(Err(e)) => push_resp(e as i64),
+					}
+				}
 				op => {
 					warn!("Unknown I/O queue operation {}", op);
 					push_resp(Error::InvalidOperation as i64);
@@ -16,7 +16,12 @@ use {
 	},
 	alloc::{boxed::Box, sync::Arc},
 	core::{mem, num::NonZeroUsize, ptr::NonNull},
-	norostb_kernel::{error::Error, io::Request, object::NewObject},
+	nora_stream_table::vectored,
+	norostb_kernel::{
+		error::Error,
+		io::{CreateFlags, DoIo, Request, MAX_IO_VECS},
+		object::NewObject,
+	},
 };
 
 #[derive(Clone, Copy)]
@@ -122,7 +127,7 @@ extern "C" fn dealloc(base: usize, size: usize, _: usize, _: usize, _: usize, _:
 			value: 0,
 		};
 	};
-	let Some(count) = NonZeroUsize::new(size / Page::MASK) else {
+	let Some(count) = NonZeroUsize::new(size / Page::SIZE) else {
 		return Return {
 			status: 0,
 			value: 0,
@@ -141,23 +146,95 @@ extern "C" fn dealloc(base: usize, size: usize, _: usize, _: usize, _: usize, _:
 extern "C" fn do_io(ty: usize, handle: usize, a: usize, b: usize, c: usize, _: usize) -> Return {
 	use super::block_on;
 	debug!(syscall "do_io {} {:?} {:#x} {:#x} {:#x}", ty, handle, a, b, c);
-	let Ok(ty) = ty.try_into() else { return Return::INVALID_OPERATION };
+	let Ok(ty) = u8::try_from(ty) else { return Return::INVALID_OPERATION };
+	let nonblocking = ty & DoIo::NONBLOCKING != 0;
+	let ty = ty & !DoIo::NONBLOCKING;
+	if nonblocking && ty != Request::READ && ty != Request::WRITE {
+		return Return::INVALID_OPERATION;
+	}
 	let process = Process::current().unwrap();
 	let Some(o) = process.get_object(handle as _) else { return Return::INVALID_OBJECT };
 	let return_u64 = |r| Return { status: 0, value: r as _ };
 	match ty {
-		Request::READ => block_on(o.read(b)).map_or_else(Return::error, |r| {
+		Request::READ => {
+			let r = if nonblocking {
+				o.read_nonblocking(b)
+			} else {
+				block_on(o.read(b))
+			};
+			r.map_or_else(Return::error, |r| {
+				assert!(r.len() <= b, "object returned too much data");
+				unsafe { (a as *mut u8).copy_from_nonoverlapping(r.as_ptr(), r.len()) }
+				Return { status: 0, value: r.len() }
+			})
+		}
+		Request::WRITE => {
+			let r = unsafe { core::slice::from_raw_parts(a as *const u8, b) };
+			let r = if nonblocking {
+				o.write_nonblocking(r)
+			} else {
+				block_on(o.write(r))
+			};
+			r.map_or_else(Return::error, |r| Return {
+				status: 0,
+				value: r.try_into().unwrap(),
+			})
+		}
+		Request::READ_AT => block_on(o.read_at(c as _, b)).map_or_else(Return::error, |r| {
 			assert!(r.len() <= b, "object returned too much data");
 			unsafe { (a as *mut u8).copy_from_nonoverlapping(r.as_ptr(), r.len()) }
 			Return { status: 0, value: r.len() }
 		}),
-		Request::WRITE => {
+		Request::WRITE_AT => {
 			let r = unsafe { core::slice::from_raw_parts(a as *const u8, b) };
-			block_on(o.write(r)).map_or_else(Return::error, |r| Return {
+			block_on(o.write_at(c as _, r)).map_or_else(Return::error, |r| Return {
 				status: 0,
 				value: r.try_into().unwrap(),
 			})
 		}
+		Request::READ_VECTORED | Request::WRITE_VECTORED => {
+			if b > MAX_IO_VECS {
+				return Return::error(Error::InvalidData);
+			}
+			// Each buffer is a pointer followed by a length.
+			let bufs = unsafe { core::slice::from_raw_parts(a as *const [usize; 2], b) };
+			let mut lens = [0; MAX_IO_VECS];
+			for (l, &[_, len]) in lens.iter_mut().zip(bufs) {
+				*l = len.try_into().unwrap_or(u32::MAX);
+			}
+			let lens = &lens[..b];
+			if ty == Request::READ_VECTORED {
+				block_on(o.read_vectored(lens)).map_or_else(Return::error, |r| {
+					match vectored::decode_read_response(&r, lens) {
+						Ok(Ok(mut data)) => {
+							let total = data.len();
+							for &[ptr, len] in bufs {
+								let (d, rest) = data.split_at(len.min(data.len()));
+								unsafe {
+									(ptr as *mut u8).copy_from_nonoverlapping(d.as_ptr(), d.len())
+								}
+								data = rest;
+							}
+							Return { status: 0, value: total }
+						}
+						Ok(Err(e)) => Return::error(e.into()),
+						Err(_) => Return::error(Error::InvalidData),
+					}
+				})
+			} else {
+				let mut data = [&[][..]; MAX_IO_VECS];
+				for (d, &[ptr, len]) in data.iter_mut().zip(bufs) {
+					*d = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+				}
+				block_on(o.write_vectored(&data[..b])).map_or_else(Return::error, |r| {
+					match vectored::decode_write_response(&r, lens) {
+						Ok(Ok(n)) => return_u64(n),
+						Ok(Err(e)) => Return::error(e.into()),
+						Err(_) => Return::error(Error::InvalidData),
+					}
+				})
+			}
+		}
 		Request::GET_META => {
 			let (prop_len, value_len) = (c as u8, (c >> 8) as u8);
 			let prop = unsafe { TinySlice::from_raw_parts(a as *const u8, prop_len) };
@@ -184,7 +261,10 @@ extern "C" fn do_io(ty: usize, handle: usize, a: usize, b: usize, c: usize, _: u
 			block_on(if ty == Request::OPEN {
 				o.open(r)
 			} else {
-				o.create(r)
+				let Some(flags) = u8::try_from(c).ok().and_then(CreateFlags::from_bits) else {
+					return Return::error(Error::InvalidData);
+				};
+				o.create_with_flags(r, flags)
 			})
 			.map_or_else(Return::error, |o| {
 				Return::handle(process.add_object(o).unwrap())
@@ -336,6 +416,7 @@ extern "C" fn map_object(
 					| MapError::Permission
 					| MapError::UnalignedOffset
 					| MapError::Reserved => Error::InvalidData,
+					MapError::Overlap => Error::AlreadyExists,
 					MapError::Arch(e) => todo!("{:?}", e),
 				}) as _,
 				value: 0,
@@ -16,6 +16,10 @@ use {
 };
 
 /// A typed arena. A generation type can be specified which is used to prevent the ABA problem.
+///
+/// Cloning an arena copies its free list and generation too, so handles of the original arena
+/// refer to the same entries in the clone and future insertions return the same handles.
+#[derive(Clone)]
 pub struct Arena<V, G: Generation> {
 	storage: vec::Vec<Entry<V, G>>,
 	free: usize,
@@ -33,6 +37,7 @@ where
 	}
 }
 
+#[derive(Clone)]
 enum Entry<V, G: Generation> {
 	Free { next: usize },
 	Occupied { value: V, generation: G },
@@ -69,6 +74,49 @@ impl<G: Generation> Handle<G> {
 	}
 }
 
+impl<G: PackedGeneration> Handle<G> {
+	/// The amount of bits available for the index in a packed handle.
+	const INDEX_BITS: u32 = {
+		assert!(
+			G::BITS <= 32,
+			"generation does not fit in the upper 32 bits"
+		);
+		64 - G::BITS
+	};
+
+	/// Pack the index and generation into a single `u64`.
+	///
+	/// The generation is stored in the upper [`G::BITS`](PackedGeneration::BITS) bits, the
+	/// index in the remaining lower bits. The layout is stable, i.e. the value can be passed
+	/// to other programs and turned back into a handle with [`Self::from_u64`].
+	///
+	/// # Panics
+	///
+	/// If the index does not fit in the lower bits.
+	pub fn to_u64(self) -> u64 {
+		let index = u64::try_from(self.index).expect("index out of range");
+		if Self::INDEX_BITS < 64 {
+			assert!(index >> Self::INDEX_BITS == 0, "index out of range");
+			index | self.generation.to_bits() << Self::INDEX_BITS
+		} else {
+			index
+		}
+	}
+
+	/// Unpack a handle packed with [`Self::to_u64`].
+	///
+	/// Returns `None` if the index does not fit in a `usize`.
+	pub fn from_u64(value: u64) -> Option<Self> {
+		let (index, generation) = if Self::INDEX_BITS < 64 {
+			let index = value & ((1 << Self::INDEX_BITS) - 1);
+			(index, G::from_bits(value >> Self::INDEX_BITS))
+		} else {
+			(value, G::from_bits(0))
+		};
+		Some(Self { index: index.try_into().ok()?, generation })
+	}
+}
+
 pub trait Generation: Copy + Eq {
 	fn increment(&mut self);
 }
@@ -77,6 +125,28 @@ impl Generation for () {
 	fn increment(&mut self) {}
 }
 
+/// A generation that can be packed in a `u64` together with an index.
+pub trait PackedGeneration: Generation {
+	/// The amount of bits used by the generation. This may not exceed 32.
+	const BITS: u32;
+
+	/// Convert the generation to bits. Only the lower [`Self::BITS`] bits may be set.
+	fn to_bits(self) -> u64;
+
+	/// Convert the lower [`Self::BITS`] bits back to a generation.
+	fn from_bits(bits: u64) -> Self;
+}
+
+impl PackedGeneration for () {
+	const BITS: u32 = 0;
+
+	fn to_bits(self) -> u64 {
+		0
+	}
+
+	fn from_bits(_: u64) -> Self {}
+}
+
 macro_rules! impl_int {
 	($ty:ty) => {
 		impl Generation for $ty {
@@ -98,6 +168,26 @@ impl_int!(i32);
 impl_int!(i64);
 impl_int!(i128);
 
+macro_rules! impl_packed {
+	($ty:ty) => {
+		impl PackedGeneration for $ty {
+			const BITS: u32 = <$ty>::BITS;
+
+			fn to_bits(self) -> u64 {
+				self.into()
+			}
+
+			fn from_bits(bits: u64) -> Self {
+				bits as _
+			}
+		}
+	};
+}
+
+impl_packed!(u8);
+impl_packed!(u16);
+impl_packed!(u32);
+
 impl<V, G: Generation + Default> Arena<V, G> {
 	pub const fn new() -> Self {
 		Default::default()
@@ -130,6 +220,17 @@ impl<V, G: Generation> Arena<V, G> {
 		}
 	}
 
+	/// Insert a value only if there are less than `max` values in the arena.
+	///
+	/// The value is returned if the arena is full.
+	pub fn try_insert_bounded(&mut self, max: usize, value: V) -> Result<Handle<G>, V> {
+		if self.count < max {
+			Ok(self.insert(value))
+		} else {
+			Err(value)
+		}
+	}
+
 	pub fn remove(&mut self, handle: Handle<G>) -> Option<V> {
 		match self.storage.get(handle.index)? {
 			Entry::Free { .. } => None,
@@ -253,3 +354,75 @@ macro_rules! iter {
 iter!(Iter, slice, &'a V);
 iter!(IterMut, slice, &'a mut V);
 iter!(Drain, vec, V);
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn try_insert_bounded() {
+		let mut a = Arena::<u32, u8>::new();
+		let h0 = a.try_insert_bounded(2, 0).unwrap();
+		let h1 = a.try_insert_bounded(2, 1).unwrap();
+		assert_eq!(a.try_insert_bounded(2, 2).unwrap_err(), 2);
+		assert_eq!(a.len(), 2);
+		assert_eq!(a.remove(h0), Some(0));
+		let h2 = a.try_insert_bounded(2, 2).unwrap();
+		assert_eq!(a[h1], 1);
+		assert_eq!(a[h2], 2);
+		assert_eq!(a.try_insert_bounded(2, 3).unwrap_err(), 3);
+	}
+
+	#[test]
+	fn pack_handle() {
+		let mut a = Arena::<u32, u16>::new();
+		let h0 = a.insert(0);
+		let h1 = a.insert(1);
+		let p1 = h1.to_u64();
+		assert_eq!(p1, 1 | 1 << 48);
+		let h = Handle::<u16>::from_u64(p1).unwrap();
+		assert_eq!(h.into_raw(), (1, 1));
+		assert_eq!(a[h], 1);
+		a.remove(h0);
+		let p0 = h0.to_u64();
+		let h2 = a.insert(2);
+		assert_eq!(h2.into_raw().0, h0.into_raw().0);
+		assert_ne!(h2.to_u64(), p0);
+		assert_eq!(a.get(Handle::from_u64(p0).unwrap()), None);
+		assert_eq!(a[Handle::from_u64(h2.to_u64()).unwrap()], 2);
+	}
+
+	#[test]
+	fn clone_preserves_handles() {
+		let mut a = Arena::<u32, u8>::new();
+		let h0 = a.insert(0);
+		let h1 = a.insert(1);
+		let h2 = a.insert(2);
+		a.remove(h1);
+		a.remove(h0);
+		let h3 = a.insert(3);
+
+		let mut b = a.clone();
+		assert_eq!(b.len(), a.len());
+		for h in [h0, h1, h2, h3] {
+			assert_eq!(b.get(h), a.get(h));
+		}
+		assert_eq!(b.get(h0), None);
+		assert_eq!(b.get(h1), None);
+		assert_eq!(b[h2], 2);
+		assert_eq!(b[h3], 3);
+
+		// The free list and generation must match too.
+		let (ha, hb) = (a.insert(4), b.insert(4));
+		assert_eq!(ha.into_raw(), hb.into_raw());
+		assert_eq!(b.remove(h2), Some(2));
+		assert_eq!(a[h2], 2);
+	}
+
+	#[test]
+	fn pack_handle_no_generation() {
+		let h = Handle::<()>::from_raw(42, ());
+		assert_eq!(h.to_u64(), 42);
+		assert_eq!(Handle::<()>::from_u64(42).unwrap().into_raw(), (42, ()));
+	}
+}
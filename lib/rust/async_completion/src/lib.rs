@@ -10,8 +10,17 @@
 extern crate alloc;
 
 #[cfg(feature = "alloc")]
-use alloc::{boxed::Box, rc::Rc, sync::Arc, vec::Vec};
+mod pool;
+
+#[cfg(feature = "alloc")]
+pub use pool::{BufferPool, PoolBuf};
+
 use core::ops::{Bound, Range, RangeBounds};
+#[cfg(feature = "alloc")]
+use {
+	alloc::{boxed::Box, rc::Rc, string::String, sync::Arc, vec::Vec},
+	core::mem::MaybeUninit,
+};
 
 pub unsafe trait Buf: Unpin + 'static {
 	fn as_ptr(&self) -> *const u8;
@@ -56,6 +65,35 @@ pub unsafe trait BufMut: Buf {
 	fn as_mut_ptr(&mut self) -> *mut u8;
 
 	unsafe fn set_bytes_init(&mut self, n: usize);
+
+	/// Zero out all uninitialized bytes, i.e. `bytes_init()..bytes_total()`.
+	///
+	/// `bytes_init` is left unchanged.
+	///
+	/// This writes to every byte in the range, which is not free for large buffers.
+	fn zero_remaining(&mut self) {
+		let init = self.bytes_init();
+		let total = self.bytes_total();
+		// SAFETY: the range is inside the buffer.
+		unsafe { self.as_mut_ptr().add(init).write_bytes(0, total - init) }
+	}
+
+	/// Zero out the entire buffer and mark all bytes as initialized.
+	///
+	/// This writes to every byte in the buffer, which is not free for large buffers.
+	///
+	/// # Safety
+	///
+	/// If this buffer is a view into a larger buffer, e.g. [`Slice`], all memory in the larger
+	/// buffer before this view must already be initialized.
+	unsafe fn init_fully(&mut self) {
+		let total = self.bytes_total();
+		// SAFETY: the range is inside the buffer.
+		unsafe {
+			self.as_mut_ptr().write_bytes(0, total);
+			self.set_bytes_init(total);
+		}
+	}
 }
 
 pub struct Slice<B: Buf> {
@@ -124,6 +162,53 @@ unsafe impl BufMut for Vec<u8> {
 	}
 }
 
+/// The memory of a [`bytes::Bytes`] is reference-counted and never moves, so the pointer stays
+/// valid for as long as the operation owns it.
+#[cfg(feature = "bytes")]
+unsafe impl Buf for bytes::Bytes {
+	fn as_ptr(&self) -> *const u8 {
+		(**self).as_ptr()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.len()
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.len()
+	}
+}
+
+/// The buffer is only reallocated when reserving more capacity, which can't happen while the
+/// operation owns it.
+#[cfg(feature = "bytes")]
+unsafe impl Buf for bytes::BytesMut {
+	fn as_ptr(&self) -> *const u8 {
+		(**self).as_ptr()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.len()
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.capacity()
+	}
+}
+
+/// Setting the amount of initialized bytes is equivalent to `bytes::BufMut::advance_mut` with
+/// the difference between the new and old length.
+#[cfg(feature = "bytes")]
+unsafe impl BufMut for bytes::BytesMut {
+	fn as_mut_ptr(&mut self) -> *mut u8 {
+		(**self).as_mut_ptr()
+	}
+
+	unsafe fn set_bytes_init(&mut self, n: usize) {
+		unsafe { self.set_len(n) }
+	}
+}
+
 macro_rules! owned_slice {
 	($ty:ident) => {
 		#[cfg(feature = "alloc")]
@@ -147,6 +232,87 @@ owned_slice!(Box);
 owned_slice!(Rc);
 owned_slice!(Arc);
 
+/// A boxed slice without any initialized bytes.
+///
+/// Use [`UninitBox`] to read into it.
+#[cfg(feature = "alloc")]
+unsafe impl Buf for Box<[MaybeUninit<u8>]> {
+	fn as_ptr(&self) -> *const u8 {
+		(**self).as_ptr().cast()
+	}
+
+	fn bytes_init(&self) -> usize {
+		0
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.len()
+	}
+}
+
+/// A boxed slice that tracks how many bytes are initialized.
+///
+/// This allows reading into a freshly allocated buffer without zeroing it first.
+#[cfg(feature = "alloc")]
+pub struct UninitBox {
+	buf: Box<[MaybeUninit<u8>]>,
+	init: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl UninitBox {
+	/// Allocate a new buffer of the given size without initializing it.
+	pub fn new(len: usize) -> Self {
+		Self::from_box(
+			core::iter::repeat_with(MaybeUninit::uninit)
+				.take(len)
+				.collect(),
+		)
+	}
+
+	/// Wrap a boxed slice. No bytes are considered to be initialized.
+	pub fn from_box(buf: Box<[MaybeUninit<u8>]>) -> Self {
+		Self { buf, init: 0 }
+	}
+
+	/// The initialized part of the buffer.
+	pub fn as_init(&self) -> &[u8] {
+		// SAFETY: the first init bytes are initialized.
+		unsafe { core::slice::from_raw_parts(self.buf.as_ptr().cast(), self.init) }
+	}
+
+	/// Return the underlying buffer and the amount of initialized bytes.
+	pub fn into_inner(self) -> (Box<[MaybeUninit<u8>]>, usize) {
+		(self.buf, self.init)
+	}
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl Buf for UninitBox {
+	fn as_ptr(&self) -> *const u8 {
+		self.buf.as_ptr().cast()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.init
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.buf.len()
+	}
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl BufMut for UninitBox {
+	fn as_mut_ptr(&mut self) -> *mut u8 {
+		self.buf.as_mut_ptr().cast()
+	}
+
+	unsafe fn set_bytes_init(&mut self, n: usize) {
+		self.init = n;
+	}
+}
+
 unsafe impl Buf for &'static [u8] {
 	fn as_ptr(&self) -> *const u8 {
 		(*self).as_ptr()
@@ -189,6 +355,83 @@ unsafe impl Buf for &'static str {
 	}
 }
 
+/// A string buffer.
+///
+/// Unlike converting a string to bytes this preserves the type, so the string can be recovered
+/// when the operation completes. This is useful for operations that take a path, such as
+/// opening or creating objects.
+#[derive(Clone, Debug)]
+pub struct StrBuf(StrBufInner);
+
+#[derive(Clone, Debug)]
+enum StrBufInner {
+	Static(&'static str),
+	#[cfg(feature = "alloc")]
+	Owned(String),
+}
+
+impl StrBuf {
+	pub fn as_str(&self) -> &str {
+		match &self.0 {
+			StrBufInner::Static(s) => s,
+			#[cfg(feature = "alloc")]
+			StrBufInner::Owned(s) => s,
+		}
+	}
+
+	/// Convert the buffer to an owned string.
+	///
+	/// This only allocates if the buffer holds a `&'static str`.
+	#[cfg(feature = "alloc")]
+	pub fn into_string(self) -> String {
+		match self.0 {
+			StrBufInner::Static(s) => s.into(),
+			StrBufInner::Owned(s) => s,
+		}
+	}
+}
+
+impl AsRef<str> for StrBuf {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl From<&'static str> for StrBuf {
+	fn from(s: &'static str) -> Self {
+		Self(StrBufInner::Static(s))
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl From<String> for StrBuf {
+	fn from(s: String) -> Self {
+		Self(StrBufInner::Owned(s))
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl From<StrBuf> for String {
+	fn from(s: StrBuf) -> Self {
+		s.into_string()
+	}
+}
+
+unsafe impl Buf for StrBuf {
+	fn as_ptr(&self) -> *const u8 {
+		self.as_str().as_ptr()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.as_str().len()
+	}
+
+	// Only the string itself is exposed, not any spare capacity of an owned string.
+	fn bytes_total(&self) -> usize {
+		self.as_str().len()
+	}
+}
+
 unsafe impl Buf for () {
 	fn as_ptr(&self) -> *const u8 {
 		1 as _
@@ -0,0 +1,193 @@
+use {
+	crate::{Buf, BufMut},
+	alloc::{boxed::Box, rc::Rc, vec::Vec},
+	core::{
+		cell::{Cell, RefCell, UnsafeCell},
+		fmt,
+		mem::MaybeUninit,
+		ptr::NonNull,
+	},
+};
+
+/// A fixed set of equally sized buffers that are allocated once and reused.
+///
+/// Buffers are taken from the pool as [`PoolBuf`]s and returned to it when dropped. The memory
+/// of the pool is a single allocation that never moves, so a buffer can be identified by its
+/// index.
+///
+/// The memory is freed once the pool and all buffers taken from it are dropped. Each buffer
+/// keeps the memory alive, so it remains valid for as long as a [`PoolBuf`] is in use by a
+/// request, even if the pool itself is dropped.
+///
+/// This is similar to registered buffers in `io_uring`.
+pub struct BufferPool {
+	inner: Rc<Inner>,
+}
+
+struct Inner {
+	/// Allocated with [`Box`] and freed when the last reference to the pool is dropped.
+	memory: NonNull<[UnsafeCell<MaybeUninit<u8>>]>,
+	buffer_size: usize,
+	/// The amount of initialized bytes in each buffer.
+	init: Box<[Cell<usize>]>,
+	free: RefCell<Vec<usize>>,
+}
+
+impl BufferPool {
+	/// Allocate `count` buffers of `size` bytes each.
+	///
+	/// # Panics
+	///
+	/// If the total size overflows.
+	pub fn new(count: usize, size: usize) -> Self {
+		let total = count.checked_mul(size).expect("pool too large");
+		let memory = core::iter::repeat_with(|| UnsafeCell::new(MaybeUninit::uninit()))
+			.take(total)
+			.collect::<Box<[_]>>();
+		Self {
+			inner: Rc::new(Inner {
+				memory: NonNull::from(Box::leak(memory)),
+				buffer_size: size,
+				init: core::iter::repeat_with(Cell::default).take(count).collect(),
+				// Reverse so buffers are handed out in ascending order.
+				free: (0..count).rev().collect::<Vec<_>>().into(),
+			}),
+		}
+	}
+
+	/// Take a free buffer from the pool.
+	///
+	/// Returns `None` if all buffers are in use.
+	pub fn take(&self) -> Option<PoolBuf> {
+		let index = self.inner.free.borrow_mut().pop()?;
+		Some(PoolBuf { pool: self.inner.clone(), index })
+	}
+
+	/// Take a specific buffer from the pool.
+	///
+	/// Returns `None` if the buffer is in use or doesn't exist.
+	pub fn take_index(&self, index: usize) -> Option<PoolBuf> {
+		let mut free = self.inner.free.borrow_mut();
+		let i = free.iter().position(|&i| i == index)?;
+		free.swap_remove(i);
+		Some(PoolBuf { pool: self.inner.clone(), index })
+	}
+
+	/// The total amount of buffers in the pool.
+	pub fn count(&self) -> usize {
+		self.inner.init.len()
+	}
+
+	/// The amount of buffers that are not in use.
+	pub fn free_count(&self) -> usize {
+		self.inner.free.borrow().len()
+	}
+
+	/// The size of each buffer.
+	pub fn buffer_size(&self) -> usize {
+		self.inner.buffer_size
+	}
+}
+
+impl Inner {
+	fn memory(&self) -> &[UnsafeCell<MaybeUninit<u8>>] {
+		// SAFETY: the memory is valid until Inner is dropped.
+		unsafe { self.memory.as_ref() }
+	}
+}
+
+impl Drop for Inner {
+	fn drop(&mut self) {
+		// SAFETY: the memory was allocated with Box in BufferPool::new and no PoolBuf refers
+		// to it anymore, as each holds a reference to Inner.
+		drop(unsafe { Box::from_raw(self.memory.as_ptr()) });
+	}
+}
+
+impl fmt::Debug for BufferPool {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct(stringify!(BufferPool))
+			.field("count", &self.count())
+			.field("free_count", &self.free_count())
+			.field("buffer_size", &self.buffer_size())
+			.finish()
+	}
+}
+
+/// A buffer taken from a [`BufferPool`].
+///
+/// The buffer is returned to the pool when dropped. The amount of initialized bytes is kept,
+/// so a buffer that is taken again may still contain old data.
+pub struct PoolBuf {
+	pool: Rc<Inner>,
+	index: usize,
+}
+
+impl PoolBuf {
+	/// The index of this buffer in the pool.
+	pub fn index(&self) -> usize {
+		self.index
+	}
+
+	/// The memory of this buffer.
+	///
+	/// The memory stays at the same address and remains valid for as long as this buffer is
+	/// not dropped.
+	pub fn memory(&self) -> &[UnsafeCell<MaybeUninit<u8>>] {
+		let size = self.pool.buffer_size;
+		&self.pool.memory()[self.index * size..][..size]
+	}
+
+	/// The initialized part of the buffer.
+	pub fn as_init(&self) -> &[u8] {
+		// SAFETY: the first bytes_init bytes are initialized.
+		unsafe { core::slice::from_raw_parts(self.as_ptr(), self.bytes_init()) }
+	}
+
+	/// Mark all bytes as uninitialized.
+	pub fn clear(&mut self) {
+		self.pool.init[self.index].set(0);
+	}
+}
+
+impl fmt::Debug for PoolBuf {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct(stringify!(PoolBuf))
+			.field("index", &self.index)
+			.field("bytes_init", &self.bytes_init())
+			.finish_non_exhaustive()
+	}
+}
+
+impl Drop for PoolBuf {
+	fn drop(&mut self) {
+		self.pool.free.borrow_mut().push(self.index);
+	}
+}
+
+// SAFETY: the memory of the pool never moves and each buffer is owned by at most one PoolBuf.
+unsafe impl Buf for PoolBuf {
+	fn as_ptr(&self) -> *const u8 {
+		self.memory().as_ptr().cast()
+	}
+
+	fn bytes_init(&self) -> usize {
+		self.pool.init[self.index].get()
+	}
+
+	fn bytes_total(&self) -> usize {
+		self.pool.buffer_size
+	}
+}
+
+// SAFETY: see Buf
+unsafe impl BufMut for PoolBuf {
+	fn as_mut_ptr(&mut self) -> *mut u8 {
+		// UnsafeCell makes it valid to mutate the memory through a shared reference.
+		self.as_ptr() as *mut _
+	}
+
+	unsafe fn set_bytes_init(&mut self, n: usize) {
+		self.pool.init[self.index].set(n);
+	}
+}
@@ -1,13 +1,18 @@
 use {
 	crate::{
-		io::{Buf, Read},
+		io::{Buf, BufMut, Read},
 		object::file_root,
 		AsyncObject,
 	},
 	alloc::vec::Vec,
+	futures_core::Stream,
+	futures_lite::stream,
 	rt::io,
 };
 
+/// The maximum length of a name returned by [`read_dir`]. Longer names are truncated.
+const MAX_NAME_LEN: usize = 256;
+
 pub struct File(AsyncObject);
 
 impl_wrap!(File read);
@@ -42,3 +47,73 @@ pub async fn read<B: Buf>(path: B) -> (io::Result<Vec<u8>>, B) {
 		}
 	}
 }
+
+/// An entry in a directory, as returned by [`read_dir`].
+pub struct DirEntry {
+	path: Vec<u8>,
+	name_start: usize,
+}
+
+impl DirEntry {
+	/// The name of the entry.
+	pub fn name(&self) -> &[u8] {
+		&self.path[self.name_start..]
+	}
+
+	/// The full path of the entry, including the directory.
+	pub fn path(&self) -> &[u8] {
+		&self.path
+	}
+
+	/// Get a property of the entry.
+	///
+	/// The entry is opened each time this is called.
+	pub async fn get_meta<B, Bm>(&self, property: B, value: Bm) -> (io::Result<u8>, B, Bm)
+	where
+		B: Buf,
+		Bm: BufMut,
+	{
+		match file_root().open(self.path.clone()).await {
+			(Ok(f), _) => f.get_meta(property, value).await,
+			(Err(e), _) => (Err(e), property, value),
+		}
+	}
+}
+
+/// Iterate over the entries of a directory.
+///
+/// The directory is opened and names are read from it one at a time until an empty name is
+/// returned.
+pub fn read_dir(path: &[u8]) -> impl Stream<Item = io::Result<DirEntry>> {
+	enum State {
+		Open(Vec<u8>),
+		Read(AsyncObject, Vec<u8>),
+		Done,
+	}
+
+	stream::unfold(State::Open(path.into()), |state| async move {
+		let (dir, path) = match state {
+			State::Open(path) => match file_root().open(path).await {
+				(Ok(dir), path) => (dir, path),
+				(Err(e), _) => return Some((Err(e), State::Done)),
+			},
+			State::Read(dir, path) => (dir, path),
+			State::Done => return None,
+		};
+		match dir.read(Vec::with_capacity(MAX_NAME_LEN)).await {
+			(Ok(0), _) => None,
+			(Ok(_), name) => {
+				let mut p = Vec::with_capacity(path.len() + 1 + name.len());
+				p.extend_from_slice(&path);
+				if !p.is_empty() && !p.ends_with(b"/") {
+					p.push(b'/');
+				}
+				let name_start = p.len();
+				p.extend_from_slice(&name);
+				let e = DirEntry { path: p, name_start };
+				Some((Ok(e), State::Read(dir, path)))
+			}
+			(Err(e), _) => Some((Err(e), State::Done)),
+		}
+	})
+}
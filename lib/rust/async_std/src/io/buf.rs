@@ -0,0 +1,184 @@
+use {
+	super::{Read, Result, Write},
+	alloc::{string::String, vec::Vec},
+	core::mem,
+};
+
+/// The default size of the buffer used by [`BufReader`] and [`BufWriter`].
+pub const DEFAULT_BUF_SIZE: usize = 4096;
+
+/// Adds buffering to a reader.
+///
+/// Small reads are served from an internal buffer, which is refilled with a single large read
+/// when it runs empty.
+pub struct BufReader<R> {
+	inner: R,
+	buf: Vec<u8>,
+	pos: usize,
+}
+
+impl<R: Read<Vec<u8>>> BufReader<R> {
+	pub fn new(inner: R) -> Self {
+		Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+	}
+
+	pub fn with_capacity(capacity: usize, inner: R) -> Self {
+		Self { inner, buf: Vec::with_capacity(capacity.max(1)), pos: 0 }
+	}
+
+	pub fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/// Unwrap the reader. Any buffered data is lost.
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+
+	/// Return the buffered data.
+	pub fn buffer(&self) -> &[u8] {
+		&self.buf[self.pos..]
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.buf.capacity()
+	}
+
+	/// Return the buffered data, reading more from the inner reader if the buffer is empty.
+	///
+	/// An empty slice is returned if the inner reader has no more data.
+	pub async fn fill_buf(&mut self) -> Result<&[u8]> {
+		if self.pos >= self.buf.len() {
+			let mut buf = mem::take(&mut self.buf);
+			buf.clear();
+			let (res, buf) = self.inner.read(buf).await;
+			self.buf = buf;
+			self.pos = 0;
+			res?;
+		}
+		Ok(self.buffer())
+	}
+
+	/// Mark `amount` bytes of the buffer as read.
+	pub fn consume(&mut self, amount: usize) {
+		self.pos = (self.pos + amount).min(self.buf.len());
+	}
+
+	/// Read data into `out`.
+	pub async fn read(&mut self, out: &mut [u8]) -> Result<usize> {
+		let data = self.fill_buf().await?;
+		let n = data.len().min(out.len());
+		out[..n].copy_from_slice(&data[..n]);
+		self.consume(n);
+		Ok(n)
+	}
+
+	/// Read until the delimiter `byte` or the end of the data is reached.
+	///
+	/// The delimiter is included in `out`. Returns the amount of bytes read.
+	pub async fn read_until(&mut self, byte: u8, out: &mut Vec<u8>) -> Result<usize> {
+		let mut total = 0;
+		loop {
+			let data = self.fill_buf().await?;
+			if data.is_empty() {
+				return Ok(total);
+			}
+			let (n, done) = match data.iter().position(|&c| c == byte) {
+				Some(i) => (i + 1, true),
+				None => (data.len(), false),
+			};
+			out.extend_from_slice(&data[..n]);
+			self.consume(n);
+			total += n;
+			if done {
+				return Ok(total);
+			}
+		}
+	}
+
+	/// Read a single line, including the newline character.
+	///
+	/// Returns [`rt::Error::InvalidData`] if the line is not valid UTF-8. `out` is left
+	/// unmodified in that case.
+	pub async fn read_line(&mut self, out: &mut String) -> Result<usize> {
+		let mut buf = Vec::new();
+		let n = self.read_until(b'\n', &mut buf).await?;
+		let s = core::str::from_utf8(&buf).map_err(|_| rt::Error::InvalidData)?;
+		out.push_str(s);
+		Ok(n)
+	}
+}
+
+/// Adds buffering to a writer.
+///
+/// Small writes are collected in an internal buffer, which is written out in one go when it is
+/// full.
+///
+/// The buffer is *not* flushed when the writer is dropped as that would require blocking.
+/// [`BufWriter::flush`] must be called explicitly, otherwise any buffered data is lost.
+pub struct BufWriter<W> {
+	inner: W,
+	buf: Vec<u8>,
+}
+
+impl<W: Write<Vec<u8>>> BufWriter<W> {
+	pub fn new(inner: W) -> Self {
+		Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+	}
+
+	pub fn with_capacity(capacity: usize, inner: W) -> Self {
+		Self { inner, buf: Vec::with_capacity(capacity.max(1)) }
+	}
+
+	pub fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	/// Unwrap the writer. Any buffered data is lost, so call [`BufWriter::flush`] first.
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+
+	/// Return the buffered data.
+	pub fn buffer(&self) -> &[u8] {
+		&self.buf
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.buf.capacity()
+	}
+
+	/// Buffer all of `data`, writing out the buffer whenever it is full.
+	pub async fn write_all(&mut self, mut data: &[u8]) -> Result<()> {
+		while !data.is_empty() {
+			if self.buf.len() == self.buf.capacity() {
+				self.flush().await?;
+			}
+			let n = (self.buf.capacity() - self.buf.len()).min(data.len());
+			self.buf.extend_from_slice(&data[..n]);
+			data = &data[n..];
+		}
+		Ok(())
+	}
+
+	/// Write out all buffered data.
+	pub async fn flush(&mut self) -> Result<()> {
+		while !self.buf.is_empty() {
+			let (res, mut buf) = self.inner.write(mem::take(&mut self.buf)).await;
+			// A zero-length write means no progress can be made.
+			let res = res.and_then(|n| {
+				if n == 0 {
+					Err(rt::Error::Unknown)
+				} else {
+					Ok(n)
+				}
+			});
+			if let Ok(n) = res {
+				buf.drain(..n);
+			}
+			self.buf = buf;
+			res?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,53 @@
+use {
+	super::{Buf, Read, Result, Slice, Write, DEFAULT_BUF_SIZE},
+	alloc::vec::Vec,
+};
+
+/// Copy all data from `reader` to `writer`.
+///
+/// Data is read into an internal buffer of [`DEFAULT_BUF_SIZE`] bytes and written out before the
+/// next read is issued. Partial writes are retried until all data in the buffer is written.
+/// Copying stops when a read returns no data.
+///
+/// Returns the total amount of bytes copied.
+pub async fn copy<R, W>(reader: &R, writer: &W) -> Result<u64>
+where
+	R: Read<Vec<u8>>,
+	W: Write<Slice<Vec<u8>>>,
+{
+	copy_with_capacity(DEFAULT_BUF_SIZE, reader, writer).await
+}
+
+/// Copy all data from `reader` to `writer` using a buffer with the given capacity.
+///
+/// See [`copy`].
+pub async fn copy_with_capacity<R, W>(capacity: usize, reader: &R, writer: &W) -> Result<u64>
+where
+	R: Read<Vec<u8>>,
+	W: Write<Slice<Vec<u8>>>,
+{
+	let mut buf = Vec::with_capacity(capacity.max(1));
+	let mut total = 0;
+	loop {
+		buf.clear();
+		let (res, b) = reader.read(buf).await;
+		buf = b;
+		if res? == 0 {
+			return Ok(total);
+		}
+		let mut pos = 0;
+		while pos < buf.len() {
+			let len = buf.len();
+			let (res, b) = writer.write(buf.slice(pos..len)).await;
+			buf = b.into_inner();
+			// A zero-length write means no progress can be made.
+			match res? {
+				0 => return Err(rt::Error::Unknown),
+				n => {
+					pos += n;
+					total += n as u64;
+				}
+			}
+		}
+	}
+}
@@ -1,6 +1,14 @@
+mod buf;
+mod copy;
 mod write_fmt;
 
-pub use {async_completion::*, rt::io::*, write_fmt::WriteFmtFuture};
+pub use {
+	async_completion::*,
+	buf::{BufReader, BufWriter, DEFAULT_BUF_SIZE},
+	copy::{copy, copy_with_capacity},
+	rt::io::*,
+	write_fmt::WriteFmtFuture,
+};
 
 use {
 	crate::object::RefAsyncObject,
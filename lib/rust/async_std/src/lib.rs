@@ -1,4 +1,5 @@
 #![no_std]
+#![feature(array_methods)]
 #![feature(const_trait_impl)]
 #![deny(unused_must_use)]
 #![deny(unsafe_op_in_unsafe_fn)]
@@ -16,6 +17,7 @@ pub mod net;
 pub mod process;
 pub mod queue;
 pub mod task;
+pub mod time;
 #[macro_use]
 mod macros;
 
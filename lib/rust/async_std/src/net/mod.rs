@@ -1,6 +1,9 @@
 use {
-	crate::{io, AsyncObject},
-	alloc::format,
+	crate::{
+		io::{self, Read, Write},
+		object, AsyncObject,
+	},
+	alloc::{format, vec::Vec},
 };
 
 pub use no_std_net::*;
@@ -9,7 +12,7 @@ pub struct TcpListener(AsyncObject);
 
 impl TcpListener {
 	pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
-		let root = rt::io::net_root().expect("no net root");
+		let root = object::net_root();
 		let mut last_err = io::Error::InvalidData;
 		for a in addr
 			.to_socket_addrs()
@@ -17,8 +20,8 @@ impl TcpListener {
 		{
 			let a = into_ip6(a);
 			let path = format!("{}/tcp/listen/{}", a.ip(), a.port());
-			match root.create(path.as_bytes()) {
-				Ok(o) => return Ok(Self(o.into())),
+			match root.create(path.into_bytes()).await.0 {
+				Ok(o) => return Ok(Self(o)),
 				Err(e) => last_err = e,
 			}
 		}
@@ -42,7 +45,99 @@ pub struct TcpStream(AsyncObject);
 impl_wrap!(TcpStream read);
 impl_wrap!(TcpStream write);
 
-impl TcpStream {}
+impl TcpStream {
+	/// Connect to a remote address.
+	///
+	/// The returned future completes once the connection is established.
+	pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+		let root = object::net_root();
+		let mut last_err = io::Error::InvalidData;
+		for a in addr
+			.to_socket_addrs()
+			.unwrap_or_else(|_| todo!("convert error"))
+		{
+			let a = into_ip6(a);
+			let path = format!("default/tcp/connect/{}/{}", a.ip(), a.port());
+			match root.create(path.into_bytes()).await.0 {
+				Ok(o) => return Ok(Self(o)),
+				Err(e) => last_err = e,
+			}
+		}
+		Err(last_err)
+	}
+}
+
+/// A UDP socket.
+///
+/// ## Framing
+///
+/// Each read or write on the underlying object transfers exactly one datagram, prefixed with an
+/// 18 byte header: a 16 byte IPv6 address (IPv4 addresses are mapped) followed by a 2 byte
+/// little-endian port. On read the header holds the source, on write the destination.
+pub struct UdpSocket(AsyncObject);
+
+impl UdpSocket {
+	/// The size of the address header that precedes each datagram.
+	const HEADER_SIZE: usize = 18;
+
+	/// Bind to the given address. If the port is `0` a port is picked by the driver.
+	pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+		let root = object::net_root();
+		let mut last_err = io::Error::InvalidData;
+		for a in addr
+			.to_socket_addrs()
+			.unwrap_or_else(|_| todo!("convert error"))
+		{
+			let a = into_ip6(a);
+			let path = format!("{}/udp/{}", a.ip(), a.port());
+			match root.create(path.into_bytes()).await.0 {
+				Ok(o) => return Ok(Self(o)),
+				Err(e) => last_err = e,
+			}
+		}
+		Err(last_err)
+	}
+
+	/// Send a single datagram to the given address.
+	///
+	/// Returns the amount of bytes sent, excluding the header.
+	pub async fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+		let addr = addr
+			.to_socket_addrs()
+			.unwrap_or_else(|_| todo!("convert error"))
+			.next()
+			.ok_or(io::Error::InvalidData)?;
+		let addr = into_ip6(addr);
+		let mut data = Vec::with_capacity(Self::HEADER_SIZE + buf.len());
+		data.extend_from_slice(&addr.ip().octets());
+		data.extend_from_slice(&addr.port().to_le_bytes());
+		data.extend_from_slice(buf);
+		let (res, _) = self.0.write(data).await;
+		res.map(|n| n.saturating_sub(Self::HEADER_SIZE))
+	}
+
+	/// Receive a single datagram.
+	///
+	/// If the datagram doesn't fit in `buf` the excess data is discarded.
+	pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+		let data = Vec::with_capacity(Self::HEADER_SIZE + buf.len());
+		let (res, data) = self.0.read(data).await;
+		let n = res?;
+		if n < Self::HEADER_SIZE {
+			return Err(io::Error::InvalidData);
+		}
+		let (hdr, payload) = data[..n].split_at(Self::HEADER_SIZE);
+		let port = u16::from_le_bytes([hdr[16], hdr[17]]);
+		let addr = match <[u8; 16]>::try_from(&hdr[..16]).unwrap() {
+			[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+				SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), port))
+			}
+			ip => SocketAddr::V6(SocketAddrV6::new(ip.into(), port, 0, 0)),
+		};
+		buf[..payload.len()].copy_from_slice(payload);
+		Ok((payload.len(), addr))
+	}
+}
 
 fn into_ip6(addr: SocketAddr) -> SocketAddrV6 {
 	match addr {
@@ -1,13 +1,15 @@
 use {
 	crate::{
 		io::{self, Buf, BufMut},
-		queue,
+		queue, time,
 	},
 	core::{
 		marker::PhantomData,
 		mem::{self, ManuallyDrop},
 		ops::Deref,
+		time::Duration,
 	},
+	io_queue_rt::CreateFlags,
 };
 
 #[repr(transparent)]
@@ -32,7 +34,16 @@ impl AsyncObject {
 	}
 
 	pub async fn create<B: Buf>(&self, path: B) -> (io::Result<Self>, B) {
-		let (res, b) = queue::submit(|q, b| q.submit_create(self.0, b), path).await;
+		self.create_with_flags(path, CreateFlags::empty()).await
+	}
+
+	/// Create an object with the given flags, e.g. [`CreateFlags::EXCLUSIVE`].
+	pub async fn create_with_flags<B: Buf>(
+		&self,
+		path: B,
+		flags: CreateFlags,
+	) -> (io::Result<Self>, B) {
+		let (res, b) = queue::submit(|q, b| q.submit_create(self.0, b, flags), path).await;
 		(res.map(Self), b)
 	}
 
@@ -46,6 +57,9 @@ impl AsyncObject {
 		(res, b, bm)
 	}
 
+	/// Share an object with this object.
+	///
+	/// The returned value is defined by this object, e.g. an ID for the shared object.
 	pub async fn share(&self, object: AsyncObject) -> (io::Result<u64>, AsyncObject) {
 		(self.share_raw(object.0).await, object)
 	}
@@ -53,6 +67,40 @@ impl AsyncObject {
 	pub async fn share_raw(&self, handle: rt::Handle) -> io::Result<u64> {
 		queue::submit(|q, ()| q.submit_share(self.0, handle), ()).await
 	}
+
+	/// Read data, giving up if the read doesn't complete within the given duration.
+	///
+	/// Returns `None` on timeout, in which case the buffer is dropped. The request is
+	/// cancelled but the kernel may still complete it after the timeout, in which case the
+	/// data is lost.
+	pub async fn read_timeout<B: BufMut>(
+		&self,
+		buf: B,
+		timeout: Duration,
+	) -> Option<(io::Result<usize>, B)> {
+		let r = time::timeout(timeout, io::Read::read(self, buf)).await;
+		if r.is_none() {
+			queue::get().cancel_dropped();
+		}
+		r
+	}
+
+	/// Write data, giving up if the write doesn't complete within the given duration.
+	///
+	/// Returns `None` on timeout, in which case the buffer is dropped. The request is
+	/// cancelled but the kernel may still complete it after the timeout, i.e. the data may
+	/// have been written regardless.
+	pub async fn write_timeout<B: Buf>(
+		&self,
+		buf: B,
+		timeout: Duration,
+	) -> Option<(io::Result<usize>, B)> {
+		let r = time::timeout(timeout, io::Write::write(self, buf)).await;
+		if r.is_none() {
+			queue::get().cancel_dropped();
+		}
+		r
+	}
 }
 
 impl From<rt::Object> for AsyncObject {
@@ -175,3 +223,7 @@ pub fn file_root() -> RefAsyncObject<'static> {
 pub fn process_root() -> RefAsyncObject<'static> {
 	RefAsyncObject::from(io::process_root().expect("no process root"))
 }
+
+pub fn net_root() -> RefAsyncObject<'static> {
+	RefAsyncObject::from(io::net_root().expect("no net root"))
+}
@@ -98,6 +98,37 @@ impl Builder {
 		Ok(())
 	}
 
+	/// Check if an object with the given name has already been added.
+	pub fn has_object(&self, name: &[u8]) -> bool {
+		let mut objects = &self.objects[..];
+		while let [a, b, ref rest @ ..] = *objects {
+			let l = usize::from(u16::from_le_bytes([a, b]));
+			if &rest[..l] == name {
+				return true;
+			}
+			objects = &rest[l + 4..];
+		}
+		false
+	}
+
+	/// Set the object to use as standard input.
+	pub async fn set_stdin(&mut self, object: &AsyncObject) -> io::Result<()> {
+		self.add_object_raw(b"in", object.as_raw()).await
+	}
+
+	/// Set the object to use as standard output.
+	pub async fn set_stdout(&mut self, object: &AsyncObject) -> io::Result<()> {
+		self.add_object_raw(b"out", object.as_raw()).await
+	}
+
+	/// Set the object to use as standard error.
+	pub async fn set_stderr(&mut self, object: &AsyncObject) -> io::Result<()> {
+		self.add_object_raw(b"err", object.as_raw()).await
+	}
+
+	/// Add the default stdio & root objects.
+	///
+	/// Objects with a name that is already in use are skipped.
 	pub async fn add_default_objects(&mut self) -> io::Result<()> {
 		self.add_default_stdio_objects().await?;
 		self.add_default_root_objects().await
@@ -109,7 +140,7 @@ impl Builder {
 			("net", io::net_root()),
 			("process", io::process_root()),
 		] {
-			if let Some(obj) = obj {
+			if let Some(obj) = obj.filter(|_| !self.has_object(name.as_bytes())) {
 				self.add_object_raw(name.as_bytes(), obj.as_raw()).await?;
 			}
 		}
@@ -122,7 +153,7 @@ impl Builder {
 			("out", rt::io::stdout()),
 			("err", rt::io::stderr()),
 		] {
-			if let Some(obj) = obj {
+			if let Some(obj) = obj.filter(|_| !self.has_object(name.as_bytes())) {
 				self.add_object_raw(name.as_bytes(), obj.as_raw()).await?;
 			}
 		}
@@ -173,6 +204,174 @@ impl Builder {
 	}
 }
 
+/// How to set up a standard I/O object of a child process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stdio {
+	/// Pass the object of this process.
+	Inherit,
+	/// Don't pass any object.
+	Null,
+	/// Create a pipe. The other end is available in [`Child`].
+	Piped,
+}
+
+impl Default for Stdio {
+	fn default() -> Self {
+		Self::Inherit
+	}
+}
+
+/// A builder for spawning child processes.
+///
+/// Unlike [`Builder`] this does not need to be awaited until the process is spawned, so it can
+/// be reused to spawn multiple processes.
+pub struct Command {
+	binary: Vec<u8>,
+	args: Vec<Vec<u8>>,
+	stdin: Stdio,
+	stdout: Stdio,
+	stderr: Stdio,
+}
+
+impl Command {
+	/// Create a new command for the binary with the given path.
+	///
+	/// The path of the binary is also passed as the first argument.
+	pub fn new(binary: impl AsRef<[u8]>) -> Self {
+		let binary = binary.as_ref().to_vec();
+		Self {
+			args: [binary.clone()].into(),
+			binary,
+			stdin: Default::default(),
+			stdout: Default::default(),
+			stderr: Default::default(),
+		}
+	}
+
+	pub fn arg(&mut self, arg: impl AsRef<[u8]>) -> &mut Self {
+		self.args.push(arg.as_ref().to_vec());
+		self
+	}
+
+	pub fn args<I>(&mut self, args: I) -> &mut Self
+	where
+		I: IntoIterator,
+		I::Item: AsRef<[u8]>,
+	{
+		self.args
+			.extend(args.into_iter().map(|a| a.as_ref().to_vec()));
+		self
+	}
+
+	pub fn stdin(&mut self, stdio: Stdio) -> &mut Self {
+		self.stdin = stdio;
+		self
+	}
+
+	pub fn stdout(&mut self, stdio: Stdio) -> &mut Self {
+		self.stdout = stdio;
+		self
+	}
+
+	pub fn stderr(&mut self, stdio: Stdio) -> &mut Self {
+		self.stderr = stdio;
+		self
+	}
+
+	/// Spawn a new process.
+	pub async fn spawn(&self) -> io::Result<Child> {
+		let mut b = Builder::new().await?;
+		b.set_binary_by_name(self.binary.clone()).await.0?;
+		b.add_args(&self.args).await?;
+
+		// Pipes return the write end first.
+		let stdin = match self.stdin {
+			Stdio::Piped => {
+				let (ours, theirs) = pipe()?;
+				b.set_stdin(&theirs).await?;
+				Some(ours)
+			}
+			s => {
+				add_stdio(&mut b, b"in", s, rt::io::stdin()).await?;
+				None
+			}
+		};
+		let stdout = match self.stdout {
+			Stdio::Piped => {
+				let (theirs, ours) = pipe()?;
+				b.set_stdout(&theirs).await?;
+				Some(ours)
+			}
+			s => {
+				add_stdio(&mut b, b"out", s, rt::io::stdout()).await?;
+				None
+			}
+		};
+		let stderr = match self.stderr {
+			Stdio::Piped => {
+				let (theirs, ours) = pipe()?;
+				b.set_stderr(&theirs).await?;
+				Some(ours)
+			}
+			s => {
+				add_stdio(&mut b, b"err", s, rt::io::stderr()).await?;
+				None
+			}
+		};
+		b.add_default_root_objects().await?;
+
+		let process = b.spawn().await?;
+		Ok(Child { process, stdin, stdout, stderr })
+	}
+}
+
+/// A child process spawned with [`Command`].
+pub struct Child {
+	process: Process,
+	/// The write end of the standard input of the child if it is [piped](Stdio::Piped).
+	pub stdin: Option<AsyncObject>,
+	/// The read end of the standard output of the child if it is [piped](Stdio::Piped).
+	pub stdout: Option<AsyncObject>,
+	/// The read end of the standard error of the child if it is [piped](Stdio::Piped).
+	pub stderr: Option<AsyncObject>,
+}
+
+impl Child {
+	pub fn as_process(&self) -> &Process {
+		&self.process
+	}
+
+	pub fn into_process(self) -> Process {
+		self.process
+	}
+
+	/// Wait until the child exits.
+	///
+	/// The standard input of the child is closed first so it doesn't wait on input forever.
+	pub async fn wait(&mut self) -> io::Result<ExitStatus> {
+		self.stdin = None;
+		self.process.wait().await
+	}
+}
+
+/// Create a pipe, returning the write and read end respectively.
+fn pipe() -> io::Result<(AsyncObject, AsyncObject)> {
+	rt::Object::new(rt::NewObject::Pipe).map(|(w, r)| (w.into(), r.into()))
+}
+
+async fn add_stdio(
+	b: &mut Builder,
+	name: &[u8],
+	stdio: Stdio,
+	inherit: Option<rt::RefObject<'static>>,
+) -> io::Result<()> {
+	match (stdio, inherit) {
+		(Stdio::Inherit, Some(obj)) => b.add_object_raw(name, obj.as_raw()).await,
+		(Stdio::Inherit, None) | (Stdio::Null, _) => Ok(()),
+		(Stdio::Piped, _) => unreachable!(),
+	}
+}
+
 fn add_str(buf: &mut Vec<u8>, s: &[u8]) -> io::Result<()> {
 	u16::try_from(s.len())
 		.map(|l| {
@@ -1,12 +1,19 @@
 use {
 	crate::io::{Buf, BufMut},
 	alloc::boxed::Box,
-	core::{sync::atomic::Ordering, time::Duration},
+	core::{cell::Cell, sync::atomic::Ordering, time::Duration},
 	io_queue_rt::{Full, Pow2Size, Queue},
+	rt::time::Monotonic,
 };
 
 static IO_QUEUE_KEY: rt::tls::AtomicKey = rt::tls::AtomicKey::default();
 
+/// The per-thread I/O queue along with the earliest time [`wait`] should return.
+struct State {
+	queue: Queue,
+	deadline: Cell<Monotonic>,
+}
+
 /// Try to submit a request, blocking & retrying if the queue is full.
 pub fn submit<F, B, R>(f: F, buf: B) -> R
 where
@@ -50,14 +57,34 @@ pub fn poll() {
 	q.process();
 }
 
+/// Wait for responses, or until the timeout or the earliest deadline passed to [`wake_at`]
+/// expires.
+///
+/// The deadline is cleared, so pending timers must register it again when polled.
 pub fn wait(timeout: Duration) {
-	let q = get();
-	q.poll();
-	q.wait(timeout);
-	q.process();
+	let s = state();
+	let deadline = s.deadline.replace(Monotonic::MAX);
+	let timeout = if deadline == Monotonic::MAX {
+		timeout
+	} else {
+		timeout.min(deadline.saturating_duration_since(Monotonic::now()))
+	};
+	s.queue.poll();
+	s.queue.wait(timeout);
+	s.queue.process();
+}
+
+/// Make the next call to [`wait`] on this thread return no later than the given deadline.
+pub fn wake_at(deadline: Monotonic) {
+	let s = state();
+	s.deadline.set(s.deadline.get().min(deadline));
 }
 
 pub fn get() -> &'static Queue {
+	&state().queue
+}
+
+fn state() -> &'static State {
 	// Get or allocate key
 	let mut key = IO_QUEUE_KEY.load(Ordering::Relaxed);
 	if key == rt::tls::Key::default() {
@@ -77,26 +104,27 @@ pub fn get() -> &'static Queue {
 	// SAFETY: we have a valid key.
 	// FIXME it's not practical to check if TLS is initialized without marking everything as
 	// unsafe.
-	let mut queue = unsafe { rt::tls::get(key) }.cast::<Queue>();
-	if queue.is_null() {
+	let mut state = unsafe { rt::tls::get(key) }.cast::<State>();
+	if state.is_null() {
 		// 2^6 * (32 + 16) = 3072 < 4096, i.e. it fits in one page.
-		let q = Queue::new(Pow2Size::P6, Pow2Size::P6).expect("failed to create I/O queue");
-		queue = Box::into_raw(Box::new(q));
+		let queue = Queue::new(Pow2Size::P6, Pow2Size::P6).expect("failed to create I/O queue");
+		let s = State { queue, deadline: Cell::new(Monotonic::MAX) };
+		state = Box::into_raw(Box::new(s));
 		// SAFETY: we have a valid key.
-		unsafe { rt::tls::set(key, queue.cast()) };
+		unsafe { rt::tls::set(key, state.cast()) };
 	}
 
 	// SAFETY: queue is not Sync, so references to it are not Send.
 	// The queue is only destroyed when the thread itself is destroyed, so
 	// it cannot be used afterwards by this thread nor other threads.
-	unsafe { &*(queue as *const _) }
+	unsafe { &*(state as *const _) }
 }
 
 /// # Safety
 ///
 /// `get` may not be called after this in the same thread.
-unsafe extern "C" fn destroy_queue(queue: *mut ()) {
-	let queue = unsafe { Box::from_raw(queue.cast::<Queue>()) };
+unsafe extern "C" fn destroy_queue(state: *mut ()) {
+	let state = unsafe { Box::from_raw(state.cast::<State>()) };
 	// FIXME we need a way to ensure all requests have been submitted.
-	queue.poll();
+	state.queue.poll();
 }
@@ -18,3 +18,102 @@ pub fn block_on<R>(fut: impl Future<Output = R>) -> R {
 		queue::wait(Duration::MAX);
 	}
 }
+
+/// Wait for the first future to complete.
+///
+/// Returns the index of the future that completed first along with its output. If multiple
+/// futures are ready at the same time the one with the lowest index is picked.
+pub fn select<F: Future, const N: usize>(futures: [F; N]) -> Select<F, N> {
+	Select { futures }
+}
+
+/// Wait for all futures to complete.
+pub fn join_all<F: Future, const N: usize>(futures: [F; N]) -> JoinAll<F, N> {
+	JoinAll { futures: futures.map(MaybeDone::Pending) }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Select<F: Future, const N: usize> {
+	futures: [F; N],
+}
+
+impl<F: Future, const N: usize> Future for Select<F, N> {
+	type Output = (usize, F::Output);
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		// SAFETY: the futures are never moved.
+		let futures = unsafe { &mut self.get_unchecked_mut().futures };
+		for (i, f) in futures.iter_mut().enumerate() {
+			// SAFETY: the futures are never moved.
+			if let Poll::Ready(r) = unsafe { Pin::new_unchecked(f) }.poll(cx) {
+				return Poll::Ready((i, r));
+			}
+		}
+		Poll::Pending
+	}
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct JoinAll<F: Future, const N: usize> {
+	futures: [MaybeDone<F>; N],
+}
+
+enum MaybeDone<F: Future> {
+	Pending(F),
+	Done(F::Output),
+	Taken,
+}
+
+impl<F: Future, const N: usize> Future for JoinAll<F, N> {
+	type Output = [F::Output; N];
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		// SAFETY: the futures are never moved. They are dropped in place when done.
+		let futures = unsafe { &mut self.get_unchecked_mut().futures };
+		let mut done = true;
+		for f in futures.iter_mut() {
+			if let MaybeDone::Pending(p) = f {
+				// SAFETY: the future is never moved.
+				let r = unsafe { Pin::new_unchecked(p) }.poll(cx);
+				match r {
+					Poll::Ready(r) => *f = MaybeDone::Done(r),
+					Poll::Pending => done = false,
+				}
+			}
+		}
+		if !done {
+			return Poll::Pending;
+		}
+		Poll::Ready(
+			futures
+				.each_mut()
+				.map(|f| match core::mem::replace(f, MaybeDone::Taken) {
+					MaybeDone::Done(r) => r,
+					_ => panic!("polled after completion"),
+				}),
+		)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use {super::*, core::future};
+
+	fn poll_once<F: Future>(f: F) -> Poll<F::Output> {
+		futures_lite::pin!(f);
+		let mut cx = Context::from_waker(futures_task::noop_waker_ref());
+		f.poll(&mut cx)
+	}
+
+	#[test]
+	fn select_ready() {
+		let r = poll_once(select([future::ready(1), future::ready(2)]));
+		assert_eq!(r, Poll::Ready((0, 1)));
+	}
+
+	#[test]
+	fn join_all_ready() {
+		let r = poll_once(join_all([future::ready(1), future::ready(2)]));
+		assert_eq!(r, Poll::Ready([1, 2]));
+	}
+}
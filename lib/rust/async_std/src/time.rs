@@ -0,0 +1,158 @@
+//! Timers.
+//!
+//! Timers are checked whenever the I/O queue of the current thread is waited on with
+//! [`crate::queue::wait`], which returns early if a timer expires. No separate kernel object is
+//! needed: the timeout of the queue wait acts as the alarm.
+//!
+//! ## Resolution
+//!
+//! Deadlines are specified in nanoseconds, but the thread is only woken when the scheduler gets
+//! to it. A timer never fires early, but it may fire late by up to a time slice, or more if the
+//! system is busy.
+//!
+//! ## Coalescing
+//!
+//! All timers of a thread are coalesced into a single deadline, the earliest one. When the wait
+//! returns all pending futures are polled again and the timers that have not expired yet
+//! register their deadline again. Timers expiring at the same time hence cost a single wakeup.
+
+use {
+	crate::queue,
+	core::{
+		future::Future,
+		pin::Pin,
+		task::{Context, Poll},
+		time::Duration,
+	},
+	rt::time::Monotonic,
+};
+
+/// Wait until the given duration has passed.
+pub fn sleep(duration: Duration) -> Sleep {
+	sleep_until(
+		Monotonic::now()
+			.checked_add(duration)
+			.unwrap_or(Monotonic::MAX),
+	)
+}
+
+/// Wait until the given point in time.
+pub fn sleep_until(deadline: Monotonic) -> Sleep {
+	Sleep { deadline }
+}
+
+/// Wait for a future to complete, giving up once the duration has passed.
+///
+/// Returns `None` if the future did not complete in time. The future is dropped along with the
+/// [`Timeout`].
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+	Timeout { future, sleep: sleep(duration) }
+}
+
+/// Create an [`Interval`] that ticks every `period`, starting after one `period`.
+///
+/// # Panics
+///
+/// If `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+	assert!(!period.is_zero(), "period must be non-zero");
+	Interval { next: sleep(period), period }
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Sleep {
+	deadline: Monotonic,
+}
+
+impl Sleep {
+	pub fn deadline(&self) -> Monotonic {
+		self.deadline
+	}
+}
+
+impl Future for Sleep {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+		if Monotonic::now() >= self.deadline {
+			Poll::Ready(())
+		} else {
+			queue::wake_at(self.deadline);
+			Poll::Pending
+		}
+	}
+}
+
+#[must_use = "futures do nothing unless polled"]
+#[pin_project::pin_project]
+pub struct Timeout<F: Future> {
+	#[pin]
+	future: F,
+	sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+	type Output = Option<F::Output>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let slf = self.project();
+		if let Poll::Ready(r) = slf.future.poll(cx) {
+			return Poll::Ready(Some(r));
+		}
+		Pin::new(slf.sleep).poll(cx).map(|()| None)
+	}
+}
+
+/// A timer for periodic work.
+///
+/// If ticks are missed, e.g. because the task was busy, they are skipped instead of fired in a
+/// burst. The next tick is then scheduled one `period` after the late tick.
+pub struct Interval {
+	next: Sleep,
+	period: Duration,
+}
+
+impl Interval {
+	/// Wait for the next tick.
+	///
+	/// Returns the time at which the tick was scheduled.
+	pub fn tick(&mut self) -> Tick<'_> {
+		Tick { interval: self }
+	}
+
+	/// Poll for the next tick.
+	pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Monotonic> {
+		let deadline = self.next.deadline;
+		Pin::new(&mut self.next).poll(cx).map(|()| {
+			let now = Monotonic::now();
+			let next = deadline
+				.checked_add(self.period)
+				.filter(|&next| next > now)
+				.unwrap_or_else(|| now.checked_add(self.period).unwrap_or(Monotonic::MAX));
+			self.next = sleep_until(next);
+			deadline
+		})
+	}
+
+	pub fn period(&self) -> Duration {
+		self.period
+	}
+
+	/// Restart the interval so the next tick happens one `period` from now.
+	pub fn reset(&mut self) {
+		self.next = sleep(self.period);
+	}
+}
+
+#[must_use = "futures do nothing unless polled"]
+pub struct Tick<'a> {
+	interval: &'a mut Interval,
+}
+
+impl Future for Tick<'_> {
+	type Output = Monotonic;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		self.get_mut().interval.poll_tick(cx)
+	}
+}
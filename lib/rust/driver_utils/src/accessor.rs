@@ -1,7 +1,30 @@
+//! Utilities for accessing memory-mapped registers.
+//!
+//! A register block is described by a type implementing [`Block`]. Each (part of a) register in
+//! the block is described by a type implementing [`Field`], which encodes the offset and bit
+//! range at the type level. [`Mmio`] uses these to perform volatile reads & writes.
+//!
+//! ```ignore
+//! use driver_utils::{accessor::Mmio, mmio_block, mmio_field};
+//!
+//! mmio_block!(Transcoder, 0x1000);
+//! // Equivalent to the Intel HD graphics `Blank` register of transcoder A.
+//! mmio_field!(BlankEnd: Transcoder @ 0x4 [28:16]);
+//! mmio_field!(BlankStart: Transcoder @ 0x4 [12:0]);
+//!
+//! let mmio = unsafe { Mmio::<Transcoder>::new(base) };
+//! let start = mmio.read::<BlankStart>();
+//! mmio.write::<BlankEnd>(start + 160);
+//! ```
+
+use core::{marker::PhantomData, ptr::NonNull};
+
 /// An identity accessor mapper
+#[cfg(feature = "accessor")]
 #[derive(Clone, Copy, Debug)]
 pub struct Identity;
 
+#[cfg(feature = "accessor")]
 impl accessor::Mapper for Identity {
 	unsafe fn map(&mut self, phys_start: usize, _bytes: usize) -> core::num::NonZeroUsize {
 		phys_start.try_into().unwrap()
@@ -9,3 +32,126 @@ impl accessor::Mapper for Identity {
 
 	fn unmap(&mut self, _virt_start: usize, _bytes: usize) {}
 }
+
+/// A block of 32-bit registers.
+pub trait Block {
+	/// The size of the block in bytes.
+	const SIZE: usize;
+}
+
+/// A range of bits inside a 32-bit register.
+pub trait Field {
+	/// The block this field belongs to.
+	type Block: Block;
+	/// The offset of the register in bytes.
+	const OFFSET: usize;
+	/// The lowest bit of the field.
+	const LOW: u32;
+	/// The highest bit of the field, inclusive.
+	const HIGH: u32;
+	/// The mask of the field, not shifted.
+	const MASK: u32 = u32::MAX >> (31 - (Self::HIGH - Self::LOW));
+}
+
+/// Compile-time checks for a field.
+struct Check<F>(PhantomData<F>);
+
+impl<F: Field> Check<F> {
+	const VALID: () = {
+		assert!(F::OFFSET % 4 == 0, "register offset is not aligned");
+		assert!(F::OFFSET + 4 <= F::Block::SIZE, "register is outside block");
+		assert!(F::LOW <= F::HIGH && F::HIGH < 32, "invalid bit range");
+	};
+}
+
+/// An accessor for a block of memory-mapped registers.
+pub struct Mmio<T: Block> {
+	base: NonNull<u32>,
+	_marker: PhantomData<T>,
+}
+
+impl<T: Block> Mmio<T> {
+	/// # Safety
+	///
+	/// `base` must point to a valid register block of `T::SIZE` bytes and be 4-byte aligned.
+	pub unsafe fn new(base: NonNull<()>) -> Self {
+		debug_assert_eq!(base.as_ptr() as usize % 4, 0, "bad alignment");
+		Self { base: base.cast(), _marker: PhantomData }
+	}
+
+	/// Read the value of a field.
+	pub fn read<F: Field<Block = T>>(&self) -> u32 {
+		(self.read_reg::<F>() >> F::LOW) & F::MASK
+	}
+
+	/// Write the value of a field. Other bits in the register are preserved.
+	///
+	/// Bits in `value` outside the field are ignored.
+	pub fn write<F: Field<Block = T>>(&self, value: u32) {
+		let reg = self.read_reg::<F>() & !(F::MASK << F::LOW);
+		self.write_reg::<F>(reg | (value & F::MASK) << F::LOW)
+	}
+
+	/// Check if a single-bit field is set.
+	pub fn is_set<F: Field<Block = T>>(&self) -> bool {
+		self.read::<F>() != 0
+	}
+
+	/// Set or clear all bits of a field.
+	pub fn set<F: Field<Block = T>>(&self, enable: bool) {
+		self.write::<F>(if enable { u32::MAX } else { 0 })
+	}
+
+	/// Read the entire register the field is part of.
+	pub fn read_reg<F: Field<Block = T>>(&self) -> u32 {
+		// SAFETY: the offset is checked to be in range and aligned.
+		unsafe { self.reg::<F>().as_ptr().read_volatile() }
+	}
+
+	/// Write the entire register the field is part of.
+	pub fn write_reg<F: Field<Block = T>>(&self, value: u32) {
+		// SAFETY: the offset is checked to be in range and aligned.
+		unsafe { self.reg::<F>().as_ptr().write_volatile(value) }
+	}
+
+	fn reg<F: Field<Block = T>>(&self) -> NonNull<u32> {
+		// Referencing the constant forces the checks to be evaluated.
+		let () = Check::<F>::VALID;
+		// SAFETY: the offset is checked to be in range.
+		unsafe { NonNull::new_unchecked(self.base.as_ptr().add(F::OFFSET / 4)) }
+	}
+}
+
+/// Declare a register block for use with [`Mmio`].
+#[macro_export]
+macro_rules! mmio_block {
+	($(#[$attr:meta])* $vis:vis $name:ident, $size:literal) => {
+		$(#[$attr])*
+		$vis struct $name;
+
+		impl $crate::accessor::Block for $name {
+			const SIZE: usize = $size;
+		}
+	};
+}
+
+/// Declare a register field for use with [`Mmio`].
+///
+/// The bit range is either a single bit (`[31]`) or an inclusive range (`[28:16]`).
+#[macro_export]
+macro_rules! mmio_field {
+	($(#[$attr:meta])* $vis:vis $name:ident: $block:ty @ $offset:literal [$bit:literal]) => {
+		$crate::mmio_field!($(#[$attr])* $vis $name: $block @ $offset [$bit:$bit]);
+	};
+	($(#[$attr:meta])* $vis:vis $name:ident: $block:ty @ $offset:literal [$high:literal:$low:literal]) => {
+		$(#[$attr])*
+		$vis struct $name;
+
+		impl $crate::accessor::Field for $name {
+			type Block = $block;
+			const OFFSET: usize = $offset;
+			const LOW: u32 = $low;
+			const HIGH: u32 = $high;
+		}
+	};
+}
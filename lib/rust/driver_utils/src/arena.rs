@@ -24,6 +24,15 @@ impl<T> Arena<T> {
 		Self::convert_from_handle(self.inner.insert_with(f)).unwrap()
 	}
 
+	/// Insert a value only if there are less than `max` values in the arena.
+	///
+	/// The value is returned if the arena is full.
+	pub fn try_insert_bounded(&mut self, max: usize, value: T) -> Result<Handle, T> {
+		self.inner
+			.try_insert_bounded(max, value)
+			.map(|h| Self::convert_from_handle(h).expect("index out of bounds"))
+	}
+
 	pub fn remove(&mut self, handle: Handle) -> Option<T> {
 		self.inner.remove(Self::convert_to_handle(handle)?)
 	}
@@ -1,7 +1,11 @@
 use {
-	alloc::string::ToString,
-	core::{num::NonZeroUsize, ptr::NonNull, str},
-	norostb_rt as rt,
+	alloc::{format, string::ToString, vec::Vec},
+	core::{
+		num::{NonZeroU64, NonZeroUsize},
+		ptr::NonNull,
+		str,
+	},
+	norostb_rt::{self as rt, sync::Mutex},
 };
 
 pub fn alloc_dma(size: NonZeroUsize) -> rt::io::Result<(NonNull<u8>, u64, NonZeroUsize)> {
@@ -10,12 +14,126 @@ pub fn alloc_dma(size: NonZeroUsize) -> rt::io::Result<(NonNull<u8>, u64, NonZer
 	Ok((buf, buf_phys, buf_size.try_into().unwrap()))
 }
 
+/// Allocate a physically contiguous region that doesn't cross a multiple of `boundary` and ends
+/// at or below `max_phys_addr`.
+///
+/// `boundary` must be a power of two that is at least as large as `size` rounded up to a page.
+/// Fails with [`rt::Error::CantCreateObject`] if no region satisfies the constraints.
+pub fn alloc_dma_bounded(
+	size: NonZeroUsize,
+	boundary: Option<NonZeroU64>,
+	max_phys_addr: u64,
+) -> rt::io::Result<(NonNull<u8>, u64, NonZeroUsize)> {
+	let (buf, buf_phys) = alloc_dma_object_bounded(size, boundary, max_phys_addr)?;
+	let (buf, buf_size) = buf.map_object(None, rt::io::RWX::RW, 0, usize::MAX)?;
+	Ok((buf, buf_phys, buf_size.try_into().unwrap()))
+}
+
 pub fn alloc_dma_object(size: NonZeroUsize) -> rt::io::Result<(rt::Object, u64)> {
-	let size = size.to_string();
+	dma_object(size.to_string().as_bytes())
+}
+
+/// Like [`alloc_dma_bounded`] but returns the object instead of mapping it.
+pub fn alloc_dma_object_bounded(
+	size: NonZeroUsize,
+	boundary: Option<NonZeroU64>,
+	max_phys_addr: u64,
+) -> rt::io::Result<(rt::Object, u64)> {
+	let boundary = boundary.map_or(0, NonZeroU64::get);
+	dma_object(format!("{},{},{}", size, boundary, max_phys_addr).as_bytes())
+}
+
+fn dma_object(args: &[u8]) -> rt::io::Result<(rt::Object, u64)> {
 	let root = rt::io::file_root().unwrap();
-	let buf = root.open(b"dma")?.create(size.as_bytes())?;
+	let buf = root.open(b"dma")?.create(args)?;
 	let mut r = [0; 32];
 	let r_len = buf.open(b"phys").unwrap().read(&mut r).unwrap();
 	let buf_phys = str::from_utf8(&r[..r_len]).unwrap().parse::<u64>().unwrap();
 	Ok((buf, buf_phys))
 }
+
+/// A pool of fixed-size DMA buffers.
+///
+/// A single physically contiguous region is allocated up front and split in slots. Slots are
+/// returned to the pool when the [`DmaBuf`] handle is dropped, which avoids fragmentation caused
+/// by many small, short-lived allocations.
+///
+/// Use [`alloc_dma`] for large or persistent allocations instead.
+pub struct DmaPool {
+	ptr: NonNull<u8>,
+	phys: u64,
+	slot_size: NonZeroUsize,
+	free: Mutex<Vec<usize>>,
+}
+
+impl DmaPool {
+	/// Allocate a pool with `slots` buffers of `slot_size` bytes each.
+	///
+	/// `slot_size` is rounded up to a multiple of [`DmaPool::ALIGN`].
+	pub fn new(slot_size: NonZeroUsize, slots: NonZeroUsize) -> rt::io::Result<Self> {
+		let slot_size = (slot_size.get() + Self::ALIGN - 1) & !(Self::ALIGN - 1);
+		let size = slot_size
+			.checked_mul(slots.get())
+			.and_then(NonZeroUsize::new)
+			.ok_or(rt::Error::InvalidData)?;
+		let (ptr, phys, size) = alloc_dma(size)?;
+		// Hand out any excess space too.
+		let slots = size.get() / slot_size;
+		Ok(Self {
+			ptr,
+			phys,
+			slot_size: slot_size.try_into().unwrap(),
+			free: Mutex::new((0..slots).rev().collect()),
+		})
+	}
+
+	/// The minimum alignment of each slot.
+	pub const ALIGN: usize = 64;
+
+	/// Take a buffer from the pool, if any is available.
+	pub fn alloc(&self) -> Option<DmaBuf<'_>> {
+		self.free
+			.lock()
+			.pop()
+			.map(|index| DmaBuf { pool: self, index })
+	}
+
+	/// The size of each buffer.
+	pub fn slot_size(&self) -> NonZeroUsize {
+		self.slot_size
+	}
+}
+
+/// A buffer from a [`DmaPool`]. It is returned to the pool when dropped.
+pub struct DmaBuf<'a> {
+	pool: &'a DmaPool,
+	index: usize,
+}
+
+impl DmaBuf<'_> {
+	/// The virtual address of the buffer.
+	pub fn as_ptr(&self) -> NonNull<u8> {
+		// SAFETY: the index is in range of the allocated region.
+		unsafe { NonNull::new_unchecked(self.pool.ptr.as_ptr().add(self.offset())) }
+	}
+
+	/// The physical address of the buffer.
+	pub fn phys(&self) -> u64 {
+		self.pool.phys + u64::try_from(self.offset()).unwrap()
+	}
+
+	/// The size of the buffer.
+	pub fn size(&self) -> NonZeroUsize {
+		self.pool.slot_size
+	}
+
+	fn offset(&self) -> usize {
+		self.index * self.pool.slot_size.get()
+	}
+}
+
+impl Drop for DmaBuf<'_> {
+	fn drop(&mut self) {
+		self.pool.free.lock().push(self.index);
+	}
+}
@@ -0,0 +1,164 @@
+use {crate::os::stream_table::JobId, alloc::collections::VecDeque};
+
+/// A bounded queue of events with a queue of readers waiting for them.
+///
+/// Events are handed directly to a waiting reader if there is one, otherwise they are buffered
+/// until a reader arrives.
+pub struct EventRing<T> {
+	events: VecDeque<T>,
+	readers: VecDeque<JobId>,
+	capacity: usize,
+	overflow: Overflow,
+}
+
+/// What to do with an event if the buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+	/// Discard the new event.
+	DropNewest,
+	/// Discard the oldest buffered event to make room for the new event.
+	DropOldest,
+	/// Return the new event to the caller, which can retry later.
+	Reject,
+}
+
+impl<T> EventRing<T> {
+	pub fn new(capacity: usize, overflow: Overflow) -> Self {
+		Self {
+			events: VecDeque::with_capacity(capacity),
+			readers: Default::default(),
+			capacity,
+			overflow,
+		}
+	}
+
+	/// Add an event.
+	///
+	/// If a reader is waiting the event is returned along with the reader's job ID so the caller
+	/// can respond to it.
+	///
+	/// Fails if the buffer is full and the overflow policy is [`Overflow::Reject`].
+	pub fn push(&mut self, event: T) -> Result<Option<(JobId, T)>, T> {
+		match self.readers.pop_front() {
+			Some(id) => Ok(Some((id, event))),
+			None => self.enqueue(event).map(|()| None),
+		}
+	}
+
+	/// Add an event to the buffer, even if a reader is waiting.
+	///
+	/// This is useful if only one reader can be responded to at a time.
+	///
+	/// Fails if the buffer is full and the overflow policy is [`Overflow::Reject`].
+	pub fn enqueue(&mut self, event: T) -> Result<(), T> {
+		if self.events.len() >= self.capacity {
+			match self.overflow {
+				Overflow::DropNewest => return Ok(()),
+				Overflow::DropOldest => drop(self.events.pop_front()),
+				Overflow::Reject => return Err(event),
+			}
+		}
+		if self.capacity > 0 {
+			self.events.push_back(event);
+		}
+		Ok(())
+	}
+
+	/// Take an event for a reader.
+	///
+	/// If no event is buffered the reader is added to the queue of waiting readers and it will
+	/// be returned by a future call to [`Self::push`].
+	pub fn read(&mut self, reader: JobId) -> Option<T> {
+		let e = self.events.pop_front();
+		if e.is_none() {
+			self.readers.push_back(reader);
+		}
+		e
+	}
+
+	/// Take an event without registering a reader.
+	pub fn pop(&mut self) -> Option<T> {
+		self.events.pop_front()
+	}
+
+	/// The amount of buffered events.
+	pub fn len(&self) -> usize {
+		self.events.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.events.is_empty()
+	}
+
+	/// The amount of readers waiting for an event.
+	pub fn waiting_readers(&self) -> usize {
+		self.readers.len()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn push(ring: &mut EventRing<u32>, event: u32) -> Result<Option<(u32, u32)>, u32> {
+		ring.push(event).map(|r| r.map(|(id, e)| (id.get(), e)))
+	}
+
+	#[test]
+	fn wraparound() {
+		let mut ring = EventRing::new(3, Overflow::Reject);
+		for i in 0..10 {
+			assert_eq!(push(&mut ring, i), Ok(None));
+			assert_eq!(push(&mut ring, i + 100), Ok(None));
+			assert_eq!(ring.pop(), Some(i));
+			assert_eq!(ring.pop(), Some(i + 100));
+			assert!(ring.is_empty());
+		}
+	}
+
+	#[test]
+	fn overflow_drop_newest() {
+		let mut ring = EventRing::new(2, Overflow::DropNewest);
+		(0..4).for_each(|i| assert_eq!(push(&mut ring, i), Ok(None)));
+		assert_eq!(ring.len(), 2);
+		assert_eq!(ring.pop(), Some(0));
+		assert_eq!(ring.pop(), Some(1));
+		assert_eq!(ring.pop(), None);
+	}
+
+	#[test]
+	fn overflow_drop_oldest() {
+		let mut ring = EventRing::new(2, Overflow::DropOldest);
+		(0..5).for_each(|i| assert_eq!(push(&mut ring, i), Ok(None)));
+		assert_eq!(ring.len(), 2);
+		assert_eq!(ring.pop(), Some(3));
+		assert_eq!(ring.pop(), Some(4));
+		assert_eq!(ring.pop(), None);
+	}
+
+	#[test]
+	fn overflow_reject() {
+		let mut ring = EventRing::new(2, Overflow::Reject);
+		assert_eq!(push(&mut ring, 0), Ok(None));
+		assert_eq!(push(&mut ring, 1), Ok(None));
+		assert_eq!(push(&mut ring, 2), Err(2));
+		assert_eq!(ring.pop(), Some(0));
+		assert_eq!(push(&mut ring, 2), Ok(None));
+		assert_eq!(ring.pop(), Some(1));
+		assert_eq!(ring.pop(), Some(2));
+	}
+
+	#[test]
+	fn waiting_reader() {
+		let mut ring = EventRing::new(2, Overflow::Reject);
+		assert_eq!(ring.read(JobId::new(7)), None);
+		assert_eq!(ring.waiting_readers(), 1);
+		// The event goes straight to the waiting reader.
+		assert_eq!(push(&mut ring, 1), Ok(Some((7, 1))));
+		assert_eq!(ring.waiting_readers(), 0);
+		assert!(ring.is_empty());
+		assert_eq!(push(&mut ring, 2), Ok(None));
+		assert_eq!(ring.read(JobId::new(8)), Some(2));
+		assert_eq!(ring.waiting_readers(), 0);
+	}
+}
@@ -4,6 +4,7 @@
 mod buf_block;
 #[cfg(feature = "std")]
 mod cache_seek;
+mod event_ring;
 #[cfg(feature = "std")]
 pub mod monitor;
 
@@ -11,5 +12,6 @@ pub mod monitor;
 pub use buf_block::BufBlock;
 #[cfg(feature = "std")]
 pub use cache_seek::CacheSeek;
+pub use event_ring::{EventRing, Overflow};
 #[cfg(feature = "std")]
 pub use monitor::Monitor;
@@ -14,12 +14,13 @@ extern crate alloc;
 mod arena;
 mod util;
 
-#[cfg(feature = "accessor")]
 pub mod accessor;
 pub mod dma;
 pub mod io;
 pub mod os;
 pub mod task;
+#[cfg(any(test, feature = "test"))]
+pub mod test;
 
 pub use self::arena::Arena;
 
@@ -0,0 +1,337 @@
+//! Typed request dispatch on top of [`StreamTable`].
+//!
+//! Instead of matching on every [`Request`] manually, implement [`Object`] for each type of
+//! object a driver exposes and let a [`Dispatcher`] route requests to the right instance.
+//!
+//! Only synchronous handlers are supported. Drivers that need to defer responses, e.g. to wait
+//! for an interrupt, should use the raw [`StreamTable`] API.
+
+use {
+	super::stream_table::{Data, JobId, Request, Response, StreamTable},
+	crate::{Arena, Handle},
+	alloc::{
+		boxed::Box,
+		vec::{self, Vec},
+	},
+	nora_stream_table::{positional, vectored},
+	norostb_rt::{self as rt, io::SeekFrom},
+};
+
+pub type Result<T> = core::result::Result<T, rt::Error>;
+
+/// An object that can be accessed through a [`Dispatcher`].
+///
+/// All methods return [`rt::Error::InvalidOperation`] by default. The object is dropped when
+/// the handle to it is closed.
+pub trait Object {
+	/// Open a child object.
+	fn open(&mut self, path: &[u8]) -> Result<Box<dyn Object>> {
+		let _ = path;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Create a child object.
+	fn create(&mut self, path: &[u8]) -> Result<Box<dyn Object>> {
+		let _ = path;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Destroy a child object.
+	fn destroy(&mut self, path: &[u8]) -> Result<u32> {
+		let _ = path;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Read data into `buf` and return the amount of bytes read.
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let _ = buf;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Write data and return the amount of bytes written.
+	fn write(&mut self, data: &[u8]) -> Result<usize> {
+		let _ = data;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Read data at an offset into `buf` and return the amount of bytes read.
+	///
+	/// This must not change the position used by [`Object::read`] and [`Object::write`].
+	fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+		let _ = (offset, buf);
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Write data at an offset and return the amount of bytes written.
+	///
+	/// This must not change the position used by [`Object::read`] and [`Object::write`].
+	fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<usize> {
+		let _ = (offset, data);
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Write the value of a property into `value` and return the length of the value.
+	fn get_meta(&mut self, property: &[u8], value: &mut [u8]) -> Result<usize> {
+		let _ = (property, value);
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Set the value of a property.
+	fn set_meta(&mut self, property: &[u8], value: &[u8]) -> Result<u32> {
+		let _ = (property, value);
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Seek and return the new position.
+	fn seek(&mut self, from: SeekFrom) -> Result<u64> {
+		let _ = from;
+		Err(rt::Error::InvalidOperation)
+	}
+
+	/// Receive an object.
+	fn share(&mut self, object: rt::Object) -> Result<u32> {
+		let _ = object;
+		Err(rt::Error::InvalidOperation)
+	}
+}
+
+/// A source of requests for a [`Dispatcher`].
+///
+/// This is implemented by [`StreamTable`]. Other implementations allow testing objects without
+/// a kernel.
+pub trait Table {
+	fn dequeue(&self) -> Option<(Handle, JobId, Request<'_>)>;
+
+	fn enqueue(&self, job_id: JobId, response: Response<'_, '_>);
+
+	fn alloc(&self, size: usize) -> Option<Data<'_>>;
+}
+
+impl Table for StreamTable {
+	fn dequeue(&self) -> Option<(Handle, JobId, Request<'_>)> {
+		StreamTable::dequeue(self)
+	}
+
+	fn enqueue(&self, job_id: JobId, response: Response<'_, '_>) {
+		StreamTable::enqueue(self, job_id, response)
+	}
+
+	fn alloc(&self, size: usize) -> Option<Data<'_>> {
+		StreamTable::alloc(self, size)
+	}
+}
+
+/// Routes requests from a [`StreamTable`] to [`Object`]s.
+///
+/// Requests on the root handle ([`Handle::MAX`]) go to the root object. Objects returned by
+/// [`Object::open`] and [`Object::create`] are given a new handle.
+pub struct Dispatcher<'a, T: Table + ?Sized = StreamTable> {
+	table: &'a T,
+	root: Box<dyn Object>,
+	objects: Arena<Box<dyn Object>>,
+	buf: Vec<u8>,
+	max_transfer: usize,
+}
+
+impl<'a, T: Table + ?Sized> Dispatcher<'a, T> {
+	/// Create a new dispatcher.
+	///
+	/// `max_transfer` limits the amount of data that is read or written at once.
+	pub fn new(table: &'a T, root: Box<dyn Object>, max_transfer: usize) -> Self {
+		Self { table, root, objects: Arena::new(), buf: Vec::new(), max_transfer }
+	}
+
+	/// Handle all pending requests.
+	///
+	/// Returns `true` if any responses were enqueued, in which case the table should be flushed.
+	pub fn poll(&mut self) -> bool {
+		let mut flush = false;
+		while let Some((handle, job_id, req)) = self.table.dequeue() {
+			if let Some(resp) = self.handle(handle, req) {
+				self.table.enqueue(job_id, resp);
+				flush = true;
+			}
+		}
+		flush
+	}
+
+	fn handle(&mut self, handle: Handle, req: Request<'a>) -> Option<Response<'a, 'static>> {
+		if let Request::Close = req {
+			if handle != Handle::MAX {
+				self.objects.remove(handle);
+			}
+			return None;
+		}
+
+		let table = self.table;
+		let Self { root, objects, buf, max_transfer, .. } = self;
+		let obj = if handle == Handle::MAX {
+			root
+		} else if let Some(obj) = objects.get_mut(handle) {
+			obj
+		} else {
+			return Some(Response::Error(rt::Error::InvalidObject));
+		};
+
+		let amount = |r: Result<u32>| r.map_or_else(Response::Error, Response::Amount);
+
+		Some(match req {
+			Request::Open { path } => match obj.open(copy(buf, &path)) {
+				Ok(o) => Response::Handle(objects.insert(o)),
+				Err(e) => Response::Error(e),
+			},
+			Request::Create { path } => match obj.create(copy(buf, &path)) {
+				Ok(o) => Response::Handle(objects.insert(o)),
+				Err(e) => Response::Error(e),
+			},
+			Request::Destroy { path } => amount(obj.destroy(copy(buf, &path))),
+			Request::Read { amount: n } => {
+				buf.resize((n as usize).min(*max_transfer), 0);
+				match obj.read(buf) {
+					Ok(n) => respond_data(table, &buf[..n.min(buf.len())]),
+					Err(e) => Response::Error(e),
+				}
+			}
+			Request::Write { data } => {
+				let data = copy(buf, &data);
+				let data = &data[..data.len().min(*max_transfer)];
+				amount(obj.write(data).map(|n| n.try_into().unwrap_or(u32::MAX)))
+			}
+			Request::ReadAt { args } => match positional::decode_read_at(copy(buf, &args)) {
+				Ok((offset, n)) => {
+					buf.resize((n as usize).min(*max_transfer), 0);
+					match obj.read_at(offset, buf) {
+						Ok(n) => respond_data(table, &buf[..n.min(buf.len())]),
+						Err(e) => Response::Error(e),
+					}
+				}
+				Err(_) => Response::Error(rt::Error::InvalidData),
+			},
+			Request::WriteAt { data } => match positional::decode_write_at(copy(buf, &data)) {
+				Ok((offset, data)) => {
+					let data = &data[..data.len().min(*max_transfer)];
+					amount(
+						obj.write_at(offset, data)
+							.map(|n| n.try_into().unwrap_or(u32::MAX)),
+					)
+				}
+				Err(_) => Response::Error(rt::Error::InvalidData),
+			},
+			Request::GetMeta { property } => {
+				let property = property.into_inner();
+				let l = property.len();
+				buf.resize(l + *max_transfer, 0);
+				let (p, v) = buf.split_at_mut(l);
+				property.copy_to(0, p);
+				match obj.get_meta(p, v) {
+					Ok(n) => respond_data(table, &v[..n.min(v.len())]),
+					Err(e) => Response::Error(e),
+				}
+			}
+			Request::SetMeta { property_value } => {
+				match property_value.try_get(&mut [0; 256][..]) {
+					Ok((p, v)) => amount(obj.set_meta(p, v)),
+					Err(_) => Response::Error(rt::Error::InvalidData),
+				}
+			}
+			Request::Seek { from } => obj
+				.seek(from)
+				.map_or_else(Response::Error, Response::Position),
+			Request::Share { share } => amount(obj.share(share)),
+			Request::Readv { ops } => match vectored::decode_read_ops(copy(buf, &ops)) {
+				Ok(ops) => {
+					let ops = ops.collect::<Vec<_>>();
+					respond_data(table, &readv(&mut **obj, &ops, *max_transfer))
+				}
+				Err(_) => Response::Error(rt::Error::InvalidData),
+			},
+			Request::Writev { ops } => match vectored::decode_write_ops(copy(buf, &ops)) {
+				Ok(ops) => respond_data(table, &writev(&mut **obj, ops, *max_transfer)),
+				Err(_) => Response::Error(rt::Error::InvalidData),
+			},
+			Request::Close => unreachable!(),
+		})
+	}
+}
+
+impl Dispatcher<'_, StreamTable> {
+	/// Wait for and handle requests forever.
+	pub fn run(&mut self) -> ! {
+		loop {
+			self.table.wait();
+			self.poll().then(|| self.table.flush());
+		}
+	}
+}
+
+/// Perform the reads of a `Readv` request and encode the response.
+fn readv(obj: &mut dyn Object, amounts: &[u32], max_transfer: usize) -> Vec<u8> {
+	let mut results = Vec::new();
+	let mut data = Vec::new();
+	for &amount in amounts {
+		let start = data.len();
+		let amount = (amount as usize).min(max_transfer.saturating_sub(start));
+		data.resize(start + amount, 0);
+		match obj.read(&mut data[start..]) {
+			Ok(n) => {
+				let n = n.min(amount);
+				data.truncate(start + n);
+				results.push(Ok(n as u32));
+				if n < amount {
+					break;
+				}
+			}
+			Err(e) => {
+				data.truncate(start);
+				results.push(Err(e as i64));
+				break;
+			}
+		}
+	}
+	let mut out = vec![0; results.len() * vectored::RESULT_SIZE];
+	vectored::encode_results(results, &mut out);
+	out.extend_from_slice(&data);
+	out
+}
+
+/// Perform the writes of a `Writev` request and encode the response.
+fn writev(obj: &mut dyn Object, ops: vectored::WriteOps<'_>, max_transfer: usize) -> Vec<u8> {
+	let mut results = Vec::new();
+	let mut total = 0;
+	for op in ops {
+		let op = &op[..op.len().min(max_transfer - total)];
+		match obj.write(op) {
+			Ok(n) => {
+				let n = n.min(op.len());
+				total += n;
+				results.push(Ok(n as u32));
+				if n < op.len() {
+					break;
+				}
+			}
+			Err(e) => {
+				results.push(Err(e as i64));
+				break;
+			}
+		}
+	}
+	let mut out = vec![0; results.len() * vectored::RESULT_SIZE];
+	vectored::encode_results(results, &mut out);
+	out
+}
+
+fn copy<'b>(buf: &'b mut Vec<u8>, data: &Data<'_>) -> &'b [u8] {
+	buf.resize(data.len(), 0);
+	data.copy_to(0, buf);
+	buf
+}
+
+fn respond_data<'a, T: Table + ?Sized>(table: &'a T, data: &[u8]) -> Response<'a, 'static> {
+	match table.alloc(data.len()) {
+		Some(d) => {
+			d.copy_from(0, data);
+			Response::Data(d)
+		}
+		None => Response::Error(rt::Error::CantCreateObject),
+	}
+}
@@ -0,0 +1,179 @@
+//! Common request handling for display drivers.
+//!
+//! All display drivers expose the same `gpu` interface to clients such as the window manager:
+//! buffers are shared with the driver and referred to by ID in flush and cursor commands. A
+//! [`Display`] keeps track of these buffers and decodes requests, leaving only the operations
+//! that actually touch the hardware to the [`DisplayDevice`] implementation.
+
+use {
+	super::stream_table::{Request, Response, StreamTable},
+	crate::{Arena, Handle},
+	alloc::string::ToString,
+	core::ptr::NonNull,
+	norostb_rt::{self as rt, Error},
+};
+
+/// Cursor command opcode, followed by a buffer ID and the inclusive width and height.
+const CURSOR_COMMAND: u8 = 0xc5;
+
+/// A display backend.
+pub trait DisplayDevice {
+	/// The resolution of the display.
+	fn resolution(&self) -> ipc_gpu::Resolution;
+
+	/// Copy an area of a buffer to the display and present it.
+	///
+	/// `handle` identifies the object the request was sent to. The area has been checked to fit
+	/// inside `buffer`. `flush.size` is inclusive, so a size of zero covers a single pixel.
+	fn flush(&mut self, handle: Handle, buffer: &Buffer, flush: ipc_gpu::Flush);
+
+	/// Set the image of the cursor.
+	///
+	/// The image is stored at the start of `buffer` as RGBA pixels with a stride equal to the
+	/// width. `width` and `height` are inclusive. The buffer is large enough to hold the image.
+	fn set_cursor_image(&mut self, buffer: &Buffer, width: u8, height: u8);
+
+	/// Move the cursor to the given position.
+	fn set_cursor_position(&mut self, x: u16, y: u16);
+}
+
+/// A buffer shared by a client.
+pub struct Buffer {
+	ptr: NonNull<u8>,
+	len: usize,
+}
+
+impl Buffer {
+	/// Map a shared object.
+	pub fn new(obj: rt::Object) -> rt::io::Result<Self> {
+		obj.map_object(None, rt::io::RWX::R, 0, 1 << 30)
+			.map(|(ptr, len)| Self { ptr, len })
+	}
+
+	/// A pointer to the start of the buffer.
+	///
+	/// The contents may be modified by the client at any time.
+	#[inline(always)]
+	pub fn as_ptr(&self) -> NonNull<u8> {
+		self.ptr
+	}
+
+	#[inline(always)]
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	#[inline(always)]
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+}
+
+impl Drop for Buffer {
+	fn drop(&mut self) {
+		// SAFETY: we have exclusive access to the buffer.
+		let _ = unsafe { rt::mem::dealloc(self.ptr, self.len) };
+	}
+}
+
+/// Handler for requests common to all display drivers.
+#[derive(Default)]
+pub struct Display {
+	buffers: Arena<Buffer>,
+}
+
+impl Display {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Handle a request.
+	///
+	/// Returns `None` if no response should be sent.
+	pub fn handle<'a, D: DisplayDevice>(
+		&mut self,
+		dev: &mut D,
+		tbl: &'a StreamTable,
+		handle: Handle,
+		req: Request<'a>,
+	) -> Option<Response<'a, 'static>> {
+		Some(match req {
+			Request::GetMeta { property } => match &*property.get(&mut [0; 64]) {
+				b"resolution" => {
+					let r = dev.resolution();
+					let (w, h) = (r.x.to_string(), r.y.to_string());
+					let data = tbl.alloc(w.len() + 1 + h.len()).expect("out of buffers");
+					data.copy_from(0, w.as_bytes());
+					data.copy_from(w.len(), &[b'x']);
+					data.copy_from(w.len() + 1, h.as_bytes());
+					Response::Data(data)
+				}
+				b"bin/resolution" => {
+					let r = dev.resolution().encode();
+					let data = tbl.alloc(r.len()).expect("out of buffers");
+					data.copy_from(0, &r);
+					Response::Data(data)
+				}
+				_ => Response::Error(Error::DoesNotExist),
+			},
+			Request::SetMeta { property_value } => match property_value.try_get(&mut [0; 64]) {
+				Ok((b"bin/cursor/pos", &mut [a, b, c, d])) => {
+					let x = u16::from_le_bytes([a, b]);
+					let y = u16::from_le_bytes([c, d]);
+					dev.set_cursor_position(x, y);
+					Response::Amount(0)
+				}
+				Ok((b"bin/buffer/unmap", &mut [a, b, c, d])) => {
+					let buffer_id = u32::from_le_bytes([a, b, c, d]);
+					match self.buffers.remove(buffer_id) {
+						Some(_) => Response::Amount(0),
+						None => Response::Error(Error::InvalidData),
+					}
+				}
+				Ok((b"bin/cursor/pos" | b"bin/buffer/unmap", _)) => {
+					Response::Error(Error::InvalidData)
+				}
+				Ok(_) => Response::Error(Error::DoesNotExist),
+				Err(_) => Response::Error(Error::InvalidData),
+			},
+			Request::Write { data } => {
+				let mut buf = [0; 64];
+				let (d, _) = data.copy_into(&mut buf);
+				if let Ok(d) = d.try_into() {
+					// Blit a specific area
+					let cmd = self
+						.buffers
+						.get(ipc_gpu::Flush::decode(d).buffer_id)
+						.and_then(|buf| {
+							Some((buf, ipc_gpu::Flush::decode_checked(d, buf.len).ok()?))
+						});
+					match cmd {
+						Some((buf, cmd)) => {
+							dev.flush(handle, buf, cmd);
+							Response::Amount(d.len().try_into().unwrap())
+						}
+						None => Response::Error(Error::InvalidData),
+					}
+				} else if let Ok([CURSOR_COMMAND, a, b, c, d, w, h]) = <[u8; 7]>::try_from(&*d) {
+					let buffer_id = u32::from_le_bytes([a, b, c, d]);
+					let l = (usize::from(w) + 1) * (usize::from(h) + 1);
+					match self.buffers.get(buffer_id).filter(|buf| l * 4 <= buf.len) {
+						Some(buf) => {
+							dev.set_cursor_image(buf, w, h);
+							Response::Amount(l as _)
+						}
+						None => Response::Error(Error::InvalidData),
+					}
+				} else {
+					Response::Error(Error::InvalidData)
+				}
+			}
+			Request::Share { share } => match Buffer::new(share) {
+				Ok(buf) => Response::Amount(self.buffers.insert(buf)),
+				Err(e) => Response::Error(e),
+			},
+			Request::Close => return None,
+			_ => Response::Error(Error::InvalidOperation),
+		})
+	}
+}
@@ -18,8 +18,36 @@ pub fn allocate(irq: Option<u16>, mode: TriggerMode) -> rt::Object {
 	rt::io::file_root().unwrap().create(&buf[..l]).unwrap()
 }
 
+/// Allocate an interrupt vector for use with MSI or MSI-X.
+///
+/// The returned [`MsiAddressData`] must be programmed into the device, e.g. with
+/// `pci::msix::TableEntry::set_message_address` and `set_message_data`.
+///
+/// The vector is released when the object is dropped. Any interrupts arriving afterwards are
+/// ignored, but the device should still be masked before dropping the object as the vector may
+/// be reused.
+pub fn allocate_msix() -> (rt::Object, MsiAddressData) {
+	let obj = rt::io::file_root()
+		.unwrap()
+		.create(b"interrupt/msi")
+		.unwrap();
+	let mut b = [0; 12];
+	let l = obj.get_meta(b"bin/msi".into(), (&mut b).into()).unwrap();
+	assert_eq!(l, b.len(), "unexpected MSI info length");
+	let address = u64::from_le_bytes(b[..8].try_into().unwrap());
+	let data = u32::from_le_bytes(b[8..].try_into().unwrap());
+	(obj, MsiAddressData { address, data })
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum TriggerMode {
 	Edge,
 	Level,
 }
+
+/// The message address and data of a MSI(-X) interrupt.
+#[derive(Clone, Copy, Debug)]
+pub struct MsiAddressData {
+	pub address: u64,
+	pub data: u32,
+}
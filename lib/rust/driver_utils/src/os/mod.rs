@@ -1,3 +1,7 @@
+#[cfg(feature = "alloc")]
+pub mod dispatch;
+#[cfg(feature = "display")]
+pub mod display;
 pub mod interrupt;
 pub mod portio;
 pub mod stream_table;
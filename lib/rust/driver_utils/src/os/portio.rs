@@ -1,4 +1,15 @@
-use {core::mem, norostb_rt as rt};
+//! # I/O port access
+//!
+//! Ports are accessed through the kernel's `portio/map` object. Drivers need access to this
+//! object, which is usually granted through the file root.
+//!
+//! Accessing ports that don't belong to the driver's device may interfere with other devices or
+//! bring down the entire system. Only access ports that are known to belong to the device.
+
+use {
+	core::{marker::PhantomData, mem},
+	norostb_rt as rt,
+};
 
 pub struct PortIo(rt::Object);
 
@@ -6,14 +17,12 @@ macro_rules! op {
 	($ty:ident $in:ident $out:ident) => {
 		pub fn $in(&self, addr: u16) -> $ty {
 			let mut b = [0; mem::size_of::<$ty>()];
-			self.0.seek(rt::io::SeekFrom::Start(addr.into())).unwrap();
-			self.0.read(&mut b).unwrap();
+			self.0.read_at(addr.into(), &mut b).unwrap();
 			<$ty>::from_le_bytes(b)
 		}
 
 		pub fn $out(&self, addr: u16, value: $ty) {
-			self.0.seek(rt::io::SeekFrom::Start(addr.into())).unwrap();
-			self.0.write(&value.to_le_bytes()).unwrap();
+			self.0.write_at(addr.into(), &value.to_le_bytes()).unwrap();
 		}
 	};
 }
@@ -29,4 +38,74 @@ impl PortIo {
 	op!(u8 in8 out8);
 	op!(u16 in16 out16);
 	op!(u32 in32 out32);
+
+	/// Bind a port to a width.
+	pub fn port<T: PortWidth>(&self, addr: u16) -> Port<'_, T> {
+		Port { io: self, addr, _marker: PhantomData }
+	}
+}
+
+impl From<rt::Object> for PortIo {
+	/// Wrap an already opened `portio/map` object.
+	fn from(obj: rt::Object) -> Self {
+		Self(obj)
+	}
+}
+
+/// A port that is always accessed with the same width.
+pub struct Port<'a, T: PortWidth> {
+	io: &'a PortIo,
+	addr: u16,
+	_marker: PhantomData<T>,
+}
+
+impl<T: PortWidth> Port<'_, T> {
+	#[inline]
+	pub fn read(&self) -> T {
+		T::read(self.io, self.addr)
+	}
+
+	#[inline]
+	pub fn write(&self, value: T) {
+		T::write(self.io, self.addr, value)
+	}
+
+	#[inline(always)]
+	pub fn addr(&self) -> u16 {
+		self.addr
+	}
+}
+
+/// The width of a port, i.e. `u8`, `u16` or `u32`.
+pub trait PortWidth: sealed::Sealed + Sized {
+	#[doc(hidden)]
+	fn read(io: &PortIo, addr: u16) -> Self;
+	#[doc(hidden)]
+	fn write(io: &PortIo, addr: u16, value: Self);
+}
+
+macro_rules! width {
+	($ty:ident $in:ident $out:ident) => {
+		impl sealed::Sealed for $ty {}
+
+		impl PortWidth for $ty {
+			#[inline(always)]
+			fn read(io: &PortIo, addr: u16) -> Self {
+				io.$in(addr)
+			}
+
+			#[inline(always)]
+			fn write(io: &PortIo, addr: u16, value: Self) {
+				io.$out(addr, value)
+			}
+		}
+	};
+}
+
+width!(u8 in8 out8);
+width!(u16 in16 out16);
+width!(u32 in32 out32);
+
+mod sealed {
+	pub trait Sealed {}
 }
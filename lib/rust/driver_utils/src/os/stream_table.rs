@@ -1,6 +1,7 @@
 use {
 	crate::Handle,
-	core::{cell::RefCell, fmt, ops::Deref},
+	async_completion::BufMut,
+	core::{cell::RefCell, fmt, mem::MaybeUninit, ops::Deref, slice},
 	nora_stream_table::{Buffers, ServerQueue, Slice},
 	norostb_rt::{
 		self as rt,
@@ -8,7 +9,7 @@ use {
 	},
 };
 
-pub use nora_stream_table::JobId;
+pub use nora_stream_table::{positional, vectored, JobId};
 
 pub struct StreamTable {
 	queue: RefCell<ServerQueue>,
@@ -56,54 +57,29 @@ impl StreamTable {
 	}
 
 	pub fn dequeue<'a>(&'a self) -> Option<(Handle, JobId, Request)> {
-		type R = nora_stream_table::Request;
 		let (h, id, r) = self.queue.borrow_mut().dequeue()?;
-		let r = match r {
-			R::Read { amount } => Request::Read { amount },
-			R::Write { data } => Request::Write { data: self.get_owned_buf(data) },
-			R::GetMeta { property } => {
-				Request::GetMeta { property: Property(self.get_owned_buf(property)) }
-			}
-			R::SetMeta { property_value } => Request::SetMeta {
-				property_value: PropertyValue(self.get_owned_buf(property_value)),
-			},
-			R::Open { path } => Request::Open { path: self.get_owned_buf(path) },
-			R::Create { path } => Request::Create { path: self.get_owned_buf(path) },
-			R::Destroy { path } => Request::Destroy { path: self.get_owned_buf(path) },
-			R::Close => Request::Close,
-			R::Seek { from } => Request::Seek {
-				from: match from {
-					nora_stream_table::SeekFrom::Start(n) => SeekFrom::Start(n),
-					nora_stream_table::SeekFrom::Current(n) => SeekFrom::Current(n),
-					nora_stream_table::SeekFrom::End(n) => SeekFrom::End(n),
-				},
-			},
-			R::Share { share } => {
-				Request::Share { share: self.table.open(&share.to_le_bytes()).unwrap() }
-			}
-		};
+		let r = Request::from_raw(&self.buffers, &self.queue, r, |share| {
+			self.table.open(&share.to_le_bytes()).unwrap()
+		});
 		Some((h, id, r))
 	}
 
 	pub fn enqueue(&self, job_id: JobId, response: Response) {
-		type R = nora_stream_table::Response;
-		let r = match response {
-			Response::Error(e) => R::Error(e as _),
-			Response::Amount(n) => R::Amount(n),
-			Response::Position(n) => R::Position(n),
-			Response::Data(d) => {
-				let d = core::mem::ManuallyDrop::new(d);
-				R::Slice(Slice {
-					offset: d.offset().try_into().unwrap(),
-					length: d.len().try_into().unwrap(),
-				})
-			}
-			Response::Handle(h) => R::Handle(h),
-			Response::Object(o) => R::Share(self.table.share(&o).unwrap() as _),
-		};
+		let r = response.into_raw(|o| self.table.share(&o).unwrap() as _);
 		self.queue.borrow_mut().try_enqueue(job_id, r).unwrap();
 	}
 
+	/// Respond to a `Read` request with up to `max` bytes of `data`.
+	///
+	/// Returns the amount of bytes sent. The remainder should be sent in response to the next
+	/// `Read` request on the same handle. See [`Response::DataChunk`].
+	pub fn enqueue_chunked(&self, job_id: JobId, data: &[u8], max: usize) -> usize {
+		self.queue
+			.borrow_mut()
+			.try_enqueue_chunked(job_id, &self.buffers, data, max)
+			.unwrap()
+	}
+
 	#[inline(always)]
 	pub fn notifier(&self) -> &rt::Object {
 		&self.notify
@@ -118,13 +94,7 @@ impl StreamTable {
 	}
 
 	pub fn alloc(&self, size: usize) -> Option<Data<'_>> {
-		self.buffers
-			.alloc(self.queue.borrow_mut().buffer_head_ref(), size)
-			.map(|data| Data { table: self, data })
-	}
-
-	fn get_owned_buf(&self, slice: nora_stream_table::Slice) -> Data<'_> {
-		Data { table: self, data: self.buffers.get(slice) }
+		Data::alloc(&self.buffers, &self.queue, size)
 	}
 }
 
@@ -140,6 +110,47 @@ pub enum Request<'a> {
 	Destroy { path: Data<'a> },
 	Seek { from: SeekFrom },
 	Share { share: rt::Object },
+	Readv { ops: Data<'a> },
+	Writev { ops: Data<'a> },
+	ReadAt { args: Data<'a> },
+	WriteAt { data: Data<'a> },
+}
+
+impl<'a> Request<'a> {
+	/// Convert a raw request. `share` opens the object of a `Share` request.
+	pub(crate) fn from_raw(
+		buffers: &'a Buffers,
+		queue: &'a RefCell<ServerQueue>,
+		request: nora_stream_table::Request,
+		share: impl FnOnce(Handle) -> rt::Object,
+	) -> Self {
+		type R = nora_stream_table::Request;
+		let data = |slice| Data { buffers, queue, data: buffers.get(slice) };
+		match request {
+			R::Read { amount } => Self::Read { amount },
+			R::Write { data: d } => Self::Write { data: data(d) },
+			R::GetMeta { property } => Self::GetMeta { property: Property(data(property)) },
+			R::SetMeta { property_value } => {
+				Self::SetMeta { property_value: PropertyValue(data(property_value)) }
+			}
+			R::Open { path } => Self::Open { path: data(path) },
+			R::Create { path } => Self::Create { path: data(path) },
+			R::Destroy { path } => Self::Destroy { path: data(path) },
+			R::Close => Self::Close,
+			R::Seek { from } => Self::Seek {
+				from: match from {
+					nora_stream_table::SeekFrom::Start(n) => SeekFrom::Start(n),
+					nora_stream_table::SeekFrom::Current(n) => SeekFrom::Current(n),
+					nora_stream_table::SeekFrom::End(n) => SeekFrom::End(n),
+				},
+			},
+			R::Share { share: h } => Self::Share { share: share(h) },
+			R::Readv { ops } => Self::Readv { ops: data(ops) },
+			R::Writev { ops } => Self::Writev { ops: data(ops) },
+			R::ReadAt { args } => Self::ReadAt { args: data(args) },
+			R::WriteAt { data: d } => Self::WriteAt { data: data(d) },
+		}
+	}
 }
 
 /// Try using these if borrowck / dropck is frying your brain.
@@ -150,6 +161,8 @@ impl<'a> Request<'a> {
 		match self {
 			Self::Write { data } => data,
 			Self::Open { path } | Self::Create { path } | Self::Destroy { path } => path,
+			Self::Readv { ops } | Self::Writev { ops } => ops,
+			Self::ReadAt { args } | Self::WriteAt { data: args } => args,
 			_ => panic!("no data or path"),
 		}
 	}
@@ -181,16 +194,65 @@ pub enum Response<'a, 'b> {
 	Amount(u32),
 	Position(u64),
 	Data(Data<'a>),
+	/// Part of the data for a `Read` request. If `more` is set the client will issue another
+	/// `Read` to get the next chunk.
+	DataChunk {
+		data: Data<'a>,
+		more: bool,
+	},
 	Handle(Handle),
 	Object(rt::RefObject<'b>),
 }
 
+impl Response<'_, '_> {
+	/// Convert to a raw response. `share` shares the object of an `Object` response.
+	pub(crate) fn into_raw(
+		self,
+		share: impl FnOnce(rt::RefObject<'_>) -> Handle,
+	) -> nora_stream_table::Response {
+		type R = nora_stream_table::Response;
+		match self {
+			Self::Error(e) => R::Error(e as _),
+			Self::Amount(n) => R::Amount(n),
+			Self::Position(n) => R::Position(n),
+			Self::Data(d) => {
+				let d = core::mem::ManuallyDrop::new(d);
+				R::Slice(Slice {
+					offset: d.offset().try_into().unwrap(),
+					length: d.len().try_into().unwrap(),
+				})
+			}
+			Self::DataChunk { data, more } => {
+				let d = core::mem::ManuallyDrop::new(data);
+				let slice = Slice {
+					offset: d.offset().try_into().unwrap(),
+					length: d.len().try_into().unwrap(),
+				};
+				R::DataChunk { slice, more }
+			}
+			Self::Handle(h) => R::Handle(h),
+			Self::Object(o) => R::Share(share(o)),
+		}
+	}
+}
+
 pub struct Data<'a> {
-	table: &'a StreamTable,
+	buffers: &'a Buffers,
+	queue: &'a RefCell<ServerQueue>,
 	data: nora_stream_table::Data<'a>,
 }
 
 impl<'a> Data<'a> {
+	pub(crate) fn alloc(
+		buffers: &'a Buffers,
+		queue: &'a RefCell<ServerQueue>,
+		size: usize,
+	) -> Option<Self> {
+		buffers
+			.alloc(queue.borrow().buffer_head_ref(), size)
+			.map(|data| Self { buffers, queue, data })
+	}
+
 	/// # Note
 	///
 	/// Returns the *total* size of the data, which may be larger than the buffer.
@@ -201,6 +263,25 @@ impl<'a> Data<'a> {
 		self.copy_to(0, b);
 		(b, l)
 	}
+
+	/// Copy the data to the start of a buffer.
+	///
+	/// If the buffer is too small only part of the data is copied. The buffer is marked as
+	/// initialized up to the amount of copied bytes.
+	///
+	/// # Note
+	///
+	/// Returns the *total* size of the data, which may be larger than the buffer.
+	pub fn copy_into_buf<B: BufMut>(&self, buf: &mut B) -> usize {
+		let l = self.len();
+		let n = l.min(buf.bytes_total());
+		// SAFETY: the BufMut impl guarantees the pointer is valid for bytes_total() bytes.
+		let b = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<MaybeUninit<u8>>(), n) };
+		self.copy_to_uninit(0, b);
+		// SAFETY: we just initialized n bytes.
+		unsafe { buf.set_bytes_init(n) };
+		l
+	}
 }
 
 impl<'a> Deref for Data<'a> {
@@ -214,8 +295,8 @@ impl<'a> Deref for Data<'a> {
 
 impl<'a> Drop for Data<'a> {
 	fn drop(&mut self) {
-		core::mem::replace(&mut self.data, self.table.buffers.alloc_empty())
-			.manual_drop(self.table.queue.borrow().buffer_head_ref());
+		core::mem::replace(&mut self.data, self.buffers.alloc_empty())
+			.manual_drop(self.queue.borrow().buffer_head_ref());
 	}
 }
 
@@ -1,5 +1,10 @@
+#[cfg(feature = "reactor")]
+mod reactor;
 pub mod waker;
 
+#[cfg(feature = "reactor")]
+pub use reactor::Reactor;
+
 use core::{
 	future::Future,
 	pin::Pin,
@@ -0,0 +1,78 @@
+use {
+	alloc::{sync::Arc, task::Wake},
+	core::{
+		future::Future,
+		pin::Pin,
+		sync::atomic::{AtomicBool, Ordering},
+		task::{Context, Poll, Waker},
+		time::Duration,
+	},
+	io_queue_rt::Queue,
+};
+
+/// Polls futures whenever a request on an I/O queue completes.
+///
+/// Futures polled with [`Reactor::poll`] are given a waker that is woken when a request they
+/// are waiting on finishes, so [`Reactor::wait`] only blocks if none can make progress.
+pub struct Reactor<'a> {
+	queue: &'a Queue,
+	woken: Arc<Woken>,
+	waker: Waker,
+}
+
+struct Woken(AtomicBool);
+
+impl Wake for Woken {
+	fn wake(self: Arc<Self>) {
+		self.wake_by_ref()
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		self.0.store(true, Ordering::Relaxed)
+	}
+}
+
+impl<'a> Reactor<'a> {
+	pub fn new(queue: &'a Queue) -> Self {
+		let woken = Arc::new(Woken(AtomicBool::new(false)));
+		let waker = Waker::from(woken.clone());
+		Self { queue, woken, waker }
+	}
+
+	/// The waker used when polling futures.
+	pub fn waker(&self) -> &Waker {
+		&self.waker
+	}
+
+	/// Poll a future once.
+	pub fn poll<F>(&self, fut: &mut F) -> Poll<F::Output>
+	where
+		F: Future + Unpin,
+	{
+		Pin::new(fut).poll(&mut Context::from_waker(&self.waker))
+	}
+
+	/// Process finished requests and wait for more to finish if no future has been woken yet.
+	pub fn wait(&self, timeout: Duration) {
+		self.queue.poll();
+		self.queue.process();
+		if !self.woken.0.swap(false, Ordering::Relaxed) {
+			self.queue.wait(timeout);
+			self.queue.process();
+			self.woken.0.store(false, Ordering::Relaxed);
+		}
+	}
+
+	/// Run a future to completion.
+	pub fn block_on<F: Future>(&self, fut: F) -> F::Output {
+		let mut fut = fut;
+		// SAFETY: the future is never moved.
+		let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+		loop {
+			if let Poll::Ready(r) = self.poll(&mut fut) {
+				return r;
+			}
+			self.wait(Duration::MAX);
+		}
+	}
+}
@@ -0,0 +1,290 @@
+//! # Testing utilities
+//!
+//! [`LoopbackTable`] connects a [`ClientQueue`] and a [`ServerQueue`] over memory owned by the
+//! current process. This allows driving [`Object`]s through a [`Dispatcher`] without a kernel:
+//! the test acts as the client and submits requests, the dispatcher acts as the server and
+//! responds.
+//!
+//! ```ignore
+//! let tbl = LoopbackTable::new(64, 16);
+//! let mut dispatcher = Dispatcher::new(&tbl, Box::new(Echo::default()), 4096);
+//! let data = tbl.alloc_data(b"hello");
+//! let resp = tbl.round_trip(&mut dispatcher, Handle::MAX, Request::Write { data });
+//! assert_eq!(resp.get(), Ok(5));
+//! ```
+
+use {
+	crate::{
+		os::{
+			dispatch::{self, Dispatcher, Object, Table},
+			stream_table::{self, Data},
+		},
+		Handle,
+	},
+	alloc::{
+		alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout},
+		collections::VecDeque,
+		vec::Vec,
+	},
+	core::{
+		cell::{Cell, RefCell},
+		ptr::NonNull,
+	},
+	nora_stream_table::{Buffers, ClientQueue, ServerQueue},
+	norostb_rt::Error,
+};
+
+pub use nora_stream_table::{AnyResponse, JobId, Request, Response, Slice};
+
+/// The size of the memory shared by the client and server queue.
+const QUEUE_SIZE: usize = 4096;
+
+/// A stream table whose client and server side both live in the current process.
+///
+/// Objects can't be shared: `Share` requests fail with [`Error::InvalidOperation`].
+pub struct LoopbackTable {
+	client: RefCell<ClientQueue>,
+	server: RefCell<ServerQueue>,
+	buffers: Buffers,
+	queue_mem: NonNull<u8>,
+	buffer_mem: NonNull<u8>,
+	buffer_layout: Layout,
+	next_job_id: Cell<u32>,
+	/// Responses that were dequeued while looking for another response.
+	responses: RefCell<Vec<(u32, AnyResponse)>>,
+}
+
+impl LoopbackTable {
+	/// Create a table with the given amount of buffer blocks.
+	///
+	/// # Panics
+	///
+	/// `block_size` is not a power of two or is smaller than 4.
+	pub fn new(block_size: u32, blocks: u32) -> Self {
+		assert!(
+			block_size.is_power_of_two() && block_size >= 4,
+			"invalid block size"
+		);
+		let queue_mem = alloc_zeroed_mem(Layout::from_size_align(QUEUE_SIZE, QUEUE_SIZE).unwrap());
+		let size = usize::try_from(blocks).unwrap() * usize::try_from(block_size).unwrap();
+		let buffer_layout =
+			Layout::from_size_align(size.max(1), block_size.try_into().unwrap()).unwrap();
+		let buffer_mem = alloc_zeroed_mem(buffer_layout);
+
+		// SAFETY: both queues share the same, valid memory region.
+		let (client, server) =
+			unsafe { (ClientQueue::new(queue_mem), ServerQueue::new(queue_mem)) };
+		// SAFETY: the memory is valid for blocks * block_size bytes.
+		let buffers = unsafe { Buffers::new(buffer_mem, size, block_size) };
+		for i in 0..blocks {
+			buffers.dealloc(server.buffer_head_ref(), i);
+		}
+
+		Self {
+			client: client.into(),
+			server: server.into(),
+			buffers,
+			queue_mem,
+			buffer_mem,
+			buffer_layout,
+			next_job_id: Cell::new(0),
+			responses: Default::default(),
+		}
+	}
+
+	/// The buffers shared by the client and server.
+	pub fn buffers(&self) -> &Buffers {
+		&self.buffers
+	}
+
+	/// Copy data into a buffer for use in a request.
+	///
+	/// # Panics
+	///
+	/// There are not enough free buffers.
+	pub fn alloc_data(&self, data: &[u8]) -> Slice {
+		let buf = self
+			.buffers
+			.alloc(self.client.borrow().buffer_head_ref(), data.len())
+			.expect("out of buffers");
+		buf.copy_from(0, data);
+		Slice {
+			offset: buf.offset().try_into().unwrap(),
+			length: data.len().try_into().unwrap(),
+		}
+	}
+
+	/// Copy the data of a slice returned in a response and free the buffer.
+	pub fn take_data(&self, slice: Slice) -> Vec<u8> {
+		let buf = self.buffers.get(slice);
+		let mut v = alloc::vec![0; buf.len()];
+		buf.copy_to(0, &mut v);
+		buf.manual_drop(self.client.borrow().buffer_head_ref());
+		v
+	}
+
+	/// Submit a request on behalf of the client.
+	///
+	/// # Panics
+	///
+	/// The request queue is full.
+	pub fn submit(&self, handle: Handle, request: Request) -> JobId {
+		let id = self.next_job_id.get();
+		self.next_job_id.set((id + 1) & 0xff_ffff);
+		let id = JobId::new(id);
+		self.client
+			.borrow_mut()
+			.try_enqueue(handle, id, request)
+			.expect("request queue is full");
+		id
+	}
+
+	/// Get the response to a job, if any.
+	pub fn response(&self, job_id: JobId) -> Option<AnyResponse> {
+		let mut responses = self.responses.borrow_mut();
+		while let Some((id, resp)) = self.client.borrow_mut().dequeue() {
+			responses.push((id.get(), resp));
+		}
+		let i = responses.iter().position(|(id, _)| *id == job_id.get())?;
+		Some(responses.remove(i).1)
+	}
+
+	/// Get the response to a job.
+	///
+	/// # Panics
+	///
+	/// There is no response for the job.
+	#[track_caller]
+	pub fn expect_response(&self, job_id: JobId) -> AnyResponse {
+		self.response(job_id)
+			.unwrap_or_else(|| panic!("no response for job {}", job_id.get()))
+	}
+
+	/// Submit a request, handle it with the given dispatcher and return the response.
+	///
+	/// # Panics
+	///
+	/// The dispatcher did not respond.
+	#[track_caller]
+	pub fn round_trip(
+		&self,
+		dispatcher: &mut Dispatcher<'_, Self>,
+		handle: Handle,
+		request: Request,
+	) -> AnyResponse {
+		let job_id = self.submit(handle, request);
+		dispatcher.poll();
+		self.expect_response(job_id)
+	}
+
+	/// # Panics
+	///
+	/// The response queue is full.
+	fn respond(&self, job_id: JobId, response: Response) {
+		self.server
+			.borrow_mut()
+			.try_enqueue(job_id, response)
+			.expect("response queue is full");
+	}
+}
+
+impl Table for LoopbackTable {
+	fn dequeue(&self) -> Option<(Handle, JobId, stream_table::Request<'_>)> {
+		loop {
+			let (handle, job_id, request) = self.server.borrow_mut().dequeue()?;
+			if let Request::Share { .. } = request {
+				self.respond(job_id, Response::Error(Error::InvalidOperation as _));
+				continue;
+			}
+			let request = stream_table::Request::from_raw(
+				&self.buffers,
+				&self.server,
+				request,
+				|_| unreachable!(),
+			);
+			return Some((handle, job_id, request));
+		}
+	}
+
+	/// # Panics
+	///
+	/// The response is an object or the response queue is full.
+	fn enqueue(&self, job_id: JobId, response: stream_table::Response<'_, '_>) {
+		let response = response.into_raw(|_| panic!("LoopbackTable can't share objects"));
+		self.respond(job_id, response);
+	}
+
+	fn alloc(&self, size: usize) -> Option<Data<'_>> {
+		Data::alloc(&self.buffers, &self.server, size)
+	}
+}
+
+impl Drop for LoopbackTable {
+	fn drop(&mut self) {
+		// SAFETY: the memory was allocated with these layouts in Self::new.
+		unsafe {
+			dealloc(
+				self.queue_mem.as_ptr(),
+				Layout::from_size_align(QUEUE_SIZE, QUEUE_SIZE).unwrap(),
+			);
+			dealloc(self.buffer_mem.as_ptr(), self.buffer_layout);
+		}
+	}
+}
+
+/// An object that returns written data on subsequent reads.
+///
+/// Other requests fail with [`Error::InvalidOperation`].
+#[derive(Default)]
+pub struct Echo {
+	data: VecDeque<u8>,
+}
+
+impl Object for Echo {
+	fn read(&mut self, buf: &mut [u8]) -> dispatch::Result<usize> {
+		let n = buf.len().min(self.data.len());
+		buf.iter_mut()
+			.zip(self.data.drain(..n))
+			.for_each(|(b, d)| *b = d);
+		Ok(n)
+	}
+
+	fn write(&mut self, data: &[u8]) -> dispatch::Result<usize> {
+		self.data.extend(data);
+		Ok(data.len())
+	}
+}
+
+fn alloc_zeroed_mem(layout: Layout) -> NonNull<u8> {
+	// SAFETY: the layout has a non-zero size.
+	NonNull::new(unsafe { alloc_zeroed(layout) }).unwrap_or_else(|| handle_alloc_error(layout))
+}
+
+#[cfg(test)]
+mod test {
+	use {super::*, alloc::boxed::Box};
+
+	#[test]
+	fn echo() {
+		let tbl = LoopbackTable::new(64, 16);
+		let mut d = Dispatcher::new(&tbl, Box::new(Echo::default()), 4096);
+		let data = tbl.alloc_data(b"hello");
+		let r = tbl.round_trip(&mut d, Handle::MAX, Request::Write { data });
+		assert_eq!(r.get(), Ok(5));
+		let r = tbl.round_trip(&mut d, Handle::MAX, Request::Read { amount: 3 });
+		assert_eq!(tbl.take_data(r.as_slice().unwrap()), b"hel");
+		let r = tbl.round_trip(&mut d, Handle::MAX, Request::Read { amount: 16 });
+		assert_eq!(tbl.take_data(r.as_slice().unwrap()), b"lo");
+	}
+
+	#[test]
+	fn unsupported() {
+		let tbl = LoopbackTable::new(64, 16);
+		let mut d = Dispatcher::new(&tbl, Box::new(Echo::default()), 4096);
+		let path = tbl.alloc_data(b"foo");
+		let r = tbl.round_trip(&mut d, Handle::MAX, Request::Open { path });
+		assert_eq!(r.get(), Err(Error::InvalidOperation as _));
+		let r = tbl.round_trip(&mut d, 0, Request::Read { amount: 1 });
+		assert_eq!(r.get(), Err(Error::InvalidObject as _));
+	}
+}
@@ -2,14 +2,17 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 #![deny(unused)]
 
+extern crate alloc;
+
 use {
+	alloc::collections::BTreeMap,
 	core::{mem::MaybeUninit, time::Duration},
 	norostb_kernel::{io, syscall},
 };
 
 pub use norostb_kernel::{
 	error,
-	io::{Handle, Response, SeekFrom, TinySlice},
+	io::{CreateFlags, Handle, Response as RawResponse, SeekFrom, TinySlice},
 	time::Monotonic,
 };
 
@@ -46,6 +49,23 @@ macro_rules! pow2size {
 			pub fn size(self) -> usize {
 				(self.into_mask() + 1).try_into().unwrap()
 			}
+
+			/// The smallest size with room for at least the given amount of entries.
+			///
+			/// Returns [`None`] if no size is large enough.
+			pub fn for_at_least(entries: usize) -> Option<Self> {
+				$(
+					if entries <= $m as usize + 1 {
+						return Some(Self::$v);
+					}
+				)*
+				None
+			}
+
+			/// The amount of entries.
+			pub fn entries(self) -> u32 {
+				self.into_mask() + 1
+			}
 		}
 	};
 }
@@ -92,6 +112,8 @@ pub struct Queue {
 	/// How many requests are in flight. This is used to avoid submitting too many requests
 	/// and potentially losing responses.
 	requests_in_flight: u32,
+	/// The kind of requests that are in flight by user data, used to interpret responses.
+	kinds: BTreeMap<u64, RequestKind>,
 }
 
 impl Queue {
@@ -108,6 +130,7 @@ impl Queue {
 				responses_mask: responses_size.into_mask(),
 			},
 			requests_in_flight: 0,
+			kinds: BTreeMap::new(),
 		})
 	}
 
@@ -129,6 +152,7 @@ impl Queue {
 		if self.inner.responses_mask < self.requests_in_flight {
 			return Err(Full);
 		}
+		let kind = request.kind();
 		// SAFETY: requests_mask is not bogus.
 		unsafe {
 			let mut expect_response = true;
@@ -143,29 +167,55 @@ impl Queue {
 						io::Request::set_meta(user_data, handle, property, value)
 					}
 					Request::Open { path } => io::Request::open(user_data, handle, path),
-					Request::Create { path } => io::Request::create(user_data, handle, path),
+					Request::Create { path, flags } => {
+						io::Request::create_with_flags(user_data, handle, path, flags)
+					}
+					Request::Destroy { path } => io::Request::destroy(user_data, handle, path),
 					Request::Seek { from } => io::Request::seek(user_data, handle, from),
 					Request::Close => {
 						expect_response = false;
 						io::Request::close(user_data, handle)
 					}
 					Request::Share { share } => io::Request::share(user_data, handle, share),
+					Request::Cancel { user_data: cancel } => {
+						expect_response = false;
+						io::Request::cancel(cancel)
+					}
+					Request::CancelAll => {
+						expect_response = false;
+						io::Request::cancel_all()
+					}
 				})
 				.map_err(|_| Full)?;
 			if expect_response {
 				self.requests_in_flight += 1;
 			}
+			if let Some(kind) = kind {
+				self.kinds.insert(user_data, kind);
+			}
 			Ok(expect_response)
 		}
 	}
 
+	/// Pop a response along with the kind of request it belongs to.
 	pub fn receive(&mut self) -> Option<Response> {
+		self.dequeue()
+			.map(|(r, kind)| Response { user_data: r.user_data, kind, raw: r.value })
+	}
+
+	/// Pop a response as returned by the kernel.
+	pub fn receive_raw(&mut self) -> Option<RawResponse> {
+		self.dequeue().map(|(r, _)| r)
+	}
+
+	fn dequeue(&mut self) -> Option<(RawResponse, Option<RequestKind>)> {
 		// SAFETY: responses_mask is not bogus.
-		let r = unsafe { self.inner.dequeue_response().ok() };
-		if r.is_some() {
-			self.requests_in_flight -= 1;
-		}
-		r
+		let r = unsafe { self.inner.dequeue_response().ok() }?;
+		self.requests_in_flight -= 1;
+		// If multiple requests with the same user data are in flight only the kind of the last
+		// one is known, which is given to the first response.
+		let kind = self.kinds.remove(&r.user_data);
+		Some((r, kind))
 	}
 
 	pub fn poll(&mut self) {
@@ -179,17 +229,28 @@ impl Queue {
 
 impl Drop for Queue {
 	fn drop(&mut self) {
-		while self.requests_in_flight > 0 {
-			// TODO we should add a cancel request so we don't get potentially get stuck
-			// if a response never arrives.
+		if self.requests_in_flight > 0 {
+			// Force any pending requests to complete.
+			let _ = self.submit(0, 0, Request::CancelAll);
+		}
+		// Don't get stuck forever if a response never arrives for whatever reason.
+		for _ in 0..DROP_MAX_WAITS {
 			self.poll();
-			self.wait(Duration::MAX);
 			while self.receive().is_some() {}
+			if self.requests_in_flight == 0 {
+				break;
+			}
+			self.wait(DROP_WAIT_TIMEOUT);
 		}
 		let _ = unsafe { syscall::destroy_io_queue(self.inner.base.cast()) };
 	}
 }
 
+/// How many times to wait for outstanding responses when dropping a [`Queue`].
+const DROP_MAX_WAITS: u32 = 16;
+/// How long to wait each time for outstanding responses when dropping a [`Queue`].
+const DROP_WAIT_TIMEOUT: Duration = Duration::from_millis(10);
+
 /// All references have a static lifetime as it is the only way to safely guarantee a buffer
 /// lives long enough for the kernel to write to it.
 pub enum Request {
@@ -212,15 +273,202 @@ pub enum Request {
 	},
 	Create {
 		path: &'static [u8],
+		flags: CreateFlags,
+	},
+	Destroy {
+		path: &'static [u8],
 	},
 	Seek {
 		from: SeekFrom,
 	},
 	Close,
+	/// Share an object with the object the request is sent to.
+	///
+	/// The response is a value defined by the receiving object, e.g. an ID the shared object
+	/// can be referred to by in later requests. It is *not* a handle.
 	Share {
 		share: Handle,
 	},
+	/// Cancel the pending request with the given user data. The handle is ignored.
+	///
+	/// The cancelled request completes with [`error::Error::Cancelled`] if it was still
+	/// pending. The cancel request itself never gets a response.
+	Cancel {
+		user_data: u64,
+	},
+	/// Cancel all pending requests. The handle is ignored.
+	CancelAll,
+}
+
+impl Request {
+	/// The kind of this request, or `None` if it doesn't get a response.
+	pub fn kind(&self) -> Option<RequestKind> {
+		Some(match self {
+			Self::Read { .. } => RequestKind::Read,
+			Self::Write { .. } => RequestKind::Write,
+			Self::GetMeta { .. } => RequestKind::GetMeta,
+			Self::SetMeta { .. } => RequestKind::SetMeta,
+			Self::Open { .. } => RequestKind::Open,
+			Self::Create { .. } => RequestKind::Create,
+			Self::Destroy { .. } => RequestKind::Destroy,
+			Self::Seek { .. } => RequestKind::Seek,
+			Self::Share { .. } => RequestKind::Share,
+			Self::Close | Self::Cancel { .. } | Self::CancelAll => return None,
+		})
+	}
+}
+
+/// The kind of a request that gets a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+	Read,
+	Write,
+	GetMeta,
+	SetMeta,
+	Open,
+	Create,
+	Destroy,
+	Seek,
+	Share,
+}
+
+/// A response to a request.
+#[derive(Debug)]
+pub struct Response {
+	/// User data that was associated with the request.
+	pub user_data: u64,
+	/// The kind of request this is a response to.
+	///
+	/// This is `None` if no in-flight request with the user data was found.
+	pub kind: Option<RequestKind>,
+	raw: i64,
+}
+
+impl Response {
+	/// Interpret the value of the response according to the kind of request.
+	pub fn value(&self) -> error::Result<ResponseKind> {
+		error::result(self.raw).map(|v| {
+			let v = v as u64;
+			match self.kind {
+				Some(RequestKind::Read | RequestKind::Write) => ResponseKind::Amount(v as usize),
+				Some(RequestKind::GetMeta) => ResponseKind::MetaLength(v as u8),
+				Some(RequestKind::Open | RequestKind::Create) => ResponseKind::Handle(v as _),
+				Some(RequestKind::Seek) => ResponseKind::Position(v),
+				Some(RequestKind::SetMeta | RequestKind::Destroy | RequestKind::Share) | None => {
+					ResponseKind::Other(v)
+				}
+			}
+		})
+	}
+
+	/// The value as returned by the kernel.
+	pub fn raw(&self) -> i64 {
+		self.raw
+	}
+}
+
+/// The value of a successful response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseKind {
+	/// The amount of bytes read or written.
+	Amount(usize),
+	/// The length of a property value.
+	MetaLength(u8),
+	/// A handle to an opened or created object.
+	Handle(Handle),
+	/// The new position of the seek head.
+	Position(u64),
+	/// The value of a request without a meaningful result or of an unknown request.
+	Other(u64),
+}
+
+impl ResponseKind {
+	pub fn amount(self) -> Option<usize> {
+		match self {
+			Self::Amount(n) => Some(n),
+			_ => None,
+		}
+	}
+
+	pub fn meta_length(self) -> Option<u8> {
+		match self {
+			Self::MetaLength(n) => Some(n),
+			_ => None,
+		}
+	}
+
+	pub fn handle(self) -> Option<Handle> {
+		match self {
+			Self::Handle(h) => Some(h),
+			_ => None,
+		}
+	}
+
+	pub fn position(self) -> Option<u64> {
+		match self {
+			Self::Position(n) => Some(n),
+			_ => None,
+		}
+	}
+
+	pub fn other(self) -> Option<u64> {
+		match self {
+			Self::Other(n) => Some(n),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Debug)]
 pub struct Full;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn for_at_least() {
+		let f = |n| Pow2Size::for_at_least(n).map(Pow2Size::entries);
+		assert_eq!(f(0), Some(1));
+		assert_eq!(f(1), Some(1));
+		assert_eq!(f(2), Some(2));
+		assert_eq!(f(3), Some(4));
+		assert_eq!(f(128), Some(128));
+		assert_eq!(f(129), Some(256));
+		assert_eq!(f(1 << 31), Some(1 << 31));
+		assert_eq!(f((1 << 31) + 1), None);
+	}
+
+	#[test]
+	fn response_value() {
+		let r = |kind, raw| Response { user_data: 0, kind, raw }.value();
+		assert_eq!(
+			r(Some(RequestKind::Read), 42).unwrap(),
+			ResponseKind::Amount(42)
+		);
+		assert_eq!(
+			r(Some(RequestKind::GetMeta), 3).unwrap(),
+			ResponseKind::MetaLength(3)
+		);
+		assert_eq!(
+			r(Some(RequestKind::Open), 7).unwrap(),
+			ResponseKind::Handle(7)
+		);
+		assert_eq!(
+			r(Some(RequestKind::Seek), 1 << 40).unwrap(),
+			ResponseKind::Position(1 << 40)
+		);
+		assert_eq!(r(None, 5).unwrap(), ResponseKind::Other(5));
+		assert!(matches!(
+			r(Some(RequestKind::Open), -2),
+			Err(error::Error::DoesNotExist)
+		));
+	}
+
+	#[test]
+	fn entries() {
+		assert_eq!(Pow2Size::P0.entries(), 1);
+		assert_eq!(Pow2Size::P8.entries(), 256);
+		assert_eq!(Pow2Size::P31.entries(), 1 << 31);
+	}
+}
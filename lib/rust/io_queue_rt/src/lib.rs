@@ -6,15 +6,15 @@
 
 extern crate alloc;
 
-pub use nora_io_queue::{error, Handle, Monotonic, Pow2Size, SeekFrom};
+pub use nora_io_queue::{error, CreateFlags, Handle, Monotonic, Pow2Size, SeekFrom};
 
 use {
-	alloc::boxed::Box,
+	alloc::{boxed::Box, vec::Vec},
 	arena::Arena,
-	async_completion::{Buf, BufMut},
+	async_completion::{Buf, BufMut, BufferPool, PoolBuf, Slice},
 	core::{
 		any::Any,
-		cell::{Cell, RefCell},
+		cell::{Cell, RefCell, UnsafeCell},
 		fmt,
 		future::Future,
 		mem::{self, MaybeUninit},
@@ -23,7 +23,7 @@ use {
 		task::{Context, Poll, Waker},
 		time::Duration,
 	},
-	nora_io_queue::{self as q, Request, TinySlice},
+	nora_io_queue::{self as q, Request, ResponseKind, TinySlice},
 };
 
 pub struct Queue {
@@ -76,22 +76,10 @@ impl Queue {
 	where
 		F: FnOnce(&'static mut [MaybeUninit<u8>]) -> Request,
 	{
-		let mut inflight = self.inflight_buffers.borrow_mut();
-		let i = inflight.insert(BufferFutureState::Inflight);
 		// SAFETY: The buffer will live at least as long as the BufferFuture,
 		// even if it is mem::forgot()ten
 		let buf = unsafe { extend_lifetime_mut(buf_as_slice_total_mut(&mut buffer)) };
-		let res = self
-			.inner
-			.borrow_mut()
-			.submit(i.into_raw().0 as u64, handle, wrap(buf));
-		match res {
-			Ok(_) => Ok(BufferFuture { queue: self, inflight_index: i, buffer: Some(buffer) }),
-			Err(_) => {
-				inflight.remove(i);
-				Err(Full(buffer))
-			}
-		}
+		self.submit_buffer(buffer, handle, wrap(buf))
 	}
 
 	/// Submit a request involving writing from byte buffers.
@@ -104,15 +92,24 @@ impl Queue {
 	where
 		F: FnOnce(&'static [u8]) -> Request,
 	{
-		let mut inflight = self.inflight_buffers.borrow_mut();
-		let i = inflight.insert(BufferFutureState::Inflight);
 		// SAFETY: The buffer will live at least as long as the BufferFuture,
 		// even if it is mem::forgot()ten
 		let buf = unsafe { extend_lifetime(buf_as_slice_init(&buffer)) };
-		let res = self
-			.inner
-			.borrow_mut()
-			.submit(i.into_raw().0 as u64, handle, wrap(buf));
+		self.submit_buffer(buffer, handle, wrap(buf))
+	}
+
+	/// Submit a request that refers to the given buffer.
+	///
+	/// The buffer is kept in the returned `BufferFuture` until the request is finished.
+	fn submit_buffer<B: Buf>(
+		&self,
+		buffer: B,
+		handle: Handle,
+		request: Request,
+	) -> Result<BufferFuture<'_, B>, Full<B>> {
+		let mut inflight = self.inflight_buffers.borrow_mut();
+		let i = inflight.insert(BufferFutureState::Inflight);
+		let res = self.inner.borrow_mut().submit(i.to_u64(), handle, request);
 		match res {
 			Ok(_) => Ok(BufferFuture { queue: self, inflight_index: i, buffer: Some(buffer) }),
 			Err(_) => {
@@ -161,7 +158,7 @@ impl Queue {
 		let res = self
 			.inner
 			.borrow_mut()
-			.submit(i.into_raw().0 as u64, handle, wrap(buf_r, buf_w));
+			.submit(i.to_u64(), handle, wrap(buf_r, buf_w));
 		match res {
 			Ok(_) => Ok(BufferFuture2 {
 				queue: self,
@@ -175,7 +172,50 @@ impl Queue {
 		}
 	}
 
+	/// Submit a request involving two tiny buffers that are both written from.
+	///
+	/// # Panics
+	///
+	/// If either buffer is larger than 255 bytes.
+	fn submit_write_write_tiny_buffers<B, Bv, F>(
+		&self,
+		buffer_a: B,
+		buffer_b: Bv,
+		handle: Handle,
+		wrap: F,
+	) -> Result<BufferFuture2<'_, B, Bv>, Full<(B, Bv)>>
+	where
+		B: Buf,
+		Bv: Buf,
+		F: FnOnce(&'static TinySlice<u8>, &'static TinySlice<u8>) -> Request,
+	{
+		let mut inflight = self.inflight_buffers.borrow_mut();
+		let i = inflight.insert(BufferFutureState::Inflight);
+		// SAFETY: The buffer will live at least as long as the BufferFuture,
+		// even if it is mem::forgot()ten
+		let buf_a = unsafe { extend_lifetime(tiny_buf_as_slice_init(&buffer_a)) };
+		let buf_b = unsafe { extend_lifetime(tiny_buf_as_slice_init(&buffer_b)) };
+		let res = self
+			.inner
+			.borrow_mut()
+			.submit(i.to_u64(), handle, wrap(buf_a, buf_b));
+		match res {
+			Ok(_) => Ok(BufferFuture2 {
+				queue: self,
+				inflight_index: i,
+				buffers: Some((buffer_a, buffer_b)),
+			}),
+			Err(_) => {
+				inflight.remove(i);
+				Err(Full((buffer_a, buffer_b)))
+			}
+		}
+	}
+
 	/// Read data from an object, advancing the seek head.
+	///
+	/// The buffer does not need to be initialized. Use [`async_completion::UninitBox`] to read
+	/// into a freshly allocated buffer without zeroing it first.
 	pub fn submit_read<B>(&self, handle: Handle, buf: B) -> Result<Read<'_, B>, Full<B>>
 	where
 		B: BufMut,
@@ -184,6 +224,85 @@ impl Queue {
 			.map(|fut| Read { fut })
 	}
 
+	/// Read data from an object, advancing the seek head.
+	///
+	/// Unlike [`Queue::submit_read`] the buffer is returned as a [`Slice`] clipped to the amount
+	/// of bytes read. Use [`Slice::into_inner`] to recover the full buffer.
+	pub fn submit_read_returning_slice<B>(
+		&self,
+		handle: Handle,
+		buf: B,
+	) -> Result<ReadSlice<'_, B>, Full<B>>
+	where
+		B: BufMut,
+	{
+		self.submit_read_buffer(buf, handle, |buffer| Request::Read { buffer })
+			.map(|fut| ReadSlice { fut })
+	}
+
+	/// Read data from an object into a free buffer of a [`BufferPool`], advancing the seek head.
+	///
+	/// The buffer is returned to the pool when it is dropped. To write from a pooled buffer
+	/// use [`Queue::submit_write_pooled`].
+	pub fn submit_read_pooled(
+		&self,
+		handle: Handle,
+		pool: &BufferPool,
+	) -> Result<Read<'_, PoolBuf>, PooledError> {
+		let buf = pool.take().ok_or(PooledError::NoBuffers)?;
+		self.submit_read_pool_buffer(handle, buf)
+	}
+
+	/// Read data from an object into a specific buffer of a [`BufferPool`], advancing the seek
+	/// head.
+	pub fn submit_read_pooled_index(
+		&self,
+		handle: Handle,
+		pool: &BufferPool,
+		index: usize,
+	) -> Result<Read<'_, PoolBuf>, PooledError> {
+		let buf = pool.take_index(index).ok_or(PooledError::NoBuffers)?;
+		self.submit_read_pool_buffer(handle, buf)
+	}
+
+	/// Write data from a buffer of a [`BufferPool`] to an object.
+	pub fn submit_write_pooled(
+		&self,
+		handle: Handle,
+		buf: PoolBuf,
+	) -> Result<Write<'_, PoolBuf>, Full<PoolBuf>> {
+		let mem = buf.memory();
+		// SAFETY: the first bytes_init bytes are initialized. The PoolBuf keeps the memory of
+		// the pool alive and is kept in the BufferFuture, even if it is mem::forgot()ten, so
+		// nothing else can modify or free it while the request is in flight.
+		let buffer = unsafe {
+			slice::from_raw_parts(UnsafeCell::raw_get(mem.as_ptr()).cast(), buf.bytes_init())
+		};
+		self.submit_buffer(buf, handle, Request::Write { buffer })
+			.map(|fut| Write { fut })
+	}
+
+	/// Submit a read into a buffer of a [`BufferPool`].
+	///
+	/// The memory of a pool doesn't move and is kept alive by the [`PoolBuf`], so the request
+	/// can refer to it directly.
+	fn submit_read_pool_buffer(
+		&self,
+		handle: Handle,
+		mut buf: PoolBuf,
+	) -> Result<Read<'_, PoolBuf>, PooledError> {
+		buf.clear();
+		let mem = buf.memory();
+		// SAFETY: the PoolBuf grants exclusive access to its memory and keeps the memory of the
+		// pool alive. It is kept in the BufferFuture until the request is finished, even if it is
+		// mem::forgot()ten.
+		let buffer =
+			unsafe { slice::from_raw_parts_mut(UnsafeCell::raw_get(mem.as_ptr()), mem.len()) };
+		self.submit_buffer(buf, handle, Request::Read { buffer })
+			.map(|fut| Read { fut })
+			.map_err(|Full(_)| PooledError::Full)
+	}
+
 	/// Write data to an object.
 	pub fn submit_write<B>(&self, handle: Handle, data: B) -> Result<Write<'_, B>, Full<B>>
 	where
@@ -203,14 +322,30 @@ impl Queue {
 	}
 
 	/// Create an object.
-	pub fn submit_create<B>(&self, handle: Handle, path: B) -> Result<Create<'_, B>, Full<B>>
+	///
+	/// Use [`CreateFlags::empty`] for the default behaviour of the object.
+	pub fn submit_create<B>(
+		&self,
+		handle: Handle,
+		path: B,
+		flags: CreateFlags,
+	) -> Result<Create<'_, B>, Full<B>>
 	where
 		B: Buf,
 	{
-		self.submit_write_buffer(path, handle, |path| Request::Create { path })
+		self.submit_write_buffer(path, handle, |path| Request::Create { path, flags })
 			.map(|fut| Create { fut })
 	}
 
+	/// Destroy an object.
+	pub fn submit_destroy<B>(&self, handle: Handle, path: B) -> Result<Destroy<'_, B>, Full<B>>
+	where
+		B: Buf,
+	{
+		self.submit_write_buffer(path, handle, |path| Request::Destroy { path })
+			.map(|fut| Destroy { fut })
+	}
+
 	pub fn submit_seek(&self, handle: Handle, from: SeekFrom) -> Result<Seek<'_>, Full<()>> {
 		self.submit_no_buffer(handle, Request::Seek { from })
 			.map(|fut| Seek { fut })
@@ -224,6 +359,11 @@ impl Queue {
 			.map_err(|_| Full(()))
 	}
 
+	/// Share an object with another object.
+	///
+	/// The future resolves to a value defined by the receiving object, e.g. an ID it assigned
+	/// to the shared object. This is *not* a handle: the receiving object gets its own handle
+	/// to the shared object, which is not visible to this process.
 	pub fn submit_share(&self, handle: Handle, share: Handle) -> Result<Share<'_>, Full<()>> {
 		self.submit_no_buffer(handle, Request::Share { share })
 			.map(|fut| Share { fut })
@@ -245,14 +385,33 @@ impl Queue {
 		.map(|fut| GetMeta { fut })
 	}
 
+	/// # Panics
+	///
+	/// If either the property or the value is larger than 255 bytes.
+	pub fn submit_set_meta<B, Bv>(
+		&self,
+		handle: Handle,
+		property: B,
+		value: Bv,
+	) -> Result<SetMeta<'_, B, Bv>, Full<(B, Bv)>>
+	where
+		B: Buf,
+		Bv: Buf,
+	{
+		self.submit_write_write_tiny_buffers(property, value, handle, |property, value| {
+			Request::SetMeta { property, value }
+		})
+		.map(|fut| SetMeta { fut })
+	}
+
 	pub fn process(&self) {
 		let mut inner = self.inner.borrow_mut();
 		let mut inflight = self.inflight_buffers.borrow_mut();
 		let mut n = 0;
 		while let Some(resp) = inner.receive() {
 			n += 1;
-			let i = arena::Handle::from_raw(resp.user_data as usize, ());
-			let s = BufferFutureState::Finished(error::result(resp.value).map(|v| v as u64));
+			let i = arena::Handle::from_u64(resp.user_data).expect("invalid user data");
+			let s = BufferFutureState::Finished(resp.value());
 			match mem::replace(&mut inflight[i], s) {
 				BufferFutureState::Cancelled(_) => {
 					inflight.remove(i).unwrap();
@@ -275,8 +434,73 @@ impl Queue {
 			self.inner.borrow_mut().wait(timeout)
 		}
 	}
+
+	/// Cancel all requests whose future has been dropped and wait until the buffers of those
+	/// requests have been freed.
+	///
+	/// Requests that are still awaited by a future are not affected.
+	///
+	/// Returns `false` if some requests did not finish within `timeout` per wait. The buffers
+	/// of those requests are kept until they finish or the queue is dropped.
+	pub fn drain(&self, timeout: Duration) -> bool {
+		self.cancel_dropped();
+		for _ in 0..DRAIN_MAX_WAITS {
+			self.poll();
+			self.process();
+			if self.cancelled().is_empty() {
+				return true;
+			}
+			self.inner.borrow_mut().wait(timeout);
+		}
+		false
+	}
+
+	/// Cancel all requests whose future has been dropped without waiting for them.
+	///
+	/// The buffers of those requests are freed once their response arrives, which may still
+	/// happen if the kernel already started processing them.
+	pub fn cancel_dropped(&self) {
+		for h in self.cancelled() {
+			let req = Request::Cancel { user_data: h.to_u64() };
+			// Cancel requests don't get a response, so they never count towards being full.
+			let _ = self.inner.borrow_mut().submit(0, 0, req);
+		}
+	}
+
+	fn cancelled(&self) -> Vec<arena::Handle<()>> {
+		self.inflight_buffers
+			.borrow()
+			.iter()
+			.filter(|(_, s)| matches!(s, BufferFutureState::Cancelled(_)))
+			.map(|(h, _)| h)
+			.collect()
+	}
 }
 
+impl Drop for Queue {
+	/// Drain the queue before dropping it.
+	///
+	/// If some requests still did not finish their buffers are leaked, as the kernel may still
+	/// write to them. The underlying [`nora_io_queue::Queue`] then cancels any remaining
+	/// requests and waits for a limited time for their responses before destroying the queue.
+	fn drop(&mut self) {
+		if !self.drain(DRAIN_WAIT_TIMEOUT) {
+			self.inflight_buffers
+				.get_mut()
+				.drain()
+				.for_each(|(_, s)| mem::forget(s));
+		}
+	}
+}
+
+/// How many times to wait for cancelled requests in [`Queue::drain`].
+const DRAIN_MAX_WAITS: u32 = 16;
+/// How long to wait each time for cancelled requests when dropping a [`Queue`].
+const DRAIN_WAIT_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The error returned for a response that doesn't match the kind of request.
+const UNEXPECTED_RESPONSE: error::Error = error::Error::InvalidData;
+
 /// # Safety
 ///
 /// The object must exist for at least as long as the static lifetime reference is used.
@@ -324,10 +548,19 @@ impl<B: Buf> fmt::Debug for Full<B> {
 	}
 }
 
+/// Error returned when submitting a request with a pooled buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PooledError {
+	/// The queue is full.
+	Full,
+	/// The requested buffer is not available.
+	NoBuffers,
+}
+
 enum BufferFutureState {
 	Inflight,
 	InflightWithWaker(Waker),
-	Finished(error::Result<u64>),
+	Finished(error::Result<ResponseKind>),
 	Cancelled(Box<dyn Any>),
 }
 
@@ -339,7 +572,7 @@ struct BufferFuture<'a, B: Buf> {
 }
 
 impl<B: Buf> Future for BufferFuture<'_, B> {
-	type Output = (Result<u64, error::Error>, B);
+	type Output = (error::Result<ResponseKind>, B);
 
 	/// Check if the read request has finished.
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -383,6 +616,7 @@ impl<B: Buf> Drop for BufferFuture<'_, B> {
 					*s = BufferFutureState::Cancelled(Box::new(buf));
 				}
 				Some(BufferFutureState::Finished(_)) => {
+					inflight.remove(i);
 					self.queue
 						.ready_responses
 						.set(self.queue.ready_responses.get() - 1);
@@ -402,7 +636,7 @@ struct BufferFuture2<'a, B: Buf, Bm: Buf> {
 }
 
 impl<B: Buf, Bm: Buf> Future for BufferFuture2<'_, B, Bm> {
-	type Output = (Result<u64, error::Error>, B, Bm);
+	type Output = (error::Result<ResponseKind>, B, Bm);
 
 	/// Check if the read request has finished.
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -446,7 +680,13 @@ impl<B: Buf, Bm: Buf> Drop for BufferFuture2<'_, B, Bm> {
 					// We can't drop the buffer yet as it is still in use by the queue.
 					*s = BufferFutureState::Cancelled(Box::new(buf));
 				}
-				Some(BufferFutureState::Finished(_)) | None => {}
+				Some(BufferFutureState::Finished(_)) => {
+					inflight.remove(i);
+					self.queue
+						.ready_responses
+						.set(self.queue.ready_responses.get() - 1);
+				}
+				None => {}
 				Some(BufferFutureState::Cancelled(_)) => unreachable!(),
 			}
 		}
@@ -458,11 +698,11 @@ fn poll_set_len<B: BufMut>(
 	cx: &mut Context<'_>,
 ) -> Poll<(error::Result<usize>, B)> {
 	Pin::new(fut).poll(cx).map(|(r, mut buf)| {
-		let r = r.map(|s| {
+		let r = r.and_then(|k| k.amount().ok_or(UNEXPECTED_RESPONSE));
+		if let Ok(s) = r {
 			// SAFETY: the kernel should have initialized the exact given amount of bytes.
-			unsafe { buf.set_bytes_init(s as _) };
-			s as _
-		});
+			unsafe { buf.set_bytes_init(s) };
+		}
 		(r, buf)
 	})
 }
@@ -481,6 +721,23 @@ impl<B: BufMut> Future for Read<'_, B> {
 	}
 }
 
+/// A pending read request that returns a [`Slice`] of the data that was read.
+pub struct ReadSlice<'a, B: BufMut> {
+	fut: BufferFuture<'a, B>,
+}
+
+impl<B: BufMut> Future for ReadSlice<'_, B> {
+	type Output = (error::Result<usize>, Slice<B>);
+
+	/// Check if the read request has finished.
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		poll_set_len(&mut self.fut, cx).map(|(r, buf)| {
+			let n = *r.as_ref().unwrap_or(&0);
+			(r, buf.slice(..n))
+		})
+	}
+}
+
 /// A pending write request.
 pub struct Write<'a, B: Buf> {
 	fut: BufferFuture<'a, B>,
@@ -493,7 +750,7 @@ impl<B: Buf> Future for Write<'_, B> {
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
 		Pin::new(&mut self.fut)
 			.poll(cx)
-			.map(|(r, buf)| (r.map(|s| s as _), buf))
+			.map(|(r, buf)| (r.and_then(|k| k.amount().ok_or(UNEXPECTED_RESPONSE)), buf))
 	}
 }
 
@@ -509,7 +766,7 @@ impl<B: Buf> Future for Open<'_, B> {
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
 		Pin::new(&mut self.fut)
 			.poll(cx)
-			.map(|(r, buf)| (r.map(|s| s as _), buf))
+			.map(|(r, buf)| (r.and_then(|k| k.handle().ok_or(UNEXPECTED_RESPONSE)), buf))
 	}
 }
 
@@ -525,7 +782,23 @@ impl<B: Buf> Future for Create<'_, B> {
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
 		Pin::new(&mut self.fut)
 			.poll(cx)
-			.map(|(r, buf)| (r.map(|s| s as _), buf))
+			.map(|(r, buf)| (r.and_then(|k| k.handle().ok_or(UNEXPECTED_RESPONSE)), buf))
+	}
+}
+
+/// A pending destroy request.
+pub struct Destroy<'a, B: Buf> {
+	fut: BufferFuture<'a, B>,
+}
+
+impl<B: Buf> Future for Destroy<'_, B> {
+	type Output = (error::Result<u64>, B);
+
+	/// Check if the destroy request has finished.
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		Pin::new(&mut self.fut)
+			.poll(cx)
+			.map(|(r, buf)| (r.and_then(|k| k.other().ok_or(UNEXPECTED_RESPONSE)), buf))
 	}
 }
 
@@ -539,11 +812,15 @@ impl Future for Seek<'_> {
 
 	/// Check if the seek request has finished.
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		Pin::new(&mut self.fut).poll(cx).map(|(r, _)| r)
+		Pin::new(&mut self.fut)
+			.poll(cx)
+			.map(|(r, _)| r.and_then(|k| k.position().ok_or(UNEXPECTED_RESPONSE)))
 	}
 }
 
 /// A pending share request.
+///
+/// Resolves to a value defined by the receiving object. See [`Queue::submit_share`].
 pub struct Share<'a> {
 	fut: BufferFuture<'a, ()>,
 }
@@ -553,7 +830,9 @@ impl Future for Share<'_> {
 
 	/// Check if the share request has finished.
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		Pin::new(&mut self.fut).poll(cx).map(|(r, _)| r)
+		Pin::new(&mut self.fut)
+			.poll(cx)
+			.map(|(r, _)| r.and_then(|k| k.other().ok_or(UNEXPECTED_RESPONSE)))
 	}
 }
 
@@ -566,11 +845,26 @@ impl<B: Buf, Bm: BufMut> Future for GetMeta<'_, B, Bm> {
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
 		Pin::new(&mut self.fut).poll(cx).map(|(r, b, mut bm)| {
-			if let Ok(l) = &r {
+			let r = r.and_then(|k| k.meta_length().ok_or(UNEXPECTED_RESPONSE));
+			if let Ok(l) = r {
 				// SAFETY: the kernel should have initialized the exact given amount of bytes.
-				unsafe { bm.set_bytes_init(*l as u8 as _) }
+				unsafe { bm.set_bytes_init(l.into()) }
 			}
-			(r.map(|l| l as u8), b, bm)
+			(r, b, bm)
 		})
 	}
 }
+
+pub struct SetMeta<'a, B: Buf, Bv: Buf> {
+	fut: BufferFuture2<'a, B, Bv>,
+}
+
+impl<B: Buf, Bv: Buf> Future for SetMeta<'_, B, Bv> {
+	type Output = (Result<u64, error::Error>, B, Bv);
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		Pin::new(&mut self.fut)
+			.poll(cx)
+			.map(|(r, b, bv)| (r.and_then(|k| k.other().ok_or(UNEXPECTED_RESPONSE)), b, bv))
+	}
+}
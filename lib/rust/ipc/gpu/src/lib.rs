@@ -77,6 +77,40 @@ impl Flush {
 		}
 	}
 
+	/// Decode and verify the area to be flushed fits inside a buffer of the given size.
+	///
+	/// Pixels are 3 bytes large. The stride is in pixels.
+	pub fn decode_checked(
+		raw: [u8; raw::Flush::BITS as usize / 8],
+		buffer_len: usize,
+	) -> Result<Self, DecodeError> {
+		let f = Self::decode(raw);
+		if f.stride <= u32::from(f.size.x) {
+			return Err(DecodeError::StrideTooSmall);
+		}
+		// The last row only needs to be as large as the width.
+		(u64::from(f.stride) * u64::from(f.size.y) + u64::from(f.size.x) + 1)
+			.checked_mul(3)
+			.and_then(|n| n.checked_add(f.offset))
+			.filter(|&n| n <= buffer_len as u64)
+			.map(|_| f)
+			.ok_or(DecodeError::OutOfBounds)
+	}
+
+	/// Clip the area to be flushed to a screen of the given size.
+	///
+	/// Returns the origin and the exclusive width and height of the visible part, or `None` if
+	/// no part is visible.
+	pub fn clip(&self, width: u32, height: u32) -> Option<(Point, u32, u32)> {
+		let Point { x, y } = self.origin;
+		if x >= width || y >= height {
+			return None;
+		}
+		let w = (u32::from(self.size.x) + 1).min(width - x);
+		let h = (u32::from(self.size.y) + 1).min(height - y);
+		Some((self.origin, w, h))
+	}
+
 	#[inline]
 	pub fn encode(self) -> [u8; raw::Flush::BITS as usize / 8] {
 		let mut f = raw::Flush::default();
@@ -91,6 +125,14 @@ impl Flush {
 	}
 }
 
+#[derive(Debug)]
+pub enum DecodeError {
+	/// The stride is smaller than the width.
+	StrideTooSmall,
+	/// The area extends beyond the end of the buffer.
+	OutOfBounds,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Resolution {
 	pub x: u32,
@@ -121,3 +163,98 @@ impl Resolution {
 		r
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A flush of a 640x480 region of a buffer with the same width, as sent by the window
+	/// manager.
+	#[test]
+	fn flush_full_width() {
+		let f = Flush {
+			offset: 0,
+			stride: 640,
+			origin: Point { x: 0, y: 0 },
+			size: SizeInclusive { x: 639, y: 479 },
+			buffer_id: 0,
+		};
+		let f = Flush::decode_checked(f.encode(), 640 * 480 * 3).unwrap();
+		assert_eq!((f.stride, f.size.x, f.size.y), (640, 639, 479));
+		assert!(matches!(
+			Flush::decode_checked(f.encode(), 640 * 480 * 3 - 1),
+			Err(DecodeError::OutOfBounds)
+		));
+	}
+
+	/// Copy the area of a flush to a screen like the drivers do.
+	fn blit(f: &Flush, src: &[u32], screen: &mut [u32], width: u32, height: u32) {
+		let (o, w, h) = f.clip(width, height).unwrap();
+		for y in 0..h {
+			for x in 0..w {
+				let i = f.offset as usize / 3 + (y * f.stride + x) as usize;
+				screen[((o.y + y) * width + o.x + x) as usize] = src[i];
+			}
+		}
+	}
+
+	#[test]
+	fn flush_single_pixel() {
+		let src: [u32; 16 * 16] = core::array::from_fn(|i| i as u32 + 1);
+		let mut screen = [0; 8 * 8];
+		let f = Flush {
+			offset: (16 + 1) * 3,
+			stride: 16,
+			origin: Point { x: 3, y: 5 },
+			size: SizeInclusive { x: 0, y: 0 },
+			buffer_id: 0,
+		};
+		blit(&f, &src, &mut screen, 8, 8);
+		for (i, &p) in screen.iter().enumerate() {
+			assert_eq!(p, if i == 5 * 8 + 3 { 16 + 2 } else { 0 }, "pixel {}", i);
+		}
+	}
+
+	#[test]
+	fn flush_full_screen() {
+		let src: [u32; 8 * 8] = core::array::from_fn(|i| i as u32 + 1);
+		let mut screen = [0; 8 * 8];
+		let f = Flush {
+			offset: 0,
+			stride: 8,
+			origin: Point { x: 0, y: 0 },
+			size: SizeInclusive { x: 7, y: 7 },
+			buffer_id: 0,
+		};
+		blit(&f, &src, &mut screen, 8, 8);
+		assert_eq!(&screen[..], &src[..]);
+	}
+
+	#[test]
+	fn flush_clip() {
+		let f = Flush {
+			offset: 0,
+			stride: 16,
+			origin: Point { x: 6, y: 7 },
+			size: SizeInclusive { x: 7, y: 7 },
+			buffer_id: 0,
+		};
+		assert!(matches!(f.clip(8, 8), Some((Point { x: 6, y: 7 }, 2, 1))));
+		assert!(f.clip(6, 8).is_none());
+	}
+
+	#[test]
+	fn flush_stride_too_small() {
+		let f = Flush {
+			offset: 0,
+			stride: 639,
+			origin: Point { x: 0, y: 0 },
+			size: SizeInclusive { x: 639, y: 479 },
+			buffer_id: 0,
+		};
+		assert!(matches!(
+			Flush::decode_checked(f.encode(), usize::MAX),
+			Err(DecodeError::StrideTooSmall)
+		));
+	}
+}
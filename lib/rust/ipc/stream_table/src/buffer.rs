@@ -121,9 +121,18 @@ impl Buffers {
 
 	#[inline]
 	pub fn get<'a>(&'a self, slice: Slice) -> Data<'a> {
-		let max = slice.offset as usize * self.block_size as usize;
-		assert!(max < self.total_size, "out of bounds");
-		Data { buffers: self, offset: slice.offset, len: slice.length.try_into().unwrap() }
+		self.try_get(slice).expect("out of bounds")
+	}
+
+	/// Get the data referred to by a slice, or `None` if the slice is out of bounds.
+	#[inline]
+	pub fn try_get<'a>(&'a self, slice: Slice) -> Option<Data<'a>> {
+		let start = slice.offset as usize * self.block_size as usize;
+		(start < self.total_size && slice.length as usize <= self.total_size).then(|| Data {
+			buffers: self,
+			offset: slice.offset,
+			len: slice.length.try_into().unwrap(),
+		})
 	}
 
 	#[inline]
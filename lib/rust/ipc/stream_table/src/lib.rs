@@ -9,7 +9,9 @@ mod raw {
 }
 mod buffer;
 
+pub mod positional;
 pub mod stack;
+pub mod vectored;
 
 pub use {buffer::*, raw::Id as JobId};
 
@@ -24,6 +26,9 @@ use core::{
 const REQUESTS_MASK: u32 = (1 << 7) - 1;
 const RESPONSES_MASK: u32 = (1 << 7) - 1;
 
+/// Bit in the length of a [`Slice`] response indicating more chunks follow.
+const CHUNK_MORE: u32 = 1 << 31;
+
 struct Queue {
 	base: NonNull<u8>,
 }
@@ -101,6 +106,11 @@ impl ServerQueue {
 			Response::Amount(a) => v.set_amount(a),
 			Response::Raw(a) => v.set_raw(a),
 			Response::Slice(s) => v.set_slice(s.into_raw()),
+			Response::DataChunk { slice, more } => {
+				assert!(slice.length < CHUNK_MORE, "chunk too large");
+				let length = slice.length | if more { CHUNK_MORE } else { 0 };
+				v.set_slice(Slice { length, ..slice }.into_raw())
+			}
 			Response::Share(h) => v.set_raw(1u64 << 32 | u64::from(h)),
 		};
 		let mut r = raw::Response::default();
@@ -121,6 +131,38 @@ impl ServerQueue {
 			.ok_or(Full)
 	}
 
+	/// Copy up to `max` bytes of `data` to a buffer and enqueue it as a
+	/// [`Response::DataChunk`].
+	///
+	/// Returns the amount of bytes that were sent. If not all data was sent the server should
+	/// keep the remainder and send it as a response to the next `Read` request on the same
+	/// handle.
+	///
+	/// [`Full`] is returned if either the queue or the buffer region is full.
+	pub fn try_enqueue_chunked(
+		&mut self,
+		job_id: JobId,
+		buffers: &Buffers,
+		data: &[u8],
+		max: usize,
+	) -> Result<usize, Full> {
+		let server_head = self.base.response_head_ref().load(Ordering::Relaxed);
+		if server_head == (self.response_tail - Wrapping(RESPONSES_MASK + 1)).0 {
+			return Err(Full);
+		}
+		let len = data.len().min(max).min(CHUNK_MORE as usize - 1);
+		let buf = buffers
+			.alloc(self.base.buffer_head_ref(), len)
+			.ok_or(Full)?;
+		buf.copy_from(0, &data[..len]);
+		let slice = Slice { offset: buf.offset() as _, length: len as _ };
+		self.enqueue(
+			job_id,
+			Response::DataChunk { slice, more: len < data.len() },
+		);
+		Ok(len)
+	}
+
 	#[inline]
 	pub fn dequeue(&mut self) -> Option<(Handle, JobId, Request)> {
 		let index = self.base.request_tail_ref().load(Ordering::Acquire);
@@ -151,6 +193,10 @@ impl ServerQueue {
 					T::SeekCurrent => R::Seek { from: S::Current(args.offset_s()) },
 					T::SeekEnd => R::Seek { from: S::End(args.offset_s() as _) },
 					T::Share => R::Share { share: args.share() },
+					T::Readv => R::Readv { ops: Slice::from_raw(args.slice()) },
+					T::Writev => R::Writev { ops: Slice::from_raw(args.slice()) },
+					T::ReadAt => R::ReadAt { args: Slice::from_raw(args.slice()) },
+					T::WriteAt => R::WriteAt { data: Slice::from_raw(args.slice()) },
 				},
 			)
 		})
@@ -199,6 +245,10 @@ impl ClientQueue {
 				SeekFrom::End(f) => (T::SeekEnd, v.set_offset_s(f)),
 			},
 			R::Share { share } => (T::Share, v.set_share(share)),
+			R::Readv { ops } => (T::Readv, v.set_slice(ops.into_raw())),
+			R::Writev { ops } => (T::Writev, v.set_slice(ops.into_raw())),
+			R::ReadAt { args } => (T::ReadAt, v.set_slice(args.into_raw())),
+			R::WriteAt { data } => (T::WriteAt, v.set_slice(data.into_raw())),
 		};
 		let mut r = raw::Request::default();
 		r.set_ty(ty);
@@ -266,6 +316,10 @@ pub enum Request {
 	Destroy { path: Slice },
 	Seek { from: SeekFrom },
 	Share { share: Handle },
+	Readv { ops: Slice },
+	Writev { ops: Slice },
+	ReadAt { args: Slice },
+	WriteAt { data: Slice },
 }
 
 pub enum Response {
@@ -275,6 +329,17 @@ pub enum Response {
 	Amount(u32),
 	Raw(u64),
 	Slice(Slice),
+	/// Part of the data for a `Read` request.
+	///
+	/// If `more` is set the client should issue another `Read` on the same handle to receive
+	/// the next chunk. The client signals it has consumed a chunk by freeing its buffer and
+	/// issuing the next `Read`. A `Read` after the last chunk starts a new transfer.
+	///
+	/// The length of the slice must be less than `1 << 31`.
+	DataChunk {
+		slice: Slice,
+		more: bool,
+	},
 	Share(u32),
 }
 
@@ -292,6 +357,16 @@ impl AnyResponse {
 		self.get()
 			.map(|v| Slice::from_raw(raw::Slice::from_raw(&v.to_le_bytes(), 0)))
 	}
+
+	/// Decode a slice that may be part of a [`Response::DataChunk`].
+	///
+	/// Returns the slice and whether more chunks follow.
+	pub fn as_chunk(&self) -> Result<(Slice, bool), i16> {
+		self.as_slice().map(|s| {
+			let length = s.length & !CHUNK_MORE;
+			(Slice { length, ..s }, s.length & CHUNK_MORE != 0)
+		})
+	}
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -0,0 +1,85 @@
+//! Encoding of positional requests.
+//!
+//! A positional request reads or writes at an explicit offset. The seek head of the handle is
+//! neither used nor modified, so concurrent positional requests on the same handle don't
+//! interfere with each other. Servers that don't support positioning, e.g. because the object
+//! is a stream, respond with an `InvalidOperation` error.
+//!
+//! All integers are little-endian.
+//!
+//! ## `ReadAt`
+//!
+//! The request data is the `u64` offset followed by the `u32` amount of bytes to read.
+//!
+//! The response is the same as that of `Read`.
+//!
+//! ## `WriteAt`
+//!
+//! The request data is the `u64` offset followed by the data to write.
+//!
+//! The response is the same as that of `Write`. The offset is not included in the amount of
+//! bytes written.
+
+/// The size of the offset that precedes the arguments of a positional request.
+pub const OFFSET_SIZE: usize = 8;
+
+/// The size of the data of a `ReadAt` request.
+pub const READ_AT_SIZE: usize = OFFSET_SIZE + 4;
+
+/// Encode the data of a `ReadAt` request.
+pub fn encode_read_at(offset: u64, amount: u32) -> [u8; READ_AT_SIZE] {
+	let mut b = [0; READ_AT_SIZE];
+	b[..OFFSET_SIZE].copy_from_slice(&offset.to_le_bytes());
+	b[OFFSET_SIZE..].copy_from_slice(&amount.to_le_bytes());
+	b
+}
+
+/// Decode the offset and amount of a `ReadAt` request.
+pub fn decode_read_at(data: &[u8]) -> Result<(u64, u32), InvalidArgs> {
+	let data = <&[u8; READ_AT_SIZE]>::try_from(data).map_err(|_| InvalidArgs)?;
+	let (o, a) = data.split_at(OFFSET_SIZE);
+	Ok((
+		u64::from_le_bytes(o.try_into().unwrap()),
+		u32::from_le_bytes(a.try_into().unwrap()),
+	))
+}
+
+/// Decode the offset of a `WriteAt` request.
+///
+/// Only the first [`OFFSET_SIZE`] bytes are used, so a buffer holding only the start of the
+/// request data is sufficient.
+pub fn decode_offset(data: &[u8]) -> Result<u64, InvalidArgs> {
+	data.get(..OFFSET_SIZE)
+		.map(|o| u64::from_le_bytes(o.try_into().unwrap()))
+		.ok_or(InvalidArgs)
+}
+
+/// Split a `WriteAt` request into the offset and the data to write.
+pub fn decode_write_at(data: &[u8]) -> Result<(u64, &[u8]), InvalidArgs> {
+	Ok((decode_offset(data)?, &data[OFFSET_SIZE..]))
+}
+
+#[derive(Debug)]
+pub struct InvalidArgs;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn read_at() {
+		let b = encode_read_at(1 << 40 | 512, 4096);
+		assert_eq!(decode_read_at(&b).unwrap(), (1 << 40 | 512, 4096));
+		assert!(decode_read_at(&b[..READ_AT_SIZE - 1]).is_err());
+	}
+
+	#[test]
+	fn write_at() {
+		let mut b = [0; 11];
+		b[..8].copy_from_slice(&1024u64.to_le_bytes());
+		b[8..].copy_from_slice(b"abc");
+		assert_eq!(decode_write_at(&b).unwrap(), (1024, &b"abc"[..]));
+		assert_eq!(decode_write_at(&b[..8]).unwrap(), (1024, &b""[..]));
+		assert!(decode_write_at(&b[..7]).is_err());
+	}
+}
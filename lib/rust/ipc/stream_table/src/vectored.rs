@@ -0,0 +1,246 @@
+//! Encoding of vectored requests.
+//!
+//! A vectored request bundles up to [`MAX_OPS`] small reads or writes on the same handle under
+//! a single job. The operations are performed in order. The server stops at the first
+//! operation that fails or transfers less than requested; the remaining operations are not
+//! included in the response.
+//!
+//! All integers are little-endian.
+//!
+//! ## `Readv`
+//!
+//! The request data is a list of `u32` amounts, one per operation.
+//!
+//! The response is a [`Response::Slice`](crate::Response::Slice) with one `i64` result per
+//! operation, followed by the data of all operations concatenated. A result is either the amount
+//! of bytes read or a negative error code.
+//!
+//! ## `Writev`
+//!
+//! The request data is a list of operations, each a `u32` length followed by that many bytes.
+//!
+//! The response is a [`Response::Slice`](crate::Response::Slice) with one `i64` result per
+//! operation. A result is either the amount of bytes written or a negative error code.
+
+/// The maximum amount of operations in a single vectored request.
+pub const MAX_OPS: usize = 16;
+
+/// The size of a single encoded result.
+pub const RESULT_SIZE: usize = 8;
+
+/// Decode the amounts of a `Readv` request.
+pub fn decode_read_ops(data: &[u8]) -> Result<impl Iterator<Item = u32> + '_, InvalidOps> {
+	if data.len() % 4 != 0 || data.len() / 4 > MAX_OPS {
+		return Err(InvalidOps);
+	}
+	Ok(data
+		.chunks_exact(4)
+		.map(|c| u32::from_le_bytes(c.try_into().unwrap())))
+}
+
+/// Decode the operations of a `Writev` request.
+pub fn decode_write_ops(data: &[u8]) -> Result<WriteOps<'_>, InvalidOps> {
+	// Validate everything up front so the iterator can't fail halfway.
+	let mut d = data;
+	for _ in 0..=MAX_OPS {
+		if d.is_empty() {
+			return Ok(WriteOps { data });
+		}
+		d = split_write_op(d).ok_or(InvalidOps)?.1;
+	}
+	Err(InvalidOps)
+}
+
+/// Encode a list of `Writev` operations.
+///
+/// Returns the amount of bytes written to `out` or `None` if `out` is too small or there are too
+/// many operations.
+pub fn encode_write_ops<'a>(
+	ops: impl IntoIterator<Item = &'a [u8]>,
+	out: &mut [u8],
+) -> Option<usize> {
+	let mut n = 0;
+	for (i, op) in ops.into_iter().enumerate() {
+		if i >= MAX_OPS {
+			return None;
+		}
+		let len = u32::try_from(op.len()).ok()?;
+		let o = out.get_mut(n..)?.get_mut(..4 + op.len())?;
+		o[..4].copy_from_slice(&len.to_le_bytes());
+		o[4..].copy_from_slice(op);
+		n += o.len();
+	}
+	Some(n)
+}
+
+/// Encode the results of a vectored request.
+///
+/// Returns the amount of bytes written to `out`.
+///
+/// # Panics
+///
+/// `out` is too small.
+pub fn encode_results(
+	results: impl IntoIterator<Item = Result<u32, i64>>,
+	out: &mut [u8],
+) -> usize {
+	let mut n = 0;
+	for r in results {
+		let r = r.map_or_else(|e| e, i64::from);
+		out[n..][..RESULT_SIZE].copy_from_slice(&r.to_le_bytes());
+		n += RESULT_SIZE;
+	}
+	n
+}
+
+/// Decode the results of a vectored request.
+///
+/// Trailing data, i.e. the data of a `Readv` response, is ignored.
+pub fn decode_results(data: &[u8], count: usize) -> impl Iterator<Item = Result<u32, i64>> + '_ {
+	data.chunks_exact(RESULT_SIZE).take(count).map(|c| {
+		let r = i64::from_le_bytes(c.try_into().unwrap());
+		u32::try_from(r).map_err(|_| r)
+	})
+}
+
+/// Decode the response to a `Readv` request.
+///
+/// `amounts` are the amounts of the request. Returns the data of all operations that succeeded
+/// or the error of the first operation if it failed.
+pub fn decode_read_response<'a>(
+	data: &'a [u8],
+	amounts: &[u32],
+) -> Result<Result<&'a [u8], i64>, InvalidOps> {
+	let (mut count, mut len) = (0, 0);
+	// Only look at results that must be present so the data isn't mistaken for a result.
+	let mut results = decode_results(data, amounts.len());
+	for &amount in amounts {
+		count += 1;
+		match results.next().ok_or(InvalidOps)? {
+			Ok(n) if n > amount => return Err(InvalidOps),
+			Ok(n) => {
+				len += n as usize;
+				if n < amount {
+					break;
+				}
+			}
+			Err(e) if count == 1 => return Ok(Err(e)),
+			Err(_) => break,
+		}
+	}
+	data.get(count * RESULT_SIZE..)
+		.and_then(|d| d.get(..len))
+		.map(Ok)
+		.ok_or(InvalidOps)
+}
+
+/// Decode the response to a `Writev` request.
+///
+/// `lengths` are the lengths of the operations of the request. Returns the total amount of
+/// bytes written or the error of the first operation if it failed.
+///
+/// Operations without a result are treated as not performed.
+pub fn decode_write_response(data: &[u8], lengths: &[u32]) -> Result<Result<u64, i64>, InvalidOps> {
+	if data.len() % RESULT_SIZE != 0 {
+		return Err(InvalidOps);
+	}
+	let mut total = 0;
+	for (i, (r, &length)) in decode_results(data, lengths.len()).zip(lengths).enumerate() {
+		match r {
+			Ok(n) if n > length => return Err(InvalidOps),
+			Ok(n) => {
+				total += u64::from(n);
+				if n < length {
+					break;
+				}
+			}
+			Err(e) if i == 0 => return Ok(Err(e)),
+			Err(_) => break,
+		}
+	}
+	Ok(Ok(total))
+}
+
+fn split_write_op(data: &[u8]) -> Option<(&[u8], &[u8])> {
+	let len = u32::from_le_bytes(data.get(..4)?.try_into().unwrap());
+	let data = &data[4..];
+	let len = usize::try_from(len).ok().filter(|&l| l <= data.len())?;
+	Some(data.split_at(len))
+}
+
+/// An iterator over the operations of a `Writev` request.
+pub struct WriteOps<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Iterator for WriteOps<'a> {
+	type Item = &'a [u8];
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (op, rest) = split_write_op(self.data)?;
+		self.data = rest;
+		Some(op)
+	}
+}
+
+#[derive(Debug)]
+pub struct InvalidOps;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn write_ops() {
+		let mut buf = [0; 64];
+		let n = encode_write_ops([&b"abc"[..], b"", b"de"], &mut buf).unwrap();
+		assert_eq!(n, 4 + 3 + 4 + 4 + 2);
+		let mut it = decode_write_ops(&buf[..n]).unwrap();
+		assert_eq!(it.next(), Some(&b"abc"[..]));
+		assert_eq!(it.next(), Some(&b""[..]));
+		assert_eq!(it.next(), Some(&b"de"[..]));
+		assert_eq!(it.next(), None);
+		assert!(decode_write_ops(&buf[..n - 1]).is_err());
+	}
+
+	#[test]
+	fn results() {
+		let mut buf = [0; 64];
+		let n = encode_results([Ok(8), Err(-2)], &mut buf);
+		let mut it = decode_results(&buf[..n], 2);
+		assert_eq!(it.next(), Some(Ok(8)));
+		assert_eq!(it.next(), Some(Err(-2)));
+		assert_eq!(it.next(), None);
+	}
+
+	#[test]
+	fn read_response() {
+		let mut buf = [0; 64];
+		let n = encode_results([Ok(4), Ok(2)], &mut buf);
+		buf[n..][..6].copy_from_slice(b"abcdef");
+		let r = decode_read_response(&buf[..n + 6], &[4, 8, 8]).unwrap();
+		assert_eq!(r, Ok(&b"abcdef"[..]));
+		assert!(decode_read_response(&buf[..n + 5], &[4, 8, 8]).is_err());
+		assert!(decode_read_response(&buf[..n + 6], &[4, 1]).is_err());
+
+		let n = encode_results([Err(-4)], &mut buf);
+		assert_eq!(decode_read_response(&buf[..n], &[4, 8]).unwrap(), Err(-4));
+		let n = encode_results([Ok(4), Err(-4)], &mut buf);
+		buf[n..][..4].copy_from_slice(b"abcd");
+		let r = decode_read_response(&buf[..n + 4], &[4, 8]).unwrap();
+		assert_eq!(r, Ok(&b"abcd"[..]));
+	}
+
+	#[test]
+	fn write_response() {
+		let mut buf = [0; 64];
+		let n = encode_results([Ok(3), Ok(2)], &mut buf);
+		assert_eq!(decode_write_response(&buf[..n], &[3, 2]).unwrap(), Ok(5));
+		assert_eq!(decode_write_response(&buf[..n], &[3, 4, 1]).unwrap(), Ok(5));
+		assert_eq!(decode_write_response(&buf[..8], &[3, 2]).unwrap(), Ok(3));
+		assert!(decode_write_response(&buf[..n - 1], &[3, 2]).is_err());
+		assert!(decode_write_response(&buf[..n], &[2, 2]).is_err());
+		let n = encode_results([Err(-8)], &mut buf);
+		assert_eq!(decode_write_response(&buf[..n], &[3]).unwrap(), Err(-8));
+	}
+}
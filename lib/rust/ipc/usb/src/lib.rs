@@ -6,9 +6,21 @@ pub const SEND_TY_PUBLIC_OBJECT: u8 = 0;
 pub const SEND_TY_DATA_OUT: u8 = 1;
 pub const SEND_TY_DATA_IN: u8 = 2;
 pub const SEND_TY_GET_DESCRIPTOR: u8 = 3;
+pub const SEND_TY_SET_REPORT: u8 = 4;
+pub const SEND_TY_GET_REPORT: u8 = 5;
+pub const SEND_TY_ISO_OUT: u8 = 6;
+pub const SEND_TY_ISO_IN: u8 = 7;
 
 pub const RECV_TY_DATA_IN: u8 = 0;
 pub const RECV_TY_ERROR: u8 = 1;
+pub const RECV_TY_ISO_IN: u8 = 2;
+
+/// Status of an isochronous packet that was received successfully.
+pub const ISO_STATUS_OK: u8 = 0;
+/// Status of an isochronous packet that was not received in its (micro)frame.
+pub const ISO_STATUS_MISSED: u8 = 1;
+/// Status of an isochronous packet that was received with errors, e.g. a CRC mismatch.
+pub const ISO_STATUS_ERROR: u8 = 2;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Endpoint {
@@ -59,6 +71,30 @@ impl TryFrom<&[u8]> for Endpoint {
 #[derive(Debug)]
 pub struct InvalidEndpoint;
 
+/// HID report type, as used in `GET_REPORT` and `SET_REPORT` requests.
+#[derive(Clone, Copy, Debug)]
+pub enum ReportType {
+	Input = 1,
+	Output = 2,
+	Feature = 3,
+}
+
+impl TryFrom<u8> for ReportType {
+	type Error = InvalidReportType;
+
+	fn try_from(n: u8) -> Result<Self, Self::Error> {
+		Ok(match n {
+			1 => Self::Input,
+			2 => Self::Output,
+			3 => Self::Feature,
+			_ => return Err(InvalidReportType),
+		})
+	}
+}
+
+#[derive(Debug)]
+pub struct InvalidReportType;
+
 pub fn send_public_object<R>(f: impl FnOnce(&[u8]) -> R) -> R {
 	f(&[SEND_TY_PUBLIC_OBJECT])
 }
@@ -83,15 +119,76 @@ pub fn send_get_descriptor<R>(
 	f(&[SEND_TY_GET_DESCRIPTOR, recipient, ty, index, a, b])
 }
 
+/// Send a HID `SET_REPORT` request to the interface.
+///
+/// The report data must be sent in a separate message immediately after.
+pub fn send_set_report<R>(ty: ReportType, id: u8, f: impl FnOnce(&[u8]) -> R) -> R {
+	f(&[SEND_TY_SET_REPORT, ty as _, id])
+}
+
+/// Send a HID `GET_REPORT` request to the interface.
+///
+/// The report is returned as [`Recv::DataIn`] on endpoint 0.
+pub fn send_get_report<R>(ty: ReportType, id: u8, len: u16, f: impl FnOnce(&[u8]) -> R) -> R {
+	let [a, b] = len.to_le_bytes();
+	f(&[SEND_TY_GET_REPORT, ty as _, id, a, b])
+}
+
+/// Send an isochronous OUT transfer.
+///
+/// The data of all packets must be sent back to back in a separate message immediately after.
+/// Every packet is `packet_size` bytes large, except the last which may be shorter.
+///
+/// Format: `[SEND_TY_ISO_OUT, ep, packets: u16le, packet_size: u16le]`
+pub fn send_iso_out<R>(
+	ep: Endpoint,
+	packets: u16,
+	packet_size: u16,
+	f: impl FnOnce(&[u8]) -> R,
+) -> R {
+	let [a, b] = packets.to_le_bytes();
+	let [c, d] = packet_size.to_le_bytes();
+	f(&[SEND_TY_ISO_OUT, ep as _, a, b, c, d])
+}
+
+/// Send an isochronous IN transfer.
+///
+/// The data is returned as [`Recv::IsoIn`]. Up to `packet_size` bytes are received per packet.
+///
+/// Format: `[SEND_TY_ISO_IN, ep, packets: u16le, packet_size: u16le]`
+pub fn send_iso_in<R>(
+	ep: Endpoint,
+	packets: u16,
+	packet_size: u16,
+	f: impl FnOnce(&[u8]) -> R,
+) -> R {
+	let [a, b] = packets.to_le_bytes();
+	let [c, d] = packet_size.to_le_bytes();
+	f(&[SEND_TY_ISO_IN, ep as _, a, b, c, d])
+}
+
 pub fn recv_parse(msg: &[u8]) -> Result<Recv<'_>, &'static str> {
 	let f = |i, j| msg.get(i..j).ok_or("truncated message");
 	let fe = |i| msg.get(i..).ok_or("truncated message");
 	let fs = |i| str::from_utf8(fe(i)?).map_err(|_| "invalid UTF-8");
 	let f1 = |i| f(i, i + 1).map(|l| l[0]);
+	let f2 = |i| f(i, i + 2).map(|l| u16::from_le_bytes(l[0..2].try_into().unwrap()));
 	let f4 = |i| f(i, i + 4).map(|l| u32::from_le_bytes(l[0..4].try_into().unwrap()));
 	Ok(match f1(0)? {
 		RECV_TY_DATA_IN => Recv::DataIn { ep: f1(1)?, data: fe(2)? },
 		RECV_TY_ERROR => Recv::Error { id: f4(1)?, code: f1(4)?, message: fs(5)? },
+		RECV_TY_ISO_IN => {
+			let end = 4 + usize::from(f2(2)?) * 3;
+			let packets = IsoPackets { descriptors: f(4, end)?, data: fe(end)? };
+			// Ensure the iterator can't go out of bounds.
+			let total = (packets.descriptors.chunks(3))
+				.map(|d| usize::from(u16::from_le_bytes([d[1], d[2]])))
+				.sum::<usize>();
+			if total > packets.data.len() {
+				return Err("truncated message");
+			}
+			Recv::IsoIn { ep: f1(1)?, packets }
+		}
 		_ => return Err("unknown message type"),
 	})
 }
@@ -99,4 +196,42 @@ pub fn recv_parse(msg: &[u8]) -> Result<Recv<'_>, &'static str> {
 pub enum Recv<'a> {
 	DataIn { ep: u8, data: &'a [u8] },
 	Error { id: u32, code: u8, message: &'a str },
+	IsoIn { ep: u8, packets: IsoPackets<'a> },
+}
+
+/// Iterator over the packets of an isochronous IN transfer.
+///
+/// Format: `[RECV_TY_ISO_IN, ep, packets: u16le, [status: u8, length: u16le]..., data...]`
+///
+/// The data of all packets is stored back to back.
+#[derive(Clone)]
+pub struct IsoPackets<'a> {
+	descriptors: &'a [u8],
+	data: &'a [u8],
+}
+
+impl<'a> Iterator for IsoPackets<'a> {
+	type Item = IsoPacket<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let d = self.descriptors.get(..3)?;
+		let (status, len) = (d[0], u16::from_le_bytes([d[1], d[2]]));
+		self.descriptors = &self.descriptors[3..];
+		let (data, rest) = self.data.split_at(len.into());
+		self.data = rest;
+		Some(IsoPacket { status, data })
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let n = self.descriptors.len() / 3;
+		(n, Some(n))
+	}
+}
+
+impl ExactSizeIterator for IsoPackets<'_> {}
+
+pub struct IsoPacket<'a> {
+	/// One of `ISO_STATUS_*`.
+	pub status: u8,
+	pub data: &'a [u8],
 }
@@ -84,6 +84,15 @@ pub enum Event {
 	Resize(Resolution),
 	Input(input::Input),
 	Close,
+	/// The pointer moved. The position is relative to the top-left corner of the window.
+	PointerMove {
+		x: u32,
+		y: u32,
+	},
+	PointerButton {
+		button: u8,
+		pressed: bool,
+	},
 }
 
 #[derive(Debug)]
@@ -99,6 +108,14 @@ impl Event {
 				Self::Input(e.args().input().try_into().map_err(|_| InvalidEvent)?)
 			}
 			raw::EventType::Close => Self::Close,
+			raw::EventType::PointerMove => {
+				let p = e.args().pointer_move();
+				Self::PointerMove { x: p.x(), y: p.y() }
+			}
+			raw::EventType::PointerButton => {
+				let b = e.args().pointer_button();
+				Self::PointerButton { button: b.button(), pressed: b.pressed() != 0 }
+			}
 			_ => return Err(InvalidEvent),
 		})
 	}
@@ -122,6 +139,21 @@ impl Event {
 			Self::Close => {
 				e.set_ty(raw::EventType::Close);
 			}
+			Self::PointerMove { x, y } => {
+				e.set_ty(raw::EventType::PointerMove);
+				let mut a = raw::EventArgs::default();
+				a.set_pointer_move(Point { x, y }.to_raw());
+				e.set_args(a);
+			}
+			Self::PointerButton { button, pressed } => {
+				e.set_ty(raw::EventType::PointerButton);
+				let mut b = raw::PointerButton::default();
+				b.set_button(button);
+				b.set_pressed(pressed.into());
+				let mut a = raw::EventArgs::default();
+				a.set_pointer_button(b);
+				e.set_args(a);
+			}
 		}
 		let mut r = [0; 14];
 		e.to_raw(&mut r, 0);
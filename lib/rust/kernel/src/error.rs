@@ -27,6 +27,7 @@ impl_! {
 	CantCreateObject 6
 	InvalidObject 7
 	InvalidData 8
+	WouldBlock 9
 }
 
 impl<T: raw::RawError> From<T> for Error {
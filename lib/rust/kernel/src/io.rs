@@ -12,8 +12,9 @@
 // programs on some platforms (e.g. 1 byte on x86 for LEA rd, [rs] vs LEA rd, [rs + off8])
 
 use core::{
+	marker::PhantomData,
 	mem::{self, MaybeUninit},
-	ops::{Deref, DerefMut},
+	ops::{BitOr, Deref, DerefMut},
 	ptr::NonNull,
 	slice,
 	sync::atomic::{AtomicU32, Ordering},
@@ -56,6 +57,23 @@ impl Request {
 	pub const SEEK: u8 = 7;
 	pub const CLOSE: u8 = 8;
 	pub const SHARE: u8 = 9;
+	pub const CANCEL: u8 = 10;
+	/// Read at an offset without using the seek head.
+	///
+	/// Only supported by [`DoIo`] for now.
+	pub const READ_AT: u8 = 11;
+	/// Write at an offset without using the seek head.
+	///
+	/// Only supported by [`DoIo`] for now.
+	pub const WRITE_AT: u8 = 12;
+	/// Read into multiple buffers at once.
+	///
+	/// Only supported by [`DoIo`] for now.
+	pub const READ_VECTORED: u8 = 13;
+	/// Write multiple buffers at once.
+	///
+	/// Only supported by [`DoIo`] for now.
+	pub const WRITE_VECTORED: u8 = 14;
 
 	#[inline(always)]
 	pub fn read(user_data: u64, handle: Handle, buf: &mut [u8]) -> Self {
@@ -154,8 +172,20 @@ impl Request {
 
 	#[inline(always)]
 	pub fn create(user_data: u64, handle: Handle, path: &[u8]) -> Self {
+		Self::create_with_flags(user_data, handle, path, CreateFlags::empty())
+	}
+
+	/// Create an object. The flags are stored in the first 8-bit argument.
+	#[inline(always)]
+	pub fn create_with_flags(
+		user_data: u64,
+		handle: Handle,
+		path: &[u8],
+		flags: CreateFlags,
+	) -> Self {
 		Self {
 			ty: Self::CREATE,
+			arguments_8: [flags.bits(), 0, 0],
 			handle,
 			arguments_64: [path.as_ptr() as u64, path.len() as u64],
 			user_data,
@@ -193,8 +223,33 @@ impl Request {
 	}
 
 	#[inline(always)]
-	pub fn destroy(user_data: u64, handle: Handle) -> Self {
-		Self { ty: Self::DESTROY, handle, user_data, ..Default::default() }
+	pub fn destroy(user_data: u64, handle: Handle, path: &[u8]) -> Self {
+		Self {
+			ty: Self::DESTROY,
+			handle,
+			arguments_64: [path.as_ptr() as u64, path.len() as u64],
+			user_data,
+			..Default::default()
+		}
+	}
+
+	/// Cancel the pending request with the given user data.
+	///
+	/// The cancelled request completes with [`Error::Cancelled`] if it was still pending.
+	/// The cancel request itself never gets a response.
+	///
+	/// [`Error::Cancelled`]: crate::error::Error::Cancelled
+	#[inline(always)]
+	pub fn cancel(cancel_user_data: u64) -> Self {
+		Self { ty: Self::CANCEL, arguments_64: [cancel_user_data, 0], ..Default::default() }
+	}
+
+	/// Cancel all pending requests.
+	///
+	/// See [`Request::cancel`].
+	#[inline(always)]
+	pub fn cancel_all() -> Self {
+		Self { ty: Self::CANCEL, arguments_8: [1, 0, 0], ..Default::default() }
 	}
 }
 
@@ -286,6 +341,47 @@ pub enum DoIoOp<'a> {
 	ReadUninit { buf: &'a mut [MaybeUninit<u8>] },
 	/// Write data to an object.
 	Write { data: &'a [u8] },
+	/// Read data at an offset from an object.
+	///
+	/// The seek head is not used nor modified. Fails with [`Error::InvalidOperation`] if the
+	/// object does not support positioning.
+	///
+	/// [`Error::InvalidOperation`]: crate::error::Error::InvalidOperation
+	ReadAt { buf: &'a mut [u8], offset: u64 },
+	/// Read data at an offset from an object.
+	///
+	/// The same caveats as [`DoIoOp::ReadAt`] apply.
+	ReadUninitAt { buf: &'a mut [MaybeUninit<u8>], offset: u64 },
+	/// Write data at an offset to an object.
+	///
+	/// The same caveats as [`DoIoOp::ReadAt`] apply.
+	WriteAt { data: &'a [u8], offset: u64 },
+	/// Read into multiple buffers in order.
+	///
+	/// At most [`MAX_IO_VECS`] buffers can be used. Reading stops at the first buffer that is
+	/// not filled completely. If a read fails after some data has been read the error is
+	/// discarded. Fails with [`Error::InvalidOperation`] if the object doesn't support vectored
+	/// reads.
+	///
+	/// [`Error::InvalidOperation`]: crate::error::Error::InvalidOperation
+	ReadVectored { bufs: &'a [IoVecMut<'a>] },
+	/// Write multiple buffers in order.
+	///
+	/// The same caveats as [`DoIoOp::ReadVectored`] apply.
+	WriteVectored { bufs: &'a [IoVec<'a>] },
+	/// Read data from an object, failing with [`Error::WouldBlock`] if no data is ready.
+	///
+	/// [`Error::WouldBlock`]: crate::error::Error::WouldBlock
+	ReadNonblocking { buf: &'a mut [u8] },
+	/// Read data from an object, failing with [`Error::WouldBlock`] if no data is ready.
+	///
+	/// [`Error::WouldBlock`]: crate::error::Error::WouldBlock
+	ReadUninitNonblocking { buf: &'a mut [MaybeUninit<u8>] },
+	/// Write data to an object, failing with [`Error::WouldBlock`] if it can't be written
+	/// immediately.
+	///
+	/// [`Error::WouldBlock`]: crate::error::Error::WouldBlock
+	WriteNonblocking { data: &'a [u8] },
 	/// Open an object at the given location.
 	Open { path: &'a [u8] },
 	/// Get meta-information about an object.
@@ -295,7 +391,7 @@ pub enum DoIoOp<'a> {
 	/// Set meta-information about an object.
 	SetMeta { property: &'a TinySlice<u8>, value: &'a TinySlice<u8> },
 	/// Create an object at the given location.
-	Create { path: &'a [u8] },
+	Create { path: &'a [u8], flags: CreateFlags },
 	/// Destroy an object at the given location.
 	Destroy { path: &'a [u8] },
 	/// Set the seek head.
@@ -307,6 +403,17 @@ pub enum DoIoOp<'a> {
 }
 
 impl DoIo<'_> {
+	/// Flag set on the request type if the operation must not block.
+	///
+	/// If the operation can't finish immediately it is not performed and fails with
+	/// [`Error::WouldBlock`]. Only reads and writes support this flag. Objects that can't tell
+	/// in advance whether an operation would block, e.g. those served by a stream table, fail
+	/// with [`Error::InvalidOperation`].
+	///
+	/// [`Error::WouldBlock`]: crate::error::Error::WouldBlock
+	/// [`Error::InvalidOperation`]: crate::error::Error::InvalidOperation
+	pub const NONBLOCKING: u8 = 1 << 7;
+
 	#[inline]
 	pub(crate) fn into_args(self) -> (u8, Handle, RawDoIo) {
 		use RawDoIo::*;
@@ -316,6 +423,38 @@ impl DoIo<'_> {
 			DoIoOp::Read { buf } => (R::READ, h, N2(buf.as_ptr() as _, buf.len())),
 			DoIoOp::ReadUninit { buf } => (R::READ, h, N2(buf.as_ptr() as _, buf.len())),
 			DoIoOp::Write { data } => (R::WRITE, h, N2(data.as_ptr() as _, data.len())),
+			DoIoOp::ReadAt { buf, offset } => {
+				(R::READ_AT, h, N3(buf.as_ptr() as _, buf.len(), offset as _))
+			}
+			DoIoOp::ReadUninitAt { buf, offset } => {
+				(R::READ_AT, h, N3(buf.as_ptr() as _, buf.len(), offset as _))
+			}
+			DoIoOp::WriteAt { data, offset } => (
+				R::WRITE_AT,
+				h,
+				N3(data.as_ptr() as _, data.len(), offset as _),
+			),
+			DoIoOp::ReadVectored { bufs } => {
+				(R::READ_VECTORED, h, N2(bufs.as_ptr() as _, bufs.len()))
+			}
+			DoIoOp::WriteVectored { bufs } => {
+				(R::WRITE_VECTORED, h, N2(bufs.as_ptr() as _, bufs.len()))
+			}
+			DoIoOp::ReadNonblocking { buf } => (
+				R::READ | Self::NONBLOCKING,
+				h,
+				N2(buf.as_ptr() as _, buf.len()),
+			),
+			DoIoOp::ReadUninitNonblocking { buf } => (
+				R::READ | Self::NONBLOCKING,
+				h,
+				N2(buf.as_ptr() as _, buf.len()),
+			),
+			DoIoOp::WriteNonblocking { data } => (
+				R::WRITE | Self::NONBLOCKING,
+				h,
+				N2(data.as_ptr() as _, data.len()),
+			),
 			DoIoOp::GetMeta { property, value } => (
 				R::GET_META,
 				h,
@@ -344,7 +483,11 @@ impl DoIo<'_> {
 				),
 			),
 			DoIoOp::Open { path } => (R::OPEN, h, N2(path.as_ptr() as _, path.len())),
-			DoIoOp::Create { path } => (R::CREATE, h, N2(path.as_ptr() as _, path.len())),
+			DoIoOp::Create { path, flags } => (
+				R::CREATE,
+				h,
+				N3(path.as_ptr() as _, path.len(), flags.bits().into()),
+			),
 			DoIoOp::Destroy { path } => (R::DESTROY, h, N2(path.as_ptr() as _, path.len())),
 			DoIoOp::Seek { from } => {
 				let (t, o) = from.into_raw();
@@ -376,6 +519,106 @@ impl Default for Request {
 	}
 }
 
+/// The maximum amount of buffers in a single vectored request.
+pub const MAX_IO_VECS: usize = 16;
+
+/// A buffer to write for a vectored request.
+///
+/// The layout is a pointer followed by a length.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct IoVec<'a> {
+	ptr: NonNull<u8>,
+	len: usize,
+	_marker: PhantomData<&'a [u8]>,
+}
+
+impl<'a> IoVec<'a> {
+	#[inline(always)]
+	pub fn new(buf: &'a [u8]) -> Self {
+		Self { ptr: NonNull::from(buf).cast(), len: buf.len(), _marker: PhantomData }
+	}
+}
+
+/// A buffer to read into for a vectored request.
+///
+/// The layout is a pointer followed by a length.
+#[repr(C)]
+pub struct IoVecMut<'a> {
+	ptr: NonNull<u8>,
+	len: usize,
+	_marker: PhantomData<&'a mut [MaybeUninit<u8>]>,
+}
+
+impl<'a> IoVecMut<'a> {
+	#[inline(always)]
+	pub fn new(buf: &'a mut [MaybeUninit<u8>]) -> Self {
+		Self { ptr: NonNull::from(&mut *buf).cast(), len: buf.len(), _marker: PhantomData }
+	}
+}
+
+/// Flags for a create request.
+///
+/// Objects that can't honour a flag fail the request with [`Error::InvalidOperation`] instead
+/// of ignoring it. Unknown flags are rejected with [`Error::InvalidData`].
+///
+/// [`Error::InvalidOperation`]: crate::error::Error::InvalidOperation
+/// [`Error::InvalidData`]: crate::error::Error::InvalidData
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CreateFlags(u8);
+
+impl CreateFlags {
+	/// Fail with [`Error::AlreadyExists`] if an object already exists at the path instead of
+	/// replacing or reusing it.
+	///
+	/// [`Error::AlreadyExists`]: crate::error::Error::AlreadyExists
+	pub const EXCLUSIVE: Self = Self(1 << 0);
+	/// Create a directory instead of a regular object.
+	pub const DIRECTORY: Self = Self(1 << 1);
+
+	const ALL: u8 = Self::EXCLUSIVE.0 | Self::DIRECTORY.0;
+
+	/// No flags, i.e. the default behaviour of the object.
+	#[inline(always)]
+	pub const fn empty() -> Self {
+		Self(0)
+	}
+
+	/// Convert raw flags. Returns `None` if any unknown flags are set.
+	#[inline]
+	pub const fn from_bits(bits: u8) -> Option<Self> {
+		if bits & !Self::ALL == 0 {
+			Some(Self(bits))
+		} else {
+			None
+		}
+	}
+
+	#[inline(always)]
+	pub const fn bits(self) -> u8 {
+		self.0
+	}
+
+	#[inline(always)]
+	pub const fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	#[inline(always)]
+	pub const fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+}
+
+impl BitOr for CreateFlags {
+	type Output = Self;
+
+	#[inline(always)]
+	fn bitor(self, rhs: Self) -> Self {
+		Self(self.0 | rhs.0)
+	}
+}
+
 /// A ring buffer of requests. The amount of entries is a power of two between 1 and 2^15
 /// inclusive.
 ///
@@ -43,8 +43,71 @@ impl Page {
 	}
 }
 
+/// A wrapper that aligns `T` to a page boundary.
+///
+/// The size is always rounded up to a multiple of [`Page::SIZE`], which is useful for structures
+/// such as DMA rings that may not cross a page boundary.
+#[derive(Clone, Copy, Default)]
+#[repr(C, align(4096))]
+pub struct PageAligned<T>(pub T);
+
+impl<T> PageAligned<T> {
+	#[inline]
+	pub const fn new(value: T) -> Self {
+		Self(value)
+	}
+
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.0
+	}
+}
+
+impl<const N: usize> PageAligned<[u8; N]> {
+	/// Return the buffer as a byte slice.
+	#[inline]
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+
+	/// Return the buffer as a mutable byte slice.
+	#[inline]
+	pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+		&mut self.0
+	}
+}
+
+impl<T> core::ops::Deref for PageAligned<T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		&self.0
+	}
+}
+
+impl<T> core::ops::DerefMut for PageAligned<T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut T {
+		&mut self.0
+	}
+}
+
 pub type Handle = u32;
 
 pub type AtomicHandle = core::sync::atomic::AtomicU32;
 
 pub use syscall::RWX;
+
+#[cfg(test)]
+mod test {
+	use {super::*, core::mem};
+
+	#[test]
+	fn page_aligned_size() {
+		assert_eq!(mem::align_of::<PageAligned<u8>>(), Page::SIZE);
+		assert_eq!(mem::size_of::<PageAligned<u8>>(), Page::SIZE);
+		assert_eq!(mem::size_of::<PageAligned<[u8; 4097]>>(), Page::SIZE * 2);
+		assert_eq!(PageAligned([0u8; 5]).as_bytes().len(), 5);
+	}
+}
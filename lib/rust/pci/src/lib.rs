@@ -224,9 +224,22 @@ impl HeaderCommon {
 	}
 
 	/// Set the flags in the command register.
+	///
+	/// This overwrites *all* flags. Use [`Self::enable_bus_master_mmio`] to enable DMA without
+	/// clobbering other flags.
 	pub fn set_command(&self, flags: u16) {
 		self.command.set(flags.into());
 	}
+
+	/// Enable MMIO and bus mastering and clear the interrupt disable flag.
+	///
+	/// The command register is read back first so all other flags are preserved.
+	pub fn enable_bus_master_mmio(&self) {
+		let mut cmd = self.command();
+		cmd &= !Self::COMMAND_INTERRUPT_DISABLE;
+		cmd |= Self::COMMAND_MMIO_MASK | Self::COMMAND_BUS_MASTER_MASK;
+		self.set_command(cmd);
+	}
 }
 
 impl fmt::Debug for HeaderCommon {
@@ -309,6 +322,10 @@ impl Header0 {
 		self.common.set_command(value);
 	}
 
+	pub fn enable_bus_master_mmio(&self) {
+		self.common.enable_bus_master_mmio();
+	}
+
 	/// Get the full address one or two BARs point to. This may be 64-bit.
 	///
 	/// Returns `None` if the BAR is invalid.
@@ -398,6 +415,8 @@ pub struct Header1 {
 }
 
 impl Header1 {
+	get_volatile!(secondary_bus_number -> u8);
+
 	/// Return the capability structures attached to this header.
 	pub fn capabilities<'a>(&'a self) -> CapabilityIter<'a> {
 		CapabilityIter {
@@ -487,6 +506,10 @@ impl<'a> Header<'a> {
 		self.common().set_command(flags);
 	}
 
+	pub fn enable_bus_master_mmio(&self) {
+		self.common().enable_bus_master_mmio();
+	}
+
 	/// Read the status register
 	pub fn status(&self) -> u16 {
 		self.common().status()
@@ -534,6 +557,9 @@ impl Capability {
 		unsafe {
 			use capability::*;
 			match self.id() {
+				0x_1 => Some(Capability::PowerManagement(
+					&*(self as *const _ as *const _),
+				)),
 				0x_5 => Some(Capability::Msi(&*(self as *const _ as *const _))),
 				0x_9 => Some(Capability::Vendor(&*(self as *const _ as *const _))),
 				0x11 => Some(Capability::MsiX(&*(self as *const _ as *const _))),
@@ -547,6 +573,7 @@ pub mod capability {
 	use super::*;
 
 	pub enum Capability<'a> {
+		PowerManagement(&'a PowerManagement),
 		Msi(&'a Msi),
 		Vendor(&'a Vendor),
 		MsiX(&'a MsiX),
@@ -555,6 +582,7 @@ pub mod capability {
 	impl fmt::Debug for Capability<'_> {
 		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 			match self {
+				Self::PowerManagement(m) => m.fmt(f),
 				Self::Msi(m) => m.fmt(f),
 				Self::Vendor(m) => m.fmt(f),
 				Self::MsiX(m) => m.fmt(f),
@@ -733,6 +761,58 @@ pub mod capability {
 			let v = u32::from(self.pending_bit_bir_offset.get());
 			(v & !0x7, (v & 0x7) as u8)
 		}
+
+		/// Program the first entries of the table with the given message address and data,
+		/// unmask them and enable MSI-X.
+		///
+		/// `map` is called with the index of the BAR the table is located in and must return
+		/// the address at which that BAR is mapped. Entries past those given are left untouched.
+		///
+		/// # Safety
+		///
+		/// The address returned by `map` must point to a valid mapping of the BAR.
+		pub unsafe fn configure(
+			&self,
+			entries: &[(u64, u32)],
+			map: impl FnOnce(u8) -> NonNull<()>,
+		) -> Result<(), ConfigureError> {
+			let mut ctrl = self.message_control();
+			if entries.len() > usize::from(ctrl.table_size()) + 1 {
+				return Err(ConfigureError::TooManyEntries);
+			}
+			let (offset, bar) = self.table();
+			let table = unsafe {
+				map(bar)
+					.as_ptr()
+					.cast::<u8>()
+					.add(offset as usize)
+					.cast::<super::msix::TableEntry>()
+			};
+			for (i, &(address, data)) in entries.iter().enumerate() {
+				// SAFETY: the caller guarantees the BAR is mapped and the index is in range.
+				let e = unsafe { &*table.add(i) };
+				e.set_message_address(address);
+				e.set_message_data(data);
+				e.set_vector_control_mask(false);
+			}
+			ctrl.set_function_mask(false);
+			ctrl.set_enable(true);
+			self.set_message_control(ctrl);
+			Ok(())
+		}
+	}
+
+	pub enum ConfigureError {
+		/// More entries were given than there are in the table.
+		TooManyEntries,
+	}
+
+	impl fmt::Debug for ConfigureError {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			f.write_str(match self {
+				Self::TooManyEntries => "too many entries",
+			})
+		}
 	}
 
 	impl MsiXMessageControl {
@@ -757,6 +837,16 @@ pub mod capability {
 			u16::from(self.0) & (1 << 14) > 0
 		}
 
+		#[inline]
+		pub fn set_function_mask(&mut self, value: bool) {
+			let mask = u16le::new(1 << 14);
+			if value {
+				self.0 |= mask;
+			} else {
+				self.0 &= !mask;
+			}
+		}
+
 		#[inline]
 		pub fn table_size(&self) -> u16 {
 			u16::from(self.0) & 0x3ff
@@ -787,6 +877,113 @@ pub mod capability {
 				.finish()
 		}
 	}
+
+	#[repr(C)]
+	pub struct PowerManagement {
+		common: super::Capability,
+		capabilities: VolatileCell<u16le>,
+		control_status: VolatileCell<u16le>,
+		bridge_extensions: VolatileCell<u8>,
+		data: VolatileCell<u8>,
+	}
+
+	#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+	pub enum PowerState {
+		D0,
+		D1,
+		D2,
+		D3Hot,
+	}
+
+	impl PowerManagement {
+		const STATE_MASK: u16 = 0x3;
+		const NO_SOFT_RESET: u16 = 1 << 3;
+		const PME_ENABLE: u16 = 1 << 8;
+		/// This bit is cleared by writing 1 to it.
+		const PME_STATUS: u16 = 1 << 15;
+
+		get_volatile!(capabilities -> u16);
+		get_volatile!(control_status -> u16);
+		get_volatile!(bridge_extensions -> u8);
+		get_volatile!(data -> u8);
+
+		/// The version of the power management specification the device complies with.
+		pub fn version(&self) -> u8 {
+			(self.capabilities() & 0x7) as u8
+		}
+
+		pub fn supports_d1(&self) -> bool {
+			self.capabilities() & 1 << 9 != 0
+		}
+
+		pub fn supports_d2(&self) -> bool {
+			self.capabilities() & 1 << 10 != 0
+		}
+
+		/// The states from which the device can generate PME#, with bit 0 being D0 and bit 4
+		/// being D3cold.
+		pub fn pme_support(&self) -> u8 {
+			(self.capabilities() >> 11) as u8
+		}
+
+		pub fn power_state(&self) -> PowerState {
+			match self.control_status() & Self::STATE_MASK {
+				0 => PowerState::D0,
+				1 => PowerState::D1,
+				2 => PowerState::D2,
+				_ => PowerState::D3Hot,
+			}
+		}
+
+		/// Transition to another power state.
+		///
+		/// The caller must wait before accessing the device: 10ms when transitioning from
+		/// D3hot to D0 and 200µs for D2.
+		pub fn set_power_state(&self, state: PowerState) {
+			let v = self.control_status() & !(Self::STATE_MASK | Self::PME_STATUS);
+			self.control_status.set((v | state as u16).into());
+		}
+
+		/// Whether the device keeps its state when transitioning from D3hot to D0.
+		pub fn no_soft_reset(&self) -> bool {
+			self.control_status() & Self::NO_SOFT_RESET != 0
+		}
+
+		pub fn pme_enable(&self) -> bool {
+			self.control_status() & Self::PME_ENABLE != 0
+		}
+
+		pub fn set_pme_enable(&self, enable: bool) {
+			let v = self.control_status() & !(Self::PME_ENABLE | Self::PME_STATUS);
+			let v = v | if enable { Self::PME_ENABLE } else { 0 };
+			self.control_status.set(v.into());
+		}
+
+		pub fn pme_status(&self) -> bool {
+			self.control_status() & Self::PME_STATUS != 0
+		}
+
+		pub fn clear_pme_status(&self) {
+			self.control_status
+				.set((self.control_status() | Self::PME_STATUS).into());
+		}
+	}
+
+	impl fmt::Debug for PowerManagement {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			f.debug_struct(stringify!(PowerManagement))
+				.field("common", &self.common)
+				.field("version", &self.version())
+				.field("supports_d1", &self.supports_d1())
+				.field("supports_d2", &self.supports_d2())
+				.field("pme_support", &format_args!("{:#07b}", self.pme_support()))
+				.field("power_state", &self.power_state())
+				.field("no_soft_reset", &self.no_soft_reset())
+				.field("pme_enable", &self.pme_enable())
+				.field("pme_status", &self.pme_status())
+				.finish_non_exhaustive()
+		}
+	}
 }
 
 impl fmt::Debug for Capability {
@@ -1064,6 +1261,13 @@ pub struct Device<'a> {
 }
 
 impl<'a> Device<'a> {
+	/// Returns an iterator over all the present functions of this device.
+	///
+	/// Functions 1 to 7 are only probed if the device is a multi-function device.
+	pub fn iter(&self) -> IterDevice<'a> {
+		IterDevice { pci: self.pci, bus: self.bus, device: self.device, function: 0 }
+	}
+
 	#[inline]
 	pub fn bus(&self) -> u8 {
 		self.bus
@@ -1124,6 +1328,65 @@ pub struct Function<'a> {
 	function: u8,
 }
 
+impl<'a> Function<'a> {
+	#[inline]
+	pub fn bus(&self) -> u8 {
+		self.bus
+	}
+
+	#[inline]
+	pub fn device(&self) -> u8 {
+		self.device
+	}
+
+	#[inline]
+	pub fn function(&self) -> u8 {
+		self.function
+	}
+
+	#[inline]
+	pub fn vendor_id(&self) -> u16 {
+		self.header().common().vendor_id.get().into()
+	}
+
+	#[inline]
+	pub fn device_id(&self) -> u16 {
+		self.header().common().device_id.get().into()
+	}
+
+	#[inline]
+	pub fn header(&self) -> Header<'a> {
+		self.pci
+			.get_unchecked(self.bus, self.device, self.function)
+			.unwrap()
+	}
+
+	#[inline]
+	pub fn header_physical_address(&self) -> usize {
+		self.pci
+			.get_physical_address(self.bus, self.device, self.function)
+	}
+
+	#[inline]
+	pub fn child_address(&self) -> u32 {
+		self.pci
+			.get_child_address(self.bus, self.device, self.function)
+	}
+}
+
+impl fmt::Debug for Function<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Function")
+			.field("vendor_id", &format_args!("0x{:x}", self.vendor_id()))
+			.field("device_id", &format_args!("0x{:x}", self.device_id()))
+			.field(
+				"location",
+				&format_args!("{} -> {}.{}", self.bus, self.device, self.function),
+			)
+			.finish_non_exhaustive()
+	}
+}
+
 impl<'a> From<Function<'a>> for Option<Header<'a>> {
 	fn from(f: Function<'a>) -> Self {
 		f.pci.get(f.bus, f.device, f.function)
@@ -1188,41 +1451,62 @@ impl<'a> Iterator for IterBus<'a> {
 	}
 }
 
-pub enum FunctionItem<'a> {
-	Header(Header<'a>),
-	Bus(Bus<'a>),
-}
-
 impl<'a> Iterator for IterDevice<'a> {
-	type Item = FunctionItem<'a>;
-
-	fn next(&mut self) -> Option<FunctionItem<'a>> {
-		if self.function == 0xff {
-			None
-		} else {
-			let h = self
-				.pci
-				.get_unchecked(self.bus, self.device, self.function)?;
-			if h.common().vendor_id.get() == 0xffff {
-				self.function = 0xff;
-				None
-			} else {
-				let ht = h.common().header_type.get();
-				if ht & 0x80 > 0 {
-					if let Header::H1(h) = h {
-						if h.common.class_code.get() == 0x6 && h.common.subclass.get() == 0x4 {
-							let sb = h.secondary_bus_number.get();
-							Some(FunctionItem::Bus(Bus { pci: self.pci, bus: sb }))
-						} else {
-							Some(FunctionItem::Header(Header::H1(h)))
-						}
-					} else {
-						Some(FunctionItem::Header(h))
+	type Item = Function<'a>;
+
+	fn next(&mut self) -> Option<Function<'a>> {
+		while self.function < 8 {
+			let function = self.function;
+			self.function += 1;
+			match self.pci.get(self.bus, self.device, function) {
+				Some(h) => {
+					// Only multi-function devices have functions other than 0.
+					if function == 0 && h.header_type() & 0x80 == 0 {
+						self.function = 8;
 					}
-				} else {
-					Some(FunctionItem::Header(h))
+					let (pci, bus, device) = (self.pci, self.bus, self.device);
+					return Some(Function { pci, bus, device, function });
 				}
+				// Function 0 must always be present.
+				None if function == 0 => self.function = 8,
+				None => {}
 			}
 		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	extern crate std;
+
+	use {super::*, std::vec};
+
+	#[test]
+	fn iter_multifunction() {
+		// Enough room for all functions of all devices on bus 0.
+		let mut cfg = vec![u32::MAX; (32 << 15) / 4];
+		let mut set = |device: u8, function: u8, device_id: u16, header_type: u8| {
+			let i = Pci::offset(0, device, function) / 4;
+			cfg[i] = u32::from(device_id) << 16 | 0x1af4;
+			cfg[i + 3] = u32::from(header_type) << 16;
+		};
+		set(0, 0, 0x10, 0x80);
+		set(0, 1, 0x11, 0x00);
+		set(1, 0, 0x20, 0x00);
+		// Not a multi-function device, so this one must be ignored.
+		set(1, 1, 0x21, 0x00);
+
+		let start = NonNull::new(cfg.as_mut_ptr()).unwrap().cast();
+		let pci = unsafe { Pci::new(start, 0, cfg.len() * 4, &[]) };
+		let bus = Bus { pci: &pci, bus: 0 };
+		let mut it = bus
+			.iter()
+			.flat_map(|d| d.iter())
+			.map(|f| (f.device(), f.function(), f.device_id()));
+		assert_eq!(it.next(), Some((0, 0, 0x10)));
+		assert_eq!(it.next(), Some((0, 1, 0x11)));
+		assert_eq!(it.next(), Some((1, 0, 0x20)));
+		assert_eq!(it.next(), None);
 	}
 }
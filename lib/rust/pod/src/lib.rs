@@ -0,0 +1,134 @@
+//! # Plain old data
+//!
+//! Checked conversions between byte slices and `#[repr(C)]` structures, which are commonly used
+//! for headers of device requests.
+
+#![no_std]
+
+use {
+	core::{mem, slice},
+	endian::{u16le, u32le, u64le},
+};
+
+/// A type that can be safely converted from and to raw bytes.
+///
+/// # Safety
+///
+/// * Any bit pattern must be a valid value.
+/// * The type must not have any padding bytes.
+/// * The type must not contain any references or pointers with a lifetime.
+///
+/// For structures this means the structure must be `#[repr(C)]` or `#[repr(transparent)]`,
+/// every field must implement `Pod` and there may be no gaps between fields.
+pub unsafe trait Pod: Copy + 'static {
+	/// Cast a byte slice to a reference to `Self`.
+	///
+	/// Returns `None` if the slice is too small or not properly aligned. Any bytes beyond
+	/// the size of `Self` are ignored.
+	fn from_bytes(bytes: &[u8]) -> Option<&Self> {
+		check::<Self>(bytes).then(|| {
+			// SAFETY: the size and alignment are correct and any bit pattern is valid.
+			unsafe { &*bytes.as_ptr().cast() }
+		})
+	}
+
+	/// Cast a mutable byte slice to a mutable reference to `Self`.
+	///
+	/// Returns `None` if the slice is too small or not properly aligned. Any bytes beyond
+	/// the size of `Self` are ignored.
+	fn from_bytes_mut(bytes: &mut [u8]) -> Option<&mut Self> {
+		check::<Self>(bytes).then(|| {
+			// SAFETY: the size and alignment are correct and any bit pattern is valid.
+			unsafe { &mut *bytes.as_mut_ptr().cast() }
+		})
+	}
+
+	/// Copy `Self` from a byte slice, which does not need to be aligned.
+	///
+	/// Returns `None` if the slice is too small.
+	fn read_from(bytes: &[u8]) -> Option<Self> {
+		(bytes.len() >= mem::size_of::<Self>()).then(|| {
+			// SAFETY: the size is correct and any bit pattern is valid.
+			unsafe { bytes.as_ptr().cast::<Self>().read_unaligned() }
+		})
+	}
+
+	fn as_bytes(&self) -> &[u8] {
+		// SAFETY: there are no padding bytes, so every byte is initialized.
+		unsafe { slice::from_raw_parts((self as *const Self).cast(), mem::size_of::<Self>()) }
+	}
+
+	fn as_bytes_mut(&mut self) -> &mut [u8] {
+		// SAFETY: there are no padding bytes and any bit pattern is valid.
+		unsafe { slice::from_raw_parts_mut((self as *mut Self).cast(), mem::size_of::<Self>()) }
+	}
+}
+
+fn check<T>(bytes: &[u8]) -> bool {
+	bytes.len() >= mem::size_of::<T>() && bytes.as_ptr().align_offset(mem::align_of::<T>()) == 0
+}
+
+macro_rules! impl_pod {
+	($($ty:ty)*) => {
+		$(
+			// SAFETY: integers have no padding and any bit pattern is valid.
+			unsafe impl Pod for $ty {}
+		)*
+	};
+}
+
+impl_pod!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+impl_pod!(u16le u32le u64le);
+
+// SAFETY: arrays have no padding between elements.
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[derive(Clone, Copy, Debug, PartialEq)]
+	#[repr(C)]
+	struct Header {
+		ty: u32le,
+		len: u16le,
+		flags: [u8; 2],
+	}
+
+	unsafe impl Pod for Header {}
+
+	#[repr(C, align(8))]
+	struct Aligned([u8; 16]);
+
+	#[test]
+	fn from_bytes() {
+		let buf = Aligned([1, 0, 0, 0, 8, 0, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0]);
+		let h = Header::from_bytes(&buf.0).unwrap();
+		assert_eq!(u32::from(h.ty), 1);
+		assert_eq!(u16::from(h.len), 8);
+		assert_eq!(h.flags, [2, 3]);
+		assert_eq!(h.as_bytes(), &buf.0[..8]);
+	}
+
+	#[test]
+	fn from_bytes_too_small() {
+		let buf = Aligned([0; 16]);
+		assert!(Header::from_bytes(&buf.0[..7]).is_none());
+		assert!(Header::read_from(&buf.0[..7]).is_none());
+	}
+
+	#[test]
+	fn from_bytes_misaligned() {
+		let buf = Aligned([0; 16]);
+		assert!(Header::from_bytes(&buf.0[1..]).is_none());
+		assert!(Header::read_from(&buf.0[1..]).is_some());
+	}
+
+	#[test]
+	fn from_bytes_mut() {
+		let mut buf = Aligned([0; 16]);
+		let h = <[u32le; 2]>::from_bytes_mut(&mut buf.0).unwrap();
+		h[1] = 0x0403_0201.into();
+		assert_eq!(&buf.0[4..8], &[1, 2, 3, 4]);
+	}
+}
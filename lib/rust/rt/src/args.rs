@@ -253,10 +253,48 @@ pub fn args() -> Args {
 	Args::new()
 }
 
+/// Get the argument at the given index. The first argument is the name of the program.
+pub fn arg(index: usize) -> Option<&'static [u8]> {
+	args().nth(index)
+}
+
+/// Get the argument at the given index as a string.
+///
+/// Returns `None` if the argument is not valid UTF-8.
+pub fn arg_str(index: usize) -> Option<&'static str> {
+	arg(index).and_then(|s| core::str::from_utf8(s).ok())
+}
+
+/// Get the first `N` arguments, including the name of the program.
+///
+/// Any additional arguments are ignored.
+pub fn args_required<const N: usize>() -> Result<[&'static [u8]; N], MissingArgument> {
+	let mut args = args();
+	let mut r = [&[][..]; N];
+	for (index, e) in r.iter_mut().enumerate() {
+		*e = args.next().ok_or(MissingArgument { index })?;
+	}
+	Ok(r)
+}
+
 pub fn env() -> Env {
 	Env::new()
 }
 
+/// Get the object with the given name.
 pub fn handle(name: &[u8]) -> Option<RefObject<'static>> {
 	handles().find_map(|(n, o)| (n == name).then(|| o))
 }
+
+/// Error returned by [`args_required`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingArgument {
+	/// The index of the first missing argument.
+	pub index: usize,
+}
+
+impl fmt::Display for MissingArgument {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "missing argument {}", self.index)
+	}
+}
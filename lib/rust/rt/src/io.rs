@@ -1,21 +1,22 @@
 pub use norostb_kernel::{
 	error::{Error, Result},
-	io::{SeekFrom, TinySlice},
+	io::{CreateFlags, SeekFrom, TinySlice},
 	object::{NewObject, Pow2Size},
 	syscall::RWX,
 	Handle,
 };
 
 use {
-	crate::RefObject,
+	crate::{sync::RawMutex, RefObject},
 	core::{
 		fmt,
 		mem::{self, MaybeUninit},
 		ptr::NonNull,
 		sync::atomic::Ordering,
+		time::Duration,
 	},
 	norostb_kernel::{
-		io::{DoIo, DoIoOp},
+		io::{DoIo, DoIoOp, IoVec, IoVecMut, MAX_IO_VECS},
 		syscall,
 	},
 };
@@ -50,6 +51,74 @@ transmute_handle!(file_root, set_file_root -> file_root_handle);
 transmute_handle!(net_root, set_net_root -> net_root_handle);
 transmute_handle!(process_root, set_process_root -> process_root_handle);
 
+/// How long [`Stdout::lock_or_bypass`] and [`Stderr::lock_or_bypass`] wait for the lock.
+const BYPASS_TIMEOUT: Duration = Duration::from_millis(100);
+
+macro_rules! locked_writer {
+	($(#[doc = $doc:literal])* $ty:ident, $fn:ident, $lock:ident) => {
+		static $lock: RawMutex = RawMutex::new();
+
+		$(#[doc = $doc])*
+		///
+		/// Other threads can't write through this writer until the guard is dropped, hence a
+		/// single `write!()` or `writeln!()` won't be interleaved with other messages.
+		pub struct $ty {
+			object: RefObject<'static>,
+			locked: bool,
+		}
+
+		impl $ty {
+			/// Acquire the lock.
+			///
+			/// Returns `None` if the object is not set.
+			pub fn lock() -> Option<Self> {
+				$fn().map(|object| {
+					$lock.lock();
+					Self { object, locked: true }
+				})
+			}
+
+			/// Acquire the lock, or write without it if it couldn't be acquired in time.
+			///
+			/// This is meant for panic handlers, as the lock may be held by the panicking
+			/// thread.
+			pub fn lock_or_bypass() -> Option<Self> {
+				$fn().map(|object| Self { object, locked: $lock.lock_timeout(BYPASS_TIMEOUT) })
+			}
+
+			pub fn write_all(&self, data: &[u8]) -> Result<()> {
+				self.object.write_all(data)
+			}
+
+			/// Convienence method for use with `write!()` et al.
+			pub fn write_fmt(&self, args: fmt::Arguments<'_>) -> Result<()> {
+				self.object.write_fmt(args)
+			}
+		}
+
+		impl Drop for $ty {
+			fn drop(&mut self) {
+				if self.locked {
+					$lock.unlock();
+				}
+			}
+		}
+	};
+}
+
+locked_writer!(
+	/// A locked handle to the standard output.
+	Stdout,
+	stdout,
+	STDOUT_LOCK
+);
+locked_writer!(
+	/// A locked handle to the standard error output.
+	Stderr,
+	stderr,
+	STDERR_LOCK
+);
+
 #[derive(Copy, Clone)]
 pub struct IoSlice<'a>(&'a [u8]);
 
@@ -112,6 +181,148 @@ pub fn write(handle: Handle, data: &[u8]) -> Result<usize> {
 	syscall::do_io(DoIo { handle, op: DoIoOp::Write { data } }).map(|v| v as _)
 }
 
+/// Read data at an offset without using or modifying the seek head.
+///
+/// Fails with [`Error::InvalidOperation`] if the object does not support positioning.
+#[inline(always)]
+pub fn read_at(handle: Handle, offset: u64, buf: &mut [u8]) -> Result<usize> {
+	// SAFETY: the kernel won't deinitialize unread bytes
+	read_uninit_at(handle, offset, unsafe { mem::transmute(buf) })
+}
+
+/// Read data at an offset without using or modifying the seek head.
+///
+/// Fails with [`Error::InvalidOperation`] if the object does not support positioning.
+#[inline(always)]
+pub fn read_uninit_at(handle: Handle, offset: u64, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::ReadUninitAt { buf, offset } }).map(|v| v as _)
+}
+
+/// Write data at an offset without using or modifying the seek head.
+///
+/// Fails with [`Error::InvalidOperation`] if the object does not support positioning.
+#[inline(always)]
+pub fn write_at(handle: Handle, offset: u64, data: &[u8]) -> Result<usize> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::WriteAt { data, offset } }).map(|v| v as _)
+}
+
+/// Read data without blocking.
+///
+/// Fails with [`Error::WouldBlock`] if the object has no data ready. Not all objects support
+/// nonblocking reads.
+#[inline(always)]
+pub fn read_nonblocking(handle: Handle, buf: &mut [u8]) -> Result<usize> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::ReadNonblocking { buf } }).map(|v| v as _)
+}
+
+/// Read data without blocking.
+///
+/// Fails with [`Error::WouldBlock`] if the object has no data ready. Not all objects support
+/// nonblocking reads.
+#[inline(always)]
+pub fn read_uninit_nonblocking(handle: Handle, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::ReadUninitNonblocking { buf } }).map(|v| v as _)
+}
+
+/// Write data without blocking.
+///
+/// Fails with [`Error::WouldBlock`] if the object can't accept the data immediately. Not all
+/// objects support nonblocking writes.
+#[inline(always)]
+pub fn write_nonblocking(handle: Handle, data: &[u8]) -> Result<usize> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::WriteNonblocking { data } }).map(|v| v as _)
+}
+
+/// Read into multiple buffers in order and return the total amount of bytes read.
+///
+/// Reading stops at the first short read. If an error occurs after some data has been read the
+/// error is discarded and the amount read so far is returned. If the object doesn't support
+/// vectored reads one read is issued per buffer.
+pub fn read_vectored(handle: Handle, bufs: &mut [&mut [MaybeUninit<u8>]]) -> Result<usize> {
+	let mut total = 0;
+	for chunk in bufs.chunks_mut(MAX_IO_VECS) {
+		let (n, want) = (chunk.len(), chunk.iter().map(|b| b.len()).sum::<usize>());
+		let mut it = chunk.iter_mut();
+		let slices: [_; MAX_IO_VECS] = core::array::from_fn(|_| {
+			IoVecMut::new(it.next().map_or(&mut [] as &mut [_], |b| &mut **b))
+		});
+		match syscall::do_io(DoIo { handle, op: DoIoOp::ReadVectored { bufs: &slices[..n] } }) {
+			Ok(l) => {
+				total += l as usize;
+				if (l as usize) < want {
+					break;
+				}
+			}
+			Err(Error::InvalidOperation) if total == 0 => return read_each(handle, bufs),
+			Err(e) if total == 0 => return Err(e),
+			Err(_) => break,
+		}
+	}
+	Ok(total)
+}
+
+/// Write multiple buffers in order and return the total amount of bytes written.
+///
+/// The same caveats as [`read_vectored`] apply.
+pub fn write_vectored(handle: Handle, bufs: &[&[u8]]) -> Result<usize> {
+	let mut total = 0;
+	for chunk in bufs.chunks(MAX_IO_VECS) {
+		let want = chunk.iter().map(|b| b.len()).sum::<usize>();
+		let mut it = chunk.iter();
+		let slices: [_; MAX_IO_VECS] =
+			core::array::from_fn(|_| IoVec::new(it.next().map_or(&[] as &[_], |b| *b)));
+		let op = DoIoOp::WriteVectored { bufs: &slices[..chunk.len()] };
+		match syscall::do_io(DoIo { handle, op }) {
+			Ok(l) => {
+				total += l as usize;
+				if (l as usize) < want {
+					break;
+				}
+			}
+			Err(Error::InvalidOperation) if total == 0 => return write_each(handle, bufs),
+			Err(e) if total == 0 => return Err(e),
+			Err(_) => break,
+		}
+	}
+	Ok(total)
+}
+
+/// Issue one read per buffer, for objects that don't support vectored reads.
+fn read_each(handle: Handle, bufs: &mut [&mut [MaybeUninit<u8>]]) -> Result<usize> {
+	let mut total = 0;
+	for buf in bufs.iter_mut().filter(|b| !b.is_empty()) {
+		match read_uninit(handle, buf) {
+			Ok(l) => {
+				total += l;
+				if l < buf.len() {
+					break;
+				}
+			}
+			Err(e) if total == 0 => return Err(e),
+			Err(_) => break,
+		}
+	}
+	Ok(total)
+}
+
+/// Issue one write per buffer, for objects that don't support vectored writes.
+fn write_each(handle: Handle, bufs: &[&[u8]]) -> Result<usize> {
+	let mut total = 0;
+	for buf in bufs.iter().filter(|b| !b.is_empty()) {
+		match write(handle, buf) {
+			Ok(l) => {
+				total += l;
+				if l < buf.len() {
+					break;
+				}
+			}
+			Err(e) if total == 0 => return Err(e),
+			Err(_) => break,
+		}
+	}
+	Ok(total)
+}
+
 #[inline(always)]
 pub fn get_meta(
 	handle: Handle,
@@ -142,7 +353,13 @@ pub fn open(handle: Handle, path: &[u8]) -> Result<Handle> {
 
 #[inline(always)]
 pub fn create(handle: Handle, path: &[u8]) -> Result<Handle> {
-	syscall::do_io(DoIo { handle, op: DoIoOp::Create { path } }).map(|v| v as _)
+	create_with_flags(handle, path, CreateFlags::empty())
+}
+
+/// Create an object with the given flags, e.g. [`CreateFlags::EXCLUSIVE`].
+#[inline(always)]
+pub fn create_with_flags(handle: Handle, path: &[u8], flags: CreateFlags) -> Result<Handle> {
+	syscall::do_io(DoIo { handle, op: DoIoOp::Create { path, flags } }).map(|v| v as _)
 }
 
 #[inline(always)]
@@ -184,20 +401,20 @@ pub fn close(handle: Handle) {
 
 #[doc(hidden)]
 pub fn _print_str(s: &str) {
-	let _ = stdout().map(|o| o.write_all(s.as_bytes()));
+	let _ = Stdout::lock().map(|o| o.write_all(s.as_bytes()));
 }
 
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments<'_>) {
-	let _ = stdout().map(|o| o.write_fmt(args));
+	let _ = Stdout::lock().map(|o| o.write_fmt(args));
 }
 
 #[doc(hidden)]
 pub fn _eprint_str(s: &str) {
-	let _ = stderr().map(|o| o.write_all(s.as_bytes()));
+	let _ = Stderr::lock().map(|o| o.write_all(s.as_bytes()));
 }
 
 #[doc(hidden)]
 pub fn _eprint(args: fmt::Arguments<'_>) {
-	let _ = stderr().map(|o| o.write_fmt(args));
+	let _ = Stderr::lock().map(|o| o.write_fmt(args));
 }
@@ -2,14 +2,14 @@
 #[macro_export]
 macro_rules! dbg {
     () => {{
-        let _ = $crate::io::stderr().map(|o| writeln!(o, "[{}:{}]", file!(), line!()));
+        let _ = $crate::io::Stderr::lock().map(|o| writeln!(o, "[{}:{}]", file!(), line!()));
     }};
     ($val:expr $(,)?) => {
         // Use of `match` here is intentional because it affects the lifetimes
         // of temporaries - https://stackoverflow.com/a/48732525/1063961
         match $val {
             tmp => {
-				let _ = $crate::io::stderr().map(|o| {
+				let _ = $crate::io::Stderr::lock().map(|o| {
 					writeln!(o, "[{}:{}] {} = {:#?}", file!(), line!(), stringify!($val), &tmp)
 				});
                 tmp
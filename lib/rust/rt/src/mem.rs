@@ -1,7 +1,7 @@
 use {
 	crate::{io, RWX},
 	core::{num::NonZeroUsize, ptr::NonNull},
-	norostb_kernel::syscall,
+	norostb_kernel::{syscall, Page},
 };
 
 #[inline]
@@ -20,3 +20,22 @@ pub fn alloc(
 pub unsafe fn dealloc(base: NonNull<u8>, size: usize) -> io::Result<()> {
 	unsafe { syscall::dealloc(base.cast(), size) }
 }
+
+/// Unmap part of a mapping. The mapping may be of any object, not just memory allocated with
+/// [`alloc`].
+///
+/// `base` must be page-aligned. `size` is rounded up to a multiple of the page size.
+///
+/// # Safety
+///
+/// The memory may not be accessed after this call.
+#[inline]
+pub unsafe fn unmap_range(base: NonNull<u8>, size: usize) -> io::Result<()> {
+	unsafe { dealloc(base, page_align(size)) }
+}
+
+/// Round up to a multiple of the page size.
+#[inline]
+pub(crate) const fn page_align(size: usize) -> usize {
+	(size + Page::SIZE - 1) & !(Page::SIZE - 1)
+}
@@ -26,6 +26,11 @@ pub struct ExitStatus {
 	pub code: u8,
 }
 
+/// Builder for spawning a new process.
+///
+/// Objects and arguments are passed to the new process in the order they were added.
+/// Object names must be unique: adding an object with a name that is already in use fails with
+/// [`io::Error::AlreadyExists`].
 pub struct Builder {
 	builder: Object,
 	objects_share: Option<Object>,
@@ -77,6 +82,9 @@ impl Builder {
 	}
 
 	pub fn add_object_raw(&mut self, name: &[u8], object: Handle) -> io::Result<()> {
+		if self.has_object(name) {
+			return Err(io::Error::AlreadyExists);
+		}
 		if self.objects_share.is_none() {
 			self.objects_share = Some(self.builder.open(b"objects")?);
 		}
@@ -87,6 +95,37 @@ impl Builder {
 		Ok(())
 	}
 
+	/// Check if an object with the given name has already been added.
+	pub fn has_object(&self, name: &[u8]) -> bool {
+		let mut objects = &self.objects[..];
+		while let [a, b, ref rest @ ..] = *objects {
+			let l = usize::from(u16::from_le_bytes([a, b]));
+			if &rest[..l] == name {
+				return true;
+			}
+			objects = &rest[l + 4..];
+		}
+		false
+	}
+
+	/// Set the object to use as standard input.
+	pub fn set_stdin(&mut self, object: &Object) -> io::Result<()> {
+		self.add_object(b"in", object)
+	}
+
+	/// Set the object to use as standard output.
+	pub fn set_stdout(&mut self, object: &Object) -> io::Result<()> {
+		self.add_object(b"out", object)
+	}
+
+	/// Set the object to use as standard error.
+	pub fn set_stderr(&mut self, object: &Object) -> io::Result<()> {
+		self.add_object(b"err", object)
+	}
+
+	/// Add the default stdio & root objects.
+	///
+	/// Objects with a name that is already in use are skipped.
 	pub fn add_default_objects(&mut self) -> io::Result<()> {
 		self.add_default_stdio_objects()?;
 		self.add_default_root_objects()
@@ -98,7 +137,7 @@ impl Builder {
 			("net", io::net_root()),
 			("process", io::process_root()),
 		] {
-			if let Some(obj) = obj {
+			if let Some(obj) = obj.filter(|_| !self.has_object(name.as_bytes())) {
 				self.add_object(name.as_bytes(), &obj)?;
 			}
 		}
@@ -111,7 +150,7 @@ impl Builder {
 			("out", io::stdout()),
 			("err", io::stderr()),
 		] {
-			if let Some(obj) = obj {
+			if let Some(obj) = obj.filter(|_| !self.has_object(name.as_bytes())) {
 				self.add_object(name.as_bytes(), &obj)?;
 			}
 		}
@@ -141,6 +180,10 @@ impl Builder {
 		}
 	}
 
+	pub fn add_arg(&mut self, arg: impl AsRef<[u8]>) -> io::Result<()> {
+		self.add_args([arg])
+	}
+
 	pub fn spawn(self) -> io::Result<Process> {
 		let mut stack = Vec::new();
 
@@ -3,9 +3,17 @@ use {
 	core::{
 		cell::UnsafeCell,
 		ops::{Deref, DerefMut},
+		time::Duration,
 	},
 };
 
+/// A mutual exclusion lock.
+///
+/// Waiting threads spin and yield until the lock is released. The lock is *not* fair: there is
+/// no queue of waiters, so any of them may acquire it next.
+///
+/// There is no poisoning. If a thread panics while holding the lock the whole process exits,
+/// so other threads never observe a partially modified value.
 #[derive(Debug, Default)]
 pub struct Mutex<T> {
 	lock: RawMutex,
@@ -15,16 +23,19 @@ pub struct Mutex<T> {
 #[derive(Debug)]
 pub struct MutexGuard<'a, T>(&'a Mutex<T>);
 
-#[derive(Debug)]
-pub struct Locked;
-
 impl<T> Mutex<T> {
 	pub const fn new(value: T) -> Self {
 		Self { lock: RawMutex::new(), value: UnsafeCell::new(value) }
 	}
 
-	pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, Locked> {
-		self.lock.try_lock().then(|| MutexGuard(self)).ok_or(Locked)
+	/// Acquire the lock if it isn't held by anyone else.
+	pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+		self.lock.try_lock().then(|| MutexGuard(self))
+	}
+
+	/// Acquire the lock, giving up if it couldn't be acquired before the timeout expires.
+	pub fn lock_timeout(&self, timeout: Duration) -> Option<MutexGuard<'_, T>> {
+		self.lock.lock_timeout(timeout).then(|| MutexGuard(self))
 	}
 
 	pub fn lock(&self) -> MutexGuard<'_, T> {
@@ -1,4 +1,7 @@
-use {crate::thread, core::sync::atomic::Ordering};
+use {
+	crate::{thread, time::Monotonic},
+	core::{sync::atomic::Ordering, time::Duration},
+};
 
 // We use an u32 because some platforms such as RISC-V don't have native
 // u8 or u16 atomic instructions. While it can be emulated it is quite a bit less efficient.
@@ -42,6 +45,31 @@ impl RawMutex {
 		}
 	}
 
+	/// Try to acquire the lock until the timeout expires.
+	///
+	/// Returns `true` if the lock was acquired.
+	pub fn lock_timeout(&self, timeout: Duration) -> bool {
+		if self.try_lock() {
+			return true;
+		}
+		let end = match Monotonic::now().checked_add(timeout) {
+			Some(end) => end,
+			None => {
+				self.lock();
+				return true;
+			}
+		};
+		loop {
+			if self.try_lock() {
+				return true;
+			}
+			if Monotonic::now() >= end {
+				return false;
+			}
+			thread::yield_now();
+		}
+	}
+
 	#[inline]
 	pub fn unlock(&self) {
 		debug_assert_eq!(
@@ -11,6 +11,9 @@ use {
 
 pub use norostb_kernel::{io::DoIo, object::NewObject, Handle};
 
+/// The maximum length of the type tag of an object.
+pub const TYPE_MAX_LEN: usize = 16;
+
 #[derive(Debug)]
 pub struct Object(Handle);
 
@@ -31,6 +34,37 @@ impl Object {
 		io::create(self.0, path).map(Self)
 	}
 
+	/// Create an object with the given flags, e.g. [`io::CreateFlags::EXCLUSIVE`].
+	#[inline(always)]
+	pub fn create_with_flags(&self, path: &[u8], flags: io::CreateFlags) -> io::Result<Self> {
+		io::create_with_flags(self.0, path, flags).map(Self)
+	}
+
+	/// Create an object with a type tag, which allows clients to tell what kind of object it is
+	/// without opening it.
+	///
+	/// A tag is 1 to [`TYPE_MAX_LEN`] bytes long and consists of lowercase ASCII letters,
+	/// digits, `-` and `_`, e.g. `gpu` or `net`.
+	pub fn create_with_type(&self, path: &[u8], typ: &[u8]) -> io::Result<Self> {
+		let obj = self.create(path)?;
+		let typ: &TinySlice<u8> = typ.try_into().map_err(|_| io::Error::InvalidData)?;
+		obj.set_meta(b"type".into(), typ)?;
+		Ok(obj)
+	}
+
+	/// Get the type tag of a child object.
+	///
+	/// Untyped objects have an empty tag.
+	pub fn object_type(&self, name: &[u8]) -> io::Result<Vec<u8>> {
+		let mut prop = Vec::with_capacity(5 + name.len());
+		prop.extend_from_slice(b"type/");
+		prop.extend_from_slice(name);
+		let prop: &TinySlice<u8> = (&*prop).try_into().map_err(|_| io::Error::InvalidData)?;
+		let mut buf = [0; TYPE_MAX_LEN];
+		let len = self.get_meta(prop, (&mut buf).into())?;
+		Ok(buf[..len].into())
+	}
+
 	#[inline(always)]
 	pub fn destroy(&self, path: &[u8]) -> io::Result<u64> {
 		io::destroy(self.0, path)
@@ -53,6 +87,42 @@ impl Object {
 		})
 	}
 
+	/// Read data without blocking.
+	///
+	/// Fails with [`io::Error::WouldBlock`] if no data is ready.
+	#[inline]
+	pub fn read_nonblocking(&self, buf: &mut [u8]) -> io::Result<usize> {
+		io::read_nonblocking(self.0, buf)
+	}
+
+	/// Read data without blocking.
+	///
+	/// Fails with [`io::Error::WouldBlock`] if no data is ready.
+	#[inline]
+	pub fn read_uninit_nonblocking<'a>(
+		&self,
+		buf: &'a mut [MaybeUninit<u8>],
+	) -> io::Result<(&'a mut [u8], &'a mut [MaybeUninit<u8>])> {
+		io::read_uninit_nonblocking(self.0, buf).map(|l| {
+			let (i, u) = buf.split_at_mut(l);
+			// SAFETY: all bytes in i are initialized
+			(unsafe { MaybeUninit::slice_assume_init_mut(i) }, u)
+		})
+	}
+
+	/// Read data at an offset without using or modifying the seek head.
+	///
+	/// Fails with [`io::Error::InvalidOperation`] if the object does not support positioning.
+	#[inline(always)]
+	pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+		io::read_at(self.0, offset, buf)
+	}
+
+	#[inline]
+	pub fn read_vectored(&self, bufs: &mut [&mut [MaybeUninit<u8>]]) -> io::Result<usize> {
+		io::read_vectored(self.0, bufs)
+	}
+
 	pub fn read_exact(&self, buf: &mut [u8]) -> io::Result<()> {
 		let mut l = 0;
 		while l < buf.len() {
@@ -84,6 +154,27 @@ impl Object {
 		io::write(self.0, data)
 	}
 
+	/// Write data without blocking.
+	///
+	/// Fails with [`io::Error::WouldBlock`] if the data can't be written immediately.
+	#[inline]
+	pub fn write_nonblocking(&self, data: &[u8]) -> io::Result<usize> {
+		io::write_nonblocking(self.0, data)
+	}
+
+	/// Write data at an offset without using or modifying the seek head.
+	///
+	/// Fails with [`io::Error::InvalidOperation`] if the object does not support positioning.
+	#[inline(always)]
+	pub fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<usize> {
+		io::write_at(self.0, offset, data)
+	}
+
+	#[inline]
+	pub fn write_vectored(&self, bufs: &[&[u8]]) -> io::Result<usize> {
+		io::write_vectored(self.0, bufs)
+	}
+
 	pub fn write_all(&self, data: &[u8]) -> io::Result<()> {
 		let mut l = 0;
 		while l < data.len() {
@@ -136,6 +227,49 @@ impl Object {
 		io::map_object(self.0, base, rwx, offset, max_length)
 	}
 
+	/// Grow or shrink a mapping of this object in place.
+	///
+	/// `base` and `old_len` must describe a mapping of this object at offset 0, as returned by
+	/// [`Object::map_object`]. Lengths are rounded up to a multiple of the page size.
+	///
+	/// The mapping is never moved. If the address range after the mapping is already in use
+	/// [`io::Error::AlreadyExists`] is returned and the existing mapping is left untouched. If the
+	/// object is smaller than `new_len` [`io::Error::InvalidData`] is returned instead.
+	///
+	/// # Safety
+	///
+	/// When shrinking, the memory past `new_len` may not be accessed after this call.
+	pub unsafe fn remap(
+		&self,
+		base: NonNull<u8>,
+		rwx: RWX,
+		old_len: usize,
+		new_len: usize,
+	) -> io::Result<()> {
+		let (old_len, new_len) = (
+			crate::mem::page_align(old_len),
+			crate::mem::page_align(new_len),
+		);
+		if new_len < old_len {
+			unsafe { crate::mem::dealloc(base.add(new_len), old_len - new_len) }
+		} else if new_len > old_len {
+			let end = unsafe { base.add(old_len) };
+			let (ptr, len) = self.map_object(Some(end), rwx, old_len, new_len - old_len)?;
+			if ptr != end || crate::mem::page_align(len) != new_len - old_len {
+				// The range was mapped elsewhere or the object is too small, so undo it.
+				let _ = unsafe { crate::mem::dealloc(ptr, len) };
+				return Err(if ptr != end {
+					io::Error::AlreadyExists
+				} else {
+					io::Error::InvalidData
+				});
+			}
+			Ok(())
+		} else {
+			Ok(())
+		}
+	}
+
 	#[inline]
 	pub const fn as_raw(&self) -> Handle {
 		self.0
@@ -1,12 +1,26 @@
 use {
-	alloc::boxed::Box,
-	core::{mem, ptr, time::Duration},
+	alloc::{boxed::Box, sync::Arc},
+	core::{cell::UnsafeCell, mem, ptr, time::Duration},
 	norostb_kernel::{error, syscall, Handle},
 };
 
 pub struct Thread(Handle);
 
 impl Thread {
+	/// Spawn a new thread and return a handle that can be used to retrieve the value returned
+	/// by the closure.
+	pub fn spawn<T, F>(stack: usize, f: F) -> error::Result<JoinHandle<T>>
+	where
+		T: Send + 'static,
+		F: FnOnce() -> T + Send + 'static,
+	{
+		let packet = Arc::new(Packet(UnsafeCell::new(None)));
+		let p = packet.clone();
+		// SAFETY: the slot is only accessed by the spawned thread until it has exited.
+		let thread = Self::new(stack, Box::new(move || unsafe { *p.0.get() = Some(f()) }))?;
+		Ok(JoinHandle { thread, packet })
+	}
+
 	/// Spawn a new thread.
 	// FIXME determine if this should be unsafe
 	// A current issue is the lack of catching panics, but that should be easy to fix once we
@@ -124,6 +138,34 @@ impl Thread {
 	}
 }
 
+/// A thread that can be joined to retrieve its return value.
+///
+/// Dropping the handle detaches the thread. The stack of the thread is freed by the thread
+/// itself when it exits.
+pub struct JoinHandle<T> {
+	thread: Thread,
+	packet: Arc<Packet<T>>,
+}
+
+impl<T> JoinHandle<T> {
+	/// Wait for the thread to exit and return the value returned by its closure.
+	pub fn join(self) -> error::Result<T> {
+		syscall::wait_thread(self.thread.0)?;
+		// The closure, including its reference to the packet, is dropped before the thread
+		// exits, so this is the only remaining reference.
+		Arc::try_unwrap(self.packet)
+			.ok()
+			.and_then(|p| p.0.into_inner())
+			.ok_or(error::Error::Unknown)
+	}
+}
+
+/// Slot for the return value of a thread.
+struct Packet<T>(UnsafeCell<Option<T>>);
+
+// SAFETY: the value is written by exactly one thread and only read after it has exited.
+unsafe impl<T: Send> Sync for Packet<T> {}
+
 pub fn sleep(duration: Duration) {
 	syscall::sleep(duration)
 }
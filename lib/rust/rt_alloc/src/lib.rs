@@ -6,10 +6,14 @@
 #![feature(slice_ptr_get, slice_ptr_len)]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+#[cfg(feature = "stats")]
+use core::sync::atomic::AtomicUsize;
 use {
 	core::{
 		alloc::{self, AllocError, Allocator as IAllocator, Layout},
+		mem,
 		ptr::{self, NonNull},
+		sync::atomic::{AtomicPtr, Ordering},
 	},
 	norostb_kernel::{
 		syscall::{self, RWX},
@@ -22,6 +26,74 @@ use {
 /// All instances use the same memory pool.
 pub struct Allocator;
 
+/// A function that is called when the OS fails to allocate memory. If it returns `true`
+/// the allocation is retried.
+pub type OomHandler = fn(Layout) -> bool;
+
+static OOM_HANDLER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+#[cfg(feature = "stats")]
+static BYTES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "stats")]
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Set a function to call when the OS fails to allocate memory.
+///
+/// The handler can free caches and return `true` to have the allocation retried. It will be
+/// called again if the retry fails too. Allocating memory inside the handler is allowed but may
+/// recurse.
+pub fn set_oom_handler(handler: Option<OomHandler>) {
+	let f = handler.map_or(ptr::null_mut(), |f| f as *mut ());
+	OOM_HANDLER.store(f, Ordering::Relaxed);
+}
+
+impl Allocator {
+	/// The total amount of bytes currently allocated, rounded up to pages.
+	#[cfg(feature = "stats")]
+	pub fn bytes_allocated() -> usize {
+		BYTES_ALLOCATED.load(Ordering::Relaxed)
+	}
+
+	/// The amount of allocations that haven't been deallocated yet.
+	#[cfg(feature = "stats")]
+	pub fn allocation_count() -> usize {
+		ALLOCATION_COUNT.load(Ordering::Relaxed)
+	}
+
+	fn alloc_pages(layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+		loop {
+			match syscall::alloc(None, Page::align_size(layout.size()), RWX::RW) {
+				Ok((ptr, size)) => {
+					#[cfg(feature = "stats")]
+					BYTES_ALLOCATED.fetch_add(size.get(), Ordering::Relaxed);
+					break Ok(NonNull::slice_from_raw_parts(ptr.cast(), size.get()));
+				}
+				Err(_) => {
+					let f = OOM_HANDLER.load(Ordering::Relaxed);
+					// SAFETY: only set_oom_handler writes to OOM_HANDLER and it only stores
+					// OomHandlers.
+					if f.is_null() || !unsafe { mem::transmute::<_, OomHandler>(f) }(layout) {
+						break Err(AllocError);
+					}
+				}
+			}
+		}
+	}
+
+	/// # Safety
+	///
+	/// The pages must have been allocated with `alloc_pages`.
+	unsafe fn dealloc_pages(ptr: NonNull<u8>, size: usize) {
+		let size = Page::align_size(size);
+		unsafe {
+			let _r = syscall::dealloc(ptr.cast(), size);
+			debug_assert!(_r.is_ok(), "{:?}", _r);
+		}
+		#[cfg(feature = "stats")]
+		BYTES_ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+	}
+}
+
 unsafe impl alloc::Allocator for Allocator {
 	#[inline]
 	fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
@@ -31,9 +103,10 @@ unsafe impl alloc::Allocator for Allocator {
 			// We don't support ridiculous alignment requirements.
 			Err(AllocError)
 		} else {
-			syscall::alloc(None, Page::align_size(layout.size()), RWX::RW)
-				.map(|(ptr, size)| NonNull::slice_from_raw_parts(ptr.cast(), size.get()))
-				.map_err(|_| AllocError)
+			let mem = Self::alloc_pages(layout)?;
+			#[cfg(feature = "stats")]
+			ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+			Ok(mem)
 		}
 	}
 
@@ -44,10 +117,9 @@ unsafe impl alloc::Allocator for Allocator {
 	#[inline]
 	unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
 		// We allocated a page directly, so we can give it directly back to the OS.
-		unsafe {
-			let _r = syscall::dealloc(ptr.cast(), Page::align_size(layout.size()));
-			debug_assert!(_r.is_ok(), "{:?}", _r);
-		}
+		unsafe { Self::dealloc_pages(ptr, layout.size()) }
+		#[cfg(feature = "stats")]
+		ALLOCATION_COUNT.fetch_sub(1, Ordering::Relaxed);
 	}
 
 	#[inline]
@@ -129,10 +201,7 @@ unsafe impl alloc::Allocator for Allocator {
 			// Give any pages we don't need back to the kernel.
 			if old != new {
 				unsafe {
-					self.deallocate(
-						NonNull::new_unchecked(ptr.as_ptr().add(new)),
-						Layout::from_size_align_unchecked(old - new, Page::SIZE),
-					);
+					Self::dealloc_pages(NonNull::new_unchecked(ptr.as_ptr().add(new)), old - new);
 				}
 			}
 			Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
@@ -7,15 +7,12 @@
 static ALLOC: rt_alloc::Allocator = rt_alloc::Allocator;
 
 fn name() -> &'static str {
-	rt::args::args()
-		.next()
-		.and_then(|s| core::str::from_utf8(s).ok())
-		.unwrap_or("??")
+	rt::args::arg_str(0).unwrap_or("??")
 }
 
 #[alloc_error_handler]
 fn alloc_error(layout: core::alloc::Layout) -> ! {
-	let _ = rt::io::stderr().map(|o| {
+	let _ = rt::io::Stderr::lock_or_bypass().map(|o| {
 		writeln!(
 			o,
 			"{}: allocation failed for size {}, alignment {}",
@@ -29,6 +26,6 @@ fn alloc_error(layout: core::alloc::Layout) -> ! {
 
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-	let _ = rt::io::stderr().map(|o| writeln!(o, "{}: {}", name(), info));
+	let _ = rt::io::Stderr::lock_or_bypass().map(|o| writeln!(o, "{}: {}", name(), info));
 	rt::exit(128)
 }
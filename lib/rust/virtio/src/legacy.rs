@@ -0,0 +1,169 @@
+//! # Legacy virtio devices
+//!
+//! Transitional devices expose a legacy interface through a single I/O BAR instead of the
+//! capability-based layout used by [`pci::Device`](crate::pci::Device). Only the lower 32
+//! feature bits can be negotiated and there is no `FEATURES_OK` status.
+//!
+//! Port I/O is not available to all programs, so it must be provided with a [`PortIo`]
+//! implementation.
+//!
+//! See section 4.1.4.8 of the virtio 1.1 specification for details.
+
+use {
+	crate::pci::{CommonConfig, ISRStatus},
+	core::fmt,
+};
+
+/// Access to I/O ports.
+pub trait PortIo {
+	fn in8(&self, port: u16) -> u8;
+	fn in16(&self, port: u16) -> u16;
+	fn in32(&self, port: u16) -> u32;
+	fn out8(&self, port: u16, value: u8);
+	fn out16(&self, port: u16, value: u16);
+	fn out32(&self, port: u16, value: u32);
+}
+
+const DEVICE_FEATURES: u16 = 0x00;
+const DRIVER_FEATURES: u16 = 0x04;
+const QUEUE_ADDRESS: u16 = 0x08;
+const QUEUE_SIZE: u16 = 0x0c;
+const QUEUE_SELECT: u16 = 0x0e;
+const QUEUE_NOTIFY: u16 = 0x10;
+const DEVICE_STATUS: u16 = 0x12;
+const ISR_STATUS: u16 = 0x13;
+const CONFIG_MSIX_VECTOR: u16 = 0x14;
+const QUEUE_MSIX_VECTOR: u16 = 0x16;
+
+/// The device configuration starts here if MSI-X is disabled.
+const DEVICE_CONFIG: u16 = 0x14;
+/// The device configuration starts here if MSI-X is enabled.
+const DEVICE_CONFIG_MSIX: u16 = 0x18;
+
+/// Legacy queues are addressed by page frame number.
+pub(crate) const QUEUE_ADDRESS_SHIFT: u32 = 12;
+
+/// A legacy virtio device on a PCI bus.
+pub struct Device<Io: PortIo> {
+	io: Io,
+	base: u16,
+	msix: bool,
+}
+
+impl<Io: PortIo> Device<Io> {
+	/// Setup a legacy virtio device.
+	///
+	/// `msix` must be `true` if MSI-X is enabled for the device, as it shifts the location of
+	/// the device configuration.
+	pub fn new(header: &pci::Header0, io: Io, msix: bool) -> Result<Self, NewDeviceError> {
+		let bar = header.base_address(0);
+		if !pci::BaseAddress::is_io(bar) {
+			return Err(NewDeviceError::NoIoBar);
+		}
+		let base = u16::try_from(bar & !0b11).map_err(|_| NewDeviceError::NoIoBar)?;
+		Ok(Self { io, base, msix })
+	}
+
+	/// Read the lower 32 feature bits offered by the device.
+	pub fn device_features(&self) -> u32 {
+		self.io.in32(self.base + DEVICE_FEATURES)
+	}
+
+	/// Set the features accepted by the driver.
+	pub fn set_driver_features(&self, features: u32) {
+		self.io.out32(self.base + DRIVER_FEATURES, features)
+	}
+
+	/// Read the device status.
+	pub fn status(&self) -> u8 {
+		self.io.in8(self.base + DEVICE_STATUS)
+	}
+
+	/// Set the device status.
+	///
+	/// The status bits are the same as those in [`CommonConfig`], except
+	/// [`CommonConfig::STATUS_FEATURES_OK`] which is not used by legacy devices.
+	pub fn set_status(&self, status: u8) {
+		self.io.out8(self.base + DEVICE_STATUS, status)
+	}
+
+	/// Reset the device.
+	pub fn reset(&self) {
+		self.set_status(CommonConfig::STATUS_RESET)
+	}
+
+	/// Read the ISR status, clearing it.
+	pub fn isr(&self) -> ISRStatus {
+		ISRStatus(self.io.in8(self.base + ISR_STATUS))
+	}
+
+	/// Notify the device that new buffers are available in a queue.
+	pub fn notify(&self, queue: u16) {
+		self.io.out16(self.base + QUEUE_NOTIFY, queue)
+	}
+
+	/// Set the MSI-X vector used for configuration changes.
+	pub fn set_config_msix_vector(&self, vector: u16) {
+		debug_assert!(self.msix, "MSI-X is not enabled");
+		self.io.out16(self.base + CONFIG_MSIX_VECTOR, vector)
+	}
+
+	/// Read a byte from the device configuration.
+	pub fn config_in8(&self, offset: u16) -> u8 {
+		self.io.in8(self.config_base() + offset)
+	}
+
+	/// Read a 16-bit value from the device configuration.
+	pub fn config_in16(&self, offset: u16) -> u16 {
+		self.io.in16(self.config_base() + offset)
+	}
+
+	/// Read a 32-bit value from the device configuration.
+	pub fn config_in32(&self, offset: u16) -> u32 {
+		self.io.in32(self.config_base() + offset)
+	}
+
+	/// Write a byte to the device configuration.
+	pub fn config_out8(&self, offset: u16, value: u8) {
+		self.io.out8(self.config_base() + offset, value)
+	}
+
+	/// Select a queue and return its size. A size of `0` means the queue does not exist.
+	pub(crate) fn select_queue(&self, index: u16) -> u16 {
+		self.io.out16(self.base + QUEUE_SELECT, index);
+		self.io.in16(self.base + QUEUE_SIZE)
+	}
+
+	/// Set the page frame number of the selected queue, which enables it.
+	pub(crate) fn set_queue_address(&self, pfn: u32) {
+		self.io.out32(self.base + QUEUE_ADDRESS, pfn)
+	}
+
+	/// Set the MSI-X vector of the selected queue.
+	pub(crate) fn set_queue_msix_vector(&self, vector: u16) {
+		debug_assert!(self.msix, "MSI-X is not enabled");
+		self.io.out16(self.base + QUEUE_MSIX_VECTOR, vector)
+	}
+
+	fn config_base(&self) -> u16 {
+		self.base
+			+ if self.msix {
+				DEVICE_CONFIG_MSIX
+			} else {
+				DEVICE_CONFIG
+			}
+	}
+}
+
+pub enum NewDeviceError {
+	/// BAR 0 is not an I/O BAR.
+	NoIoBar,
+}
+
+impl fmt::Debug for NewDeviceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::NoIoBar => "no I/O BAR",
+		})
+	}
+}
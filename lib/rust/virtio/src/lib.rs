@@ -2,6 +2,8 @@
 #![feature(int_log)]
 #![deny(unused)]
 
+#[cfg(feature = "legacy")]
+pub mod legacy;
 pub mod pci;
 pub mod phys;
 pub mod queue;
@@ -90,8 +90,89 @@ impl CommonConfig {
 	pub const STATUS_FEATURES_OK: u8 = 0x8;
 	pub const STATUS_DEVICE_NEED_RESET: u8 = 0x40;
 	pub const STATUS_FAILED: u8 = 0x80;
+
+	/// Check whether the device accepted the negotiated features.
+	///
+	/// This must be called after setting [`Self::STATUS_FEATURES_OK`]. If the bit has been
+	/// cleared by the device it does not support the selected features and can't be used.
+	pub fn features_ok(&self) -> bool {
+		self.device_status.get() & Self::STATUS_FEATURES_OK != 0
+	}
+}
+
+/// Resets a device and negotiates the features to use with it.
+///
+/// Once the queues are set up [`FeatureNegotiation::driver_ok`] must be called to let the device
+/// know it is ready for use.
+pub struct FeatureNegotiation<'a> {
+	common: &'a CommonConfig,
+}
+
+impl<'a> FeatureNegotiation<'a> {
+	/// Indicates compliance with version 1 of the specification. This is in the high 32 bits.
+	///
+	/// It is required by the modern interface and is always selected.
+	pub const VERSION_1: u32 = 1 << (32 - 32);
+
+	/// Reset the device and acknowledge it.
+	pub fn new(common: &'a CommonConfig) -> Self {
+		common.device_status.set(CommonConfig::STATUS_RESET);
+		common.device_status.set(CommonConfig::STATUS_ACKNOWLEDGE);
+		common
+			.device_status
+			.set(CommonConfig::STATUS_ACKNOWLEDGE | CommonConfig::STATUS_DRIVER);
+		Self { common }
+	}
+
+	/// The low and high 32 bits of the features offered by the device.
+	pub fn device_features(&self) -> (u32, u32) {
+		let get = |i: u32| {
+			self.common.device_feature_select.set(i.into());
+			u32::from(self.common.device_feature.get())
+		};
+		(get(0), get(1))
+	}
+
+	/// Select the features to use.
+	///
+	/// Features not offered by the device are dropped. Returns the low and high 32 bits of the
+	/// features that were selected.
+	///
+	/// Fails if the device does not accept the features, in which case it is marked as failed.
+	pub fn negotiate(&self, low: u32, high: u32) -> Result<(u32, u32), FeaturesRejected> {
+		let (dev_low, dev_high) = self.device_features();
+		let (low, high) = (low & dev_low, (high | Self::VERSION_1) & dev_high);
+		for (i, f) in [low, high].into_iter().enumerate() {
+			self.common.driver_feature_select.set((i as u32).into());
+			self.common.driver_feature.set(f.into());
+		}
+		self.common.device_status.set(
+			CommonConfig::STATUS_ACKNOWLEDGE
+				| CommonConfig::STATUS_DRIVER
+				| CommonConfig::STATUS_FEATURES_OK,
+		);
+		if !self.common.features_ok() {
+			self.common.device_status.set(CommonConfig::STATUS_FAILED);
+			return Err(FeaturesRejected);
+		}
+		Ok((low, high))
+	}
+
+	/// Tell the device the driver is ready.
+	pub fn driver_ok(self) {
+		self.common.device_status.set(
+			CommonConfig::STATUS_ACKNOWLEDGE
+				| CommonConfig::STATUS_DRIVER
+				| CommonConfig::STATUS_FEATURES_OK
+				| CommonConfig::STATUS_DRIVER_OK,
+		);
+	}
 }
 
+/// The device did not accept the selected features.
+#[derive(Debug)]
+pub struct FeaturesRejected;
+
 #[repr(C)]
 pub struct ISR {
 	status: VolatileCell<ISRStatus>,
@@ -106,7 +187,7 @@ impl ISR {
 
 #[derive(Clone, Copy)]
 #[repr(transparent)]
-pub struct ISRStatus(u8);
+pub struct ISRStatus(pub(crate) u8);
 
 impl ISRStatus {
 	const QUEUE_INTERRUPT: u8 = 0x1;
@@ -161,10 +242,14 @@ pub struct Device<'a> {
 
 impl<'a> Device<'a> {
 	/// Setup a new virtio device on a PCI bus.
+	///
+	/// Fails with [`NewDeviceError::MissingCapability`] if the device does not have the
+	/// capabilities of modern devices. Transitional devices may still be usable through the
+	/// legacy interface, which requires the `legacy` feature.
 	pub fn new(
 		header: &'a pci::Header0,
 		mut map_bar: impl FnMut(u8) -> NonNull<()>,
-	) -> Result<Device<'a>, ()> {
+	) -> Result<Device<'a>, NewDeviceError> {
 		let mut common = None;
 		let mut notify = None;
 		let mut isr = None;
@@ -195,10 +280,10 @@ impl<'a> Device<'a> {
 			}
 		}
 
-		let common = common.unwrap();
-		let notify = notify.unwrap();
-		let isr = isr.unwrap();
-		let device = device.unwrap();
+		let (common, notify, isr, device) = match (common, notify, isr, device) {
+			(Some(c), Some(n), Some(i), Some(d)) => (c, n, i, d),
+			_ => return Err(NewDeviceError::MissingCapability),
+		};
 
 		let mut mapped_bars = [None; 6];
 
@@ -225,3 +310,16 @@ impl<'a> Device<'a> {
 		}
 	}
 }
+
+pub enum NewDeviceError {
+	/// One of the capabilities required for modern devices is absent.
+	MissingCapability,
+}
+
+impl fmt::Debug for NewDeviceError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::MissingCapability => "missing capability",
+		})
+	}
+}
@@ -51,6 +51,14 @@ pub struct PhysRegion {
 	pub size: u32,
 }
 
+impl PhysRegion {
+	/// The maximum size of a region yielded by [`PhysMap::regions`].
+	///
+	/// This is the largest power of two that fits in the `size` field so that the base of each
+	/// region stays aligned.
+	pub const MAX_SIZE: u32 = 1 << 31;
+}
+
 pub struct PhysMap<'a> {
 	virt: NonNull<u8>,
 	phys: PhysAddr,
@@ -87,6 +95,22 @@ impl<'a> PhysMap<'a> {
 		self.size
 	}
 
+	/// Iterate over the physically contiguous regions of this buffer.
+	///
+	/// A `PhysMap` is always physically contiguous, but a [`PhysRegion`] can describe at most
+	/// [`PhysRegion::MAX_SIZE`] bytes. Larger buffers are split in multiple regions.
+	#[inline]
+	pub fn regions(&self) -> Regions {
+		// The regions are only contiguous if the buffer doesn't wrap around the address space.
+		debug_assert!(
+			u64::from(self.phys.0)
+				.checked_add(self.size as u64)
+				.is_some(),
+			"physical buffer is not contiguous"
+		);
+		Regions { base: self.phys, remaining: self.size }
+	}
+
 	/// Split the buffer at a specific point.
 	///
 	/// # Panics
@@ -164,3 +188,32 @@ impl<'a> PhysMap<'a> {
 
 #[derive(Debug)]
 pub struct BufferTooSmall;
+
+/// Iterator over the physically contiguous regions of a [`PhysMap`].
+#[derive(Clone)]
+pub struct Regions {
+	base: PhysAddr,
+	remaining: usize,
+}
+
+impl Iterator for Regions {
+	type Item = PhysRegion;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		(self.remaining > 0).then(|| {
+			let size = self.remaining.min(PhysRegion::MAX_SIZE as usize);
+			let region = PhysRegion { base: self.base, size: size as u32 };
+			self.base = self.base + size as u64;
+			self.remaining -= size;
+			region
+		})
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let max = PhysRegion::MAX_SIZE as usize;
+		let n = self.remaining / max + usize::from(self.remaining % max != 0);
+		(n, Some(n))
+	}
+}
+
+impl ExactSizeIterator for Regions {}
@@ -5,8 +5,10 @@ use {
 	core::{
 		cell::Cell,
 		convert::{TryFrom, TryInto},
-		fmt, mem,
-		ptr::NonNull,
+		fmt,
+		marker::PhantomData,
+		mem,
+		ptr::{self, NonNull},
 		slice,
 		sync::atomic::{self, Ordering},
 	},
@@ -69,7 +71,7 @@ struct UsedTail {
 }
 
 pub struct Queue<'a> {
-	_config: &'a super::pci::CommonConfig,
+	_config: PhantomData<&'a super::pci::CommonConfig>,
 	mask: u16,
 	last_used: u16,
 	alloc: DescriptorAlloc,
@@ -131,7 +133,8 @@ unsafe fn return_table<'s, T>(ptr: &'s mut NonNull<T>, mask: u16) -> &'s mut [T]
 impl<'a> Queue<'a> {
 	/// Create a new split virtqueue and attach it to the device.
 	///
-	/// The size must be a power of 2.
+	/// The size of the queue is the largest power of two that exceeds neither `max_size` nor
+	/// the maximum size supported by the device. Use [`Queue::size`] to get the actual size.
 	pub fn new<DmaError>(
 		config: &'a super::pci::CommonConfig,
 		index: u16,
@@ -139,8 +142,69 @@ impl<'a> Queue<'a> {
 		msix: Option<u16>,
 		dma_alloc: impl FnOnce(usize, usize) -> Result<(NonNull<()>, PhysAddr), DmaError>,
 	) -> Result<Self, NewQueueError<DmaError>> {
-		// TODO ensure max_size is a power of 2
-		let size = usize::from(u16::from(config.queue_size.get()).min(max_size));
+		config.queue_select.set(index.into());
+		let size = u16::from(config.queue_size.get()).min(max_size);
+		if size == 0 {
+			return Err(NewQueueError::Unavailable);
+		}
+		// Round down to a power of two.
+		let size = 1 << (15 - size.leading_zeros());
+		let (q, [d_phys, a_phys, u_phys]) =
+			Self::alloc(size, 0, dma_alloc).map_err(NewQueueError::DmaError)?;
+
+		config.queue_descriptors.set(d_phys);
+		config.queue_driver.set(a_phys);
+		config.queue_device.set(u_phys);
+		config.queue_size.set(size.into());
+		config.queue_enable.set(1.into());
+
+		let notify_offset = config.queue_notify_off.get().into();
+
+		msix.map(|msix| config.queue_msix_vector.set(msix.into()));
+
+		Ok(Self { notify_offset, ..q })
+	}
+
+	/// Create a new split virtqueue and attach it to a legacy device.
+	///
+	/// Legacy devices don't allow changing the size of a queue, so the size reported by the
+	/// device is used as is. [`Queue::notify_offset`] returns the index of the queue, which
+	/// is what must be passed to [`legacy::Device::notify`].
+	///
+	/// [`legacy::Device::notify`]: crate::legacy::Device::notify
+	#[cfg(feature = "legacy")]
+	pub fn new_legacy<Io: crate::legacy::PortIo, DmaError>(
+		device: &'a crate::legacy::Device<Io>,
+		index: u16,
+		msix: Option<u16>,
+		dma_alloc: impl FnOnce(usize, usize) -> Result<(NonNull<()>, PhysAddr), DmaError>,
+	) -> Result<Self, NewLegacyQueueError<DmaError>> {
+		let size = device.select_queue(index);
+		if size == 0 || !size.is_power_of_two() {
+			return Err(NewLegacyQueueError::InvalidSize);
+		}
+		let (q, [d_phys, ..]) =
+			Self::alloc(size, index, dma_alloc).map_err(NewLegacyQueueError::DmaError)?;
+
+		let pfn = u64::from(d_phys.0) >> crate::legacy::QUEUE_ADDRESS_SHIFT;
+		let pfn = u32::try_from(pfn).map_err(|_| NewLegacyQueueError::AddressOutOfRange)?;
+		device.set_queue_address(pfn);
+
+		msix.map(|msix| device.set_queue_msix_vector(msix));
+
+		Ok(q)
+	}
+
+	/// Allocate the descriptor table and rings of a queue.
+	///
+	/// The rings are laid out as required by legacy devices, which is also valid for modern
+	/// devices. Returns the physical addresses of the table, available ring and used ring.
+	fn alloc<DmaError>(
+		size: u16,
+		notify_offset: u16,
+		dma_alloc: impl FnOnce(usize, usize) -> Result<(NonNull<()>, PhysAddr), DmaError>,
+	) -> Result<(Self, [PhysAddr; 3]), DmaError> {
+		let size = usize::from(size);
 		let desc_size = mem::size_of::<Descriptor>() * size;
 		let avail_size = mem::size_of::<AvailHead>()
 			+ mem::size_of::<AvailElement>() * size
@@ -151,8 +215,7 @@ impl<'a> Queue<'a> {
 
 		let align = |s| (s + 0xfff) & !0xfff;
 
-		let (mem, phys) = dma_alloc(align(desc_size + avail_size) + align(used_size), 4096)
-			.map_err(NewQueueError::DmaError)?;
+		let (mem, phys) = dma_alloc(align(desc_size + avail_size) + align(used_size), 4096)?;
 		let mem = mem.cast::<u8>();
 
 		let descriptors = mem.cast();
@@ -166,19 +229,8 @@ impl<'a> Queue<'a> {
 		let a_phys = phys + u64::try_from(desc_size).unwrap();
 		let u_phys = phys + u64::try_from(align(desc_size + avail_size)).unwrap();
 
-		config.queue_select.set(index.into());
-		config.queue_descriptors.set(d_phys);
-		config.queue_driver.set(a_phys);
-		config.queue_device.set(u_phys);
-		config.queue_size.set((size as u16).into());
-		config.queue_enable.set(1.into());
-
-		let notify_offset = config.queue_notify_off.get().into();
-
-		msix.map(|msix| config.queue_msix_vector.set(msix.into()));
-
 		let mut q = Queue {
-			_config: config,
+			_config: PhantomData,
 			mask: size as u16 - 1,
 			last_used: 0,
 			alloc: DescriptorAlloc { free_head: 0, free_count: 0 },
@@ -190,7 +242,7 @@ impl<'a> Queue<'a> {
 
 		(0..size).for_each(|i| q.alloc.push_free_descr(descriptors_table!(q), i as _));
 
-		Ok(q)
+		Ok((q, [d_phys, a_phys, u_phys]))
 	}
 
 	/// Convert an iterator of `(address, data)` into a linked list of descriptors and put it in the
@@ -277,10 +329,23 @@ impl<'a> Queue<'a> {
 		usize::from(head_index.wrapping_sub(last))
 	}
 
+	/// Return the amount of used buffers that have not been collected yet.
+	pub fn used_count(&self) -> u16 {
+		// SAFETY: the used ring head is valid for the lifetime of the queue.
+		let index = unsafe { ptr::read_volatile(&(*self.used.as_ptr().cast::<UsedHead>()).index) };
+		// The index wraps around at 2^16 regardless of the queue size.
+		u16::from(index).wrapping_sub(self.last_used)
+	}
+
 	/// Return the offset relative to the notify address to flush this queue.
 	pub fn notify_offset(&self) -> u16 {
 		self.notify_offset
 	}
+
+	/// The amount of descriptors in this queue.
+	pub fn size(&self) -> u16 {
+		self.mask + 1
+	}
 }
 
 impl DescriptorAlloc {
@@ -313,6 +378,18 @@ impl fmt::Debug for NoBuffers {
 #[derive(Debug)]
 pub enum NewQueueError<DmaError> {
 	DmaError(DmaError),
+	/// The device has no queue at the given index or the requested size is zero.
+	Unavailable,
+}
+
+#[cfg(feature = "legacy")]
+#[derive(Debug)]
+pub enum NewLegacyQueueError<DmaError> {
+	DmaError(DmaError),
+	/// The size reported by the device is zero or not a power of two.
+	InvalidSize,
+	/// The queue is located above the address range supported by the device.
+	AddressOutOfRange,
 }
 
 /// A token for a single descriptor that has been sent to the device.
@@ -0,0 +1,239 @@
+//! Asynchronous interface on top of [`BlockDevice`].
+//!
+//! Instead of matching [`OpToken`]s manually, [`AsyncBlockDevice::read`] and
+//! [`AsyncBlockDevice::write`] return a future that resolves when the device has finished the
+//! operation. [`AsyncBlockDevice::poll_finished`] must be called whenever the device signals an
+//! interrupt.
+//!
+//! The device only has a single request header, so only one operation is submitted at a time.
+//! Other futures are queued and submit their operation when the active one finishes.
+
+use {
+	crate::{BlockDevice, OpToken, ReadError, RequestError, WriteError},
+	arena::{Arena, Handle},
+	core::{
+		cell::RefCell,
+		fmt,
+		future::Future,
+		pin::Pin,
+		task::{Context, Poll, Waker},
+	},
+	virtio::PhysRegion,
+};
+
+/// A [`BlockDevice`] with an asynchronous interface.
+pub struct AsyncBlockDevice<'a> {
+	inner: RefCell<Inner<'a>>,
+}
+
+struct Inner<'a> {
+	dev: BlockDevice<'a>,
+	ops: Arena<Op, ()>,
+	/// The operation the device is currently processing.
+	active: Option<(OpToken, Handle<()>)>,
+}
+
+enum Op {
+	/// Waiting for the active operation to finish before submitting.
+	Queued(Waker),
+	/// Submitted to the device.
+	Submitted(Waker),
+	Finished(Result<(), RequestError>),
+	/// The future was dropped while the device was still processing the operation.
+	Abandoned,
+}
+
+impl<'a> AsyncBlockDevice<'a> {
+	pub fn new(dev: BlockDevice<'a>) -> Self {
+		Self { inner: RefCell::new(Inner { dev, ops: Arena::new(), active: None }) }
+	}
+
+	/// Whether the device rejects writes.
+	pub fn is_read_only(&self) -> bool {
+		self.inner.borrow().dev.is_read_only()
+	}
+
+	/// Read in sectors.
+	///
+	/// # Safety
+	///
+	/// The physical regions must be valid until the operation has finished, even if the future
+	/// is dropped. The regions must each have a size that is a multiple of 512.
+	pub unsafe fn read<I>(&self, data: I, sector_start: u64) -> Request<'_, 'a, I>
+	where
+		I: ExactSizeIterator<Item = PhysRegion> + Unpin,
+	{
+		Request { dev: self, data: Some(data), sector_start, read: true, handle: None }
+	}
+
+	/// Write out sectors.
+	///
+	/// # Safety
+	///
+	/// The physical regions must be valid until the operation has finished, even if the future
+	/// is dropped.
+	pub unsafe fn write<I>(&self, data: I, sector_start: u64) -> Request<'_, 'a, I>
+	where
+		I: ExactSizeIterator<Item = PhysRegion> + Unpin,
+	{
+		Request { dev: self, data: Some(data), sector_start, read: false, handle: None }
+	}
+
+	/// Check for finished operations and wake the corresponding futures.
+	pub fn poll_finished(&self) -> usize {
+		let mut inner = self.inner.borrow_mut();
+		let Inner { dev, ops, active } = &mut *inner;
+		let n = dev.poll_finished(|tk, status| {
+			let h = match *active {
+				Some((t, h)) if t == tk => h,
+				_ => return,
+			};
+			*active = None;
+			match ops.get_mut(h) {
+				Some(Op::Submitted(w)) => {
+					w.wake_by_ref();
+					ops[h] = Op::Finished(status);
+				}
+				Some(Op::Abandoned) => {
+					ops.remove(h);
+				}
+				_ => unreachable!("active operation is not submitted"),
+			}
+		});
+		if active.is_none() {
+			for (_, op) in ops.iter() {
+				if let Op::Queued(w) = op {
+					w.wake_by_ref();
+				}
+			}
+		}
+		n
+	}
+
+	#[inline]
+	pub fn was_interrupted(&self) -> bool {
+		self.inner.borrow().dev.was_interrupted()
+	}
+
+	/// Get the underlying device.
+	pub fn into_inner(self) -> BlockDevice<'a> {
+		self.inner.into_inner().dev
+	}
+}
+
+/// A read or write operation.
+#[must_use = "futures do nothing unless polled"]
+pub struct Request<'d, 'a, I> {
+	dev: &'d AsyncBlockDevice<'a>,
+	data: Option<I>,
+	sector_start: u64,
+	read: bool,
+	handle: Option<Handle<()>>,
+}
+
+impl<I> Future for Request<'_, '_, I>
+where
+	I: ExactSizeIterator<Item = PhysRegion> + Unpin,
+{
+	type Output = Result<(), OpError>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let this = self.get_mut();
+		let mut inner = this.dev.inner.borrow_mut();
+		let Inner { dev, ops, active } = &mut *inner;
+
+		if let Some(h) = this.handle {
+			match &mut ops[h] {
+				Op::Finished(_) => {
+					this.handle = None;
+					return match ops.remove(h) {
+						Some(Op::Finished(r)) => Poll::Ready(r.map_err(OpError::Request)),
+						_ => unreachable!(),
+					};
+				}
+				Op::Queued(w) | Op::Submitted(w) if active.is_some() => {
+					w.clone_from(cx.waker());
+					return Poll::Pending;
+				}
+				Op::Queued(_) => {}
+				Op::Submitted(_) | Op::Abandoned => unreachable!("inconsistent state"),
+			}
+		}
+
+		if active.is_some() {
+			let waker = cx.waker().clone();
+			this.handle = Some(ops.insert(Op::Queued(waker)));
+			return Poll::Pending;
+		}
+
+		let data = this.data.take().expect("poll after completion");
+		// SAFETY: the caller guarantees the regions remain valid.
+		let tk = unsafe {
+			if this.read {
+				dev.read(data, this.sector_start).map_err(|e| match e {
+					ReadError::QueueFull => OpError::QueueFull,
+				})
+			} else {
+				dev.write(data, this.sector_start).map_err(|e| match e {
+					WriteError::QueueFull => OpError::QueueFull,
+					WriteError::ReadOnly => OpError::ReadOnly,
+				})
+			}
+		};
+		let tk = match tk {
+			Ok(tk) => tk,
+			Err(e) => {
+				if let Some(h) = this.handle.take() {
+					ops.remove(h);
+				}
+				return Poll::Ready(Err(e));
+			}
+		};
+		let waker = cx.waker().clone();
+		let h = match this.handle {
+			Some(h) => {
+				ops[h] = Op::Submitted(waker);
+				h
+			}
+			None => *this.handle.insert(ops.insert(Op::Submitted(waker))),
+		};
+		*active = Some((tk, h));
+		Poll::Pending
+	}
+}
+
+impl<I> Drop for Request<'_, '_, I> {
+	fn drop(&mut self) {
+		if let Some(h) = self.handle {
+			let mut inner = self.dev.inner.borrow_mut();
+			match &mut inner.ops[h] {
+				// The device may still access the regions, so keep the entry around until
+				// poll_finished picks it up.
+				op @ Op::Submitted(_) => *op = Op::Abandoned,
+				_ => {
+					inner.ops.remove(h);
+				}
+			}
+		}
+	}
+}
+
+/// An error that occured while performing an operation.
+pub enum OpError {
+	/// The queue is too small for the amount of regions.
+	QueueFull,
+	/// The device is read-only.
+	ReadOnly,
+	/// The device failed to perform the operation.
+	Request(RequestError),
+}
+
+impl fmt::Debug for OpError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::QueueFull => f.write_str("queue full"),
+			Self::ReadOnly => f.write_str("read-only"),
+			Self::Request(e) => e.fmt(f),
+		}
+	}
+}
@@ -1,26 +1,35 @@
 #![no_std]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+#[cfg(feature = "async")]
+extern crate alloc;
+
+#[cfg(feature = "async")]
+pub mod future;
 mod sector;
 
+#[cfg(feature = "async")]
+pub use future::AsyncBlockDevice;
 pub use sector::Sector;
 
 use {
 	core::{
-		convert::TryInto,
 		fmt, mem,
 		ptr::NonNull,
 		sync::atomic::{self, Ordering},
 	},
 	endian::{u16le, u32le, u64le},
 	memoffset::offset_of_tuple,
-	virtio::{pci::CommonConfig, queue, PhysAddr, PhysRegion},
+	virtio::{
+		pci::FeatureNegotiation,
+		queue::{self, NoBuffers},
+		PhysAddr, PhysMap, PhysRegion,
+	},
 };
 
 const SIZE_MAX: u32 = 1 << 1;
 const SEG_MAX: u32 = 1 << 2;
 const GEOMETRY: u32 = 1 << 4;
-#[allow(dead_code)]
 const RO: u32 = 1 << 5;
 const BLK_SIZE: u32 = 1 << 6;
 #[allow(dead_code)]
@@ -49,6 +58,7 @@ pub struct BlockDevice<'a> {
 	request_header_status_phys: PhysAddr,
 	/// The amount of sectors available
 	_capacity: u64,
+	read_only: bool,
 }
 
 #[repr(C)]
@@ -102,6 +112,12 @@ struct RequestStatus {
 	status: u8,
 }
 
+impl RequestStatus {
+	const OK: u8 = 0;
+	const IOERR: u8 = 1;
+	const UNSUPP: u8 = 2;
+}
+
 /// PCI MSI-X configuration.
 pub struct Msix {
 	/// The MSI-X vector to use for queue interrupts.
@@ -130,20 +146,11 @@ impl<'a> BlockDevice<'a> {
 
 		let dev = virtio::pci::Device::new(pci, map_bar).unwrap();
 
-		dev.common.device_status.set(CommonConfig::STATUS_RESET);
-
-		let features = SIZE_MAX | SEG_MAX | GEOMETRY | BLK_SIZE | TOPOLOGY;
-		dev.common.device_feature_select.set(0.into());
-
-		let features = u32le::from(features) & dev.common.device_feature.get();
-		dev.common.device_feature.set(features);
-
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK,
-		);
-		// TODO check device status to ensure features were enabled correctly.
+		let negotiation = FeatureNegotiation::new(dev.common);
+		let features = SIZE_MAX | SEG_MAX | GEOMETRY | RO | BLK_SIZE | TOPOLOGY;
+		let (features, _) = negotiation
+			.negotiate(features, 0)
+			.map_err(|_| SetupError::FeaturesRejected)?;
 
 		let blk_cfg = unsafe { dev.device.cast::<Config>() };
 
@@ -151,15 +158,11 @@ impl<'a> BlockDevice<'a> {
 		let queue = queue::Queue::<'a>::new(dev.common, 0, 16, msix.queue, dma_alloc).map_err(
 			|e| match e {
 				queue::NewQueueError::DmaError(e) => SetupError::DmaError(e),
+				queue::NewQueueError::Unavailable => SetupError::QueueUnavailable,
 			},
 		)?;
 
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK
-				| CommonConfig::STATUS_DRIVER_OK,
-		);
+		negotiation.driver_ok();
 
 		Ok(Self {
 			queue,
@@ -168,11 +171,25 @@ impl<'a> BlockDevice<'a> {
 			request_header_status: request_header_status.cast(),
 			request_header_status_phys,
 			_capacity: blk_cfg.capacity.into(),
+			read_only: features & RO != 0,
 		})
 	}
 
+	/// Whether the device rejects writes.
+	pub fn is_read_only(&self) -> bool {
+		self.read_only
+	}
+
+	/// The maximum amount of regions a single operation can use.
+	pub fn max_regions(&self) -> usize {
+		// The header and status each take a descriptor too.
+		usize::from(self.queue.size()) - 2
+	}
+
 	/// Write out sectors.
 	///
+	/// Fails with [`WriteError::ReadOnly`] if the device [is read-only](Self::is_read_only).
+	///
 	/// # Safety
 	///
 	/// The physical region must be valid for the duration of the operation.
@@ -181,7 +198,13 @@ impl<'a> BlockDevice<'a> {
 		data: impl ExactSizeIterator<Item = PhysRegion>,
 		sector_start: u64,
 	) -> Result<OpToken, WriteError> {
-		unsafe { self.do_op(data, sector_start, false).map_err(|()| todo!()) }
+		if self.read_only {
+			return Err(WriteError::ReadOnly);
+		}
+		unsafe {
+			self.do_op(data, sector_start, false)
+				.map_err(|NoBuffers| WriteError::QueueFull)
+		}
 	}
 
 	/// Read in sectors
@@ -195,7 +218,10 @@ impl<'a> BlockDevice<'a> {
 		data: impl ExactSizeIterator<Item = PhysRegion>,
 		sector_start: u64,
 	) -> Result<OpToken, ReadError> {
-		unsafe { self.do_op(data, sector_start, true).map_err(|()| todo!()) }
+		unsafe {
+			self.do_op(data, sector_start, true)
+				.map_err(|NoBuffers| ReadError::QueueFull)
+		}
 	}
 
 	unsafe fn do_op(
@@ -203,7 +229,7 @@ impl<'a> BlockDevice<'a> {
 		data: impl ExactSizeIterator<Item = PhysRegion>,
 		sector_start: u64,
 		read: bool,
-	) -> Result<OpToken, ()> {
+	) -> Result<OpToken, NoBuffers> {
 		unsafe {
 			self.request_header_status.as_ptr().write((
 				RequestHeader {
@@ -220,25 +246,30 @@ impl<'a> BlockDevice<'a> {
 			));
 		}
 
-		let header = (
-			self.request_header_status_phys
-				+ u64::try_from(offset_of_tuple!((RequestHeader, RequestStatus), 0)).unwrap(),
-			mem::size_of::<RequestHeader>().try_into().unwrap(),
-			false,
+		// SAFETY: the header and status are only accessed by the device while the operation is
+		// in flight.
+		let mut map = unsafe {
+			PhysMap::new(
+				self.request_header_status.cast(),
+				self.request_header_status_phys,
+				mem::size_of::<(RequestHeader, RequestStatus)>(),
+			)
+		};
+		let mut field = |offset, size| map.split_at(offset).1.split_at(size).0;
+		let header = field(
+			offset_of_tuple!((RequestHeader, RequestStatus), 0),
+			mem::size_of::<RequestHeader>(),
 		);
-		let data = data.map(|d| (d.base, d.size, read));
-		let footer = (
-			self.request_header_status_phys
-				+ u64::try_from(offset_of_tuple!((RequestHeader, RequestStatus), 1)).unwrap(),
-			mem::size_of::<RequestStatus>().try_into().unwrap(),
-			true,
+		let footer = field(
+			offset_of_tuple!((RequestHeader, RequestStatus), 1),
+			mem::size_of::<RequestStatus>(),
 		);
-		let data = [header].into_iter().chain(data).chain([footer]);
+		let header = header.regions().map(|r| (r.base, r.size, false));
+		let data = data.map(|d| (d.base, d.size, read));
+		let footer = footer.regions().map(|r| (r.base, r.size, true));
+		let data = header.chain(data).chain(footer);
 
-		let tk = self
-			.queue
-			.send(ExactSizeIterStub(data))
-			.expect("Failed to send data");
+		let tk = self.queue.send(ExactSizeIterStub(data))?;
 
 		self.flush();
 
@@ -246,8 +277,26 @@ impl<'a> BlockDevice<'a> {
 	}
 
 	/// Check for finished operations.
-	pub fn poll_finished(&mut self, mut f: impl FnMut(OpToken)) -> usize {
-		self.queue.collect_used(|t, _| f(OpToken(t)))
+	///
+	/// The status reported by the device is passed along with each token. There is only one
+	/// status buffer, so the status is only accurate if a single operation is in flight.
+	pub fn poll_finished(&mut self, mut f: impl FnMut(OpToken, Result<(), RequestError>)) -> usize {
+		let status = self.request_header_status;
+		self.queue.collect_used(|t, _| {
+			atomic::fence(Ordering::Acquire);
+			// SAFETY: the device has finished writing the status.
+			let status =
+				unsafe { core::ptr::addr_of!((*status.as_ptr()).1.status).read_volatile() };
+			f(
+				OpToken(t),
+				match status {
+					RequestStatus::OK => Ok(()),
+					RequestStatus::IOERR => Err(RequestError::Io),
+					RequestStatus::UNSUPP => Err(RequestError::Unsupported),
+					s => Err(RequestError::Unknown(s)),
+				},
+			)
+		})
 	}
 
 	pub fn flush(&self) {
@@ -270,29 +319,58 @@ impl Drop for BlockDevice<'_> {
 #[derive(Debug)]
 pub enum SetupError<DmaError> {
 	DmaError(DmaError),
+	/// The device did not accept the negotiated features.
+	FeaturesRejected,
+	/// A required queue is not available.
+	QueueUnavailable,
 }
 
-pub enum WriteError {}
+pub enum WriteError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+	/// The device is read-only.
+	ReadOnly,
+}
 
 impl fmt::Debug for WriteError {
-	fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-		/*
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		f.write_str(match self {
+			Self::QueueFull => "queue full",
+			Self::ReadOnly => "read-only",
 		})
-		*/
-		Ok(())
 	}
 }
 
-pub enum ReadError {}
+pub enum ReadError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
 
 impl fmt::Debug for ReadError {
-	fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-		/*
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		f.write_str(match self {
+			Self::QueueFull => "queue full",
 		})
-		*/
-		Ok(())
+	}
+}
+
+/// An error reported by the device for a finished operation.
+pub enum RequestError {
+	/// The device failed to perform the operation.
+	Io,
+	/// The device does not support the operation.
+	Unsupported,
+	/// The device reported a status that is not defined by the specification.
+	Unknown(u8),
+}
+
+impl fmt::Debug for RequestError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io => f.write_str("I/O error"),
+			Self::Unsupported => f.write_str("unsupported"),
+			Self::Unknown(s) => write!(f, "unknown status {}", s),
+		}
 	}
 }
 
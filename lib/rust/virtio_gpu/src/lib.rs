@@ -16,8 +16,8 @@ use {
 	cursorq::{CursorPosition, MoveCursor, UpdateCursor},
 	endian::{u32le, u64le},
 	virtio::{
-		pci::{CommonConfig, Notify},
-		queue::{NewQueueError, Queue},
+		pci::{FeatureNegotiation, Notify},
+		queue::{NewQueueError, NoBuffers, Queue},
 		PhysAddr, PhysMap,
 	},
 	volatile::VolatileCell,
@@ -165,12 +165,33 @@ struct BackingStorageInner {
 }
 
 impl<'a> BackingStorage<'a> {
-	/// Create a new [`BackingStorage`] with up to the given amount of memory entries.
+	/// Create a new [`BackingStorage`] with up to [`Self::capacity`] memory entries.
+	///
+	/// Use [`Self::size_for`] to determine how large `storage` should be.
+	///
+	/// # Panics
+	///
+	/// `storage` is too small to hold the header.
 	pub fn new(mut storage: PhysMap<'a>) -> Self {
 		storage.write(&AttachBacking::new(0, 0, None));
 		Self { storage }
 	}
 
+	/// The size of the storage needed to hold the given amount of memory entries.
+	pub const fn size_for(entries: usize) -> usize {
+		mem::size_of::<AttachBacking>() + mem::size_of::<MemoryEntry>() * entries
+	}
+
+	/// The maximum amount of memory entries this storage can hold.
+	pub fn capacity(&self) -> usize {
+		(self.storage.size() - mem::size_of::<AttachBacking>()) / mem::size_of::<MemoryEntry>()
+	}
+
+	/// The amount of memory entries that can still be added.
+	pub fn remaining(&self) -> usize {
+		self.capacity() - self.len()
+	}
+
 	/// Add an entry.
 	///
 	/// # Panics
@@ -182,19 +203,44 @@ impl<'a> BackingStorage<'a> {
 		self.try_push(map).expect("failed to add entry")
 	}
 
-	/// Try to add an entry. Returns an error if the storage is full.
-	pub fn try_push(&mut self, map: &PhysMap<'a>) -> Result<(), virtio::phys::BufferTooSmall> {
-		self.storage
-			.try_split_at(self.total_size())?
-			.1
-			.write(&MemoryEntry::new(
-				map.phys(),
-				map.size().try_into().unwrap(),
-			));
-		self.attach_backing_mut().entities_count += 1;
+	/// Try to add an entry for each region of the map.
+	///
+	/// If there is not enough space for all regions no entries are added. The storage can be
+	/// replaced with a larger one with [`Self::grow`], after which the push can be retried.
+	pub fn try_push(&mut self, map: &PhysMap<'a>) -> Result<(), StorageFull> {
+		let regions = map.regions();
+		if self.remaining() < regions.len() {
+			return Err(StorageFull { required: self.len() + regions.len() });
+		}
+		for r in regions {
+			let offset = self.total_size();
+			self.storage
+				.split_at(offset)
+				.1
+				.write(&MemoryEntry::new(r.base, r.size));
+			self.attach_backing_mut().entities_count += 1;
+		}
 		Ok(())
 	}
 
+	/// Move all entries to a larger storage.
+	///
+	/// On success the old storage is returned so it can be deallocated. On failure `storage` is
+	/// returned instead.
+	pub fn grow(&mut self, storage: PhysMap<'a>) -> Result<PhysMap<'a>, PhysMap<'a>> {
+		if storage.size() < self.total_size() {
+			return Err(storage);
+		}
+		// SAFETY: both maps are valid for at least total_size() bytes and are distinct buffers.
+		unsafe {
+			self.storage
+				.virt()
+				.as_ptr()
+				.copy_to_nonoverlapping(storage.virt().as_ptr(), self.total_size());
+		}
+		Ok(mem::replace(&mut self.storage, storage))
+	}
+
 	pub fn set_resource_id(&mut self, id: u32) {
 		self.attach_backing_mut().resource_id = id.into();
 	}
@@ -217,7 +263,7 @@ impl<'a> BackingStorage<'a> {
 
 	/// The total amount of valid data in the backing storage.
 	fn total_size(&self) -> usize {
-		mem::size_of::<AttachBacking>() + mem::size_of::<MemoryEntry>() * self.len()
+		Self::size_for(self.len())
 	}
 }
 
@@ -231,6 +277,7 @@ pub struct Device<'a> {
 	notify: Notify<'a>,
 	controlq: Queue<'a>,
 	cursorq: Queue<'a>,
+	config: &'a Config,
 }
 
 impl<'a> Device<'a> {
@@ -245,35 +292,30 @@ impl<'a> Device<'a> {
 	) -> Result<Self, SetupError<DmaError>> {
 		let dev = virtio::pci::Device::new(pci, map_bar).unwrap();
 
-		let features = FEATURE_EDID;
-		dev.common.device_feature_select.set(0.into());
-
-		let features = u32le::from(features) & dev.common.device_feature.get();
-		dev.common.device_feature.set(features);
-
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK,
-		);
-		// TODO check device status to ensure features were enabled correctly.
+		let negotiation = FeatureNegotiation::new(dev.common);
+		negotiation
+			.negotiate(FEATURE_EDID, 0)
+			.map_err(|_| SetupError::FeaturesRejected)?;
 
 		let map_err = |e| match e {
 			NewQueueError::DmaError(e) => SetupError::DmaError(e),
+			NewQueueError::Unavailable => SetupError::QueueUnavailable,
 		};
 		let controlq =
 			Queue::<'a>::new(dev.common, 0, 8, msix.control, &mut dma_alloc).map_err(map_err)?;
 		let cursorq =
 			Queue::<'a>::new(dev.common, 1, 8, msix.cursor, &mut dma_alloc).map_err(map_err)?;
 
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK
-				| CommonConfig::STATUS_DRIVER_OK,
-		);
+		negotiation.driver_ok();
 
-		Ok(Self { controlq, cursorq, notify: dev.notify })
+		let config = dev.device.cast::<Config>();
+
+		Ok(Self { controlq, cursorq, notify: dev.notify, config })
+	}
+
+	/// The maximum amount of scanouts supported by the device.
+	pub fn num_scanouts(&self) -> u32 {
+		self.config.num_scanouts.get().into()
 	}
 
 	pub unsafe fn init_scanout(
@@ -284,7 +326,8 @@ impl<'a> Device<'a> {
 		buffer: &mut PhysMap,
 	) -> Result<ControlOpToken, InitScanoutError> {
 		let cmd = SetScanout::new(scanout_id, resource_id.get(), rect, Some(0));
-		self.control_request(buffer, cmd).map_err(|_| todo!())
+		self.control_request(buffer, cmd)
+			.map_err(|NoBuffers| InitScanoutError::QueueFull)
 	}
 
 	/// # Safety
@@ -300,9 +343,15 @@ impl<'a> Device<'a> {
 	) -> Result<CursorOpToken, InitCursorError> {
 		let pos = CursorPosition::new(scanout_id, x, y);
 		let cmd = UpdateCursor::new(pos, resource_id.get(), 0, 0, Some(0));
-		self.cursor_request(buffer, cmd).map_err(|_| todo!())
+		self.cursor_request(buffer, cmd)
+			.map_err(|NoBuffers| InitCursorError::QueueFull)
 	}
 
+	/// Set the cursor image and position.
+	///
+	/// `hot_x` and `hot_y` are the hotspot of the cursor, relative to the top-left corner of the
+	/// cursor image.
+	///
 	/// # Safety
 	///
 	/// `buffer` must remain valid for the duration of the operation.
@@ -318,9 +367,15 @@ impl<'a> Device<'a> {
 	) -> Result<CursorOpToken, UpdateCursorError> {
 		let pos = CursorPosition::new(scanout_id, x, y);
 		let cmd = UpdateCursor::new(pos, resource_id.get(), hot_x, hot_y, Some(0));
-		self.cursor_request(buffer, cmd).map_err(|_| todo!())
+		self.cursor_request(buffer, cmd)
+			.map_err(|NoBuffers| UpdateCursorError::QueueFull)
 	}
 
+	/// Move the cursor without changing its image.
+	///
+	/// The cursor must have been set with [`Device::update_cursor`] or [`Device::init_cursor`]
+	/// first.
+	///
 	/// # Safety
 	///
 	/// `buffer` must remain valid for the duration of the operation.
@@ -334,9 +389,16 @@ impl<'a> Device<'a> {
 	) -> Result<CursorOpToken, MoveCursorError> {
 		let pos = CursorPosition::new(scanout_id, x, y);
 		let cmd = MoveCursor::new(pos, resource_id.get(), Some(0));
-		self.cursor_request(buffer, cmd).map_err(|_| todo!())
+		self.cursor_request(buffer, cmd)
+			.map_err(|NoBuffers| MoveCursorError::QueueFull)
 	}
 
+	/// Copy a rect from the backing storage of a resource to the host.
+	///
+	/// The first row of the rect is read from the start of the backing storage. Each following
+	/// row is read at the stride of the resource, i.e. its width times the size of a pixel. The
+	/// backing storage thus only needs to be as large as the rows that are transferred.
+	///
 	/// # Safety
 	///
 	/// `buffer` must remain valid for the duration of the operation.
@@ -347,7 +409,8 @@ impl<'a> Device<'a> {
 		buffer: &mut PhysMap,
 	) -> Result<ControlOpToken, DrawError> {
 		let cmd = TransferToHost2D::new(resource_id.get(), 0, rect, Some(0));
-		self.control_request(buffer, cmd).map_err(|_| todo!())
+		self.control_request(buffer, cmd)
+			.map_err(|NoBuffers| DrawError::QueueFull)
 	}
 
 	/// # Safety
@@ -360,7 +423,8 @@ impl<'a> Device<'a> {
 		buffer: &mut PhysMap,
 	) -> Result<ControlOpToken, DrawError> {
 		let cmd = Flush::new(resource_id.get(), rect, Some(0));
-		self.control_request(buffer, cmd).map_err(|_| todo!())
+		self.control_request(buffer, cmd)
+			.map_err(|NoBuffers| DrawError::QueueFull)
 	}
 
 	/// # Panics
@@ -376,7 +440,7 @@ impl<'a> Device<'a> {
 		rect: Rect,
 		format: Format,
 		buffer: &mut PhysMap,
-	) -> Result<ControlOpToken, ()> {
+	) -> Result<ControlOpToken, NoBuffers> {
 		let cmd = Create2D::new(
 			resource_id.get(),
 			format,
@@ -384,7 +448,7 @@ impl<'a> Device<'a> {
 			rect.height(),
 			Some(0),
 		);
-		self.control_request(buffer, cmd).map_err(|_| todo!())
+		self.control_request(buffer, cmd)
 	}
 
 	pub unsafe fn attach_resource_2d(
@@ -392,14 +456,13 @@ impl<'a> Device<'a> {
 		resource_id: NonZeroU32,
 		mut backend: BackingStorage,
 		buffer: &mut PhysMap,
-	) -> Result<ControlOpToken, ()> {
+	) -> Result<ControlOpToken, NoBuffers> {
 		backend.set_resource_id(resource_id.get());
 		self.control_request_raw(
 			buffer,
 			backend.storage.phys(),
 			backend.total_size().try_into().unwrap(),
 		)
-		.map_err(|_| todo!())
 	}
 
 	/// Send a request to the control queue.
@@ -411,11 +474,11 @@ impl<'a> Device<'a> {
 		&mut self,
 		buf: &mut PhysMap,
 		data: T,
-	) -> Result<ControlOpToken, ()> {
+	) -> Result<ControlOpToken, NoBuffers> {
 		Self::request(&mut self.controlq, &self.notify, 0, buf, data).map(ControlOpToken)
 	}
 
-	/// Send a request to the control queue.
+	/// Send a request to the cursor queue.
 	///
 	/// # Safety
 	///
@@ -424,7 +487,7 @@ impl<'a> Device<'a> {
 		&mut self,
 		buf: &mut PhysMap,
 		data: T,
-	) -> Result<CursorOpToken, ()> {
+	) -> Result<CursorOpToken, NoBuffers> {
 		Self::request(&mut self.cursorq, &self.notify, 1, buf, data).map(CursorOpToken)
 	}
 
@@ -438,7 +501,7 @@ impl<'a> Device<'a> {
 		buf: &mut PhysMap,
 		data: PhysAddr,
 		len: u32,
-	) -> Result<ControlOpToken, ()> {
+	) -> Result<ControlOpToken, NoBuffers> {
 		Self::request_raw(&mut self.controlq, &self.notify, 0, buf, data, len).map(ControlOpToken)
 	}
 
@@ -453,7 +516,7 @@ impl<'a> Device<'a> {
 		queue_id: u16,
 		buf: &mut PhysMap,
 		data: T,
-	) -> Result<virtio::queue::Token, ()> {
+	) -> Result<virtio::queue::Token, NoBuffers> {
 		let (mut resp, mut data_buf) = buf.split_at(mem::size_of::<ControlHeader>());
 		data_buf.write(&data);
 		Self::request_raw(
@@ -478,7 +541,7 @@ impl<'a> Device<'a> {
 		resp: &mut PhysMap,
 		data: PhysAddr,
 		len: u32,
-	) -> Result<virtio::queue::Token, ()> {
+	) -> Result<virtio::queue::Token, NoBuffers> {
 		resp.write(&ControlHeader::new(0, None));
 
 		let data = [
@@ -489,9 +552,7 @@ impl<'a> Device<'a> {
 				true,
 			),
 		];
-		let tk = queue
-			.send(data.iter().copied())
-			.expect("failed to send data");
+		let tk = queue.send(data.iter().copied())?;
 		notify.send(queue_id);
 
 		Ok(tk)
@@ -506,6 +567,16 @@ impl<'a> Device<'a> {
 	pub fn poll_cursor_queue(&mut self, mut f: impl FnMut(CursorOpToken)) -> usize {
 		self.cursorq.collect_used(|t, _| f(CursorOpToken(t)))
 	}
+
+	/// The amount of finished operations in the control queue that have not been polled yet.
+	pub fn control_queue_pending(&self) -> u16 {
+		self.controlq.used_count()
+	}
+
+	/// The amount of finished operations in the cursor queue that have not been polled yet.
+	pub fn cursor_queue_pending(&self) -> u16 {
+		self.cursorq.used_count()
+	}
 }
 
 /// A token for an active control queue operation.
@@ -519,19 +590,45 @@ pub struct CursorOpToken(virtio::queue::Token);
 #[derive(Debug)]
 pub enum SetupError<DmaError> {
 	DmaError(DmaError),
+	/// The device did not accept the negotiated features.
+	FeaturesRejected,
+	/// A required queue is not available.
+	QueueUnavailable,
 }
 
+/// The [`BackingStorage`] can't hold any more memory entries.
 #[derive(Debug)]
-pub enum InitScanoutError {}
+pub struct StorageFull {
+	/// The total amount of memory entries that would be needed.
+	pub required: usize,
+}
 
 #[derive(Debug)]
-pub enum InitCursorError {}
+pub enum InitScanoutError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
 
 #[derive(Debug)]
-pub enum UpdateCursorError {}
+pub enum InitCursorError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
 
 #[derive(Debug)]
-pub enum MoveCursorError {}
+pub enum UpdateCursorError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
 
 #[derive(Debug)]
-pub enum DrawError {}
+pub enum MoveCursorError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
+
+#[derive(Debug)]
+pub enum DrawError {
+	/// The queue has no free descriptors. Retry after some operations have finished.
+	QueueFull,
+}
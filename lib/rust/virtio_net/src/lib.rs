@@ -9,9 +9,19 @@
 )]
 
 use {
-	core::{alloc::Layout, convert::TryInto, fmt, mem, ptr::NonNull},
-	endian::{u16le, u32le},
-	virtio::{pci::CommonConfig, queue, PhysAddr, PhysRegion},
+	core::{
+		alloc::Layout,
+		convert::TryInto,
+		fmt, mem,
+		ptr::{self, NonNull},
+		sync::atomic::{self, Ordering},
+	},
+	endian::u16le,
+	virtio::{
+		pci::FeatureNegotiation,
+		queue::{self, NoBuffers},
+		PhysAddr, PhysRegion,
+	},
 };
 
 /// Device handles packets with partial checksum. This "checksum offload" is a common feature on
@@ -59,13 +69,10 @@ const HOST_UFO: u32 = 1 << 14;
 #[allow(dead_code)]
 const MRG_RXBUF: u32 = 1 << 15;
 /// Configuration status field is available.
-#[allow(dead_code)]
 const STATUS: u32 = 1 << 16;
 /// Control channel is available.
-#[allow(dead_code)]
 const CTRL_VQ: u32 = 1 << 17;
 /// Control channel RX mode support.
-#[allow(dead_code)]
 const CTRL_RX: u32 = 1 << 18;
 /// Control channel VLAN filtering.
 #[allow(dead_code)]
@@ -77,7 +84,6 @@ const GUEST_ANNOUNCE: u32 = 1 << 21;
 #[allow(dead_code)]
 const MQ: u32 = 1 << 22;
 /// Set MAC address through control channel.
-#[allow(dead_code)]
 const CTRL_MAC_ADDR: u32 = 1 << 23;
 /// Device can process duplicated ACKs and report number of coalesced segments and duplicated ACKs.
 #[allow(dead_code)]
@@ -95,7 +101,6 @@ struct Config {
 }
 
 impl Config {
-	#[allow(dead_code)]
 	const STATUS_LINK_UP: u16 = 1 << 0;
 	#[allow(dead_code)]
 	const STATUS_ANNOUNCE: u16 = 1 << 1;
@@ -185,8 +190,33 @@ struct NetworkControl {
 	// ack: u8 after command_specific_data
 }
 
+impl NetworkControl {
+	const CLASS_RX: u8 = 0;
+	const CLASS_MAC: u8 = 1;
+
+	const RX_PROMISC: u8 = 0;
+	const RX_ALLMULTI: u8 = 1;
+
+	const MAC_TABLE_SET: u8 = 0;
+	const MAC_ADDR_SET: u8 = 1;
+
+	const OK: u8 = 0;
+	const ERR: u8 = 1;
+
+	/// The size of the buffer used for commands, including the header and ack.
+	const BUFFER_SIZE: usize = 256;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
 pub struct Mac([u8; 6]);
 
+impl From<[u8; 6]> for Mac {
+	fn from(mac: [u8; 6]) -> Self {
+		Self(mac)
+	}
+}
+
 impl AsRef<[u8; 6]> for Mac {
 	fn as_ref(&self) -> &[u8; 6] {
 		&self.0
@@ -217,6 +247,8 @@ pub struct Msix {
 	pub receive_queue: Option<u16>,
 	/// The MSI-X vector to use for transmit queue interrupts.
 	pub transmit_queue: Option<u16>,
+	/// The MSI-X vector to use for control queue interrupts.
+	pub control_queue: Option<u16>,
 }
 
 /// A driver for a virtio network (Ethernet) device.
@@ -225,6 +257,26 @@ pub struct Device<'a> {
 	rx_queue: queue::Queue<'a>,
 	notify: virtio::pci::Notify<'a>,
 	isr: &'a virtio::pci::ISR,
+	config: &'a Config,
+	/// Whether the `status` field in the configuration is valid.
+	has_status: bool,
+	/// The control queue, if the device supports it.
+	control: Option<Control<'a>>,
+}
+
+/// The control queue and the buffer for commands.
+///
+/// There is only one buffer, so only one command can be in flight at any time.
+struct Control<'a> {
+	queue: queue::Queue<'a>,
+	buffer: NonNull<u8>,
+	buffer_phys: PhysAddr,
+	/// The offset of the ack byte of the command in flight, if any.
+	in_flight: Option<usize>,
+	/// Whether RX mode and MAC filtering commands are supported.
+	rx: bool,
+	/// Whether the MAC address can be set.
+	mac_addr: bool,
 }
 
 impl<'a> Device<'a> {
@@ -237,63 +289,76 @@ impl<'a> Device<'a> {
 	) -> Result<(Self, Mac), SetupError<DmaError>> {
 		let dev = virtio::pci::Device::new(pci, map_bar).unwrap();
 
-		dev.common.device_status.set(CommonConfig::STATUS_RESET);
-		dev.common
-			.device_status
-			.set(CommonConfig::STATUS_ACKNOWLEDGE);
-		dev.common
-			.device_status
-			.set(CommonConfig::STATUS_ACKNOWLEDGE | CommonConfig::STATUS_DRIVER);
-
-		let features = MAC;
-		//let features = MAC | MRG_RXBUF;
-		dev.common.device_feature_select.set(0.into());
-		let features = u32le::from(features) & dev.common.device_feature.get();
-		dev.common.driver_feature_select.set(0.into());
-		dev.common.driver_feature.set(features);
-
-		const VIRTIO_F_VERSION_1: u32 = 1 << (32 - 32);
-		let features = VIRTIO_F_VERSION_1;
-		dev.common.device_feature_select.set(1.into());
-		let features = u32le::from(features) & dev.common.device_feature.get();
-		assert_eq!(
-			u32::from(features),
-			VIRTIO_F_VERSION_1,
-			"New virtio-net is unsupported"
-		);
-		dev.common.driver_feature_select.set(1.into());
-		dev.common.driver_feature.set(features);
+		let negotiation = FeatureNegotiation::new(dev.common);
 
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK,
+		let features = MAC | STATUS | CTRL_VQ | CTRL_RX | CTRL_MAC_ADDR;
+		//let features = MAC | STATUS | MRG_RXBUF;
+		let mut features = negotiation.device_features().0 & features;
+		if features & CTRL_VQ == 0 {
+			// These depend on the control queue.
+			features &= !(CTRL_RX | CTRL_MAC_ADDR);
+		}
+		let (features, _) = negotiation
+			.negotiate(features, 0)
+			.map_err(|_| SetupError::FeaturesRejected)?;
+		let has_status = features & STATUS != 0;
+		let (has_ctrl_vq, ctrl_rx, ctrl_mac_addr) = (
+			features & CTRL_VQ != 0,
+			features & CTRL_RX != 0,
+			features & CTRL_MAC_ADDR != 0,
 		);
-		// TODO check device status to ensure features were enabled correctly.
 
 		// Set up queues.
 		let rx_queue =
 			queue::Queue::<'a>::new(dev.common, 0, 8, msix.receive_queue, &mut dma_alloc).map_err(
 				|e| match e {
 					queue::NewQueueError::DmaError(e) => SetupError::DmaError(e),
+					queue::NewQueueError::Unavailable => SetupError::QueueUnavailable,
 				},
 			)?;
 		let tx_queue =
 			queue::Queue::<'a>::new(dev.common, 1, 8, msix.transmit_queue, &mut dma_alloc)
 				.map_err(|e| match e {
 					queue::NewQueueError::DmaError(e) => SetupError::DmaError(e),
+					queue::NewQueueError::Unavailable => SetupError::QueueUnavailable,
 				})?;
+		let control = if has_ctrl_vq {
+			// Without multiqueue there is only one receive and transmit queue, so the control
+			// queue is at index 2.
+			let queue =
+				queue::Queue::<'a>::new(dev.common, 2, 8, msix.control_queue, &mut dma_alloc)
+					.map_err(|e| match e {
+						queue::NewQueueError::DmaError(e) => SetupError::DmaError(e),
+						queue::NewQueueError::Unavailable => SetupError::QueueUnavailable,
+					})?;
+			let (buffer, buffer_phys) =
+				dma_alloc(NetworkControl::BUFFER_SIZE, 1).map_err(SetupError::DmaError)?;
+			Some(Control {
+				queue,
+				buffer: buffer.cast(),
+				buffer_phys,
+				in_flight: None,
+				rx: ctrl_rx,
+				mac_addr: ctrl_mac_addr,
+			})
+		} else {
+			None
+		};
 
-		dev.common.device_status.set(
-			CommonConfig::STATUS_ACKNOWLEDGE
-				| CommonConfig::STATUS_DRIVER
-				| CommonConfig::STATUS_FEATURES_OK
-				| CommonConfig::STATUS_DRIVER_OK,
-		);
+		negotiation.driver_ok();
 
-		let mac = Mac(dev.device.cast::<Config>().mac);
+		let config = dev.device.cast::<Config>();
+		let mac = Mac(config.mac);
 
-		let s = Self { rx_queue, tx_queue, notify: dev.notify, isr: dev.isr };
+		let s = Self {
+			rx_queue,
+			tx_queue,
+			notify: dev.notify,
+			isr: dev.isr,
+			config,
+			has_status,
+			control,
+		};
 		Ok((s, mac))
 	}
 
@@ -325,7 +390,7 @@ impl<'a> Device<'a> {
 		let tk = self
 			.tx_queue
 			.send(data.iter().copied())
-			.expect("Failed to send data");
+			.map_err(|NoBuffers| SendError::QueueFull)?;
 
 		self.notify.send(self.tx_queue.notify_offset());
 
@@ -345,11 +410,152 @@ impl<'a> Device<'a> {
 		Ok(self.rx_queue.collect_used(|tk, p| f(RxToken(tk), p)))
 	}
 
+	/// Whether the link is up.
+	///
+	/// If the device doesn't report the link status it is assumed to always be up.
+	pub fn link_up(&self) -> bool {
+		// SAFETY: config points to a valid MMIO region.
+		let status = unsafe { ptr::read_volatile(&self.config.status) };
+		!self.has_status || u16::from(status) & Config::STATUS_LINK_UP != 0
+	}
+
 	#[inline]
 	pub fn was_interrupted(&self) -> bool {
 		self.isr.read().queue_update()
 	}
 
+	/// Whether the device has a control queue.
+	///
+	/// If not, all commands fail with [`ControlError::Unsupported`].
+	pub fn has_control_queue(&self) -> bool {
+		self.control.is_some()
+	}
+
+	/// Enable or disable promiscuous mode, i.e. receive all packets regardless of their
+	/// destination.
+	pub fn set_promiscuous(&mut self, enable: bool) -> Result<ControlToken, ControlError> {
+		self.rx_command(NetworkControl::RX_PROMISC, enable)
+	}
+
+	/// Enable or disable receiving all multicast packets.
+	pub fn set_all_multicast(&mut self, enable: bool) -> Result<ControlToken, ControlError> {
+		self.rx_command(NetworkControl::RX_ALLMULTI, enable)
+	}
+
+	fn rx_command(&mut self, command: u8, enable: bool) -> Result<ControlToken, ControlError> {
+		self.check_control(|c| c.rx)?;
+		self.control_command(NetworkControl::CLASS_RX, command, &[&[enable.into()]])
+	}
+
+	/// Set the MAC address of the device.
+	pub fn set_mac(&mut self, mac: &Mac) -> Result<ControlToken, ControlError> {
+		self.check_control(|c| c.mac_addr)?;
+		self.control_command(
+			NetworkControl::CLASS_MAC,
+			NetworkControl::MAC_ADDR_SET,
+			&[&mac.0],
+		)
+	}
+
+	/// Set the unicast and multicast addresses to receive packets for, in addition to the
+	/// address of the device.
+	pub fn set_mac_filter(
+		&mut self,
+		unicast: &[Mac],
+		multicast: &[Mac],
+	) -> Result<ControlToken, ControlError> {
+		self.check_control(|c| c.rx)?;
+		let len = |t: &[Mac]| u32::try_from(t.len()).map_err(|_| ControlError::TooLarge);
+		let (u, m) = (len(unicast)?.to_le_bytes(), len(multicast)?.to_le_bytes());
+		// SAFETY: Mac is a transparent wrapper around [u8; 6] without padding.
+		let bytes = |t: &[Mac]| unsafe {
+			core::slice::from_raw_parts(t.as_ptr().cast::<u8>(), t.len() * 6)
+		};
+		self.control_command(
+			NetworkControl::CLASS_MAC,
+			NetworkControl::MAC_TABLE_SET,
+			&[&u, bytes(unicast), &m, bytes(multicast)],
+		)
+	}
+
+	/// Check if the device has a control queue that supports a command.
+	fn check_control(&self, supported: impl FnOnce(&Control) -> bool) -> Result<(), ControlError> {
+		self.control
+			.as_ref()
+			.filter(|c| supported(c))
+			.map(|_| ())
+			.ok_or(ControlError::Unsupported)
+	}
+
+	fn control_command(
+		&mut self,
+		class: u8,
+		command: u8,
+		data: &[&[u8]],
+	) -> Result<ControlToken, ControlError> {
+		let c = self.control.as_mut().ok_or(ControlError::Unsupported)?;
+		if c.in_flight.is_some() {
+			return Err(ControlError::Busy);
+		}
+		let ack = 2 + data.iter().map(|d| d.len()).sum::<usize>();
+		if ack >= NetworkControl::BUFFER_SIZE {
+			return Err(ControlError::TooLarge);
+		}
+		// SAFETY: the buffer is large enough and the device isn't accessing it.
+		unsafe {
+			let mut p = c.buffer.as_ptr();
+			for d in [&[class, command][..]].iter().chain(data) {
+				p.copy_from_nonoverlapping(d.as_ptr(), d.len());
+				p = p.add(d.len());
+			}
+			// Ensure a stale ack is never mistaken for a response.
+			p.write_volatile(u8::MAX);
+		}
+
+		let data = [
+			(c.buffer_phys, ack.try_into().unwrap(), false),
+			(c.buffer_phys + u64::try_from(ack).unwrap(), 1, true),
+		];
+		let tk = c
+			.queue
+			.send(data.iter().copied())
+			.map_err(|NoBuffers| ControlError::QueueFull)?;
+		c.in_flight = Some(ack);
+
+		atomic::fence(Ordering::Release);
+		self.notify.send(c.queue.notify_offset());
+
+		Ok(ControlToken(tk))
+	}
+
+	/// Check for finished control commands.
+	///
+	/// The result reported by the device is passed along with each token.
+	pub fn poll_control(
+		&mut self,
+		mut f: impl FnMut(ControlToken, Result<(), CommandError>),
+	) -> usize {
+		let c = match &mut self.control {
+			Some(c) => c,
+			None => return 0,
+		};
+		let (buffer, in_flight) = (c.buffer, &mut c.in_flight);
+		c.queue.collect_used(|tk, _| {
+			atomic::fence(Ordering::Acquire);
+			let ack = in_flight.take().expect("no command in flight");
+			// SAFETY: the device has finished writing the ack.
+			let ack = unsafe { buffer.as_ptr().add(ack).read_volatile() };
+			f(
+				ControlToken(tk),
+				match ack {
+					NetworkControl::OK => Ok(()),
+					NetworkControl::ERR => Err(CommandError::Rejected),
+					a => Err(CommandError::Unknown(a)),
+				},
+			)
+		})
+	}
+
 	/// Get the layout requirements of a single packet. Useful for allocation.
 	pub fn packet_layout(&self) -> Layout {
 		Layout::new::<Packet>()
@@ -382,7 +588,7 @@ impl<'a> Device<'a> {
 		let tk = self
 			.rx_queue
 			.send(data.iter().copied())
-			.expect("Failed to send data");
+			.map_err(|NoBuffers| Full)?;
 
 		self.notify.send(self.rx_queue.notify_offset());
 
@@ -404,20 +610,68 @@ pub struct RxToken(virtio::queue::Token);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TxToken(virtio::queue::Token);
 
+/// A token for an active control command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ControlToken(virtio::queue::Token);
+
 #[derive(Debug)]
 pub enum SetupError<DmaError> {
 	DmaError(DmaError),
+	/// The device did not accept the negotiated features.
+	FeaturesRejected,
+	/// A required queue is not available.
+	QueueUnavailable,
 }
 
-pub enum SendError {}
+pub enum SendError {
+	/// The queue has no free descriptors. Retry after some packets have been sent.
+	QueueFull,
+}
 
 impl fmt::Debug for SendError {
-	fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-		/*
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		f.write_str(match self {
+			Self::QueueFull => "queue full",
 		})
-		*/
-		Ok(())
+	}
+}
+
+pub enum ControlError {
+	/// The device does not support the command.
+	Unsupported,
+	/// Another command is still in flight. Retry after it has finished.
+	Busy,
+	/// The queue has no free descriptors.
+	QueueFull,
+	/// The command does not fit in the command buffer.
+	TooLarge,
+}
+
+impl fmt::Debug for ControlError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::Unsupported => "unsupported",
+			Self::Busy => "busy",
+			Self::QueueFull => "queue full",
+			Self::TooLarge => "too large",
+		})
+	}
+}
+
+/// An error reported by the device for a finished control command.
+pub enum CommandError {
+	/// The device rejected the command.
+	Rejected,
+	/// The device reported an ack that is not defined by the specification.
+	Unknown(u8),
+}
+
+impl fmt::Debug for CommandError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Rejected => f.write_str("rejected"),
+			Self::Unknown(a) => write!(f, "unknown ack {}", a),
+		}
 	}
 }
 
@@ -1,5 +1,9 @@
 #![no_std]
-use core::{cell::UnsafeCell, ptr};
+use core::{
+	cell::UnsafeCell,
+	marker::PhantomData,
+	ptr::{self, NonNull},
+};
 
 // TODO how does this interact with Drop?
 #[repr(transparent)]
@@ -19,3 +23,83 @@ where
 		unsafe { ptr::write_volatile(self.0.get(), value) }
 	}
 }
+
+/// A pointer to a borrowed region of memory that must only be accessed with volatile operations,
+/// e.g. MMIO.
+///
+/// Unlike a reference no assumptions are made about the memory, i.e. reads are never elided or
+/// reordered.
+pub struct VolatilePtr<'a, T> {
+	ptr: NonNull<T>,
+	_marker: PhantomData<&'a UnsafeCell<T>>,
+}
+
+impl<'a, T> VolatilePtr<'a, T> {
+	/// # Safety
+	///
+	/// `ptr` must be properly aligned and valid for reads and writes for the lifetime `'a`.
+	pub unsafe fn new(ptr: NonNull<T>) -> Self {
+		Self { ptr, _marker: PhantomData }
+	}
+
+	pub fn as_ptr(self) -> NonNull<T> {
+		self.ptr
+	}
+
+	pub fn read(self) -> T
+	where
+		T: Copy,
+	{
+		unsafe { ptr::read_volatile(self.ptr.as_ptr()) }
+	}
+
+	pub fn write(self, value: T)
+	where
+		T: Copy,
+	{
+		unsafe { ptr::write_volatile(self.ptr.as_ptr(), value) }
+	}
+
+	/// Project the pointer to a part of the region.
+	///
+	/// Use [`map_field`] to safely project to a field.
+	///
+	/// # Safety
+	///
+	/// The returned pointer must point inside the region of `T`.
+	pub unsafe fn map<U>(self, f: impl FnOnce(*mut T) -> *mut U) -> VolatilePtr<'a, U> {
+		VolatilePtr::new(NonNull::new_unchecked(f(self.ptr.as_ptr())))
+	}
+
+	/// Get a pointer to a value of type `U` at the given byte offset.
+	///
+	/// # Safety
+	///
+	/// The offset must be in range of the region and the resulting pointer must be properly
+	/// aligned for `U`.
+	pub unsafe fn byte_offset<U>(self, offset: usize) -> VolatilePtr<'a, U> {
+		self.map(|p| p.cast::<u8>().add(offset).cast())
+	}
+}
+
+impl<T> Clone for VolatilePtr<'_, T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for VolatilePtr<'_, T> {}
+
+/// Project a [`VolatilePtr`] to one of its fields.
+///
+/// ```ignore
+/// let status = volatile::map_field!(regs, status).read();
+/// ```
+#[macro_export]
+macro_rules! map_field {
+	($ptr:expr, $field:ident) => {{
+		let ptr: $crate::VolatilePtr<'_, _> = $ptr;
+		// SAFETY: the field is always inside the region.
+		unsafe { ptr.map(|p| ::core::ptr::addr_of_mut!((*p).$field)) }
+	}};
+}